@@ -41,7 +41,11 @@ struct Func {
 #[derive(Default, Debug, Clone)]
 #[cfg_attr(feature = "structopt", derive(structopt::StructOpt))]
 pub struct Opts {
-    // ...
+    /// Whether or not to emit an object file for each exported interface
+    /// which embeds the interface in a custom section of the module it's
+    /// linked into, so tools like `wasmlink` can discover it.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub embed_interface: bool,
 }
 
 impl Opts {
@@ -712,6 +716,19 @@ impl Return {
 
 impl Generator for C {
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
+        // Compiled C code can only address the default, 32-bit, linear
+        // memory.
+        assert!(
+            iface.memory == 0,
+            "interface `{}` uses memory {}, but only memory 0 is supported",
+            iface.name,
+            iface.memory,
+        );
+        assert!(
+            !iface.memory64,
+            "interface `{}` uses a 64-bit memory, which is not supported",
+            iface.name,
+        );
         let variant = Self::abi_variant(dir);
         self.sizes.fill(variant, iface);
         self.in_import = variant == AbiVariant::GuestImport;
@@ -1273,9 +1290,40 @@ impl Generator for C {
             &format!("{}.h", iface.name.to_kebab_case()),
             self.src.header.as_bytes(),
         );
+
+        if !self.in_import && self.opts.embed_interface {
+            files.push(
+                &format!("{}-interface.o", iface.name.to_kebab_case()),
+                &interface_object(iface),
+            );
+        }
     }
 }
 
+/// Creates a relocatable object file which, when linked in, embeds the given
+/// interface in a custom section of the final module.
+///
+/// C has no way to create a custom section itself, so this is the equivalent
+/// of the `#[link_section]` static that the Rust generator emits.
+fn interface_object(iface: &Interface) -> Vec<u8> {
+    let wit = iface
+        .to_wit()
+        .unwrap_or_else(|e| panic!("failed to embed interface `{}`: {}", iface.name, e));
+
+    let mut module = wasm_encoder::Module::new();
+    module.section(&wasm_encoder::CustomSection {
+        name: &format!("{}{}", INTERFACE_SECTION_PREFIX, iface.name),
+        data: wit.as_bytes(),
+    });
+    // `wasm-ld` only accepts objects with a `linking` section, which here
+    // consists of just the version of the linking metadata.
+    module.section(&wasm_encoder::CustomSection {
+        name: "linking",
+        data: &[2],
+    });
+    module.finish()
+}
+
 struct FunctionBindgen<'a> {
     gen: &'a mut C,
     locals: Ns,
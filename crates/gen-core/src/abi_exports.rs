@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+use wit_parser::Interface;
+
+/// One of the exports of a wasm module which bindings use to implement the
+/// canonical ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiExportKind {
+    /// The linear memory, `memory` by default.
+    ///
+    /// An interface using a memory other than memory 0 must name this export,
+    /// as a module's exports don't say which index a memory has.
+    Memory,
+    /// The allocation function, `canonical_abi_realloc` by default.
+    Realloc,
+    /// The deallocation function, `canonical_abi_free` by default.
+    Free,
+}
+
+impl AbiExportKind {
+    pub fn default_name(&self) -> &'static str {
+        match self {
+            AbiExportKind::Memory => "memory",
+            AbiExportKind::Realloc => "canonical_abi_realloc",
+            AbiExportKind::Free => "canonical_abi_free",
+        }
+    }
+}
+
+/// Renames an export used for the canonical ABI, either for all interfaces or
+/// only for the interface named `iface`.
+///
+/// This is parsed from `kind=name` or `iface:kind=name`, where `kind` is one
+/// of `memory`, `realloc` or `free`. For example `realloc=cabi_realloc`, or
+/// `wasi:memory=memory1` for a module with several memories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiExport {
+    pub iface: Option<String>,
+    pub kind: AbiExportKind,
+    pub name: String,
+}
+
+impl FromStr for AbiExport {
+    type Err = String;
+    fn from_str(s: &str) -> Result<AbiExport, String> {
+        let (key, name) = match s.find('=') {
+            Some(i) => (s[..i].trim(), s[i + 1..].trim()),
+            None => return Err(format!("expected `kind=name`, found `{}`", s)),
+        };
+        let (iface, kind) = match key.find(':') {
+            Some(i) => (Some(key[..i].trim().to_string()), key[i + 1..].trim()),
+            None => (None, key),
+        };
+        let kind = match kind {
+            "memory" => AbiExportKind::Memory,
+            "realloc" => AbiExportKind::Realloc,
+            "free" => AbiExportKind::Free,
+            other => {
+                return Err(format!(
+                    "unknown export `{}`, expected `memory`, `realloc` or `free`",
+                    other
+                ))
+            }
+        };
+        if name.is_empty() {
+            return Err(format!("missing export name in `{}`", s));
+        }
+        Ok(AbiExport {
+            iface,
+            kind,
+            name: name.to_string(),
+        })
+    }
+}
+
+/// Returns the name of the `kind` export used by the interface `iface`.
+///
+/// Entries of `exports` for `iface` take precedence over entries for all
+/// interfaces, and later entries take precedence over earlier ones.
+///
+/// # Panics
+///
+/// Panics if `iface` uses a memory other than memory 0 and `exports` doesn't
+/// name its memory export.
+pub fn abi_export_name<'a>(
+    exports: &'a [AbiExport],
+    iface: &Interface,
+    kind: AbiExportKind,
+) -> Cow<'a, str> {
+    let matching = |iface: Option<&str>| {
+        exports
+            .iter()
+            .rev()
+            .find(|e| e.kind == kind && e.iface.as_deref() == iface)
+    };
+    if let Some(e) = matching(Some(&iface.name)).or_else(|| matching(None)) {
+        return Cow::Borrowed(&e.name);
+    }
+    assert!(
+        kind != AbiExportKind::Memory || iface.memory == 0,
+        "interface `{}` uses memory {}, so its export must be named with `memory=<name>`",
+        iface.name,
+        iface.memory,
+    );
+    Cow::Borrowed(kind.default_name())
+}
@@ -6,8 +6,10 @@ use wit_parser::abi::Abi;
 use wit_parser::*;
 
 pub use wit_parser;
+mod abi_exports;
 mod ns;
 
+pub use abi_exports::{abi_export_name, AbiExport, AbiExportKind};
 pub use ns::Ns;
 
 /// This is the direction from the user's perspective. Are we importing
@@ -386,6 +388,40 @@ pub fn load(path: impl AsRef<Path>) -> Result<Interface> {
     Interface::parse_file(path)
 }
 
+/// Returns whether `ty` is, or transitively contains, a named type listed in
+/// `names`.
+///
+/// Generators use this to redact values of sensitive types from traces.
+pub fn contains_named_type(iface: &Interface, ty: &Type, names: &[String]) -> bool {
+    let id = match ty {
+        Type::Id(id) => *id,
+        _ => return false,
+    };
+    let def = &iface.types[id];
+    if let Some(name) = &def.name {
+        if names.iter().any(|n| n == name) {
+            return true;
+        }
+    }
+    match &def.kind {
+        TypeDefKind::Record(r) => r
+            .fields
+            .iter()
+            .any(|f| contains_named_type(iface, &f.ty, names)),
+        TypeDefKind::Variant(v) => v
+            .cases
+            .iter()
+            .filter_map(|c| c.ty.as_ref())
+            .any(|t| contains_named_type(iface, t, names)),
+        TypeDefKind::List(t)
+        | TypeDefKind::PushBuffer(t)
+        | TypeDefKind::PullBuffer(t)
+        | TypeDefKind::Pointer(t)
+        | TypeDefKind::ConstPointer(t)
+        | TypeDefKind::Type(t) => contains_named_type(iface, t, names),
+    }
+}
+
 #[derive(Default)]
 pub struct Source {
     s: String,
@@ -453,7 +489,10 @@ impl From<Source> for String {
 
 #[cfg(test)]
 mod tests {
-    use super::{Generator, Source};
+    use super::{
+        abi_export_name, contains_named_type, AbiExport, AbiExportKind, Generator, Source,
+    };
+    use wit_parser::Interface;
 
     #[test]
     fn simple_append() {
@@ -501,6 +540,80 @@ mod tests {
         assert_eq!(s.s, "function() {\n  x\n}");
     }
 
+    #[test]
+    fn named_types() {
+        let iface = Interface::parse(
+            "x",
+            "
+                record secret { a: u32 }
+                record wrapper { b: list<secret> }
+                type other = u32
+                f: function(a: wrapper, b: option<other>, c: u32)
+            ",
+        )
+        .unwrap();
+        let names = ["secret".to_string()];
+        let params = &iface.functions[0].params;
+        assert!(contains_named_type(&iface, &params[0].1, &names));
+        assert!(!contains_named_type(&iface, &params[1].1, &names));
+        assert!(!contains_named_type(&iface, &params[2].1, &names));
+        assert!(contains_named_type(
+            &iface,
+            &params[1].1,
+            &["other".to_string()]
+        ));
+    }
+
+    #[test]
+    fn abi_exports() {
+        let exports = [
+            "realloc=cabi_realloc".parse::<AbiExport>().unwrap(),
+            "x: memory = mem1".parse().unwrap(),
+            "memory=mem0".parse().unwrap(),
+        ];
+        assert_eq!(
+            exports[1],
+            AbiExport {
+                iface: Some("x".to_string()),
+                kind: AbiExportKind::Memory,
+                name: "mem1".to_string(),
+            }
+        );
+        let x = Interface::parse("x", "").unwrap();
+        let mut y = Interface::parse("y", "").unwrap();
+        assert_eq!(abi_export_name(&exports, &x, AbiExportKind::Memory), "mem1");
+        assert_eq!(abi_export_name(&exports, &y, AbiExportKind::Memory), "mem0");
+        assert_eq!(
+            abi_export_name(&exports, &y, AbiExportKind::Realloc),
+            "cabi_realloc"
+        );
+        assert_eq!(
+            abi_export_name(&exports, &y, AbiExportKind::Free),
+            "canonical_abi_free"
+        );
+
+        // Another memory has to be named, but only its memory export.
+        y.memory = 2;
+        assert_eq!(abi_export_name(&exports, &y, AbiExportKind::Memory), "mem0");
+        assert_eq!(
+            abi_export_name(&[], &y, AbiExportKind::Realloc),
+            "canonical_abi_realloc"
+        );
+        y.memory = 0;
+        assert_eq!(abi_export_name(&[], &y, AbiExportKind::Memory), "memory");
+        assert!("table=t".parse::<AbiExport>().is_err());
+        assert!("memory".parse::<AbiExport>().is_err());
+        assert!("memory=".parse::<AbiExport>().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "interface `y` uses memory 2")]
+    fn abi_export_unnamed_memory() {
+        let mut y = Interface::parse("y", "").unwrap();
+        y.memory = 2;
+        abi_export_name(&[], &y, AbiExportKind::Memory);
+    }
+
     #[test]
     fn generator_is_object_safe() {
         fn _assert(_: &dyn Generator) {}
@@ -0,0 +1,22 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+#define PY_SSIZE_T_CLEAN
+#include <Python.h>
+
+#include "cpw/abort.h"
+
+void cpw_abort(const char* msg) {
+    fprintf(stderr, "Error: %s", msg);
+
+    if (Py_IsInitialized() && PyErr_Occurred()) {
+        fprintf(stderr, ":\n");
+        // Prints the exception value and its traceback to `sys.stderr`.
+        PyErr_Print();
+    } else {
+        fprintf(stderr, "\n");
+    }
+
+    fflush(stderr);
+    abort();
+}
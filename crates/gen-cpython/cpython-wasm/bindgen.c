@@ -0,0 +1,885 @@
+/*!
+ * This module implements the intrinsics used by code emitted in the
+ * `wit_bindgen_gen_cpython::Bindgen` trait implementation.
+ *
+ * All of the Python values that the glue code is working with live in the
+ * `OPERANDS` vector, and the glue code refers to them by index. Each slot in
+ * these vectors holds a strong reference (or `NULL` if the slot is empty).
+ */
+
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "cpw/abort.h"
+#include "cpw/bindgen.h"
+#include "cpw/logging.h"
+#include "cpw/wasm.h"
+
+/**
+ * A growable vector of strong references to Python objects.
+ */
+typedef struct {
+    PyObject** elems;
+    size_t len;
+    size_t cap;
+} ObjectVector;
+
+// Used for general Wasm<-->Python conversions.
+static ObjectVector OPERANDS;
+
+// Used for holding arguments to Python calls.
+static ObjectVector ARGS;
+
+// Used for holding returns from Wasm calls.
+static ObjectVector RETS;
+
+// Used for holding the Python objects that exported resources' handles refer
+// to. A handle's representation is its index in this vector.
+static ObjectVector RESOURCES;
+
+// The indices of the empty slots in `RESOURCES`, to be reused.
+static uint32_t* FREE_RESOURCES = NULL;
+static size_t FREE_RESOURCES_LEN = 0;
+static size_t FREE_RESOURCES_CAP = 0;
+
+static PyObject* USER_MODULE = NULL;
+
+void cpw_init_user_module(PyObject* user_module) {
+    assert(!USER_MODULE && "USER_MODULE should only be initialized once");
+    Py_INCREF(user_module);
+    USER_MODULE = user_module;
+}
+
+PyObject* cpw_get_user_module(void) {
+    assert(USER_MODULE && "USER_MODULE should be initialized");
+    return USER_MODULE;
+}
+
+static void vector_reserve(ObjectVector* vec, size_t len) {
+    if (len <= vec->cap) {
+        return;
+    }
+    size_t cap = vec->cap == 0 ? 8 : vec->cap;
+    while (cap < len) {
+        cap *= 2;
+    }
+    PyObject** elems = realloc(vec->elems, cap * sizeof(PyObject*));
+    if (!elems) {
+        cpw_abort("failed to reserve capacity for an object vector");
+    }
+    memset(elems + vec->cap, 0, (cap - vec->cap) * sizeof(PyObject*));
+    vec->elems = elems;
+    vec->cap = cap;
+}
+
+/**
+ * Push a new strong reference to `obj` onto the end of `vec`.
+ */
+static void vector_push(ObjectVector* vec, PyObject* obj) {
+    vector_reserve(vec, vec->len + 1);
+    Py_INCREF(obj);
+    vec->elems[vec->len++] = obj;
+}
+
+static void vector_clear(ObjectVector* vec) {
+    for (size_t i = 0; i < vec->len; i++) {
+        Py_CLEAR(vec->elems[i]);
+    }
+    vec->len = 0;
+}
+
+static PyObject* operand(size_t i) {
+    assert(i < OPERANDS.len && "operand index out of bounds");
+    PyObject* obj = OPERANDS.elems[i];
+    assert(obj && "operand slot is empty");
+    return obj;
+}
+
+/**
+ * Save a new strong reference to `obj` in `operands[dest]`, releasing whatever
+ * was there previously.
+ */
+static void save_operand(size_t dest, PyObject* obj) {
+    CPW_LOG("operands[%zu] = %p\n", dest, (void*) obj);
+
+    if (OPERANDS.len <= dest) {
+        vector_reserve(&OPERANDS, dest + 1);
+        OPERANDS.len = dest + 1;
+    }
+
+    Py_INCREF(obj);
+    Py_XSETREF(OPERANDS.elems[dest], obj);
+}
+
+WASM_EXPORT
+void canonical_abi_free(void* ptr, size_t size, size_t align) {
+    (void) size;
+    (void) align;
+    free(ptr);
+}
+
+WASM_EXPORT
+void* canonical_abi_realloc(void* ptr, size_t old_size, size_t align, size_t new_size) {
+    (void) old_size;
+    (void) align;
+    return realloc(ptr, new_size);
+}
+
+WASM_EXPORT
+void CPW_fill_operands(PyObject* const* args, Py_ssize_t nargs, Py_ssize_t expected) {
+    CPW_LOG("CPW_fill_operands(args = %p, nargs = %zd, expected = %zd)\n",
+            (void*) args, nargs, expected);
+
+    if (nargs != expected) {
+        PyErr_Format(PyExc_TypeError,
+                     "expected %zd arguments, got %zd",
+                     expected,
+                     nargs);
+        cpw_abort("imported function called with the wrong number of arguments");
+    }
+
+    vector_reserve(&OPERANDS, (size_t) nargs);
+    for (Py_ssize_t i = 0; i < nargs; i++) {
+        vector_push(&OPERANDS, args[i]);
+    }
+}
+
+WASM_EXPORT
+void CPW_clear_operands(void) {
+    CPW_LOG("CPW_clear_operands\n");
+    vector_clear(&OPERANDS);
+}
+
+WASM_EXPORT
+void CPW_push_arg(size_t i) {
+    CPW_LOG("CPW_push_arg(i = %zu)\n", i);
+    vector_push(&ARGS, operand(i));
+}
+
+WASM_EXPORT
+void CPW_call(char* func_name, size_t func_name_len, size_t num_results, size_t dest) {
+    CPW_LOG("CPW_call(func_name = \"%.*s\", num_results = %zu, dest = %zu)\n",
+            (int) func_name_len,
+            func_name,
+            num_results,
+            dest);
+
+    // The function name is owned by the glue module, which copied it into our
+    // memory once during initialization, so it must not be freed here.
+    PyObject* name = PyUnicode_DecodeUTF8(func_name, (Py_ssize_t) func_name_len, NULL);
+    if (!name) {
+        cpw_abort("failed to decode function name");
+    }
+
+    PyObject* func = PyObject_GetAttr(cpw_get_user_module(), name);
+    Py_DECREF(name);
+    if (!func) {
+        cpw_abort("user module does not have the requested export");
+    }
+    if (!PyCallable_Check(func)) {
+        PyErr_Format(PyExc_TypeError, "'%.200s' object is not callable", Py_TYPE(func)->tp_name);
+        cpw_abort("exported value is not callable");
+    }
+
+    PyObject* result = PyObject_Vectorcall(func, ARGS.elems, ARGS.len, NULL);
+    Py_DECREF(func);
+    vector_clear(&ARGS);
+    if (!result) {
+        cpw_abort("calling export function failed");
+    }
+
+    if (num_results == 0) {
+        // Nothing to push onto the operands vector.
+    } else if (num_results == 1) {
+        save_operand(dest, result);
+    } else {
+        // Treat the "physical" return value as a sequence and unpack the
+        // "logical" return values from within it. This allows Python to
+        // return multiple WIT values as a tuple, list, or any other iterable.
+        PyObject* seq = PySequence_Fast(result, "function's return value is not iterable");
+        if (!seq) {
+            cpw_abort("failed to convert return value to a sequence");
+        }
+        if ((size_t) PySequence_Fast_GET_SIZE(seq) != num_results) {
+            PyErr_Format(PyExc_ValueError,
+                         "expected %zu return values, got %zd",
+                         num_results,
+                         PySequence_Fast_GET_SIZE(seq));
+            cpw_abort("function returned the wrong number of return values");
+        }
+        for (size_t i = 0; i < num_results; i++) {
+            save_operand(dest + i, PySequence_Fast_GET_ITEM(seq, i));
+        }
+        Py_DECREF(seq);
+    }
+
+    Py_DECREF(result);
+}
+
+WASM_EXPORT
+void CPW_push_return_value(size_t i) {
+    CPW_LOG("CPW_push_return_value(i = %zu)\n", i);
+    vector_push(&RETS, operand(i));
+}
+
+/**
+ * Package up the pushed return values into the single (new reference) Python
+ * object that an imported function returns to its Python caller.
+ */
+WASM_EXPORT
+PyObject* CPW_finish_returns(void) {
+    CPW_LOG("CPW_finish_returns\n");
+
+    PyObject* result = NULL;
+    switch (RETS.len) {
+    case 0:
+        Py_INCREF(Py_None);
+        result = Py_None;
+        break;
+    case 1:
+        result = RETS.elems[0];
+        Py_INCREF(result);
+        break;
+    default:
+        result = PyTuple_New((Py_ssize_t) RETS.len);
+        if (!result) {
+            cpw_abort("failed to allocate tuple for function's return values");
+        }
+        for (size_t i = 0; i < RETS.len; i++) {
+            Py_INCREF(RETS.elems[i]);
+            PyTuple_SET_ITEM(result, (Py_ssize_t) i, RETS.elems[i]);
+        }
+        break;
+    }
+
+    vector_clear(&RETS);
+    return result;
+}
+
+/**
+ * Convert `operands[i]` to an unsigned integer, aborting if it is not an
+ * integer or is greater than `max`.
+ */
+static unsigned long long operand_as_unsigned(size_t i, unsigned long long max, const char* ty) {
+    PyObject* index = PyNumber_Index(operand(i));
+    if (!index) {
+        cpw_abort("failed to convert value to an integer");
+    }
+    unsigned long long x = PyLong_AsUnsignedLongLong(index);
+    Py_DECREF(index);
+    if (x == (unsigned long long) -1 && PyErr_Occurred()) {
+        cpw_abort("integer out of range");
+    }
+    if (x > max) {
+        PyErr_Format(PyExc_OverflowError, "value does not fit in a %s", ty);
+        cpw_abort("integer out of range");
+    }
+    return x;
+}
+
+/**
+ * Convert `operands[i]` to a signed integer, aborting if it is not an integer
+ * or is outside of `[min, max]`.
+ */
+static long long operand_as_signed(size_t i, long long min, long long max, const char* ty) {
+    PyObject* index = PyNumber_Index(operand(i));
+    if (!index) {
+        cpw_abort("failed to convert value to an integer");
+    }
+    long long x = PyLong_AsLongLong(index);
+    Py_DECREF(index);
+    if (x == -1 && PyErr_Occurred()) {
+        cpw_abort("integer out of range");
+    }
+    if (x < min || x > max) {
+        PyErr_Format(PyExc_OverflowError, "value does not fit in a %s", ty);
+        cpw_abort("integer out of range");
+    }
+    return x;
+}
+
+/**
+ * Save `val`, a new reference, to `operands[dest]`, aborting with `msg` if it
+ * is `NULL`.
+ */
+static void save_new_operand(size_t dest, PyObject* val, const char* msg) {
+    if (!val) {
+        cpw_abort(msg);
+    }
+    save_operand(dest, val);
+    Py_DECREF(val);
+}
+
+WASM_EXPORT
+uint32_t CPW_i32_from_u32(size_t i) {
+    CPW_LOG("CPW_i32_from_u32(i = %zu)\n", i);
+    return (uint32_t) operand_as_unsigned(i, UINT32_MAX, "u32");
+}
+
+WASM_EXPORT
+uint32_t CPW_i32_from_s32(size_t i) {
+    CPW_LOG("CPW_i32_from_s32(i = %zu)\n", i);
+    return (uint32_t) operand_as_signed(i, INT32_MIN, INT32_MAX, "s32");
+}
+
+WASM_EXPORT
+uint32_t CPW_i32_from_u16(size_t i) {
+    CPW_LOG("CPW_i32_from_u16(i = %zu)\n", i);
+    return (uint32_t) operand_as_unsigned(i, UINT16_MAX, "u16");
+}
+
+WASM_EXPORT
+uint32_t CPW_i32_from_s16(size_t i) {
+    CPW_LOG("CPW_i32_from_s16(i = %zu)\n", i);
+    return (uint32_t) operand_as_signed(i, INT16_MIN, INT16_MAX, "s16");
+}
+
+WASM_EXPORT
+uint32_t CPW_i32_from_u8(size_t i) {
+    CPW_LOG("CPW_i32_from_u8(i = %zu)\n", i);
+    return (uint32_t) operand_as_unsigned(i, UINT8_MAX, "u8");
+}
+
+WASM_EXPORT
+uint32_t CPW_i32_from_s8(size_t i) {
+    CPW_LOG("CPW_i32_from_s8(i = %zu)\n", i);
+    return (uint32_t) operand_as_signed(i, INT8_MIN, INT8_MAX, "s8");
+}
+
+WASM_EXPORT
+void CPW_u32_from_i32(uint32_t x, size_t dest) {
+    CPW_LOG("CPW_u32_from_i32(x = %u, dest = %zu)\n", x, dest);
+    save_new_operand(dest, PyLong_FromUnsignedLong(x), "failed to allocate integer");
+}
+
+WASM_EXPORT
+void CPW_s32_from_i32(int32_t x, size_t dest) {
+    CPW_LOG("CPW_s32_from_i32(x = %d, dest = %zu)\n", x, dest);
+    save_new_operand(dest, PyLong_FromLong(x), "failed to allocate integer");
+}
+
+WASM_EXPORT
+uint64_t CPW_i64_from_u64(size_t i) {
+    CPW_LOG("CPW_i64_from_u64(i = %zu)\n", i);
+    return (uint64_t) operand_as_unsigned(i, UINT64_MAX, "u64");
+}
+
+WASM_EXPORT
+uint64_t CPW_i64_from_s64(size_t i) {
+    CPW_LOG("CPW_i64_from_s64(i = %zu)\n", i);
+    return (uint64_t) operand_as_signed(i, INT64_MIN, INT64_MAX, "s64");
+}
+
+WASM_EXPORT
+void CPW_u64_from_i64(uint64_t x, size_t dest) {
+    CPW_LOG("CPW_u64_from_i64(x = %llu, dest = %zu)\n", (unsigned long long) x, dest);
+    save_new_operand(dest, PyLong_FromUnsignedLongLong(x), "failed to allocate integer");
+}
+
+WASM_EXPORT
+void CPW_s64_from_i64(int64_t x, size_t dest) {
+    CPW_LOG("CPW_s64_from_i64(x = %lld, dest = %zu)\n", (long long) x, dest);
+    save_new_operand(dest, PyLong_FromLongLong(x), "failed to allocate integer");
+}
+
+WASM_EXPORT
+double CPW_f64_from_float(size_t i) {
+    CPW_LOG("CPW_f64_from_float(i = %zu)\n", i);
+
+    double x = PyFloat_AsDouble(operand(i));
+    if (x == -1.0 && PyErr_Occurred()) {
+        cpw_abort("failed to convert value to a float");
+    }
+    return x;
+}
+
+WASM_EXPORT
+void CPW_float_from_f64(double x, size_t dest) {
+    CPW_LOG("CPW_float_from_f64(x = %f, dest = %zu)\n", x, dest);
+    save_new_operand(dest, PyFloat_FromDouble(x), "failed to allocate float");
+}
+
+WASM_EXPORT
+uint32_t CPW_i32_from_char(size_t i) {
+    CPW_LOG("CPW_i32_from_char(i = %zu)\n", i);
+
+    PyObject* str = operand(i);
+    if (!PyUnicode_Check(str) || PyUnicode_GetLength(str) != 1) {
+        PyErr_Format(PyExc_TypeError,
+                     "expected a str of length 1, got '%.200s'",
+                     Py_TYPE(str)->tp_name);
+        cpw_abort("value is not a char");
+    }
+    Py_UCS4 c = PyUnicode_ReadChar(str, 0);
+    // Lone surrogates are valid in a Python `str`, but aren't Unicode scalar
+    // values.
+    if (c >= 0xD800 && c <= 0xDFFF) {
+        PyErr_SetString(PyExc_ValueError, "surrogates are not valid chars");
+        cpw_abort("value is not a char");
+    }
+    return (uint32_t) c;
+}
+
+WASM_EXPORT
+void CPW_char_from_i32(uint32_t c, size_t dest) {
+    CPW_LOG("CPW_char_from_i32(c = %u, dest = %zu)\n", c, dest);
+
+    if (c > 0x10FFFF || (c >= 0xD800 && c <= 0xDFFF)) {
+        cpw_abort("invalid char");
+    }
+    Py_UCS4 ch = (Py_UCS4) c;
+    save_new_operand(dest,
+                     PyUnicode_FromKindAndData(PyUnicode_4BYTE_KIND, &ch, 1),
+                     "failed to create Python string from char");
+}
+
+WASM_EXPORT
+void CPW_string_canon_lower(uint32_t* ret_ptr, size_t i) {
+    CPW_LOG("CPW_string_canon_lower(ret_ptr = %p, i = %zu)\n", (void*) ret_ptr, i);
+
+    PyObject* str = operand(i);
+    if (!PyUnicode_Check(str)) {
+        PyErr_Format(PyExc_TypeError, "expected str, got '%.200s'", Py_TYPE(str)->tp_name);
+        cpw_abort("value is not a string");
+    }
+
+    Py_ssize_t len = 0;
+    const char* utf8 = PyUnicode_AsUTF8AndSize(str, &len);
+    if (!utf8) {
+        cpw_abort("failed to encode string as UTF-8");
+    }
+
+    // The canonical ABI owns this copy, not the Python string object.
+    char* ptr = malloc(len == 0 ? 1 : (size_t) len);
+    if (!ptr) {
+        cpw_abort("out of memory");
+    }
+    memcpy(ptr, utf8, (size_t) len);
+
+    ret_ptr[0] = (uint32_t) (uintptr_t) ptr;
+    ret_ptr[1] = (uint32_t) len;
+}
+
+WASM_EXPORT
+void CPW_string_canon_lift(char* ptr, size_t len, size_t dest) {
+    CPW_LOG("CPW_string_canon_lift(ptr = %p, len = %zu, dest = %zu)\n", (void*) ptr, len, dest);
+
+    PyObject* str = PyUnicode_DecodeUTF8(ptr, (Py_ssize_t) len, NULL);
+    if (!str) {
+        cpw_abort("failed to create Python string from UTF-8 buffer");
+    }
+    save_operand(dest, str);
+    Py_DECREF(str);
+}
+
+WASM_EXPORT
+uint32_t CPW_spread_into_list(size_t i) {
+    CPW_LOG("CPW_spread_into_list(i = %zu)\n", i);
+
+    PyObject* iterable = operand(i);
+    if (PyList_Check(iterable)) {
+        return (uint32_t) PyList_GET_SIZE(iterable);
+    }
+
+    PyObject* list = PySequence_List(iterable);
+    if (!list) {
+        cpw_abort("failed to convert operand value to a list");
+    }
+    save_operand(i, list);
+    Py_DECREF(list);
+
+    return (uint32_t) PyList_GET_SIZE(list);
+}
+
+WASM_EXPORT
+void CPW_get_list_element(size_t list, size_t index, size_t dest) {
+    CPW_LOG("CPW_get_list_element(list = %zu, index = %zu, dest = %zu)\n", list, index, dest);
+
+    PyObject* list_obj = operand(list);
+    assert(PyList_Check(list_obj));
+    assert(index < (size_t) PyList_GET_SIZE(list_obj));
+    save_operand(dest, PyList_GET_ITEM(list_obj, (Py_ssize_t) index));
+}
+
+WASM_EXPORT
+void CPW_new_list(size_t dest) {
+    CPW_LOG("CPW_new_list(dest = %zu)\n", dest);
+
+    PyObject* list = PyList_New(0);
+    if (!list) {
+        cpw_abort("failed to allocate a new Python list");
+    }
+    save_operand(dest, list);
+    Py_DECREF(list);
+}
+
+WASM_EXPORT
+void CPW_list_append(size_t list, size_t elem) {
+    CPW_LOG("CPW_list_append(list = %zu, elem = %zu)\n", list, elem);
+
+    PyObject* list_obj = operand(list);
+    assert(PyList_Check(list_obj));
+    if (PyList_Append(list_obj, operand(elem)) < 0) {
+        cpw_abort("failed to append to Python list");
+    }
+}
+
+WASM_EXPORT
+void CPW_new_record(size_t dest) {
+    CPW_LOG("CPW_new_record(dest = %zu)\n", dest);
+
+    PyObject* types = PyImport_ImportModule("types");
+    if (!types) {
+        cpw_abort("failed to import `types`");
+    }
+    PyObject* record = PyObject_CallMethod(types, "SimpleNamespace", NULL);
+    Py_DECREF(types);
+    save_new_operand(dest, record, "failed to allocate a new record");
+}
+
+/**
+ * Create a new Python string for the attribute name `name`.
+ */
+static PyObject* attr_name(const char* name, size_t name_len) {
+    PyObject* str = PyUnicode_DecodeUTF8(name, (Py_ssize_t) name_len, NULL);
+    if (!str) {
+        cpw_abort("failed to decode attribute name");
+    }
+    return str;
+}
+
+WASM_EXPORT
+void CPW_get_attr(size_t obj, const char* name, size_t name_len, size_t dest) {
+    CPW_LOG("CPW_get_attr(obj = %zu, name = \"%.*s\", dest = %zu)\n",
+            obj,
+            (int) name_len,
+            name,
+            dest);
+
+    PyObject* attr = attr_name(name, name_len);
+    PyObject* val = PyObject_GetAttr(operand(obj), attr);
+    Py_DECREF(attr);
+    save_new_operand(dest, val, "failed to get record field");
+}
+
+WASM_EXPORT
+void CPW_set_attr(size_t obj, const char* name, size_t name_len, size_t val) {
+    CPW_LOG("CPW_set_attr(obj = %zu, name = \"%.*s\", val = %zu)\n",
+            obj,
+            (int) name_len,
+            name,
+            val);
+
+    PyObject* attr = attr_name(name, name_len);
+    int ret = PyObject_SetAttr(operand(obj), attr, operand(val));
+    Py_DECREF(attr);
+    if (ret < 0) {
+        cpw_abort("failed to set record field");
+    }
+}
+
+WASM_EXPORT
+void CPW_get_tuple_element(size_t tuple, size_t index, size_t dest) {
+    CPW_LOG("CPW_get_tuple_element(tuple = %zu, index = %zu, dest = %zu)\n", tuple, index, dest);
+
+    PyObject* elem = PySequence_GetItem(operand(tuple), (Py_ssize_t) index);
+    save_new_operand(dest, elem, "failed to get tuple element");
+}
+
+WASM_EXPORT
+void CPW_new_tuple(size_t len, size_t dest) {
+    CPW_LOG("CPW_new_tuple(len = %zu, dest = %zu)\n", len, dest);
+
+    // Fill the tuple with `None` so that it is always valid, even before its
+    // elements are set.
+    PyObject* tuple = PyTuple_New((Py_ssize_t) len);
+    if (!tuple) {
+        cpw_abort("failed to allocate a new tuple");
+    }
+    for (size_t i = 0; i < len; i++) {
+        Py_INCREF(Py_None);
+        PyTuple_SET_ITEM(tuple, (Py_ssize_t) i, Py_None);
+    }
+    save_new_operand(dest, tuple, "failed to allocate a new tuple");
+}
+
+WASM_EXPORT
+void CPW_set_tuple_element(size_t tuple, size_t index, size_t val) {
+    CPW_LOG("CPW_set_tuple_element(tuple = %zu, index = %zu, val = %zu)\n", tuple, index, val);
+
+    PyObject* tuple_obj = operand(tuple);
+    assert(PyTuple_Check(tuple_obj));
+    assert(index < (size_t) PyTuple_GET_SIZE(tuple_obj));
+    PyObject* elem = operand(val);
+    PyObject* old = PyTuple_GET_ITEM(tuple_obj, (Py_ssize_t) index);
+    Py_INCREF(elem);
+    PyTuple_SET_ITEM(tuple_obj, (Py_ssize_t) index, elem);
+    Py_DECREF(old);
+}
+
+WASM_EXPORT
+uint32_t CPW_flags_lower(size_t i, size_t num_flags, size_t chunk) {
+    CPW_LOG("CPW_flags_lower(i = %zu, num_flags = %zu, chunk = %zu)\n", i, num_flags, chunk);
+
+    assert(num_flags <= 64 && "flags with more than 64 flags are lowered in chunks");
+    unsigned long long max = num_flags == 64 ? UINT64_MAX : (1ULL << num_flags) - 1;
+    unsigned long long bits = operand_as_unsigned(i, max, "flags");
+    return (uint32_t) (bits >> (32 * chunk));
+}
+
+WASM_EXPORT
+void CPW_flags_lift(uint32_t bits, size_t chunk, size_t dest) {
+    CPW_LOG("CPW_flags_lift(bits = %u, chunk = %zu, dest = %zu)\n", bits, chunk, dest);
+
+    PyObject* val = PyLong_FromUnsignedLong(bits);
+    if (!val) {
+        cpw_abort("failed to allocate integer");
+    }
+    if (chunk == 0) {
+        save_new_operand(dest, val, "failed to allocate integer");
+        return;
+    }
+
+    // Shift this chunk into place and combine it with the previous chunks.
+    PyObject* shift = PyLong_FromSize_t(32 * chunk);
+    PyObject* shifted = shift ? PyNumber_Lshift(val, shift) : NULL;
+    Py_DECREF(val);
+    Py_XDECREF(shift);
+    if (!shifted) {
+        cpw_abort("failed to shift flags");
+    }
+    PyObject* combined = PyNumber_Or(operand(dest), shifted);
+    Py_DECREF(shifted);
+    save_new_operand(dest, combined, "failed to combine flags");
+}
+
+/**
+ * The Python representation of a WIT variant. Must be kept in sync with
+ * `VariantRepr` in `wit_bindgen_gen_cpython`.
+ */
+typedef enum {
+    // `True` or `False`.
+    VARIANT_REPR_BOOL = 0,
+    // `None` for `none`, otherwise the payload itself.
+    VARIANT_REPR_OPTION = 1,
+    // The case name as a `str`.
+    VARIANT_REPR_ENUM = 2,
+    // A `("case-name", payload)` tuple.
+    VARIANT_REPR_TAGGED = 3,
+} VariantRepr;
+
+/**
+ * Find the index of `tag` within the `num_cases` NUL-terminated case names laid
+ * out back to back in `case_names`.
+ */
+static uint32_t find_case(PyObject* tag, const char* case_names, size_t num_cases) {
+    if (!PyUnicode_Check(tag)) {
+        PyErr_Format(PyExc_TypeError,
+                     "expected a str variant case name, got '%.200s'",
+                     Py_TYPE(tag)->tp_name);
+        cpw_abort("variant case name is not a string");
+    }
+
+    const char* case_name = case_names;
+    for (size_t i = 0; i < num_cases; i++) {
+        if (PyUnicode_CompareWithASCIIString(tag, case_name) == 0) {
+            return (uint32_t) i;
+        }
+        case_name += strlen(case_name) + 1;
+    }
+
+    PyErr_Format(PyExc_ValueError, "invalid variant case name: %R", tag);
+    cpw_abort("invalid variant case name");
+}
+
+/**
+ * Get the `("case-name", payload)` tuple at `operands[i]` as a sequence.
+ */
+static PyObject* tagged_variant(size_t i) {
+    PyObject* seq = PySequence_Fast(operand(i), "variant value is not a sequence");
+    if (!seq) {
+        cpw_abort("variant value is not a (case name, payload) pair");
+    }
+    if (PySequence_Fast_GET_SIZE(seq) != 2) {
+        PyErr_Format(PyExc_ValueError,
+                     "expected a (case name, payload) pair, got %zd elements",
+                     PySequence_Fast_GET_SIZE(seq));
+        cpw_abort("variant value is not a (case name, payload) pair");
+    }
+    return seq;
+}
+
+WASM_EXPORT
+uint32_t CPW_variant_discriminant(size_t i,
+                                  VariantRepr repr,
+                                  const char* case_names,
+                                  size_t num_cases) {
+    CPW_LOG("CPW_variant_discriminant(i = %zu, repr = %u, case_names = %p, num_cases = %zu)\n",
+            i,
+            (uint32_t) repr,
+            (void*) case_names,
+            num_cases);
+
+    PyObject* val = operand(i);
+    switch (repr) {
+    case VARIANT_REPR_BOOL: {
+        int truthy = PyObject_IsTrue(val);
+        if (truthy < 0) {
+            cpw_abort("failed to convert value to a bool");
+        }
+        return (uint32_t) truthy;
+    }
+    case VARIANT_REPR_OPTION:
+        return val == Py_None ? 0 : 1;
+    case VARIANT_REPR_ENUM:
+        return find_case(val, case_names, num_cases);
+    case VARIANT_REPR_TAGGED: {
+        PyObject* seq = tagged_variant(i);
+        uint32_t discriminant = find_case(PySequence_Fast_GET_ITEM(seq, 0), case_names, num_cases);
+        Py_DECREF(seq);
+        return discriminant;
+    }
+    default:
+        cpw_abort("unknown variant representation");
+    }
+}
+
+WASM_EXPORT
+void CPW_variant_payload(size_t i, VariantRepr repr, size_t dest) {
+    CPW_LOG("CPW_variant_payload(i = %zu, repr = %u, dest = %zu)\n", i, (uint32_t) repr, dest);
+
+    switch (repr) {
+    case VARIANT_REPR_OPTION:
+        save_operand(dest, operand(i));
+        break;
+    case VARIANT_REPR_TAGGED: {
+        PyObject* seq = tagged_variant(i);
+        save_operand(dest, PySequence_Fast_GET_ITEM(seq, 1));
+        Py_DECREF(seq);
+        break;
+    }
+    default:
+        cpw_abort("variant representation does not have a payload");
+    }
+}
+
+WASM_EXPORT
+void CPW_variant_lift(VariantRepr repr,
+                      uint32_t discriminant,
+                      const char* case_name,
+                      size_t case_name_len,
+                      size_t payload,
+                      size_t dest) {
+    CPW_LOG("CPW_variant_lift(repr = %u, discriminant = %u, case_name = %p, case_name_len = %zu, payload = %zu, dest = %zu)\n",
+            (uint32_t) repr,
+            discriminant,
+            (void*) case_name,
+            case_name_len,
+            payload,
+            dest);
+
+    // `SIZE_MAX` means that this case does not have a payload.
+    PyObject* payload_obj = payload == SIZE_MAX ? Py_None : operand(payload);
+
+    switch (repr) {
+    case VARIANT_REPR_BOOL:
+        save_operand(dest, discriminant ? Py_True : Py_False);
+        break;
+    case VARIANT_REPR_OPTION:
+        save_operand(dest, discriminant == 0 ? Py_None : payload_obj);
+        break;
+    case VARIANT_REPR_ENUM:
+        save_new_operand(dest,
+                         PyUnicode_DecodeUTF8(case_name, (Py_ssize_t) case_name_len, NULL),
+                         "failed to create Python string for enum case");
+        break;
+    case VARIANT_REPR_TAGGED: {
+        PyObject* name = PyUnicode_DecodeUTF8(case_name, (Py_ssize_t) case_name_len, NULL);
+        if (!name) {
+            cpw_abort("failed to create Python string for variant case name");
+        }
+        PyObject* tuple = PyTuple_Pack(2, name, payload_obj);
+        Py_DECREF(name);
+        save_new_operand(dest, tuple, "failed to allocate variant tuple");
+        break;
+    }
+    default:
+        cpw_abort("unknown variant representation");
+    }
+}
+
+WASM_EXPORT
+uint32_t CPW_resource_insert(size_t i) {
+    CPW_LOG("CPW_resource_insert(i = %zu)\n", i);
+
+    PyObject* obj = operand(i);
+    if (FREE_RESOURCES_LEN > 0) {
+        uint32_t rep = FREE_RESOURCES[--FREE_RESOURCES_LEN];
+        Py_INCREF(obj);
+        RESOURCES.elems[rep] = obj;
+        return rep;
+    }
+
+    vector_push(&RESOURCES, obj);
+    return (uint32_t) (RESOURCES.len - 1);
+}
+
+WASM_EXPORT
+void CPW_resource_get(uint32_t rep, size_t dest) {
+    CPW_LOG("CPW_resource_get(rep = %u, dest = %zu)\n", rep, dest);
+
+    if (rep >= RESOURCES.len || !RESOURCES.elems[rep]) {
+        cpw_abort("invalid resource representation");
+    }
+    save_operand(dest, RESOURCES.elems[rep]);
+}
+
+WASM_EXPORT
+void CPW_resource_drop(uint32_t rep, const char* drop_func_name, size_t drop_func_name_len) {
+    CPW_LOG("CPW_resource_drop(rep = %u, drop_func_name = \"%.*s\")\n",
+            rep,
+            (int) drop_func_name_len,
+            drop_func_name);
+
+    if (rep >= RESOURCES.len || !RESOURCES.elems[rep]) {
+        cpw_abort("invalid resource representation");
+    }
+
+    // Take the resource table's reference to the object.
+    PyObject* obj = RESOURCES.elems[rep];
+    RESOURCES.elems[rep] = NULL;
+    if (FREE_RESOURCES_LEN == FREE_RESOURCES_CAP) {
+        size_t cap = FREE_RESOURCES_CAP == 0 ? 8 : FREE_RESOURCES_CAP * 2;
+        uint32_t* free_resources = realloc(FREE_RESOURCES, cap * sizeof(uint32_t));
+        if (!free_resources) {
+            cpw_abort("failed to reserve capacity for the free resources");
+        }
+        FREE_RESOURCES = free_resources;
+        FREE_RESOURCES_CAP = cap;
+    }
+    FREE_RESOURCES[FREE_RESOURCES_LEN++] = rep;
+
+    // If the user module defines a destructor for this resource, give it a
+    // chance to clean up after the object.
+    PyObject* name = attr_name(drop_func_name, drop_func_name_len);
+    PyObject* drop_func = PyObject_GetAttr(cpw_get_user_module(), name);
+    Py_DECREF(name);
+    if (!drop_func) {
+        if (!PyErr_ExceptionMatches(PyExc_AttributeError)) {
+            cpw_abort("failed to get resource destructor");
+        }
+        PyErr_Clear();
+        Py_DECREF(obj);
+        return;
+    }
+
+    PyObject* result = PyObject_CallOneArg(drop_func, obj);
+    Py_DECREF(drop_func);
+    Py_DECREF(obj);
+    if (!result) {
+        cpw_abort("calling resource destructor failed");
+    }
+    Py_DECREF(result);
+}
@@ -0,0 +1,10 @@
+#ifndef _cpw_abort_h
+#define _cpw_abort_h
+
+/**
+ * Print the given error message, along with the pending Python exception if
+ * there is one, and abort.
+ */
+void cpw_abort(const char* msg) __attribute__((noreturn));
+
+#endif // _cpw_abort_h
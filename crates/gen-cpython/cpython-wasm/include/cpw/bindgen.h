@@ -0,0 +1,10 @@
+#ifndef _cpw_bindgen_h
+#define _cpw_bindgen_h
+
+#define PY_SSIZE_T_CLEAN
+#include <Python.h>
+
+void cpw_init_user_module(PyObject* user_module);
+PyObject* cpw_get_user_module(void);
+
+#endif // _cpw_bindgen_h
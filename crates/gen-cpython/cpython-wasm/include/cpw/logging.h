@@ -0,0 +1,15 @@
+#ifndef _cpw_logging_h
+#define _cpw_logging_h
+
+#if LOGGING==1
+
+#include <stdio.h>
+#define CPW_LOG(msg, ...) fprintf(stderr, msg, ##__VA_ARGS__)
+
+#else // LOGGING==1
+
+#define CPW_LOG(msg, ...) do { } while(0)
+
+#endif // LOGGING==1
+
+#endif // _cpw_logging_h
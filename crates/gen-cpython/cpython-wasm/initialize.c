@@ -0,0 +1,217 @@
+/*!
+ * Python interpreter initialization and top-level evaluation.
+ *
+ * This file contains the code to start up the interpreter, define import-able
+ * modules from the glue module's functions, and evaluate the user's Python.
+ */
+
+#include <assert.h>
+#include <stdbool.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "cpw/abort.h"
+#include "cpw/bindgen.h"
+#include "cpw/logging.h"
+#include "cpw/wasm.h"
+
+/**
+ * Where the standard library is found inside the WASI filesystem. Whoever
+ * instantiates `cpython.wasm` must preopen the `lib` directory produced by the
+ * `Makefile` at this path.
+ */
+#define CPW_STDLIB_PATH L"/lib/python3.11"
+
+static bool INITIALIZED = false;
+
+/**
+ * Initialize the Python interpreter.
+ */
+WASM_EXPORT
+void CPW_initialize_interpreter(void) {
+    assert(!INITIALIZED);
+
+    PyConfig config;
+    PyConfig_InitIsolatedConfig(&config);
+
+    // There are no signals in WASI, we never want to write `*.pyc` files into
+    // the (likely read-only) preopened standard library, and we don't want
+    // `site` poking around the filesystem looking for `site-packages`.
+    config.install_signal_handlers = 0;
+    config.write_bytecode = 0;
+    config.site_import = 0;
+
+    PyStatus status = PyConfig_SetString(&config, &config.program_name, L"cpython.wasm");
+    if (PyStatus_Exception(status)) {
+        goto fail;
+    }
+
+    config.module_search_paths_set = 1;
+    status = PyWideStringList_Append(&config.module_search_paths, CPW_STDLIB_PATH);
+    if (PyStatus_Exception(status)) {
+        goto fail;
+    }
+
+    status = Py_InitializeFromConfig(&config);
+    if (PyStatus_Exception(status)) {
+        goto fail;
+    }
+    PyConfig_Clear(&config);
+
+    INITIALIZED = true;
+    return;
+
+  fail:
+    PyConfig_Clear(&config);
+    fprintf(stderr, "Error: %s\n", status.err_msg ? status.err_msg : "unknown error");
+    cpw_abort("initializing the Python interpreter failed");
+}
+
+/**
+ * A synthesized module that exports the glue module's import functions.
+ */
+typedef struct {
+    PyObject* module;
+} ModuleBuilder;
+
+WASM_EXPORT
+ModuleBuilder* CPW_new_module_builder(char* module_name, size_t module_name_len) {
+    assert(INITIALIZED);
+
+    PyObject* name = PyUnicode_DecodeUTF8(module_name, (Py_ssize_t) module_name_len, NULL);
+    free(module_name);
+    if (!name) {
+        cpw_abort("failed to decode module name");
+    }
+
+    ModuleBuilder* builder = malloc(sizeof(ModuleBuilder));
+    if (!builder) {
+        cpw_abort("failed to create new ModuleBuilder");
+    }
+    builder->module = PyModule_NewObject(name);
+    Py_DECREF(name);
+    if (!builder->module) {
+        cpw_abort("failed to create new Python module");
+    }
+
+    return builder;
+}
+
+/**
+ * Add an exported function to this module and take ownership of `func_name`.
+ *
+ * `func` is a `METH_FASTCALL` function; on Wasm, function pointers are indices
+ * into the indirect function table, which is where the glue module places its
+ * import functions.
+ */
+WASM_EXPORT
+void CPW_module_builder_add_export(ModuleBuilder* builder,
+                                   char* func_name,
+                                   size_t func_name_len,
+                                   _PyCFunctionFast func) {
+    assert(builder && "builder must not be NULL");
+    assert(func_name && "func_name must not be NULL");
+    assert(func_name_len > 0 && "func_name_len must be greater than 0");
+    assert(func && "func must not be NULL");
+
+    // The method definition must outlive the function object created from
+    // it, and that function object lives as long as the interpreter does, so
+    // intentionally leak these.
+    char* name = malloc(func_name_len + 1);
+    PyMethodDef* def = calloc(1, sizeof(PyMethodDef));
+    if (!name || !def) {
+        cpw_abort("out of memory");
+    }
+    memcpy(name, func_name, func_name_len);
+    name[func_name_len] = '\0';
+    free(func_name);
+
+    def->ml_name = name;
+    def->ml_meth = (PyCFunction)(void(*)(void)) func;
+    def->ml_flags = METH_FASTCALL;
+    def->ml_doc = NULL;
+
+    PyObject* module_name = PyModule_GetNameObject(builder->module);
+    if (!module_name) {
+        cpw_abort("failed to get module name");
+    }
+    PyObject* py_func = PyCFunction_NewEx(def, NULL, module_name);
+    Py_DECREF(module_name);
+    if (!py_func) {
+        cpw_abort("failed to create new Python function");
+    }
+
+    if (PyModule_AddObject(builder->module, name, py_func) < 0) {
+        Py_DECREF(py_func);
+        cpw_abort("failed to add function to module");
+    }
+}
+
+WASM_EXPORT
+void CPW_finish_module_builder(ModuleBuilder* builder) {
+    // Registering the module in `sys.modules` is enough to make `import
+    // ${module}` find it.
+    PyObject* modules = PyImport_GetModuleDict();
+    PyObject* name = PyModule_GetNameObject(builder->module);
+    if (!name) {
+        cpw_abort("failed to get module name");
+    }
+    if (PyDict_SetItem(modules, name, builder->module) < 0) {
+        cpw_abort("failed to register module in `sys.modules`");
+    }
+    Py_DECREF(name);
+    Py_DECREF(builder->module);
+    free(builder);
+}
+
+/**
+ * Evaluate the top level of the user's Python source as the `__main__` module.
+ *
+ * Takes ownership of its parameters.
+ */
+WASM_EXPORT
+void CPW_eval_module(char* py_file_name,
+                     size_t py_file_name_len,
+                     char* py_source,
+                     size_t py_source_len) {
+    assert(INITIALIZED);
+
+    // The compiler wants NUL-terminated strings.
+    char* file_name = malloc(py_file_name_len + 1);
+    char* source = malloc(py_source_len + 1);
+    if (!file_name || !source) {
+        cpw_abort("out of memory");
+    }
+    memcpy(file_name, py_file_name, py_file_name_len);
+    file_name[py_file_name_len] = '\0';
+    memcpy(source, py_source, py_source_len);
+    source[py_source_len] = '\0';
+    free(py_file_name);
+    free(py_source);
+
+    PyObject* code = Py_CompileString(source, file_name, Py_file_input);
+    free(source);
+    free(file_name);
+    if (!code) {
+        cpw_abort("module compilation failed");
+    }
+
+    PyObject* main = PyImport_AddModule("__main__");
+    if (!main) {
+        cpw_abort("failed to create `__main__` module");
+    }
+    PyObject* globals = PyModule_GetDict(main);
+
+    PyObject* result = PyEval_EvalCode(code, globals, globals);
+    Py_DECREF(code);
+    if (!result) {
+        cpw_abort("failed to evaluate module");
+    }
+    Py_DECREF(result);
+
+    cpw_init_user_module(main);
+
+    // Collect any garbage created while evaluating the top level so that it
+    // doesn't end up in a Wizer snapshot.
+    PyGC_Collect();
+}
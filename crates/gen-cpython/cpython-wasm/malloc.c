@@ -0,0 +1,14 @@
+#include <stdint.h>
+#include <stdlib.h>
+
+#include "cpw/abort.h"
+#include "cpw/wasm.h"
+
+WASM_EXPORT
+void* CPW_malloc(size_t size) {
+    void* p = malloc(size);
+    if (p == NULL) {
+        cpw_abort("out of memory");
+    }
+    return p;
+}
@@ -0,0 +1,3369 @@
+//! Interface types bindings generator support for `cpython.wasm`.
+
+#![deny(missing_docs)]
+
+use heck::SnakeCase;
+use lazy_static::lazy_static;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::{collections::HashMap, mem};
+use wasm_encoder::Instruction;
+use wit_bindgen_gen_core::{
+    wit_parser::{
+        abi::{self, AbiVariant, WasmSignature, WasmType},
+        Docs, Function, Interface, Record, ResourceId, SizeAlign, Type, TypeDefKind, TypeId,
+        Variant,
+    },
+    Direction, Files, Generator,
+};
+use wit_bindgen_gen_wasm_glue::{
+    convert_ty, emit_wasm_instruction, is_nullable_option, mem_arg, DataSegments, EmbeddedModule,
+    GlueFunction, InstructionSink,
+};
+
+#[allow(missing_docs)]
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "structopt", derive(structopt::StructOpt))]
+pub struct Opts {
+    /// The path to the Python module.
+    pub py: PathBuf,
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub import_cpython: bool,
+    /// The path to the `cpython.wasm` to embed in the generated module,
+    /// unless `--import-cpython` is given.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub cpython_wasm: Option<PathBuf>,
+}
+
+#[allow(missing_docs)]
+impl Opts {
+    pub fn build<'a>(self, py_source: impl Into<Cow<'a, str>>) -> CPythonWasm<'a> {
+        let mut builder = CPythonWasm::new(self.py, py_source);
+        builder.import_cpython(self.import_cpython);
+        builder
+    }
+}
+
+lazy_static! {
+    /// Functions exported from `cpython.wasm`
+    static ref CPW_EXPORTS: Vec<(&'static str, WasmSignature)> = vec![
+        (
+            "_initialize",
+            WasmSignature {
+                params: vec![],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "canonical_abi_free",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "canonical_abi_realloc",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_initialize_interpreter",
+            WasmSignature {
+                params: vec![],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_new_module_builder",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_module_builder_add_export",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_finish_module_builder",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_eval_module",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_malloc",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_fill_operands",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_clear_operands",
+            WasmSignature {
+                params: vec![],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_push_arg",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_call",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_push_return_value",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_finish_returns",
+            WasmSignature {
+                params: vec![],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i32_from_u32",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_u32_from_i32",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_string_canon_lower",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_string_canon_lift",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_spread_into_list",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_get_list_element",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_list_append",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_new_list",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i32_from_s32",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i32_from_u16",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i32_from_s16",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i32_from_u8",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i32_from_s8",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_s32_from_i32",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i64_from_u64",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I64],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i64_from_s64",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I64],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_u64_from_i64",
+            WasmSignature {
+                params: vec![WasmType::I64, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_s64_from_i64",
+            WasmSignature {
+                params: vec![WasmType::I64, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_f64_from_float",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::F64],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_float_from_f64",
+            WasmSignature {
+                params: vec![WasmType::F64, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_i32_from_char",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_char_from_i32",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_new_record",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_get_attr",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_set_attr",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_get_tuple_element",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_new_tuple",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_set_tuple_element",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_flags_lower",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_flags_lift",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_variant_discriminant",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_variant_payload",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_variant_lift",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_resource_insert",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_resource_get",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "CPW_resource_drop",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+    ];
+}
+
+/// The `cpython.wasm` bindings generator.
+///
+/// ## Code Shape
+///
+/// The output is a single Wasm file that imports and exports the functions
+/// defined in the given WIT files and additionally
+///
+/// * embeds or imports (configurable) a `cpython.wasm` instance, and
+///
+/// * exports a `wizer.initialize` function that initializes the Python
+///   interpreter and evaluates the top level of the Python module.
+///
+/// ### Initialization
+///
+/// As an API contract, the `wizer.initialize` function must be invoked before
+/// any other function. It must only be invoked once.
+///
+/// The initialization function performs the following tasks:
+///
+/// * Calls `cpython.wasm`'s `_initialize` function, which runs C global
+///   contructors.
+///
+/// * `malloc`s space in `cpython.wasm`'s linear memory and copies the Python
+///   source code from its linear memory into the malloc'd space.
+///
+/// * Registers a Python module in `sys.modules` for each imported WIT
+///   interface, whose functions are the import glue functions described below.
+///
+/// * Evaluates the Python source as the `__main__` module, compiling it to
+///   bytecode and defining top-level functions in the process.
+///
+/// ### Imports
+///
+/// By the time an imported WIT function is called, we have the following
+/// layers of code on the stack, listed from older to younger frames:
+///
+/// * User Python code (inside `cpython.wasm`'s interpreter loop)
+///
+///   This is the user's Python code that is running inside of `cpython.wasm`
+///   and which wants to call an external, imported function that is described
+///   with WIT.
+///
+/// * Import glue Wasm code (on the Wasm stack)
+///
+///   This is a synthesized Wasm function that understands both the canonical
+///   ABI and the CPython C API. It is a `METH_FASTCALL` built-in function as
+///   far as CPython is concerned. It translates outgoing arguments from Python
+///   objects into the canonical ABI representation, calls the actual imported
+///   Wasm function, and then translates the incoming results from the
+///   canonical ABI representation into Python objects.
+///
+/// * Imported function (on the Wasm Stack)
+///
+///   This is the actual Wasm function whose signature is described in WIT and
+///   uses the canonical ABI.
+///
+/// ### Exports
+///
+/// By the time an exported Python function that implements a WIT signature is
+/// called, we have the following frames on the stack, listed form older to
+/// younger frames:
+///
+/// * External caller (on the Wasm or native stack)
+///
+///   This is whoever is calling our Python-implemented WIT export, using the
+///   canonical ABI. This might be another Wasm module or it might be some
+///   native code in the host.
+///
+/// * Export glue Wasm code (on the Wasm stack)
+///
+///   This is a synthesized function that understands both the canonical ABI and
+///   the CPython C API. It translates incoming arguments from the canonical ABI
+///   representation into Python objects, calls the Python function that
+///   implements this export with those objects, and then translates the
+///   function's outgoing results from Python objects into the canonical ABI
+///   representation.
+///
+/// * Python function implementing the WIT signature (inside `cpython.wasm`'s
+///   interpreter loop)
+///
+///   This is the user-written, top-level Python function that is being
+///   exported, named after the `snake_case` version of the WIT function's
+///   name. It accepts and returns the Python values that correspond to the
+///   interface types used in the WIT signature, returning a tuple when there
+///   are multiple results.
+///
+/// ### Python Values
+///
+/// WIT values are represented in Python as follows:
+///
+/// * Integers are `int`s and floats are `float`s. Lowering an `int` that is
+///   out of range for its WIT type traps rather than wrapping it.
+///
+/// * `char`s are `str`s containing a single character.
+///
+/// * Records are `types.SimpleNamespace`s with an attribute for each field,
+///   named in `snake_case`. Any object with those attributes may be lowered as
+///   a record. Tuples are `tuple`s, and any sequence may be lowered as a tuple.
+///
+/// * Flags are `int`s where bit `i` is set if the `i`th flag is set, as with
+///   `enum.IntFlag`.
+///
+/// * `bool`s are `bool`s. An `option<T>` is `None` or a `T`, unless `T` is
+///   itself an option, in which case it is represented like any other variant.
+///   Enums are their case name as a `str`. All other variants, including
+///   `expected<T, E>`, are `("case-name", payload)` tuples, where the payload
+///   is `None` for cases without one.
+///
+/// * Handles to resources defined by an imported interface are the raw handle
+///   number. The Python module for the imported interface has a
+///   `drop_${resource}` function to release a handle when it is no longer
+///   needed.
+///
+/// * Handles to resources defined by the exported interface may be any Python
+///   object. When the last handle to a resource is dropped, the user's
+///   top-level `drop_${resource}` function is called with that object, if it
+///   exists.
+pub struct CPythonWasm<'a> {
+    /// The filename to use for the Python.
+    py_name: PathBuf,
+
+    /// The Python source code.
+    py: Cow<'a, str>,
+
+    i64_return_pointer_area_size: usize,
+
+    num_import_functions: Option<u32>,
+    num_import_glue_functions: Option<u32>,
+    num_export_functions: Option<u32>,
+
+    import_cpython: bool,
+
+    /// The `cpython.wasm` to embed, if we aren't importing it.
+    cpython_wasm: Option<Cow<'a, [u8]>>,
+
+    /// Whether the interface we are currently generating bindings for is
+    /// imported or exported.
+    in_import: bool,
+
+    /// Function types that we use in this Wasm module.
+    types: wasm_encoder::TypeSection,
+
+    /// A map from wasm signature to its index in the `self.types` types
+    /// section. We use this to reuse earlier type definitions when possible.
+    wasm_sig_to_index: HashMap<WasmSignature, u32>,
+
+    /// The imports section containing the raw canonical ABI function imports
+    /// for each imported function we are wrapping in glue.
+    imports: wasm_encoder::ImportSection,
+
+    /// The glue functions we've generated for imported canonical ABI functions
+    /// thus far.
+    import_glue_fns: Vec<wasm_encoder::Function>,
+
+    /// A map from `module_name -> func_name -> index`.
+    import_fn_name_to_index: HashMap<String, HashMap<String, u32>>,
+
+    /// The Python modules to define for imported interfaces, as a map from
+    /// `module_name -> [(func_name, import_glue_index)]`.
+    py_import_modules: HashMap<String, Vec<(String, u32)>>,
+
+    exports: wasm_encoder::ExportSection,
+
+    /// The glue functions we've generated for exported canonical ABI functions
+    /// thus far, and their type index.
+    export_glue_fns: Vec<(wasm_encoder::Function, u32)>,
+
+    data_segments: DataSegments,
+
+    /// The static strings that are copied into `cpython.wasm`'s memory during
+    /// initialization: `(offset in the glue memory, length)`. The pointer to
+    /// the `i`th string is saved in the `FIRST_STRING_GLOBAL + i` global.
+    strings: Vec<(u32, u32)>,
+
+    /// A map from a static string to its index in `strings`.
+    string_to_index: HashMap<String, u32>,
+
+    sizes: SizeAlign,
+    function_names: Vec<(u32, String)>,
+    local_names: Vec<(u32, wasm_encoder::NameMap)>,
+}
+
+impl<'a> CPythonWasm<'a> {
+    /// Construct a new `CPythonWasm` bindings generator using the given
+    /// Python module.
+    pub fn new(py_name: impl Into<PathBuf>, py: impl Into<Cow<'a, str>>) -> Self {
+        let py_name = py_name.into();
+        let py = py.into();
+        CPythonWasm {
+            py_name,
+            py,
+            i64_return_pointer_area_size: 0,
+            num_import_functions: None,
+            num_import_glue_functions: None,
+            num_export_functions: None,
+            import_cpython: false,
+            cpython_wasm: None,
+            in_import: false,
+            types: wasm_encoder::TypeSection::new(),
+            wasm_sig_to_index: Default::default(),
+            imports: wasm_encoder::ImportSection::new(),
+            import_glue_fns: Default::default(),
+            import_fn_name_to_index: Default::default(),
+            py_import_modules: Default::default(),
+            exports: wasm_encoder::ExportSection::new(),
+            export_glue_fns: Default::default(),
+            data_segments: DataSegments::new(1),
+            strings: Vec::new(),
+            string_to_index: Default::default(),
+            sizes: Default::default(),
+            function_names: Vec::new(),
+            local_names: Vec::new(),
+        }
+    }
+
+    fn abi_variant(dir: Direction) -> AbiVariant {
+        // This generator uses the obvious direction to ABI variant mapping.
+        match dir {
+            Direction::Export => AbiVariant::GuestExport,
+            Direction::Import => AbiVariant::GuestImport,
+        }
+    }
+
+    /// Configure how `cpython.wasm` is linked.
+    ///
+    /// By default, the whole `cpython.wasm` module is embedded inside our
+    /// generated glue module's `module` section, and then instantiated in the
+    /// `instance` section.
+    ///
+    /// If `import` is `true`, then `cpython.wasm` is not embedded into the
+    /// generated glue module. Instead, the glue module imports a
+    /// `cpython.wasm` instance.
+    pub fn import_cpython(&mut self, import: bool) {
+        self.import_cpython = import;
+    }
+
+    /// Provide the `cpython.wasm` module to embed in the generated glue
+    /// module.
+    ///
+    /// This is required unless `cpython.wasm` is imported.
+    pub fn cpython_wasm(&mut self, wasm: impl Into<Cow<'a, [u8]>>) {
+        self.cpython_wasm = Some(wasm.into());
+    }
+
+    fn intern_type(&mut self, wasm_sig: WasmSignature) -> u32 {
+        if let Some(idx) = self.wasm_sig_to_index.get(&&wasm_sig) {
+            return *idx;
+        }
+
+        let idx = self.types.len();
+
+        self.types.function(
+            wasm_sig.params.iter().copied().map(convert_ty),
+            wasm_sig.results.iter().copied().map(convert_ty),
+        );
+
+        self.wasm_sig_to_index.insert(wasm_sig, idx);
+
+        idx
+    }
+
+    /// Add a raw function import of `module.name` and return its function
+    /// index.
+    fn import_function(&mut self, module: &str, name: &str, wasm_sig: WasmSignature) -> u32 {
+        let type_index = self.intern_type(wasm_sig);
+        let index = self.wit_import(self.imports.len());
+        self.imports.import(
+            module,
+            Some(name),
+            wasm_encoder::EntityType::Function(type_index),
+        );
+
+        let existing = self
+            .import_fn_name_to_index
+            .entry(module.to_string())
+            .or_default()
+            .insert(name.to_string(), index);
+        assert!(existing.is_none(), "duplicate import: {}.{}", module, name);
+
+        self.function_names
+            .push((index, format!("{}.{}", module, name)));
+        index
+    }
+
+    /// Get the function index of the given `canonical_abi` resource
+    /// intrinsic.
+    fn canonical_abi_import(&self, name: &str) -> u32 {
+        *self
+            .import_fn_name_to_index
+            .get("canonical_abi")
+            .and_then(|funcs| funcs.get(name))
+            .unwrap_or_else(|| panic!("unknown `canonical_abi` import: {}", name))
+    }
+
+    /// Get the global holding a pointer to a copy of `s` in `cpython.wasm`'s
+    /// memory, along with the length of `s`.
+    ///
+    /// The copy is made once, in `wizer.initialize`, and lives forever.
+    fn string_global(&mut self, s: &str) -> (u32, u32) {
+        let len = u32::try_from(s.len()).unwrap();
+        if let Some(i) = self.string_to_index.get(s) {
+            return (FIRST_STRING_GLOBAL + *i, len);
+        }
+
+        let i = u32::try_from(self.strings.len()).unwrap();
+        let offset = self.data_segments.add(s.as_bytes().iter().copied());
+        self.strings.push((offset, len));
+        self.string_to_index.insert(s.to_string(), i);
+        (FIRST_STRING_GLOBAL + i, len)
+    }
+
+    fn link_cpython_wasm(
+        &mut self,
+        modules: &mut wasm_encoder::ModuleSection,
+        instances: &mut wasm_encoder::InstanceSection,
+        aliases: &mut wasm_encoder::AliasSection,
+    ) {
+        let mut forwarded_instances = 0;
+        if self.import_cpython {
+            // Import an instance that exports all the expected
+            // `cpython.wasm` things.
+            let exports: Vec<_> = CPW_EXPORTS
+                .iter()
+                .map(|(name, sig)| {
+                    let idx = self.intern_type(sig.clone());
+                    (*name, wasm_encoder::EntityType::Function(idx))
+                })
+                .chain(Some((
+                    "memory",
+                    wasm_encoder::EntityType::Memory(wasm_encoder::MemoryType {
+                        minimum: 0,
+                        maximum: None,
+                        memory64: false,
+                    }),
+                )))
+                .chain(Some((
+                    "__indirect_function_table",
+                    wasm_encoder::EntityType::Table(wasm_encoder::TableType {
+                        element_type: wasm_encoder::ValType::FuncRef,
+                        minimum: 0,
+                        maximum: None,
+                    }),
+                )))
+                .collect();
+            let instance_type_index = self.types.len();
+            self.types.instance(exports);
+            self.imports.import(
+                "cpython",
+                None,
+                wasm_encoder::EntityType::Instance(instance_type_index),
+            );
+        } else {
+            // Embed `cpython.wasm` in the modules section and then instantiate
+            // it. Its imports are grouped by module into instance imports of
+            // our own, which we forward along. Importing instances rather than
+            // functions leaves our function index space as-is.
+            //
+            // Our import glue functions are put in its function table by
+            // `wizer.initialize`, so no table slots are reserved for them.
+            let wasm = self.cpython_wasm.take().unwrap_or_else(|| {
+                panic!(
+                    "`cpython.wasm` must be provided with `CPythonWasm::cpython_wasm` \
+                     unless it is imported"
+                )
+            });
+            let embedded = EmbeddedModule::new("cpython.wasm", &wasm, 0)
+                .unwrap_or_else(|e| panic!("failed to embed `cpython.wasm`: {:?}", e));
+
+            let mut cpw_imports: Vec<(String, Vec<(String, WasmSignature)>)> = Vec::new();
+            for (module, name, sig) in embedded.imports {
+                match cpw_imports.iter_mut().find(|(m, _)| *m == module) {
+                    Some((_, funcs)) => funcs.push((name, sig)),
+                    None => cpw_imports.push((module, vec![(name, sig)])),
+                }
+            }
+
+            let first_instance = u32::try_from(self.import_fn_name_to_index.len()).unwrap();
+            let mut args = Vec::new();
+            for (i, (module, funcs)) in cpw_imports.iter().enumerate() {
+                let exports: Vec<_> = funcs
+                    .iter()
+                    .map(|(name, sig)| {
+                        let idx = self.intern_type(sig.clone());
+                        (name.as_str(), wasm_encoder::EntityType::Function(idx))
+                    })
+                    .collect();
+                let instance_type_index = self.types.len();
+                self.types.instance(exports);
+                self.imports.import(
+                    module,
+                    None,
+                    wasm_encoder::EntityType::Instance(instance_type_index),
+                );
+                args.push((
+                    module.as_str(),
+                    wasm_encoder::Export::Instance(first_instance + u32::try_from(i).unwrap()),
+                ));
+            }
+
+            modules.module(&embedded.module);
+            instances.instantiate(0, args);
+            forwarded_instances = cpw_imports.len();
+        }
+
+        // Regardless whether we imported an instance or instantiated an embedded
+        // module, we now have an instance of `cpython.wasm`. Alias its
+        // exported functions and exported memory into this module's index
+        // spaces. Each module we import functions from (including
+        // `canonical_abi`) is an implicit instance that precedes it, as are the
+        // instances we import to satisfy an embedded `cpython.wasm`'s imports.
+        let instance_index =
+            u32::try_from(self.import_fn_name_to_index.len() + forwarded_instances).unwrap();
+        aliases.instance_export(instance_index, wasm_encoder::ItemKind::Memory, "memory");
+        aliases.instance_export(
+            instance_index,
+            wasm_encoder::ItemKind::Table,
+            "__indirect_function_table",
+        );
+        for (name, _) in &*CPW_EXPORTS {
+            aliases.instance_export(instance_index, wasm_encoder::ItemKind::Function, name);
+            let idx = self.cpython_import(name);
+            self.function_names.push((idx, name.to_string()));
+        }
+    }
+
+    /// Malloc `size` bytes and save the result to `local`.
+    ///
+    /// Note that `CPW_malloc` will never return `NULL`.
+    ///
+    /// ```wat
+    /// (local.set ${local} (call $CPW_malloc (i32.const ${size})))
+    /// ```
+    fn malloc_static_size<'b, F>(&mut self, func: &mut F, size: u32, result_local: u32)
+    where
+        F: InstructionSink<'b>,
+    {
+        // []
+        func.instruction(Instruction::I32Const(size as _));
+        // [i32]
+        func.instruction(Instruction::Call(self.cpython_import("CPW_malloc")));
+        // [i32]
+        func.instruction(Instruction::LocalSet(result_local));
+        // []
+    }
+
+    /// Malloc `size` bytes and save the result to `local`. Trap if `malloc`
+    /// returned `NULL`.
+    ///
+    /// Note that `CPW_malloc` will never return `NULL`.
+    ///
+    /// ```wat
+    /// (local.set ${result_local} (call $malloc (local.get ${size_local})))
+    /// ```
+    fn malloc_dynamic_size<'b, F>(&mut self, func: &mut F, size_local: u32, result_local: u32)
+    where
+        F: InstructionSink<'b>,
+    {
+        // []
+        func.instruction(Instruction::LocalGet(size_local));
+        // [i32]
+        func.instruction(Instruction::Call(self.cpython_import("CPW_malloc")));
+        // [i32]
+        func.instruction(Instruction::LocalSet(result_local));
+        // []
+    }
+
+    /// Copy data from the root glue module's linear memory into
+    /// `cpython.wasm`'s linear memory:
+    ///
+    /// ```wat
+    /// (memory.copy 0 1 (local.get ${to_local})
+    ///                  (i32.const ${from_offset})
+    ///                  (i32.const ${len}))
+    /// ```
+    fn copy_to_cpw<'b, F>(&self, func: &mut F, from_offset: u32, to_local: u32, len: u32)
+    where
+        F: InstructionSink<'b>,
+    {
+        // []
+        func.instruction(Instruction::LocalGet(to_local));
+        // [i32]
+        func.instruction(Instruction::I32Const(from_offset as _));
+        // [i32 i32]
+        func.instruction(Instruction::I32Const(len as _));
+        // [i32 i32 i32]
+        func.instruction(Instruction::MemoryCopy {
+            src: GLUE_MEMORY,
+            dst: PY_MEMORY,
+        });
+        // []
+    }
+
+    fn clear_py_operands<'b, F>(&self, func: &mut F)
+    where
+        F: InstructionSink<'b>,
+    {
+        // []
+        func.instruction(Instruction::Call(self.cpython_import("CPW_clear_operands")));
+        // []
+    }
+
+    /// Define the Python glue function for dropping a handle to an imported
+    /// resource, and export it from the interface's Python module as
+    /// `drop_${resource}`:
+    ///
+    /// ```wat
+    /// (func (param $self i32) (param $args i32) (param $nargs i32) (result i32)
+    ///   (local $result i32)
+    ///   (call $CPW_fill_operands (local.get $args) (local.get $nargs) (i32.const 1))
+    ///   (call $canonical_abi.resource_drop_${resource}
+    ///     (call $CPW_i32_from_u32 (i32.const 0)))
+    ///   (local.set $result (call $CPW_finish_returns))
+    ///   (call $CPW_clear_operands)
+    ///   (local.get $result))
+    /// ```
+    fn define_resource_drop_import_glue(&mut self, iface: &Interface, name: &str) {
+        let drop_index = self.canonical_abi_import(&format!("resource_drop_{}", name));
+        let glue_index = u32::try_from(self.import_glue_fns.len()).unwrap();
+        self.py_import_modules
+            .entry(iface.name.clone())
+            .or_default()
+            .push((format!("drop_{}", name.to_snake_case()), glue_index));
+
+        let result_local = 3;
+        let mut glue = wasm_encoder::Function::new(vec![(1, wasm_encoder::ValType::I32)]);
+        // []
+        glue.instruction(&Instruction::LocalGet(1));
+        // [i32]
+        glue.instruction(&Instruction::LocalGet(2));
+        // [i32 i32]
+        glue.instruction(&Instruction::I32Const(1));
+        // [i32 i32 i32]
+        glue.instruction(&Instruction::Call(self.cpython_import("CPW_fill_operands")));
+        // []
+        glue.instruction(&Instruction::I32Const(0));
+        // [i32]
+        glue.instruction(&Instruction::Call(self.cpython_import("CPW_i32_from_u32")));
+        // [i32]
+        glue.instruction(&Instruction::Call(drop_index));
+        // []
+        glue.instruction(&Instruction::Call(
+            self.cpython_import("CPW_finish_returns"),
+        ));
+        // [i32]
+        glue.instruction(&Instruction::LocalSet(result_local));
+        // []
+        self.clear_py_operands(&mut glue);
+        // []
+        glue.instruction(&Instruction::LocalGet(result_local));
+        // [i32]
+        glue.instruction(&Instruction::End);
+
+        self.import_glue_fns.push(glue);
+    }
+
+    /// Define and export the `canonical_abi_drop_${resource}` function for an
+    /// exported resource, which is called when the last handle to the resource
+    /// is dropped:
+    ///
+    /// ```wat
+    /// (func (param $rep i32)
+    ///   (call $CPW_resource_drop (local.get $rep)
+    ///                            (global.get ${drop_func_name})
+    ///                            (i32.const ${drop_func_name.len()})))
+    /// ```
+    fn define_resource_drop_export_glue(&mut self, name: &str) {
+        let export_fn_index = self.wit_export(u32::try_from(self.export_glue_fns.len()).unwrap());
+        let export_name = format!("canonical_abi_drop_{}", name);
+        self.exports.export(
+            &export_name,
+            wasm_encoder::Export::Function(export_fn_index),
+        );
+        self.function_names.push((export_fn_index, export_name));
+
+        let type_index = self.intern_type(WasmSignature {
+            params: vec![WasmType::I32],
+            results: vec![],
+            retptr: None,
+        });
+
+        let (drop_func_name, drop_func_name_len) =
+            self.string_global(&format!("drop_{}", name.to_snake_case()));
+
+        let mut glue = wasm_encoder::Function::new(vec![]);
+        // []
+        glue.instruction(&Instruction::LocalGet(0));
+        // [i32]
+        glue.instruction(&Instruction::GlobalGet(drop_func_name));
+        // [i32 i32]
+        glue.instruction(&Instruction::I32Const(drop_func_name_len as i32));
+        // [i32 i32 i32]
+        glue.instruction(&Instruction::Call(self.cpython_import("CPW_resource_drop")));
+        // []
+        glue.instruction(&Instruction::End);
+
+        self.export_glue_fns.push((glue, type_index));
+    }
+
+    fn define_wizer_initialize(
+        &mut self,
+        funcs: &mut wasm_encoder::FunctionSection,
+        code: &mut wasm_encoder::CodeSection,
+        py_name_offset: u32,
+        py_name_len: u32,
+        py_offset: u32,
+        py_len: u32,
+    ) {
+        assert_eq!(funcs.len(), code.len());
+
+        let wizer_init_index = self.wit_import_functions_len()
+            + u32::try_from(CPW_EXPORTS.len()).unwrap()
+            + funcs.len();
+        self.function_names
+            .push((wizer_init_index, format!("wizer.initialize")));
+
+        let ty_index = self.intern_type(WasmSignature {
+            params: vec![],
+            results: vec![],
+            retptr: None,
+        });
+        funcs.function(ty_index);
+
+        let locals = vec![(8, wasm_encoder::ValType::I32)];
+        let py_name_local = 0;
+        let py_local = 1;
+        let module_name_local = 2;
+        let module_builder_local = 3;
+        let table_size_local = 4;
+        let func_name_local = 5;
+        let ret_ptr_local = 6;
+        let string_local = 7;
+
+        let mut local_names = wasm_encoder::NameMap::new();
+        local_names.append(py_name_local, "py_name");
+        local_names.append(py_local, "py");
+        local_names.append(module_name_local, "module_name");
+        local_names.append(module_builder_local, "module_builder");
+        local_names.append(table_size_local, "table_size");
+        local_names.append(func_name_local, "func_name");
+        local_names.append(ret_ptr_local, "ret_ptr");
+        local_names.append(string_local, "string");
+        self.local_names.push((wizer_init_index, local_names));
+
+        let mut wizer_init = wasm_encoder::Function::new(locals);
+
+        // Call `_initialize` because that must be called before any other
+        // exports per the WASI reactor ABI.
+        let init_index = self.cpython_import("_initialize");
+        wizer_init.instruction(&Instruction::Call(init_index));
+
+        // Malloc space in `cpython.wasm`'s linear memory for the Python file
+        // name and the Python source.
+        self.malloc_static_size(&mut wizer_init, py_name_len, py_name_local);
+        self.malloc_static_size(&mut wizer_init, py_len, py_local);
+
+        // Copy the data into the freshly allocated regions.
+        self.copy_to_cpw(&mut wizer_init, py_name_offset, py_name_local, py_name_len);
+        self.copy_to_cpw(&mut wizer_init, py_offset, py_local, py_len);
+
+        // Allocate space in the `cpython.wasm` memory for the return pointer
+        // area and save it to the return pointer global. The area is measured
+        // in `i64`s.
+        self.malloc_static_size(
+            &mut wizer_init,
+            u32::try_from(self.i64_return_pointer_area_size * 8).unwrap(),
+            ret_ptr_local,
+        );
+        // []
+        wizer_init.instruction(&Instruction::LocalGet(ret_ptr_local));
+        // [i32]
+        wizer_init.instruction(&Instruction::GlobalSet(RET_PTR_GLOBAL));
+        // []
+
+        // Copy each of our static strings into `cpython.wasm`'s memory and
+        // save the pointer to its global.
+        for (i, (offset, len)) in self.strings.clone().into_iter().enumerate() {
+            self.malloc_static_size(&mut wizer_init, len, string_local);
+            self.copy_to_cpw(&mut wizer_init, offset, string_local, len);
+            // []
+            wizer_init.instruction(&Instruction::LocalGet(string_local));
+            // [i32]
+            wizer_init.instruction(&Instruction::GlobalSet(
+                FIRST_STRING_GLOBAL + u32::try_from(i).unwrap(),
+            ));
+            // []
+        }
+
+        // Call `CPW_initialize_interpreter`:
+        //
+        //     (call $CPW_initialize_interpreter)
+        let cpw_initialize_interpreter = self.cpython_import("CPW_initialize_interpreter");
+        wizer_init.instruction(&Instruction::Call(cpw_initialize_interpreter));
+
+        // Define a Python module for each WIT module that is imported. This
+        // Python module will export each of our generated glue functions for
+        // that WIT module.
+        let cpw_new_module_builder = self.cpython_import("CPW_new_module_builder");
+        let py_import_modules = std::mem::replace(&mut self.py_import_modules, Default::default());
+        for (module, funcs) in &py_import_modules {
+            let module = module.to_snake_case();
+            // Malloc space for the module name.
+            self.malloc_static_size(
+                &mut wizer_init,
+                u32::try_from(module.len()).unwrap(),
+                module_name_local,
+            );
+
+            // Copy the module name into the malloc'd space.
+            let module_offset = self.data_segments.add(module.as_bytes().iter().copied());
+            self.copy_to_cpw(
+                &mut wizer_init,
+                module_offset,
+                module_name_local,
+                u32::try_from(module.len()).unwrap(),
+            );
+
+            // Call `CPW_new_module_builder`, passing it the module name:
+            //
+            //     (call $CPW_new_module_builder (local.get ${module_name})
+            //                                   (i32.const ${module.len()}))
+            //     local.set ${module_builder}
+            wizer_init
+                // []
+                .instruction(&Instruction::LocalGet(module_name_local))
+                // [i32]
+                .instruction(&Instruction::I32Const(i32::try_from(module.len()).unwrap()))
+                // [i32 i32]
+                .instruction(&Instruction::Call(cpw_new_module_builder))
+                // [i32]
+                .instruction(&Instruction::LocalSet(module_builder_local));
+            // []
+
+            // Grow enough space in the function table for the functions we will
+            // add to it. Check for failure to allocate and trap if so.
+            //
+            //     (table.grow (ref.null) (i32.const ${funcs.len()}))
+            //     local.tee ${table_size}
+            //     i32.const -1
+            //     i32.eq
+            //     if
+            //       unreachable
+            //     end
+            wizer_init
+                // []
+                .instruction(&Instruction::RefNull(wasm_encoder::ValType::FuncRef))
+                // [funcref]
+                .instruction(&Instruction::I32Const(i32::try_from(funcs.len()).unwrap()))
+                // [funcref i32]
+                .instruction(&Instruction::TableGrow { table: 0 })
+                // [i32]
+                .instruction(&Instruction::LocalTee(table_size_local))
+                // [i32]
+                .instruction(&Instruction::I32Const(-1))
+                // [i32 i32]
+                .instruction(&Instruction::I32Eq)
+                // [i32]
+                .instruction(&Instruction::If(wasm_encoder::BlockType::Empty))
+                // []
+                .instruction(&Instruction::Unreachable)
+                // []
+                .instruction(&Instruction::End);
+            // []
+
+            for (i, (func, glue_index)) in funcs.iter().enumerate() {
+                // Malloc space for the function's name.
+                self.malloc_static_size(
+                    &mut wizer_init,
+                    u32::try_from(func.len()).unwrap(),
+                    func_name_local,
+                );
+
+                // Copy the function's name into the malloc'd space.
+                let func_name_offset = self.data_segments.add(func.as_bytes().iter().copied());
+                self.copy_to_cpw(
+                    &mut wizer_init,
+                    func_name_offset,
+                    func_name_local,
+                    u32::try_from(func.len()).unwrap(),
+                );
+
+                // Set `table[orig_size + i]` to our synthesized import glue
+                // function:
+                //
+                //     (table.set (i32.add (i32.const ${i}) (local.get ${table_size}))
+                //                (ref.func ${glue_func_index}))
+                let glue_func_index = self.wit_import_glue_fn(*glue_index);
+                wizer_init
+                    // []
+                    .instruction(&Instruction::I32Const(i32::try_from(i).unwrap()))
+                    // [i32]
+                    .instruction(&Instruction::LocalGet(table_size_local))
+                    // [i32 i32]
+                    .instruction(&Instruction::I32Add)
+                    // [i32]
+                    .instruction(&Instruction::RefFunc(glue_func_index))
+                    // [i32 funcref]
+                    .instruction(&Instruction::TableSet { table: 0 });
+                // []
+
+                // Call `CPW_module_builder_add_export` passing the index of the
+                // function that we just inserted into the table:
+                //
+                //     (call $CPW_module_builder_add_export (local.get ${module_builder})
+                //                                          (local.get ${func_name})
+                //                                          (i32.const ${func.len()})
+                //                                          (i32.add (i32.const ${i}) (local.get ${table_size})))
+                let cpw_module_builder_add_export =
+                    self.cpython_import("CPW_module_builder_add_export");
+                wizer_init
+                    // []
+                    .instruction(&Instruction::LocalGet(module_builder_local))
+                    // [i32]
+                    .instruction(&Instruction::LocalGet(func_name_local))
+                    // [i32 i32]
+                    .instruction(&Instruction::I32Const(i32::try_from(func.len()).unwrap()))
+                    // [i32 i32 i32]
+                    .instruction(&Instruction::I32Const(i32::try_from(i).unwrap()))
+                    // [i32 i32 i32 i32]
+                    .instruction(&Instruction::LocalGet(table_size_local))
+                    // [i32 i32 i32 i32 i32]
+                    .instruction(&Instruction::I32Add)
+                    // [i32 i32 i32 i32]
+                    .instruction(&Instruction::Call(cpw_module_builder_add_export));
+                // []
+            }
+
+            // Call `CPW_finish_module_builder` to register the module:
+            //
+            //     (call $CPW_finish_module_builder (local.get ${module_builder}))
+            let cpw_finish_module_builder = self.cpython_import("CPW_finish_module_builder");
+            wizer_init
+                // []
+                .instruction(&Instruction::LocalGet(module_builder_local))
+                // [i32]
+                .instruction(&Instruction::Call(cpw_finish_module_builder));
+            // []
+        }
+
+        // Call `CPW_eval_module`, passing it the Python file name and Python
+        // source:
+        //
+        //     (call $CPW_eval_module (local.get 0) (i32.const ${py_name_len})
+        //                            (local.get 1) (i32.const ${py_len}))
+        let cpw_eval_module = self.cpython_import("CPW_eval_module");
+        wizer_init
+            // []
+            .instruction(&Instruction::LocalGet(py_name_local))
+            // [i32]
+            .instruction(&Instruction::I32Const(py_name_len as i32))
+            // [i32 i32]
+            .instruction(&Instruction::LocalGet(py_local))
+            // [i32 i32 i32]
+            .instruction(&Instruction::I32Const(py_len as i32))
+            // [i32 i32 i32 i32]
+            .instruction(&Instruction::Call(cpw_eval_module));
+        // []
+
+        wizer_init.instruction(&Instruction::End);
+        code.function(&wizer_init);
+
+        self.exports.export(
+            "wizer.initialize",
+            wasm_encoder::Export::Function(wizer_init_index),
+        );
+    }
+}
+
+// ### Function Index Space
+//
+// The generated glue module's function index space is laid out as follows:
+//
+// ```text
+// |wit imports...|cpython.wasm imports...|import glue...|export glue...|wizer.initialize|
+// ```
+//
+// The WIT imports include the `canonical_abi` resource intrinsics, and the
+// import and export glue include the glue for dropping resources.
+impl CPythonWasm<'_> {
+    /// Get the number of imported WIT functions.
+    fn wit_import_functions_len(&self) -> u32 {
+        self.num_import_functions
+            .expect("must call `preprocess_all` before generating bindings")
+    }
+
+    /// Get the function index for the i^th WIT import.
+    fn wit_import(&self, i: u32) -> u32 {
+        i
+    }
+
+    /// Get the function index for the given `cpython.wasm` function.
+    fn cpython_import(&self, name: &str) -> u32 {
+        self.wit_import_functions_len()
+            + u32::try_from(
+                CPW_EXPORTS
+                    .iter()
+                    .position(|(n, _)| *n == name)
+                    .unwrap_or_else(|| panic!("unknown `cpython.wasm` export: {}", name)),
+            )
+            .unwrap()
+    }
+
+    /// Get the function index where WIT import glue functions start.
+    fn wit_import_glue_fns_start(&self) -> u32 {
+        self.wit_import_functions_len() + u32::try_from(CPW_EXPORTS.len()).unwrap()
+    }
+
+    /// Get the number of synthesized glue functions for WIT imports.
+    fn wit_import_glue_fns_len(&self) -> u32 {
+        self.num_import_glue_functions
+            .expect("must call `preprocess_all` before generating bindings")
+    }
+
+    /// Get the range of indices for our synthesized glue functions for WIT
+    /// imports.
+    fn wit_import_glue_fn_range(&self) -> Range<u32> {
+        let start = self.wit_import_glue_fns_start();
+        let end = self.wit_export_start();
+        start..end
+    }
+
+    /// Get the function index for the i^th synthesized glue function for a WIT
+    /// import.
+    fn wit_import_glue_fn(&self, i: u32) -> u32 {
+        assert!(
+            i < self.wit_import_glue_fns_len(),
+            "{} < {}",
+            i,
+            self.wit_import_glue_fns_len()
+        );
+        let start = self.wit_import_glue_fns_start();
+        start + i
+    }
+
+    /// Get the function index where WIT export glue functions start.
+    fn wit_export_start(&self) -> u32 {
+        self.wit_import_glue_fns_start() + self.wit_import_glue_fns_len()
+    }
+
+    fn wit_exports_len(&self) -> u32 {
+        self.num_export_functions
+            .expect("must call `preprocess_all` before generating bindings")
+    }
+
+    /// Get the function index for the i^th WIT export.
+    fn wit_export(&self, i: u32) -> u32 {
+        assert!(i < self.wit_exports_len());
+        self.wit_export_start() + i
+    }
+}
+
+impl Generator for CPythonWasm<'_> {
+    fn preprocess_all(&mut self, imports: &[Interface], exports: &[Interface]) {
+        assert!(
+            self.num_import_functions.is_none() && self.num_export_functions.is_none(),
+            "must call `preprocess_all` exactly once"
+        );
+        assert!(
+            exports.len() <= 1,
+            "only one exported interface is currently supported"
+        );
+        let num_functions =
+            |ifaces: &[Interface]| -> usize { ifaces.iter().map(|i| i.functions.len()).sum() };
+        let num_resources =
+            |ifaces: &[Interface]| -> usize { ifaces.iter().map(|i| i.resources.len()).sum() };
+
+        // Imported resources need a `resource_drop_*` intrinsic and exported
+        // resources need `resource_{new,get}_*` intrinsics. Imported resources
+        // additionally get a Python glue function for dropping handles, and
+        // exported resources get a `canonical_abi_drop_*` export.
+        self.num_import_functions = Some(
+            u32::try_from(
+                num_functions(imports) + num_resources(imports) + 2 * num_resources(exports),
+            )
+            .unwrap(),
+        );
+        self.num_import_glue_functions =
+            Some(u32::try_from(num_functions(imports) + num_resources(imports)).unwrap());
+        self.num_export_functions =
+            Some(u32::try_from(num_functions(exports) + num_resources(exports)).unwrap());
+
+        // Import all of the `canonical_abi` intrinsics up front, so that they
+        // are contiguous in the imports section.
+        let drop_sig = WasmSignature {
+            params: vec![WasmType::I32],
+            results: vec![],
+            retptr: None,
+        };
+        let new_get_sig = WasmSignature {
+            params: vec![WasmType::I32],
+            results: vec![WasmType::I32],
+            retptr: None,
+        };
+        for iface in imports {
+            for (_, resource) in iface.resources.iter() {
+                self.import_function(
+                    "canonical_abi",
+                    &format!("resource_drop_{}", resource.name),
+                    drop_sig.clone(),
+                );
+            }
+        }
+        for iface in exports {
+            for (_, resource) in iface.resources.iter() {
+                self.import_function(
+                    "canonical_abi",
+                    &format!("resource_new_{}", resource.name),
+                    new_get_sig.clone(),
+                );
+                self.import_function(
+                    "canonical_abi",
+                    &format!("resource_get_{}", resource.name),
+                    new_get_sig.clone(),
+                );
+            }
+        }
+
+        // Figure out what the maximum return pointer area we will need is.
+        for (iface, variant) in imports
+            .iter()
+            .zip(std::iter::repeat(AbiVariant::GuestImport))
+            .chain(
+                exports
+                    .iter()
+                    .zip(std::iter::repeat(AbiVariant::GuestExport)),
+            )
+        {
+            for func in iface.functions.iter() {
+                let sig = iface.wasm_signature(variant, func);
+                if let Some(results) = sig.retptr {
+                    self.i64_return_pointer_area_size =
+                        self.i64_return_pointer_area_size.max(results.len());
+                }
+            }
+        }
+    }
+
+    fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
+        // Canonical ABI values live in `cpython.wasm`'s memory, which is the
+        // only memory an interface may use.
+        assert!(
+            iface.memory == 0,
+            "interface `{}` uses memory {}, but only memory 0 is supported",
+            iface.name,
+            iface.memory,
+        );
+        assert!(
+            !iface.memory64,
+            "interface `{}` uses a 64-bit memory, which is not supported",
+            iface.name,
+        );
+        for (_, ty) in iface.types.iter() {
+            match ty.kind {
+                TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => panic!(
+                    "interface `{}` uses buffers, which are not supported",
+                    iface.name,
+                ),
+                _ => {}
+            }
+        }
+        self.sizes.fill(Self::abi_variant(dir), iface);
+        self.in_import = dir == Direction::Import;
+    }
+
+    // Python is dynamically typed, so there are no type definitions to emit
+    // into the glue module for any of the `type_*` hooks.
+
+    fn type_record(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _record: &Record,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_variant(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _variant: &Variant,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_resource(&mut self, iface: &Interface, ty: ResourceId) {
+        let name = &iface.resources[ty].name;
+        if self.in_import {
+            self.define_resource_drop_import_glue(iface, name);
+        } else {
+            self.define_resource_drop_export_glue(name);
+        }
+    }
+
+    fn type_alias(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_list(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pointer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _const_: bool,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+        unreachable!("we do not support the preview1 ABI")
+    }
+
+    fn type_builtin(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_push_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pull_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn import(&mut self, iface: &Interface, func: &Function) {
+        assert!(!func.is_async, "async not supported yet");
+        assert!(
+            func.abi == abi::Abi::Canonical,
+            "We only support the canonical ABI right now"
+        );
+
+        // Add the raw Wasm import.
+        let wasm_sig = iface.wasm_signature(AbiVariant::GuestImport, func);
+        self.import_function(&iface.name, &func.name, wasm_sig.clone());
+
+        // Export the glue function from the Python module for this interface.
+        let glue_index = u32::try_from(self.import_glue_fns.len()).unwrap();
+        self.py_import_modules
+            .entry(iface.name.clone())
+            .or_default()
+            .push((func.name.to_snake_case(), glue_index));
+
+        let mut bindgen = Bindgen::new(self, &wasm_sig, func, abi::LiftLower::LowerArgsLiftResults);
+        iface.call(
+            AbiVariant::GuestImport,
+            abi::LiftLower::LowerArgsLiftResults,
+            func,
+            &mut bindgen,
+        );
+        let func_encoder = bindgen.finish();
+        self.import_glue_fns.push(func_encoder);
+    }
+
+    fn export(&mut self, iface: &Interface, func: &Function) {
+        assert!(!func.is_async, "async not supported yet");
+        assert!(
+            func.abi == abi::Abi::Canonical,
+            "We only support the canonical ABI right now"
+        );
+
+        let wasm_sig = iface.wasm_signature(AbiVariant::GuestExport, func);
+        let type_index = self.intern_type(wasm_sig.clone());
+        let export_fn_index = self.wit_export(u32::try_from(self.export_glue_fns.len()).unwrap());
+        self.exports
+            .export(&func.name, wasm_encoder::Export::Function(export_fn_index));
+        self.function_names
+            .push((export_fn_index, format!("{}.{}", iface.name, func.name)));
+
+        let mut bindgen = Bindgen::new(self, &wasm_sig, func, abi::LiftLower::LiftArgsLowerResults);
+        iface.call(
+            AbiVariant::GuestExport,
+            abi::LiftLower::LiftArgsLowerResults,
+            func,
+            &mut bindgen,
+        );
+        let func_encoder = bindgen.finish();
+        self.export_glue_fns.push((func_encoder, type_index));
+    }
+
+    fn finish_one(&mut self, _iface: &Interface, _files: &mut Files) {
+        // Nothing to do until wil finish all interfaces and generate our Wasm
+        // glue code.
+    }
+
+    fn finish_all(&mut self, files: &mut Files) {
+        let mut module = wasm_encoder::Module::new();
+        let mut modules = wasm_encoder::ModuleSection::new();
+        let mut instances = wasm_encoder::InstanceSection::new();
+        let mut aliases = wasm_encoder::AliasSection::new();
+        let mut mems = wasm_encoder::MemorySection::new();
+        let mut funcs = wasm_encoder::FunctionSection::new();
+        let mut globals = wasm_encoder::GlobalSection::new();
+        let mut elems = wasm_encoder::ElementSection::new();
+        let mut code = wasm_encoder::CodeSection::new();
+
+        self.link_cpython_wasm(&mut modules, &mut instances, &mut aliases);
+
+        // Define the return pointer global.
+        globals.global(
+            wasm_encoder::GlobalType {
+                val_type: wasm_encoder::ValType::I32,
+                mutable: true,
+            },
+            &Instruction::I32Const(0),
+        );
+
+        // Define the globals for our static strings. These are initialized in
+        // `wizer.initialize`.
+        for _ in &self.strings {
+            globals.global(
+                wasm_encoder::GlobalType {
+                    val_type: wasm_encoder::ValType::I32,
+                    mutable: true,
+                },
+                &Instruction::I32Const(0),
+            );
+        }
+
+        // Re-export `cpython.wasm`'s memory and canonical ABI functions.
+        self.exports
+            .export("memory", wasm_encoder::Export::Memory(PY_MEMORY));
+        self.exports.export(
+            "canonical_abi_free",
+            wasm_encoder::Export::Function(self.cpython_import("canonical_abi_free")),
+        );
+        self.exports.export(
+            "canonical_abi_realloc",
+            wasm_encoder::Export::Function(self.cpython_import("canonical_abi_realloc")),
+        );
+
+        // Add the WIT function imports (add their import glue functions) to
+        // the module.
+        //
+        // Each of these functions has the Wasm equivalent of this function
+        // signature:
+        //
+        //     typedef PyObject *(*_PyCFunctionFast)(PyObject *self,
+        //                                           PyObject *const *args,
+        //                                           Py_ssize_t nargs);
+        let py_cfunction_fast_type_index = self.intern_type(WasmSignature {
+            params: vec![
+                // PyObject *self
+                WasmType::I32,
+                // PyObject *const *args
+                WasmType::I32,
+                // Py_ssize_t nargs
+                WasmType::I32,
+            ],
+            results: vec![
+                // PyObject *
+                WasmType::I32,
+            ],
+            retptr: None,
+        });
+        for f in &self.import_glue_fns {
+            funcs.function(py_cfunction_fast_type_index);
+            code.function(f);
+        }
+        for (f, ty_idx) in &self.export_glue_fns {
+            funcs.function(*ty_idx);
+            code.function(f);
+        }
+
+        // We will use `ref.func` to get a reference to each of our synthesized
+        // import glue functions, so we need to declare them as reference-able.
+        let func_indices: Vec<u32> = self.wit_import_glue_fn_range().collect();
+        if !func_indices.is_empty() {
+            elems.declared(
+                wasm_encoder::ValType::FuncRef,
+                wasm_encoder::Elements::Functions(&func_indices),
+            );
+        }
+
+        let py_name = self.py_name.display().to_string();
+        let py_name_offset = self.data_segments.add(py_name.as_bytes().iter().copied());
+        let py_offset = self.data_segments.add(self.py.as_bytes().iter().copied());
+
+        self.define_wizer_initialize(
+            &mut funcs,
+            &mut code,
+            py_name_offset,
+            u32::try_from(py_name.len()).unwrap(),
+            py_offset,
+            u32::try_from(self.py.len()).unwrap(),
+        );
+
+        module.section(&self.types).section(&self.imports);
+
+        if !self.import_cpython {
+            module.section(&modules).section(&instances);
+        }
+
+        mems.memory(self.data_segments.memory_type());
+        let data = self.data_segments.take_data();
+
+        // Fill out the `names` section to assist in debugging the generated
+        // wasm.
+        let mut names = wasm_encoder::NameSection::new();
+        self.function_names.sort_by_key(|a| a.0);
+        let mut function_names = wasm_encoder::NameMap::new();
+        for (i, name) in self.function_names.iter() {
+            function_names.append(*i, name);
+        }
+        names.functions(&function_names);
+
+        self.local_names.sort_by_key(|a| a.0);
+        let mut local_names = wasm_encoder::IndirectNameMap::new();
+        for (i, names) in self.local_names.iter() {
+            local_names.append(*i, names);
+        }
+        names.locals(&local_names);
+
+        let mut table_names = wasm_encoder::NameMap::new();
+        table_names.append(0, "py_function_table");
+        names.tables(&table_names);
+
+        let mut memory_names = wasm_encoder::NameMap::new();
+        memory_names.append(PY_MEMORY, "py_mem");
+        memory_names.append(GLUE_MEMORY, "glue_mem");
+        names.memories(&memory_names);
+
+        module
+            .section(&aliases)
+            .section(&funcs)
+            .section(&mems)
+            .section(&globals)
+            .section(&self.exports)
+            .section(&elems)
+            .section(&code)
+            .section(&data)
+            .section(&names);
+
+        let wasm = module.finish();
+
+        let py_file_stem = self.py_name.file_stem().unwrap_or_else(|| {
+            panic!(
+                "input Python file path does not have a file stem: {}",
+                self.py_name.display()
+            )
+        });
+        let py_file_stem = py_file_stem.to_str().unwrap_or_else(|| {
+            panic!(
+                "input Python file path is not UTF-8 representable: {}",
+                self.py_name.display()
+            )
+        });
+        let wasm_name = format!("{}.wasm", py_file_stem);
+
+        files.push(&wasm_name, &wasm);
+    }
+}
+
+const RET_PTR_GLOBAL: u32 = 0;
+const FIRST_STRING_GLOBAL: u32 = 1;
+
+const PY_MEMORY: u32 = 0;
+const GLUE_MEMORY: u32 = 1;
+
+/// How a variant is represented in Python.
+///
+/// This must be kept in sync with `VariantRepr` in `bindgen.c`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VariantRepr {
+    /// `True` or `False`.
+    Bool = 0,
+    /// `None` for `none`, otherwise the payload itself.
+    Option = 1,
+    /// The case name as a `str`.
+    Enum = 2,
+    /// A `("case-name", payload)` tuple.
+    Tagged = 3,
+}
+
+impl VariantRepr {
+    fn new(iface: &Interface, variant: &Variant, name: Option<&str>) -> Self {
+        if variant.is_bool() {
+            VariantRepr::Bool
+        } else if variant.is_enum() && name.is_some() {
+            VariantRepr::Enum
+        } else if is_nullable_option(iface, variant) {
+            VariantRepr::Option
+        } else {
+            VariantRepr::Tagged
+        }
+    }
+
+    /// Does this representation name the case of a variant value, rather than
+    /// implying it from the value's shape?
+    fn has_case_names(self) -> bool {
+        match self {
+            VariantRepr::Bool | VariantRepr::Option => false,
+            VariantRepr::Enum | VariantRepr::Tagged => true,
+        }
+    }
+}
+
+struct Bindgen<'a, 'b> {
+    gen: &'a mut CPythonWasm<'b>,
+    sig: &'a WasmSignature,
+    lift_lower: abi::LiftLower,
+    locals: Vec<wasm_encoder::ValType>,
+    py_count: u32,
+
+    blocks: Vec<Vec<Instruction<'a>>>,
+    block_results: Vec<Vec<Operand>>,
+
+    /// The `i`th Python operand that is our current iteration element, if any.
+    iter_elem: Vec<u32>,
+
+    /// The Wasm local for our current iteration's base pointer, if any.
+    iter_base_pointer: Vec<u32>,
+
+    /// The Python operands for the payloads of the variant cases whose blocks
+    /// we are currently lowering.
+    payloads: Vec<u32>,
+
+    /// Allocations to free after the call.
+    ///
+    /// `(local holding pointer, local holding length, alignment)`
+    to_free: Vec<(u32, u32, u32)>,
+}
+
+impl<'a, 'b> Bindgen<'a, 'b> {
+    fn new(
+        gen: &'a mut CPythonWasm<'b>,
+        sig: &'a WasmSignature,
+        func: &'a Function,
+        lift_lower: abi::LiftLower,
+    ) -> Self {
+        let py_count = match lift_lower {
+            abi::LiftLower::LiftArgsLowerResults => 0,
+            abi::LiftLower::LowerArgsLiftResults => u32::try_from(func.params.len()).unwrap(),
+        };
+
+        let mut insts = vec![];
+        if lift_lower == abi::LiftLower::LowerArgsLiftResults {
+            // Initialize `bindgen.c`'s Python operands vector with the
+            // arguments given to this function. Nothing checks the number of
+            // arguments given to a `METH_FASTCALL` function for us, so this
+            // also does that and is done even when there are no parameters.
+            //
+            // []
+            insts.push(Instruction::LocalGet(1));
+            // [i32]
+            insts.push(Instruction::LocalGet(2));
+            // [i32 i32]
+            insts.push(Instruction::I32Const(
+                i32::try_from(func.params.len()).unwrap(),
+            ));
+            // [i32 i32 i32]
+            insts.push(Instruction::Call(gen.cpython_import("CPW_fill_operands")));
+            // []
+        }
+
+        Bindgen {
+            gen,
+            sig,
+            lift_lower,
+            locals: vec![],
+            py_count,
+            blocks: vec![insts],
+            block_results: vec![],
+            iter_elem: vec![],
+            iter_base_pointer: vec![],
+            payloads: vec![],
+            to_free: vec![],
+        }
+    }
+
+    fn inst(&mut self, inst: Instruction<'a>) {
+        self.current_block().push(inst);
+    }
+
+    fn current_block(&mut self) -> &mut Vec<Instruction<'a>> {
+        self.blocks.last_mut().unwrap()
+    }
+
+    fn pop_block(&mut self) -> (Vec<Instruction<'a>>, Vec<Operand>) {
+        (
+            self.blocks.pop().unwrap(),
+            self.block_results.pop().unwrap(),
+        )
+    }
+
+    /// Create a new Wasm local for this function and return its index.
+    fn new_local(&mut self, ty: wasm_encoder::ValType) -> u32 {
+        let offset = match self.lift_lower {
+            abi::LiftLower::LiftArgsLowerResults => self.sig.params.len(),
+            // `_PyCFunctionFast` functions take three `i32` arguments: self,
+            // args, and nargs.
+            abi::LiftLower::LowerArgsLiftResults => 3,
+        };
+        let idx = u32::try_from(self.locals.len() + offset).unwrap();
+        self.locals.push(ty);
+        idx
+    }
+
+    /// Get the next Python operand.
+    fn next_py(&mut self) -> Operand {
+        let py = self.py_count;
+        self.py_count += 1;
+        Operand::Py(py)
+    }
+
+    /// Blocks are pushed and finished in order, one per variant case, so pop
+    /// the last `n` of them back off in that same order.
+    fn pop_blocks(&mut self, n: usize) -> Vec<(Vec<Instruction<'a>>, Vec<Operand>)> {
+        let mut blocks: Vec<_> = (0..n).map(|_| self.pop_block()).collect();
+        blocks.reverse();
+        blocks
+    }
+
+    /// Convert the Python object `py` into a new Wasm local of type `ty` by
+    /// calling the given `cpython.wasm` intrinsic and then applying `convert`
+    /// to its result.
+    fn py_to_wasm(
+        &mut self,
+        py: u32,
+        intrinsic: &str,
+        convert: Vec<Instruction<'a>>,
+        ty: wasm_encoder::ValType,
+    ) -> Operand {
+        let local = self.new_local(ty);
+
+        // []
+        self.inst(Instruction::I32Const(py as i32));
+        // [i32]
+        self.inst(Instruction::Call(self.gen.cpython_import(intrinsic)));
+        // [T]
+        for inst in convert {
+            self.inst(inst);
+        }
+        // [ty]
+        self.inst(Instruction::LocalSet(local));
+        // []
+
+        Operand::Wasm(local)
+    }
+
+    /// Convert the Wasm value in `local` into a new Python object by applying
+    /// `convert` to it and then calling the given `cpython.wasm` intrinsic.
+    fn wasm_to_py(
+        &mut self,
+        local: u32,
+        convert: Vec<Instruction<'a>>,
+        intrinsic: &str,
+    ) -> Operand {
+        let result = self.next_py();
+
+        // []
+        self.inst(Instruction::LocalGet(local));
+        // [T]
+        for inst in convert {
+            self.inst(inst);
+        }
+        // [U]
+        self.inst(Instruction::I32Const(result.unwrap_py() as i32));
+        // [U i32]
+        self.inst(Instruction::Call(self.gen.cpython_import(intrinsic)));
+        // []
+
+        result
+    }
+
+    /// Push the pointer and length of the interned string `s` onto the Wasm
+    /// stack.
+    fn push_string(&mut self, s: &str) {
+        let (global, len) = self.gen.string_global(s);
+        // []
+        self.inst(Instruction::GlobalGet(global));
+        // [i32]
+        self.inst(Instruction::I32Const(len as i32));
+        // [i32 i32]
+    }
+
+    /// Get the `name` attribute of the Python object `obj` and save it to
+    /// `dest`.
+    fn get_attr(&mut self, obj: u32, name: &str, dest: u32) {
+        // []
+        self.inst(Instruction::I32Const(obj as i32));
+        // [i32]
+        self.push_string(name);
+        // [i32 i32 i32]
+        self.inst(Instruction::I32Const(dest as i32));
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::Call(self.gen.cpython_import("CPW_get_attr")));
+        // []
+    }
+
+    /// Set the `name` attribute of the Python object `obj` to the Python
+    /// object `val`.
+    fn set_attr(&mut self, obj: u32, name: &str, val: u32) {
+        // []
+        self.inst(Instruction::I32Const(obj as i32));
+        // [i32]
+        self.push_string(name);
+        // [i32 i32 i32]
+        self.inst(Instruction::I32Const(val as i32));
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::Call(self.gen.cpython_import("CPW_set_attr")));
+        // []
+    }
+
+    /// Push the `chunk`th 32 bits of the flags `int` `py`, which has
+    /// `num_flags` flags, onto the Wasm stack.
+    fn flags_lower_chunk(&mut self, py: u32, num_flags: usize, chunk: usize) {
+        // []
+        self.inst(Instruction::I32Const(py as i32));
+        // [i32]
+        self.inst(Instruction::I32Const(num_flags as i32));
+        // [i32 i32]
+        self.inst(Instruction::I32Const(chunk as i32));
+        // [i32 i32 i32]
+        self.inst(Instruction::Call(
+            self.gen.cpython_import("CPW_flags_lower"),
+        ));
+        // [i32]
+    }
+
+    /// Pop the `chunk`th 32 bits of a flags value off of the Wasm stack and
+    /// add them to the flags `int` `dest`, which is created by the first
+    /// chunk.
+    fn flags_lift_chunk(&mut self, chunk: usize, dest: u32) {
+        // [i32]
+        self.inst(Instruction::I32Const(chunk as i32));
+        // [i32 i32]
+        self.inst(Instruction::I32Const(dest as i32));
+        // [i32 i32 i32]
+        self.inst(Instruction::Call(self.gen.cpython_import("CPW_flags_lift")));
+        // []
+    }
+
+    /// Push the discriminant of the Python object `py`, whose variant is
+    /// represented with `repr` and has the given cases, onto the Wasm stack.
+    fn variant_discriminant(&mut self, py: u32, repr: VariantRepr, case_names: &[&str]) {
+        // []
+        self.inst(Instruction::I32Const(py as i32));
+        // [i32]
+        self.inst(Instruction::I32Const(repr as i32));
+        // [i32 i32]
+        if repr.has_case_names() {
+            // The case names are laid out back to back, each terminated by a
+            // NUL, so only the pointer to the first is needed.
+            let names: String = case_names.iter().map(|n| format!("{}\0", n)).collect();
+            let (global, _len) = self.gen.string_global(&names);
+            self.inst(Instruction::GlobalGet(global));
+            // [i32 i32 i32]
+            self.inst(Instruction::I32Const(case_names.len() as i32));
+        } else {
+            self.inst(Instruction::I32Const(0));
+            // [i32 i32 i32]
+            self.inst(Instruction::I32Const(0));
+        }
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::Call(
+            self.gen.cpython_import("CPW_variant_discriminant"),
+        ));
+        // [i32]
+    }
+
+    /// Lift a variant case, represented with `repr`, into the Python object
+    /// `dest`.
+    ///
+    /// `discriminant` pushes the case's discriminant onto the Wasm stack, and
+    /// `payload` is the Python operand holding the case's lifted payload, if
+    /// any.
+    fn variant_lift(
+        &mut self,
+        repr: VariantRepr,
+        discriminant: Instruction<'a>,
+        case_name: &str,
+        payload: Option<u32>,
+        dest: u32,
+    ) {
+        // []
+        self.inst(Instruction::I32Const(repr as i32));
+        // [i32]
+        self.inst(discriminant);
+        // [i32 i32]
+        if repr.has_case_names() {
+            self.push_string(case_name);
+        } else {
+            self.inst(Instruction::I32Const(0));
+            self.inst(Instruction::I32Const(0));
+        }
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::I32Const(match payload {
+            Some(py) => py as i32,
+            // `SIZE_MAX` means there is no payload.
+            None => -1,
+        }));
+        // [i32 i32 i32 i32 i32]
+        self.inst(Instruction::I32Const(dest as i32));
+        // [i32 i32 i32 i32 i32 i32]
+        self.inst(Instruction::Call(
+            self.gen.cpython_import("CPW_variant_lift"),
+        ));
+        // []
+    }
+
+    /// Finish generating these bindings and return the encoded Wasm function.
+    fn finish(self) -> wasm_encoder::Function {
+        // TODO: Coalesce contiguous locals of the same type here into the
+        // compact encoding, like `[(i32, 3)]` rather than `[(i32, 1), (i32, 1),
+        // (i32, 1)]`.
+        let mut f = wasm_encoder::Function::new(self.locals.into_iter().map(|l| (1, l)));
+
+        // By the time we get here, we should have finished all nested blocks.
+        assert_eq!(self.blocks.len(), 1);
+
+        for inst in &self.blocks[0] {
+            f.instruction(inst);
+        }
+        f.instruction(&Instruction::End);
+        f
+    }
+}
+
+impl<'a> GlueFunction<'a> for Bindgen<'a, '_> {
+    fn inst(&mut self, inst: Instruction<'a>) {
+        Bindgen::inst(self, inst);
+    }
+
+    fn new_local(&mut self, ty: wasm_encoder::ValType) -> u32 {
+        Bindgen::new_local(self, ty)
+    }
+}
+
+/// Operands are locals that either hold the value directly or refer to an index
+/// in `bindgen.c`'s Python operand vector depending on if we're dealing with a
+/// Python or Wasm value:
+///
+/// * When we are _importing_ a function, we are lifting arguments and lowering
+///   results, so `operands` always refer to the `n`th local and `results` refer
+///   to the `n`th value in `bindgen.c`'s Python operand vector.
+///
+/// * When we are _exporting_ a function, we are lowering arguments and lifting
+///   results, so `operands` always refer to the `n`th value in `bindgen.c`'s
+///   Python operand vector and `results` always refer to the `n`th local.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    /// The `n`th Python object in `bindgen.c`'s operand vector.
+    Py(u32),
+    /// The `n`th Wasm local.
+    Wasm(u32),
+}
+
+impl Operand {
+    fn unwrap_py(&self) -> u32 {
+        match *self {
+            Operand::Py(py) => py,
+            Operand::Wasm(_) => panic!("Operand::unwrap_py on a Wasm operand"),
+        }
+    }
+    fn unwrap_wasm(&self) -> u32 {
+        match *self {
+            Operand::Wasm(w) => w,
+            Operand::Py(_) => panic!("Operand::unwrap_wasm on a Python operand"),
+        }
+    }
+}
+
+fn pop_wasm(operands: &mut Vec<Operand>) -> u32 {
+    match operands.pop() {
+        Some(op) => op.unwrap_wasm(),
+        None => panic!("`pop_wasm` with an empty stack"),
+    }
+}
+
+fn pop_py(operands: &mut Vec<Operand>) -> u32 {
+    match operands.pop() {
+        Some(op) => op.unwrap_py(),
+        None => panic!("`pop_py` with an empty stack"),
+    }
+}
+
+fn py_mem_arg(offset: u32) -> wasm_encoder::MemArg {
+    mem_arg(offset, PY_MEMORY)
+}
+
+impl abi::Bindgen for Bindgen<'_, '_> {
+    type Operand = Operand;
+
+    fn emit(
+        &mut self,
+        iface: &Interface,
+        inst: &abi::Instruction<'_>,
+        operands: &mut Vec<Self::Operand>,
+        results: &mut Vec<Self::Operand>,
+    ) {
+        match inst {
+            abi::Instruction::GetArg { nth } => {
+                let nth = u32::try_from(*nth).unwrap();
+                results.push(match self.lift_lower {
+                    abi::LiftLower::LiftArgsLowerResults => Operand::Wasm(nth),
+                    abi::LiftLower::LowerArgsLiftResults => Operand::Py(nth),
+                });
+            }
+            abi::Instruction::I32Const { .. }
+            | abi::Instruction::Bitcasts { .. }
+            | abi::Instruction::ConstZero { .. }
+            | abi::Instruction::I32Load { .. }
+            | abi::Instruction::I32Load8U { .. }
+            | abi::Instruction::I32Load8S { .. }
+            | abi::Instruction::I32Load16U { .. }
+            | abi::Instruction::I32Load16S { .. }
+            | abi::Instruction::I64Load { .. }
+            | abi::Instruction::F32Load { .. }
+            | abi::Instruction::F64Load { .. }
+            | abi::Instruction::I32Store { .. }
+            | abi::Instruction::I32Store8 { .. }
+            | abi::Instruction::I32Store16 { .. }
+            | abi::Instruction::I64Store { .. }
+            | abi::Instruction::F32Store { .. }
+            | abi::Instruction::F64Store { .. } => {
+                let locals: Vec<_> = operands.drain(..).map(|op| op.unwrap_wasm()).collect();
+                let new_locals = emit_wasm_instruction(self, inst, &locals, PY_MEMORY);
+                results.extend(new_locals.into_iter().map(Operand::Wasm));
+            }
+
+            // Each integer type has its own intrinsic, which traps if the
+            // `int` is out of that type's range.
+            abi::Instruction::I32FromChar => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_char", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::I64FromU64 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i64_from_u64", vec![], wasm_encoder::ValType::I64);
+                results.push(result);
+            }
+            abi::Instruction::I64FromS64 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i64_from_s64", vec![], wasm_encoder::ValType::I64);
+                results.push(result);
+            }
+            abi::Instruction::I32FromU32 | abi::Instruction::I32FromUsize => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_u32", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::I32FromS32 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_s32", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::I32FromU16 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_u16", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::I32FromS16 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_s16", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::I32FromU8 | abi::Instruction::I32FromChar8 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_u8", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::I32FromS8 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_s8", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::F32FromIf32 => {
+                let py = pop_py(operands);
+                let result = self.py_to_wasm(
+                    py,
+                    "CPW_f64_from_float",
+                    vec![Instruction::F32DemoteF64],
+                    wasm_encoder::ValType::F32,
+                );
+                results.push(result);
+            }
+            abi::Instruction::F64FromIf64 => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_f64_from_float", vec![], wasm_encoder::ValType::F64);
+                results.push(result);
+            }
+
+            abi::Instruction::S8FromI32 => {
+                let local = pop_wasm(operands);
+                let result =
+                    self.wasm_to_py(local, vec![Instruction::I32Extend8S], "CPW_s32_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::U8FromI32 | abi::Instruction::Char8FromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(
+                    local,
+                    vec![Instruction::I32Const(0xff), Instruction::I32And],
+                    "CPW_u32_from_i32",
+                );
+                results.push(result);
+            }
+            abi::Instruction::S16FromI32 => {
+                let local = pop_wasm(operands);
+                let result =
+                    self.wasm_to_py(local, vec![Instruction::I32Extend16S], "CPW_s32_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::U16FromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(
+                    local,
+                    vec![Instruction::I32Const(0xffff), Instruction::I32And],
+                    "CPW_u32_from_i32",
+                );
+                results.push(result);
+            }
+            abi::Instruction::S32FromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(local, vec![], "CPW_s32_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::U32FromI32 | abi::Instruction::UsizeFromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(local, vec![], "CPW_u32_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::S64FromI64 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(local, vec![], "CPW_s64_from_i64");
+                results.push(result);
+            }
+            abi::Instruction::U64FromI64 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(local, vec![], "CPW_u64_from_i64");
+                results.push(result);
+            }
+            abi::Instruction::CharFromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(local, vec![], "CPW_char_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::If32FromF32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(
+                    local,
+                    vec![Instruction::F64PromoteF32],
+                    "CPW_float_from_f64",
+                );
+                results.push(result);
+            }
+            abi::Instruction::If64FromF64 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(local, vec![], "CPW_float_from_f64");
+                results.push(result);
+            }
+
+            // Handles to imported resources are plain `int`s in Python.
+            abi::Instruction::I32FromBorrowedHandle { ty: _ } => {
+                let py = pop_py(operands);
+                let result =
+                    self.py_to_wasm(py, "CPW_i32_from_u32", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::HandleOwnedFromI32 { ty: _ } => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_py(local, vec![], "CPW_u32_from_i32");
+                results.push(result);
+            }
+
+            // Exported resources can be any Python object, which we keep in
+            // `bindgen.c`'s resource table and identify by their index in that
+            // table when creating a handle with the canonical ABI.
+            abi::Instruction::I32FromOwnedHandle { ty } => {
+                let py = pop_py(operands);
+                let resource_new = self
+                    .gen
+                    .canonical_abi_import(&format!("resource_new_{}", iface.resources[*ty].name));
+                let result = self.py_to_wasm(
+                    py,
+                    "CPW_resource_insert",
+                    vec![Instruction::Call(resource_new)],
+                    wasm_encoder::ValType::I32,
+                );
+                results.push(result);
+            }
+            abi::Instruction::HandleBorrowedFromI32 { ty } => {
+                let local = pop_wasm(operands);
+                let resource_get = self
+                    .gen
+                    .canonical_abi_import(&format!("resource_get_{}", iface.resources[*ty].name));
+                let result = self.wasm_to_py(
+                    local,
+                    vec![Instruction::Call(resource_get)],
+                    "CPW_resource_get",
+                );
+                results.push(result);
+            }
+            abi::Instruction::ListCanonLower { element, realloc } => {
+                let py = pop_py(operands);
+                let ptr = self.new_local(wasm_encoder::ValType::I32);
+                let len = self.new_local(wasm_encoder::ValType::I32);
+
+                // Make sure our return pointer area can hold at least two
+                // `u32`s, since we will use it that way with
+                // `CPW_{list,string}_canon_lower`.
+                self.gen.i64_return_pointer_area_size =
+                    self.gen.i64_return_pointer_area_size.max(1);
+
+                // []
+                self.inst(Instruction::GlobalGet(RET_PTR_GLOBAL));
+                // [i32]
+                self.inst(Instruction::I32Const(py as _));
+                // [i32 i32]
+                self.inst(Instruction::Call(
+                    self.gen.cpython_import("CPW_string_canon_lower"),
+                ));
+                // []
+
+                // Read the pointer from the return pointer area.
+                //
+                // []
+                self.inst(Instruction::GlobalGet(RET_PTR_GLOBAL));
+                // [i32]
+                self.inst(Instruction::I32Load(py_mem_arg(0)));
+                // [i32]
+                self.inst(Instruction::LocalSet(ptr));
+                // []
+
+                // Read the length from the return pointer area.
+                //
+                // []
+                self.inst(Instruction::GlobalGet(RET_PTR_GLOBAL));
+                // [i32]
+                self.inst(Instruction::I32Load(py_mem_arg(4)));
+                // [i32]
+                self.inst(Instruction::LocalSet(len));
+                // []
+
+                // If `realloc` is `None`, then we are responsible for freeing
+                // this pointer after the call.
+                if realloc.is_none() {
+                    self.to_free.push((
+                        ptr,
+                        len,
+                        u32::try_from(self.gen.sizes.align(element)).unwrap(),
+                    ));
+                }
+
+                results.push(Operand::Wasm(ptr));
+                results.push(Operand::Wasm(len));
+            }
+            abi::Instruction::ListLower { element, realloc } => {
+                let iterable = pop_py(operands);
+                let (block, block_results) = self.pop_block();
+                assert!(block_results.is_empty());
+                let iter_elem = self.iter_elem.pop().unwrap();
+                let iter_base_pointer = self.iter_base_pointer.pop().unwrap();
+
+                let length = self.new_local(wasm_encoder::ValType::I32);
+                let index = self.new_local(wasm_encoder::ValType::I32);
+                let ptr = self.new_local(wasm_encoder::ValType::I32);
+
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+
+                // []
+                self.inst(Instruction::I32Const(iterable as i32));
+                // [i32]
+                self.inst(Instruction::Call(
+                    self.gen.cpython_import("CPW_spread_into_list"),
+                ));
+                // [i32]
+                self.inst(Instruction::LocalSet(length));
+                // []
+
+                // `malloc` space for the result: `length * size` bytes.
+                //
+                // []
+                self.inst(Instruction::LocalGet(length));
+                // [i32]
+                self.inst(Instruction::I32Const(u32::try_from(size).unwrap() as _));
+                // [i32 i32]
+                self.inst(Instruction::I32Mul);
+                // [i32]
+                self.inst(Instruction::LocalSet(ptr));
+                // []
+                self.gen
+                    .malloc_dynamic_size(self.blocks.last_mut().unwrap(), ptr, ptr);
+
+                // Create a new block and loop. The block is so we can branch to
+                // it to exit out of the loop.
+                //
+                // Also re-zero the index since the current block itself might
+                // be reused multiple times if it is part of a loop body.
+                //
+                // []
+                self.inst(Instruction::I32Const(0));
+                // [i32]
+                self.inst(Instruction::LocalSet(index));
+                // []
+                self.inst(Instruction::Block(wasm_encoder::BlockType::Empty));
+                // []
+                self.inst(Instruction::Loop(wasm_encoder::BlockType::Empty));
+                // []
+
+                // Check the loop's exit condition: `index >= length`.
+                //
+                // []
+                self.inst(Instruction::LocalGet(index));
+                // [i32]
+                self.inst(Instruction::LocalGet(length));
+                // [i32 i32]
+                self.inst(Instruction::I32GeU);
+                // [i32]
+                self.inst(Instruction::BrIf(1));
+                // []
+
+                // Update the element for this iteration.
+                //
+                // []
+                self.inst(Instruction::I32Const(iterable as i32));
+                // [i32]
+                self.inst(Instruction::LocalGet(index));
+                // [i32 32]
+                self.inst(Instruction::I32Const(iter_elem as i32));
+                // [i32 i32 i32]
+                self.inst(Instruction::Call(
+                    self.gen.cpython_import("CPW_get_list_element"),
+                ));
+                // []
+
+                // Update the base pointer for this iteration.
+                //
+                // []
+                self.inst(Instruction::LocalGet(index));
+                // [i32]
+                self.inst(Instruction::I32Const(u32::try_from(size).unwrap() as _));
+                // [i32 i32]
+                self.inst(Instruction::I32Mul);
+                // [i32]
+                self.inst(Instruction::LocalGet(ptr));
+                // [i32 i32]
+                self.inst(Instruction::I32Add);
+                // [i32]
+                self.inst(Instruction::LocalSet(iter_base_pointer));
+                // []
+
+                // Now do include the snippet that lowers a single list element!
+                self.current_block().extend(block);
+
+                // Increment our index counter.
+                //
+                // []
+                self.inst(Instruction::LocalGet(index));
+                // [i32]
+                self.inst(Instruction::I32Const(1));
+                // [i32 i32]
+                self.inst(Instruction::I32Add);
+                // [i32]
+                self.inst(Instruction::LocalSet(index));
+                // []
+
+                // Unconditionally jump back to the loop head, and close out our blocks.
+                //
+                // []
+                self.inst(Instruction::Br(0));
+                // []
+                self.inst(Instruction::End);
+                // []
+                self.inst(Instruction::End);
+                // []
+
+                // If `realloc` is `None`, then we are responsible for freeing
+                // this pointer after the call.
+                if realloc.is_none() {
+                    self.to_free
+                        .push((ptr, length, u32::try_from(align).unwrap()));
+                }
+
+                results.push(Operand::Wasm(ptr));
+                results.push(Operand::Wasm(length));
+            }
+            abi::Instruction::ListCanonLift {
+                element,
+                free,
+                ty: _,
+            } => {
+                assert_eq!(**element, Type::Char);
+
+                let len = pop_wasm(operands);
+                let ptr = pop_wasm(operands);
+                let result = self.next_py();
+
+                // []
+                self.inst(Instruction::LocalGet(ptr));
+                // [i32]
+                self.inst(Instruction::LocalGet(len));
+                // [i32 i32]
+                self.inst(Instruction::I32Const(result.unwrap_py() as i32));
+                // [i32 i32 i32]
+                self.inst(Instruction::Call(
+                    self.gen.cpython_import("CPW_string_canon_lift"),
+                ));
+                // []
+
+                if let Some(free) = free {
+                    // []
+                    self.inst(Instruction::LocalGet(ptr));
+                    // [i32]
+                    self.inst(Instruction::LocalGet(len));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Const(self.gen.sizes.align(element) as _));
+                    // [i32 i32 i32]
+                    self.inst(Instruction::Call(self.gen.cpython_import(free)));
+                    // []
+                }
+
+                results.push(result);
+            }
+            abi::Instruction::ListLift {
+                element,
+                free,
+                ty: _,
+            } => {
+                let len = pop_wasm(operands);
+                let ptr = pop_wasm(operands);
+                let (block, block_results) = self.pop_block();
+                assert_eq!(block_results.len(), 1);
+                let iter_base_pointer = self.iter_base_pointer.pop().unwrap();
+
+                let index = self.new_local(wasm_encoder::ValType::I32);
+
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+
+                let result = self.next_py();
+
+                // Create a new Python list object that will be the result of this
+                // lifting.
+                //
+                // []
+                self.inst(Instruction::I32Const(result.unwrap_py() as i32));
+                // [i32]
+                self.inst(Instruction::Call(self.gen.cpython_import("CPW_new_list")));
+                // []
+
+                // Create a block and a loop. The block is for branching to when
+                // we need to exit the loop.
+                //
+                // Also re-zero the loop index because it might be reused across
+                // multiple loops if the current block itself is also a loop
+                // body.
+                //
+                // []
+                self.inst(Instruction::Block(wasm_encoder::BlockType::Empty));
+                // []
+                self.inst(Instruction::I32Const(0));
+                // [i32]
+                self.inst(Instruction::LocalSet(index));
+                // []
+                self.inst(Instruction::Loop(wasm_encoder::BlockType::Empty));
+                // []
+
+                // Check for our loop's exit condition: `index >= len`.
+                //
+                // []
+                self.inst(Instruction::LocalGet(index));
+                // [i32]
+                self.inst(Instruction::LocalGet(len));
+                // [i32 i32]
+                self.inst(Instruction::I32GeU);
+                // [i32]
+                self.inst(Instruction::BrIf(1));
+                // []
+
+                // Update the base pointer for this iteration.
+                //
+                // []
+                self.inst(Instruction::LocalGet(index));
+                // [i32]
+                self.inst(Instruction::I32Const(u32::try_from(size).unwrap() as _));
+                // [i32 i32]
+                self.inst(Instruction::I32Mul);
+                // [i32]
+                self.inst(Instruction::LocalGet(ptr));
+                // [i32 i32]
+                self.inst(Instruction::I32Add);
+                // [i32]
+                self.inst(Instruction::LocalSet(iter_base_pointer));
+                // []
+
+                self.current_block().extend(block);
+
+                // Append the result of this iteration's lifting to our Python list.
+                //
+                // []
+                self.inst(Instruction::I32Const(result.unwrap_py() as i32));
+                // [i32]
+                self.inst(Instruction::I32Const(block_results[0].unwrap_py() as i32));
+                // [i32 i32]
+                self.inst(Instruction::Call(
+                    self.gen.cpython_import("CPW_list_append"),
+                ));
+                // []
+
+                // Increment the index variable, unconditionally jump back to
+                // the head of the loop, and close out our blocks.
+                //
+                // []
+                self.inst(Instruction::I32Const(1));
+                // [i32]
+                self.inst(Instruction::LocalGet(index));
+                // [i32 i32]
+                self.inst(Instruction::I32Add);
+                // [i32]
+                self.inst(Instruction::LocalSet(index));
+                // []
+                self.inst(Instruction::Br(0));
+                // []
+                self.inst(Instruction::End);
+                // []
+                self.inst(Instruction::End);
+                // []
+
+                if let Some(free) = free {
+                    // []
+                    self.inst(Instruction::LocalGet(ptr));
+                    // [i32]
+                    self.inst(Instruction::LocalGet(len));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Const(u32::try_from(align).unwrap() as _));
+                    // [i32 i32 i32]
+                    self.inst(Instruction::Call(self.gen.cpython_import(free)));
+                    // []
+                }
+
+                results.push(result);
+            }
+            abi::Instruction::IterElem { element: _ } => {
+                let iter_elem = self.next_py();
+                self.iter_elem.push(iter_elem.unwrap_py());
+                results.push(iter_elem);
+            }
+            abi::Instruction::IterBasePointer => {
+                let iter_base_pointer = self.new_local(wasm_encoder::ValType::I32);
+                self.iter_base_pointer.push(iter_base_pointer);
+                results.push(Operand::Wasm(iter_base_pointer));
+            }
+
+            // Buffers are rejected up front in `preprocess_one`.
+            abi::Instruction::BufferPayloadName
+            | abi::Instruction::BufferLowerPtrLen { .. }
+            | abi::Instruction::BufferLowerHandle { .. }
+            | abi::Instruction::BufferLiftPtrLen { .. }
+            | abi::Instruction::BufferLiftHandle { .. } => {
+                unreachable!("buffers are not supported")
+            }
+
+            abi::Instruction::RecordLower { record, .. } => {
+                let py = pop_py(operands);
+                for (i, field) in record.fields.iter().enumerate() {
+                    let dest = self.next_py();
+                    if record.is_tuple() {
+                        // []
+                        self.inst(Instruction::I32Const(py as i32));
+                        // [i32]
+                        self.inst(Instruction::I32Const(i as i32));
+                        // [i32 i32]
+                        self.inst(Instruction::I32Const(dest.unwrap_py() as i32));
+                        // [i32 i32 i32]
+                        self.inst(Instruction::Call(
+                            self.gen.cpython_import("CPW_get_tuple_element"),
+                        ));
+                        // []
+                    } else {
+                        self.get_attr(py, &field.name.to_snake_case(), dest.unwrap_py());
+                    }
+                    results.push(dest);
+                }
+            }
+            abi::Instruction::RecordLift { record, .. } => {
+                let fields: Vec<_> = operands.drain(..).map(|op| op.unwrap_py()).collect();
+                let result = self.next_py().unwrap_py();
+                if record.is_tuple() {
+                    // []
+                    self.inst(Instruction::I32Const(fields.len() as i32));
+                    // [i32]
+                    self.inst(Instruction::I32Const(result as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::Call(self.gen.cpython_import("CPW_new_tuple")));
+                    // []
+                    for (i, py) in fields.into_iter().enumerate() {
+                        // []
+                        self.inst(Instruction::I32Const(result as i32));
+                        // [i32]
+                        self.inst(Instruction::I32Const(i as i32));
+                        // [i32 i32]
+                        self.inst(Instruction::I32Const(py as i32));
+                        // [i32 i32 i32]
+                        self.inst(Instruction::Call(
+                            self.gen.cpython_import("CPW_set_tuple_element"),
+                        ));
+                        // []
+                    }
+                } else {
+                    // []
+                    self.inst(Instruction::I32Const(result as i32));
+                    // [i32]
+                    self.inst(Instruction::Call(self.gen.cpython_import("CPW_new_record")));
+                    // []
+                    for (field, py) in record.fields.iter().zip(fields) {
+                        self.set_attr(result, &field.name.to_snake_case(), py);
+                    }
+                }
+                results.push(Operand::Py(result));
+            }
+
+            // Flags are represented as `int` bitmasks, which are lowered and
+            // lifted in 32-bit chunks. `CPW_flags_lower` traps if any bits are
+            // set that don't correspond to a flag.
+            abi::Instruction::FlagsLower { record, .. } => {
+                let py = pop_py(operands);
+                for i in 0..record.num_i32s() {
+                    let local = self.new_local(wasm_encoder::ValType::I32);
+                    self.flags_lower_chunk(py, record.fields.len(), i);
+                    // [i32]
+                    self.inst(Instruction::LocalSet(local));
+                    // []
+                    results.push(Operand::Wasm(local));
+                }
+            }
+            abi::Instruction::FlagsLower64 { record, .. } => {
+                let py = pop_py(operands);
+                let local = self.new_local(wasm_encoder::ValType::I64);
+
+                self.flags_lower_chunk(py, record.fields.len(), 0);
+                // [i32]
+                self.inst(Instruction::I64ExtendI32U);
+                // [i64]
+                self.flags_lower_chunk(py, record.fields.len(), 1);
+                // [i64 i32]
+                self.inst(Instruction::I64ExtendI32U);
+                // [i64 i64]
+                self.inst(Instruction::I64Const(32));
+                // [i64 i64 i64]
+                self.inst(Instruction::I64Shl);
+                // [i64 i64]
+                self.inst(Instruction::I64Or);
+                // [i64]
+                self.inst(Instruction::LocalSet(local));
+                // []
+
+                results.push(Operand::Wasm(local));
+            }
+            abi::Instruction::FlagsLift { record, .. } => {
+                let locals: Vec<_> = operands.drain(..).map(|op| op.unwrap_wasm()).collect();
+                let result = self.next_py().unwrap_py();
+
+                // Trap if any bits are set that don't correspond to a flag.
+                for (i, local) in locals.iter().enumerate() {
+                    let num_flags = (record.fields.len() - i * 32).min(32);
+                    if num_flags == 32 {
+                        continue;
+                    }
+                    // []
+                    self.inst(Instruction::LocalGet(*local));
+                    // [i32]
+                    self.inst(Instruction::I32Const(!((1u32 << num_flags) - 1) as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32And);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    self.inst(Instruction::Unreachable);
+                    self.inst(Instruction::End);
+                    // []
+                }
+
+                for (i, local) in locals.into_iter().enumerate() {
+                    // []
+                    self.inst(Instruction::LocalGet(local));
+                    // [i32]
+                    self.flags_lift_chunk(i, result);
+                    // []
+                }
+
+                results.push(Operand::Py(result));
+            }
+            abi::Instruction::FlagsLift64 { record, .. } => {
+                let local = pop_wasm(operands);
+                let result = self.next_py().unwrap_py();
+
+                // Trap if any bits are set that don't correspond to a flag.
+                if record.fields.len() < 64 {
+                    // []
+                    self.inst(Instruction::LocalGet(local));
+                    // [i64]
+                    self.inst(Instruction::I64Const(
+                        !((1u64 << record.fields.len()) - 1) as i64,
+                    ));
+                    // [i64 i64]
+                    self.inst(Instruction::I64And);
+                    // [i64]
+                    self.inst(Instruction::I64Const(0));
+                    // [i64 i64]
+                    self.inst(Instruction::I64Neq);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    self.inst(Instruction::Unreachable);
+                    self.inst(Instruction::End);
+                    // []
+                }
+
+                // []
+                self.inst(Instruction::LocalGet(local));
+                // [i64]
+                self.inst(Instruction::I32WrapI64);
+                // [i32]
+                self.flags_lift_chunk(0, result);
+                // []
+                self.inst(Instruction::LocalGet(local));
+                // [i64]
+                self.inst(Instruction::I64Const(32));
+                // [i64 i64]
+                self.inst(Instruction::I64ShrU);
+                // [i64]
+                self.inst(Instruction::I32WrapI64);
+                // [i32]
+                self.flags_lift_chunk(1, result);
+                // []
+
+                results.push(Operand::Py(result));
+            }
+
+            abi::Instruction::VariantPayloadName => {
+                let payload = self.next_py();
+                self.payloads.push(payload.unwrap_py());
+                results.push(payload);
+            }
+            abi::Instruction::VariantLower {
+                variant,
+                name,
+                ty: _,
+                results: result_types,
+            } => {
+                let py = pop_py(operands);
+                let blocks = self.pop_blocks(variant.cases.len());
+                let payloads = self
+                    .payloads
+                    .split_off(self.payloads.len() - variant.cases.len());
+                let repr = VariantRepr::new(iface, variant, *name);
+
+                let discriminant = self.new_local(wasm_encoder::ValType::I32);
+                let case_names: Vec<_> = variant.cases.iter().map(|c| c.name.as_str()).collect();
+                self.variant_discriminant(py, repr, &case_names);
+                // [i32]
+                self.inst(Instruction::LocalSet(discriminant));
+                // []
+
+                let locals: Vec<_> = result_types
+                    .iter()
+                    .map(|ty| self.new_local(convert_ty(*ty)))
+                    .collect();
+
+                // Select the block for the discriminant's case with a chain
+                // of `if`/`else`s, trapping if none of them match.
+                for (i, ((case, (block, block_results)), payload)) in
+                    variant.cases.iter().zip(blocks).zip(payloads).enumerate()
+                {
+                    // []
+                    self.inst(Instruction::LocalGet(discriminant));
+                    // [i32]
+                    self.inst(Instruction::I32Const(i as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Eq);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    // []
+
+                    if case.ty.is_some() {
+                        // []
+                        self.inst(Instruction::I32Const(py as i32));
+                        // [i32]
+                        self.inst(Instruction::I32Const(repr as i32));
+                        // [i32 i32]
+                        self.inst(Instruction::I32Const(payload as i32));
+                        // [i32 i32 i32]
+                        self.inst(Instruction::Call(
+                            self.gen.cpython_import("CPW_variant_payload"),
+                        ));
+                        // []
+                    }
+
+                    self.current_block().extend(block);
+
+                    assert_eq!(block_results.len(), locals.len());
+                    for (local, result) in locals.iter().zip(block_results) {
+                        // []
+                        self.inst(Instruction::LocalGet(result.unwrap_wasm()));
+                        // [T]
+                        self.inst(Instruction::LocalSet(*local));
+                        // []
+                    }
+
+                    self.inst(Instruction::Else);
+                }
+                self.inst(Instruction::Unreachable);
+                for _ in &variant.cases {
+                    self.inst(Instruction::End);
+                }
+
+                results.extend(locals.into_iter().map(Operand::Wasm));
+            }
+            abi::Instruction::VariantLift {
+                variant,
+                name,
+                ty: _,
+            } => {
+                let discriminant = pop_wasm(operands);
+                let blocks = self.pop_blocks(variant.cases.len());
+                let repr = VariantRepr::new(iface, variant, *name);
+                let result = self.next_py().unwrap_py();
+
+                // Select the block for the discriminant's case with a chain
+                // of `if`/`else`s, trapping if none of them match.
+                for (i, (case, (block, block_results))) in
+                    variant.cases.iter().zip(blocks).enumerate()
+                {
+                    // []
+                    self.inst(Instruction::LocalGet(discriminant));
+                    // [i32]
+                    self.inst(Instruction::I32Const(i as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Eq);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    // []
+
+                    self.current_block().extend(block);
+
+                    let payload = block_results.first().map(|op| op.unwrap_py());
+                    self.variant_lift(
+                        repr,
+                        Instruction::I32Const(i as i32),
+                        &case.name,
+                        payload,
+                        result,
+                    );
+
+                    self.inst(Instruction::Else);
+                }
+                self.inst(Instruction::Unreachable);
+                for _ in &variant.cases {
+                    self.inst(Instruction::End);
+                }
+
+                results.push(Operand::Py(result));
+            }
+            abi::Instruction::CallWasm { module, name, sig } => {
+                // Push the Wasm arguments.
+                //
+                // []
+                let locals: Vec<_> = sig.params.iter().map(|_| pop_wasm(operands)).collect();
+                for local in locals.into_iter().rev() {
+                    self.inst(Instruction::LocalGet(local));
+                }
+                // [A...]
+
+                let func_index = *self
+                    .gen
+                    .import_fn_name_to_index
+                    .get(*module)
+                    .unwrap()
+                    .get(*name)
+                    .unwrap();
+                self.inst(Instruction::Call(func_index));
+                // [R...]
+
+                // Allocate locals for the results and pop the return values off
+                // the Wasm stack, saving each of them to the associated local.
+                let locals: Vec<_> = sig
+                    .results
+                    .iter()
+                    .map(|ty| self.new_local(convert_ty(*ty)))
+                    .collect();
+                // [R...]
+                for l in locals.iter().rev() {
+                    self.inst(Instruction::LocalSet(*l));
+                }
+                // []
+
+                results.extend(locals.into_iter().map(Operand::Wasm));
+
+                for (ptr, len, alignment) in mem::replace(&mut self.to_free, vec![]) {
+                    // []
+                    self.inst(Instruction::LocalGet(ptr));
+                    // [i32]
+                    self.inst(Instruction::LocalGet(len));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Const(alignment as _));
+                    // [i32 i32 i32]
+                    self.inst(Instruction::Call(
+                        self.gen.cpython_import("canonical_abi_free"),
+                    ));
+                    // []
+                }
+            }
+            abi::Instruction::CallInterface { module: _, func } => {
+                // TODO: Rather than always dynamically pushing all of our
+                // Python arguments, make `CPW_call_{0,1,...,n}` up to the
+                // largest common `n` so we can directly pass the arguments for
+                // most function calls.
+
+                // Push the Python arguments.
+                let py_args: Vec<_> = func.params.iter().map(|_| pop_py(operands)).collect();
+                for py in py_args.into_iter().rev() {
+                    // []
+                    self.inst(Instruction::I32Const(py as _));
+                    // [i32]
+                    self.inst(Instruction::Call(self.gen.cpython_import("CPW_push_arg")));
+                    // []
+                }
+
+                let first_result = if func.results.is_empty() {
+                    // If there aren't any function results, then this argument
+                    // to `CPW_call` is going to be ignored. Use a highly
+                    // visible placeholder so that if this is ever accidentally
+                    // used it is easier to debug.
+                    0xffffffff
+                } else {
+                    let py = self.next_py();
+                    results.push(py);
+                    results.extend((0..(func.results.len() - 1)).map(|_| self.next_py()));
+                    py.unwrap_py()
+                };
+
+                // Make the call. Python functions are named in `snake_case`,
+                // and the name was copied into `cpython.wasm`'s memory once,
+                // in `wizer.initialize`.
+                //
+                // []
+                self.push_string(&func.name.to_snake_case());
+                // [i32 i32]
+                self.inst(Instruction::I32Const(
+                    u32::try_from(func.results.len()).unwrap() as _,
+                ));
+                // [i32 i32 i32]
+                self.inst(Instruction::I32Const(first_result as i32));
+                // [i32 i32 i32 i32]
+                self.inst(Instruction::Call(self.gen.cpython_import("CPW_call")));
+                // []
+            }
+
+            // Async functions are rejected in `import` and `export`.
+            abi::Instruction::CallWasmAsyncExport { .. }
+            | abi::Instruction::CallWasmAsyncImport { .. } => {
+                unreachable!("async functions are not supported")
+            }
+
+            abi::Instruction::Return { amt, func: _ } => {
+                match self.lift_lower {
+                    abi::LiftLower::LowerArgsLiftResults => {
+                        // Build up the Python return value via a series of
+                        // `CPW_push_return_value` calls, followed by a single
+                        // `CPW_finish_returns` call, which gives us a new
+                        // reference to `None`, the single result, or a tuple
+                        // of the results.
+                        //
+                        // TODO: introduce fast path intrinsics for common
+                        // small numbers of return values so that we don't
+                        // have to do multiple intrinsic calls here, and can
+                        // instead do a single `CPW_return_{1,2,...,n}` call.
+                        let vals: Vec<_> = (0..*amt).map(|_| pop_py(operands)).collect();
+                        for val in vals.into_iter().rev() {
+                            // []
+                            self.inst(Instruction::I32Const(val as _));
+                            // [i32]
+                            self.inst(Instruction::Call(
+                                self.gen.cpython_import("CPW_push_return_value"),
+                            ));
+                            // []
+                        }
+                        let result = self.new_local(wasm_encoder::ValType::I32);
+                        // []
+                        self.inst(Instruction::Call(
+                            self.gen.cpython_import("CPW_finish_returns"),
+                        ));
+                        // [i32]
+                        self.inst(Instruction::LocalSet(result));
+                        // []
+
+                        // NB: only clear the Python operands after we've
+                        // created the return value, since that borrows from
+                        // them.
+                        self.gen.clear_py_operands(self.blocks.last_mut().unwrap());
+
+                        // Return the new reference to the caller.
+                        //
+                        // []
+                        self.inst(Instruction::LocalGet(result));
+                        // [i32]
+                        self.inst(Instruction::Return);
+                        // []
+                    }
+                    abi::LiftLower::LiftArgsLowerResults => {
+                        self.gen.clear_py_operands(self.blocks.last_mut().unwrap());
+
+                        // Get the return values out of their locals and push
+                        // them onto the Wasm stack.
+                        //
+                        // []
+                        for _ in 0..*amt {
+                            let local = pop_wasm(operands);
+                            self.inst(Instruction::LocalGet(local));
+                        }
+                        // [R...]
+                        self.inst(Instruction::Return);
+                        // []
+                    }
+                }
+            }
+
+            abi::Instruction::ReturnAsyncExport { .. }
+            | abi::Instruction::ReturnAsyncImport { .. } => {
+                unreachable!("async functions are not supported")
+            }
+
+            abi::Instruction::Witx { instr: _ } => {
+                unreachable!("we do not support the preview1 ABI")
+            }
+        }
+    }
+
+    fn allocate_typed_space(&mut self, _iface: &Interface, _ty: TypeId) -> Self::Operand {
+        unreachable!("we do not support the preview1 ABI")
+    }
+
+    fn i64_return_pointer_area(&mut self, amt: usize) -> Self::Operand {
+        assert!(amt <= self.gen.i64_return_pointer_area_size);
+        let local = self.new_local(wasm_encoder::ValType::I32);
+
+        // []
+        self.inst(Instruction::GlobalGet(RET_PTR_GLOBAL));
+        // [i32]
+        self.inst(Instruction::LocalSet(local));
+        // []
+
+        Operand::Wasm(local)
+    }
+
+    fn push_block(&mut self) {
+        self.blocks.push(vec![]);
+    }
+
+    fn finish_block(&mut self, results: &mut Vec<Self::Operand>) {
+        self.block_results.push(results.to_vec());
+    }
+
+    fn sizes(&self) -> &SizeAlign {
+        &self.gen.sizes
+    }
+
+    fn is_list_canonical(&self, _iface: &Interface, _ty: &Type) -> bool {
+        // TODO: we will want to support canonical lists for `bytes` and
+        // `array.array`.
+        false
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+mod imports {
+    test_helpers::codegen_cpython_import!(
+        "char.wit"
+        "conventions.wit"
+        "empty.wit"
+        "flags.wit"
+        "floats.wit"
+        "integers.wit"
+        "lists.wit"
+        "records.wit"
+        "resource.wit"
+        "simple-functions.wit"
+        "simple-lists.wit"
+        "smoke.wit"
+        "strings.wit"
+        "variants.wit"
+    );
+}
+
+mod exports {
+    test_helpers::codegen_cpython_export!(
+        "char.wit"
+        "conventions.wit"
+        "empty.wit"
+        "flags.wit"
+        "floats.wit"
+        "integers.wit"
+        "lists.wit"
+        "records.wit"
+        "resource.wit"
+        "simple-functions.wit"
+        "simple-lists.wit"
+        "smoke.wit"
+        "strings.wit"
+        "variants.wit"
+    );
+}
+
+fn verify(dir: &str, _name: &str) {
+    let wasm = std::fs::read(Path::new(dir).join("foo.wasm")).unwrap();
+    let mut validator = wasmparser::Validator::new();
+    validator.wasm_features(wasmparser::WasmFeatures {
+        bulk_memory: true,
+        reference_types: true,
+        module_linking: true,
+        multi_memory: true,
+        ..wasmparser::WasmFeatures::default()
+    });
+    validator.validate_all(&wasm).expect("wasm isn't valid");
+}
@@ -4,7 +4,10 @@ use std::mem;
 use wit_bindgen_gen_core::wit_parser::abi::{
     AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType, WitxInstruction,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator};
+use wit_bindgen_gen_core::{
+    abi_export_name, contains_named_type, wit_parser::*, AbiExport, AbiExportKind, Direction,
+    Files, Generator,
+};
 
 #[derive(Default)]
 pub struct Js {
@@ -42,6 +45,23 @@ struct Exports {
 pub struct Opts {
     #[cfg_attr(feature = "structopt", structopt(long = "no-typescript"))]
     pub no_typescript: bool,
+
+    /// Whether or not to report each call to the hook set with
+    /// `setTraceHook`, recording its parameters, results, duration and errors.
+    /// Durations are whole microseconds in `duration_us`.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub tracing: bool,
+
+    /// Names of types whose values are recorded as `<redacted>` rather than
+    /// with their contents when tracing, including within other types.
+    #[cfg_attr(feature = "structopt", structopt(long = "tracing-redact"))]
+    pub tracing_redact: Vec<String>,
+
+    /// Renames the exports used for the canonical ABI, which are `memory`,
+    /// `canonical_abi_realloc` and `canonical_abi_free` by default. Each entry
+    /// is `kind=name`, or `iface:kind=name` to rename it for one interface.
+    #[cfg_attr(feature = "structopt", structopt(long = "abi-export"))]
+    pub abi_exports: Vec<AbiExport>,
 }
 
 impl Opts {
@@ -76,6 +96,10 @@ enum Intrinsic {
     Slab,
     Promises,
     WithCurrentPromise,
+    TraceCall,
+    IsLe,
+    ListToLe,
+    ListFromLe,
 }
 
 impl Intrinsic {
@@ -103,6 +127,10 @@ impl Intrinsic {
             Intrinsic::Slab => "Slab",
             Intrinsic::Promises => "PROMISES",
             Intrinsic::WithCurrentPromise => "with_current_promise",
+            Intrinsic::TraceCall => "trace_call",
+            Intrinsic::IsLe => "IS_LE",
+            Intrinsic::ListToLe => "list_to_le",
+            Intrinsic::ListFromLe => "list_from_le",
         }
     }
 }
@@ -368,10 +396,85 @@ impl Js {
         self.intrinsics.insert(i, i.name().to_string());
         return i.name().to_string();
     }
+
+    /// Returns the value recorded in traces for `value` of type `ty`.
+    fn trace_value(&self, iface: &Interface, ty: &Type, value: &str) -> String {
+        if contains_named_type(iface, ty, &self.opts.tracing_redact) {
+            "'<redacted>'".to_string()
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Returns the start of a call to `trace_call` for `func`, which is
+    /// followed by the closure performing the call.
+    ///
+    /// The results of `func` are read from the value returned by the call
+    /// with `result_key`, given the name of each result, if there are several
+    /// named results.
+    fn trace_call(
+        &mut self,
+        iface: &Interface,
+        func: &Function,
+        params: &[String],
+        result_key: impl Fn(&str) -> String,
+    ) -> String {
+        let trace = self.intrinsic(Intrinsic::TraceCall);
+        let record = |fields: Vec<String>| {
+            if fields.is_empty() {
+                String::new()
+            } else {
+                format!(" {} ", fields.join(", "))
+            }
+        };
+        let params = func
+            .params
+            .iter()
+            .zip(params)
+            .map(|((name, ty), value)| {
+                format!(
+                    "{}: {}",
+                    name.to_mixed_case(),
+                    self.trace_value(iface, ty, value)
+                )
+            })
+            .collect::<Vec<_>>();
+        let unnamed = func.results.iter().any(|(name, _)| name.is_empty());
+        let results = func
+            .results
+            .iter()
+            .enumerate()
+            .map(|(i, (name, ty))| {
+                let (key, value) = if func.results.len() == 1 {
+                    let key = if name.is_empty() { "result" } else { name };
+                    (key.to_mixed_case(), "ret".to_string())
+                } else if unnamed {
+                    (format!("result{}", i), format!("ret[{}]", i))
+                } else {
+                    (name.to_mixed_case(), format!("ret.{}", result_key(name)))
+                };
+                format!("{}: {}", key, self.trace_value(iface, ty, &value))
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{}('{}', '{}', {}, {{{}}}, ret => ({{{}}}), ",
+            trace,
+            iface.name,
+            func.name,
+            func.is_async,
+            record(params),
+            record(results),
+        )
+    }
 }
 
 impl Generator for Js {
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
+        assert!(
+            !iface.memory64,
+            "interface `{}` uses a 64-bit memory, which is not supported",
+            iface.name,
+        );
         let variant = Self::abi_variant(dir);
         self.sizes.fill(variant, iface);
         self.in_import = variant == AbiVariant::GuestImport;
@@ -392,36 +495,17 @@ impl Generator for Js {
             self.print_tuple(iface, record);
             self.src.ts(";\n");
         } else if record.is_flags() {
-            let repr = iface
-                .flags_repr(record)
-                .expect("unsupported number of flags");
-            let suffix = if repr == Int::U64 {
-                self.src
-                    .ts(&format!("export type {} = bigint;\n", name.to_camel_case()));
-                "n"
-            } else {
+            // Flags are represented as an object with a boolean property per
+            // flag. Properties which are missing are considered to be unset
+            // when lowering, and lifting always produces every property.
+            self.src
+                .ts(&format!("export interface {} {{\n", name.to_camel_case()));
+            for field in record.fields.iter() {
+                self.docs(&field.docs);
                 self.src
-                    .ts(&format!("export type {} = number;\n", name.to_camel_case()));
-                ""
-            };
-            let name = name.to_shouty_snake_case();
-            for (i, field) in record.fields.iter().enumerate() {
-                let field = field.name.to_shouty_snake_case();
-                self.src.js(&format!(
-                    "export const {}_{} = {}{};\n",
-                    name,
-                    field,
-                    1u64 << i,
-                    suffix,
-                ));
-                self.src.ts(&format!(
-                    "export const {}_{} = {}{};\n",
-                    name,
-                    field,
-                    1u64 << i,
-                    suffix,
-                ));
+                    .ts(&format!("{}?: boolean,\n", field.name.to_mixed_case()));
             }
+            self.src.ts("}\n");
         } else {
             self.src
                 .ts(&format!("export interface {} {{\n", name.to_camel_case()));
@@ -602,22 +686,29 @@ impl Generator for Js {
             ..
         } = f;
 
+        let exports = &self.opts.abi_exports;
         if needs_memory {
             self.needs_get_export = true;
-            // TODO: hardcoding "memory"
-            self.src.js("const memory = get_export(\"memory\");\n");
+            self.src.js(&format!(
+                "const memory = get_export(\"{}\");\n",
+                abi_export_name(exports, iface, AbiExportKind::Memory),
+            ));
         }
 
-        if let Some(name) = needs_realloc {
+        if needs_realloc.is_some() {
             self.needs_get_export = true;
-            self.src
-                .js(&format!("const realloc = get_export(\"{}\");\n", name));
+            self.src.js(&format!(
+                "const realloc = get_export(\"{}\");\n",
+                abi_export_name(exports, iface, AbiExportKind::Realloc),
+            ));
         }
 
-        if let Some(name) = needs_free {
+        if needs_free.is_some() {
             self.needs_get_export = true;
-            self.src
-                .js(&format!("const free = get_export(\"{}\");\n", name));
+            self.src.js(&format!(
+                "const free = get_export(\"{}\");\n",
+                abi_export_name(exports, iface, AbiExportKind::Free),
+            ));
         }
         self.src.js(&src.js);
 
@@ -687,6 +778,14 @@ impl Generator for Js {
         if !first_is_operand {
             params.remove(0);
         }
+        if self.opts.tracing {
+            let trace = self.trace_call(iface, func, &params, |name| name.to_mixed_case());
+            self.src.js(&format!(
+                "return {}{}() => {{\n",
+                trace,
+                if func.is_async { "async " } else { "" },
+            ));
+        }
         let mut f = FunctionBindgen::new(self, false, params);
         f.src_object = src_object;
         iface.call(
@@ -704,26 +803,34 @@ impl Generator for Js {
             src_object,
             ..
         } = f;
+        let exports = &self.opts.abi_exports;
         if needs_memory {
-            // TODO: hardcoding "memory"
-            self.src
-                .js(&format!("const memory = {}._exports.memory;\n", src_object));
+            self.src.js(&format!(
+                "const memory = {}._exports[\"{}\"];\n",
+                src_object,
+                abi_export_name(exports, iface, AbiExportKind::Memory),
+            ));
         }
 
-        if let Some(name) = needs_realloc {
+        if needs_realloc.is_some() {
             self.src.js(&format!(
                 "const realloc = {}._exports[\"{}\"];\n",
-                src_object, name
+                src_object,
+                abi_export_name(exports, iface, AbiExportKind::Realloc),
             ));
         }
 
-        if let Some(name) = needs_free {
+        if needs_free.is_some() {
             self.src.js(&format!(
                 "const free = {}._exports[\"{}\"];\n",
-                src_object, name
+                src_object,
+                abi_export_name(exports, iface, AbiExportKind::Free),
             ));
         }
         self.src.js(&src.js);
+        if self.opts.tracing {
+            self.src.js("});\n");
+        }
         self.src.js("}\n");
 
         let exports = self
@@ -1107,6 +1214,22 @@ impl Generator for Js {
             ");
         }
 
+        if self.intrinsics.contains_key(&Intrinsic::TraceCall) {
+            self.src
+                .js("export { setTraceHook } from './intrinsics.js';\n");
+            self.src.ts("
+                export interface TraceRecord {
+                    module: string;
+                    function: string;
+                    params: Record<string, any>;
+                    results?: Record<string, any>;
+                    error?: any;
+                    duration_us: number;
+                }
+                export function setTraceHook(hook: ((record: TraceRecord) => void) | null): void;
+            ");
+        }
+
         if self.intrinsics.len() > 0 {
             self.src.js("import { ");
             for (i, (intrinsic, name)) in mem::take(&mut self.intrinsics).into_iter().enumerate() {
@@ -1157,6 +1280,8 @@ struct FunctionBindgen<'a> {
     needs_free: Option<String>,
     params: Vec<String>,
     src_object: String,
+    /// Statements writing back push buffers after the call, if any.
+    push_buffers: Vec<String>,
 }
 
 impl FunctionBindgen<'_> {
@@ -1173,6 +1298,7 @@ impl FunctionBindgen<'_> {
             needs_free: None,
             params,
             src_object: "this".to_string(),
+            push_buffers: Vec::new(),
         }
     }
 
@@ -1224,6 +1350,90 @@ impl FunctionBindgen<'_> {
         ));
     }
 
+    /// Returns the validation intrinsic and the mask of valid bits for the
+    /// flags `record`.
+    fn flags_validate(&mut self, record: &Record, suffix: &str) -> (String, String) {
+        if suffix.is_empty() {
+            let mask = (1u64 << record.fields.len()) - 1;
+            (
+                self.gen.intrinsic(Intrinsic::ValidateFlags),
+                mask.to_string(),
+            )
+        } else {
+            let mask = (1u128 << record.fields.len()) - 1;
+            (
+                self.gen.intrinsic(Intrinsic::ValidateFlags64),
+                mask.to_string(),
+            )
+        }
+    }
+
+    /// Converts an object of booleans into its bit representation, where
+    /// `suffix` is `"n"` for flags represented with a `bigint`.
+    fn flags_lower(
+        &mut self,
+        record: &Record,
+        suffix: &str,
+        operands: &[String],
+        results: &mut Vec<String>,
+    ) {
+        let tmp = self.tmp();
+        self.src
+            .js(&format!("const flags{} = {};\n", tmp, operands[0]));
+        self.src.js(&format!(
+            "if (typeof flags{0} !== 'object' || flags{0} === null)\n",
+            tmp
+        ));
+        self.src
+            .js("throw new TypeError('flags must be an object');\n");
+        let mut bits = Vec::new();
+        for (i, field) in record.fields.iter().enumerate() {
+            let flag = format!("Boolean(flags{}.{})", tmp, field.name.to_mixed_case());
+            if suffix.is_empty() {
+                bits.push(format!("{} << {}", flag, i));
+            } else {
+                bits.push(format!("BigInt({}) << {}n", flag, i));
+            }
+        }
+        let (validate, mask) = self.flags_validate(record, suffix);
+        results.push(format!(
+            "{}({}, {}{})",
+            validate,
+            bits.join(" | "),
+            mask,
+            suffix
+        ));
+    }
+
+    /// Converts the bit representation of flags into an object with a boolean
+    /// property for each flag, validating that no unknown bits are set.
+    fn flags_lift(
+        &mut self,
+        record: &Record,
+        suffix: &str,
+        operands: &[String],
+        results: &mut Vec<String>,
+    ) {
+        let (validate, mask) = self.flags_validate(record, suffix);
+        let tmp = self.tmp();
+        self.src.js(&format!(
+            "const flags{} = {}({}, {}{});\n",
+            tmp, validate, operands[0], mask, suffix
+        ));
+        let mut result = "{\n".to_string();
+        for (i, field) in record.fields.iter().enumerate() {
+            result.push_str(&format!(
+                "{}: Boolean(flags{} & {}{}),\n",
+                field.name.to_mixed_case(),
+                tmp,
+                1u64 << i,
+                suffix
+            ));
+        }
+        result.push_str("}");
+        results.push(result);
+    }
+
     fn bind_results(&mut self, amt: usize, results: &mut Vec<String>) {
         match amt {
             0 => {}
@@ -1503,20 +1713,19 @@ impl Bindgen for FunctionBindgen<'_> {
                 }
             }
 
-            Instruction::FlagsLower { record, .. } | Instruction::FlagsLift { record, .. } => {
-                match record.num_i32s() {
-                    0 | 1 => {
-                        let validate = self.gen.intrinsic(Intrinsic::ValidateFlags);
-                        let mask = (1u64 << record.fields.len()) - 1;
-                        results.push(format!("{}({}, {})", validate, operands[0], mask));
-                    }
-                    _ => panic!("unsupported bitflags"),
-                }
+            Instruction::FlagsLower { record, .. } => match record.num_i32s() {
+                0 | 1 => self.flags_lower(record, "", operands, results),
+                _ => panic!("unsupported bitflags"),
+            },
+            Instruction::FlagsLower64 { record, .. } => {
+                self.flags_lower(record, "n", operands, results);
             }
-            Instruction::FlagsLower64 { record, .. } | Instruction::FlagsLift64 { record, .. } => {
-                let validate = self.gen.intrinsic(Intrinsic::ValidateFlags64);
-                let mask = (1u128 << record.fields.len()) - 1;
-                results.push(format!("{}({}, {}n)", validate, operands[0], mask));
+            Instruction::FlagsLift { record, .. } => match record.num_i32s() {
+                0 | 1 => self.flags_lift(record, "", operands, results),
+                _ => panic!("unsupported bitflags"),
+            },
+            Instruction::FlagsLift64 { record, .. } => {
+                self.flags_lift(record, "n", operands, results);
             }
 
             Instruction::VariantPayloadName => results.push("e".to_string()),
@@ -1732,11 +1941,9 @@ impl Bindgen for FunctionBindgen<'_> {
                             "const ptr{} = realloc(0, 0, {}, len{0} * {});\n",
                             tmp, align, size,
                         ));
-                        // TODO: this is the wrong endianness
-                        self.src.js(&format!(
-                            "(new Uint8Array(memory.buffer, ptr{}, len{0} * {})).set(new Uint8Array(val{0}.buffer));\n",
-                            tmp, size,
-                        ));
+                        let to_le = self.gen.intrinsic(Intrinsic::ListToLe);
+                        self.src
+                            .js(&format!("{}(val{}, memory, ptr{1});\n", to_le, tmp,));
                     }
                 };
                 results.push(format!("ptr{}", tmp));
@@ -1761,15 +1968,10 @@ impl Bindgen for FunctionBindgen<'_> {
                         )
                     }
                     _ => {
-                        // TODO: this is the wrong endianness
                         let array_ty = self.gen.array_ty(iface, element).unwrap();
+                        let from_le = self.gen.intrinsic(Intrinsic::ListFromLe);
                         (
-                            format!(
-                                "new {}(memory.buffer.slice(ptr{}, ptr{1} + len{1} * {}))",
-                                array_ty,
-                                tmp,
-                                self.gen.sizes.size(element),
-                            ),
+                            format!("{}({}, memory, ptr{}, len{2})", from_le, array_ty, tmp,),
                             self.gen.sizes.align(element),
                         )
                     }
@@ -1866,9 +2068,36 @@ impl Bindgen for FunctionBindgen<'_> {
                     .js(&format!("const ptr{} = {};\n", tmp, operands[1]));
                 self.src
                     .js(&format!("const len{} = {};\n", tmp, operands[2]));
-                if let Some(ty) = self.gen.array_ty(iface, ty) {
-                    // TODO: this is the wrong endianness
-                    results.push(format!("new {}(memory.buffer, ptr{}, len{1})", ty, tmp));
+                if let Some(array_ty) = self.gen.array_ty(iface, ty) {
+                    let view = format!("new {}(memory.buffer, ptr{}, len{1})", array_ty, tmp);
+                    if self.gen.sizes.size(ty) == 1 {
+                        results.push(view);
+                    } else if *push {
+                        // Big-endian hosts push values into a converted copy
+                        // instead, which is written back in little-endian
+                        // once the call returns.
+                        let is_le = self.gen.intrinsic(Intrinsic::IsLe);
+                        let from_le = self.gen.intrinsic(Intrinsic::ListFromLe);
+                        let to_le = self.gen.intrinsic(Intrinsic::ListToLe);
+                        self.src.js(&format!(
+                            "const buf{} = {} ? {} : {}({}, memory, ptr{0}, len{0});\n",
+                            tmp, is_le, view, from_le, array_ty,
+                        ));
+                        self.push_buffers.push(format!(
+                            "if (!{}) {}(buf{}, memory, ptr{2});\n",
+                            is_le, to_le, tmp,
+                        ));
+                        results.push(format!("buf{}", tmp));
+                    } else {
+                        // Big-endian hosts can't view little-endian values in
+                        // place, so they get a converted copy instead.
+                        let is_le = self.gen.intrinsic(Intrinsic::IsLe);
+                        let from_le = self.gen.intrinsic(Intrinsic::ListFromLe);
+                        results.push(format!(
+                            "({} ? {} : {}({}, memory, ptr{}, len{4}))",
+                            is_le, view, from_le, array_ty, tmp,
+                        ));
+                    }
                 } else {
                     let size = self.gen.sizes.size(ty);
                     if *push {
@@ -2011,6 +2240,16 @@ impl Bindgen for FunctionBindgen<'_> {
             }
 
             Instruction::CallInterface { module: _, func } => {
+                // Parameters are recorded when tracing as well as passed to
+                // the call, so they're only evaluated once here.
+                if self.gen.opts.tracing {
+                    for op in operands.iter_mut() {
+                        let tmp = self.tmp();
+                        let name = format!("param{}", tmp);
+                        self.src.js(&format!("const {} = {};\n", name, op));
+                        *op = name;
+                    }
+                }
                 let call = |me: &mut FunctionBindgen<'_>| match &func.kind {
                     FunctionKind::Freestanding | FunctionKind::Static { .. } => {
                         me.src.js(&format!(
@@ -2058,6 +2297,20 @@ impl Bindgen for FunctionBindgen<'_> {
                     }
                 };
 
+                let traced_call = |me: &mut FunctionBindgen<'_>| {
+                    if me.gen.opts.tracing {
+                        let trace = me
+                            .gen
+                            .trace_call(iface, func, operands, |name| name.to_string());
+                        me.src.js(&trace);
+                        me.src.js("() => ");
+                        call(me);
+                        me.src.js(")");
+                    } else {
+                        call(me);
+                    }
+                };
+
                 if func.is_async {
                     let with = self.gen.intrinsic(Intrinsic::WithCurrentPromise);
                     let promises = self.gen.intrinsic(Intrinsic::Promises);
@@ -2067,16 +2320,22 @@ impl Bindgen for FunctionBindgen<'_> {
                         "const catch_closure = e => {}.remove(cur_promise)(e);\n",
                         promises
                     ));
-                    call(self);
+                    traced_call(self);
                     self.src.js(".then(e => {\n");
+                    for write_back in mem::take(&mut self.push_buffers) {
+                        self.src.js(&write_back);
+                    }
                     if func.results.len() > 0 {
                         bind_results(self);
                         self.src.js("e;\n");
                     }
                 } else {
                     bind_results(self);
-                    call(self);
+                    traced_call(self);
                     self.src.js(";\n");
+                    for write_back in mem::take(&mut self.push_buffers) {
+                        self.src.js(&write_back);
+                    }
                 }
             }
 
@@ -2177,6 +2436,11 @@ impl Js {
         if self.all_intrinsics.contains(&Intrinsic::Promises) {
             self.all_intrinsics.insert(Intrinsic::Slab);
         }
+        if self.all_intrinsics.contains(&Intrinsic::ListToLe)
+            || self.all_intrinsics.contains(&Intrinsic::ListFromLe)
+        {
+            self.all_intrinsics.insert(Intrinsic::IsLe);
+        }
 
         for i in mem::take(&mut self.all_intrinsics) {
             self.print_intrinsic(i);
@@ -2444,6 +2708,77 @@ impl Js {
                     }
                 }
             "),
+
+            Intrinsic::IsLe => self.src.js("
+                export const IS_LE = new Uint8Array(new Uint16Array([1]).buffer)[0] === 1;
+            "),
+
+            Intrinsic::ListToLe => self.src.js("
+                export function list_to_le(val, memory, ptr) {
+                    const size = val.BYTES_PER_ELEMENT;
+                    const dst = new Uint8Array(memory.buffer, ptr, val.byteLength);
+                    dst.set(new Uint8Array(val.buffer, val.byteOffset, val.byteLength));
+                    if (!IS_LE && size > 1) {
+                        for (let i = 0; i < dst.length; i += size)
+                            dst.subarray(i, i + size).reverse();
+                    }
+                }
+            "),
+
+            Intrinsic::ListFromLe => self.src.js("
+                export function list_from_le(ty, memory, ptr, len) {
+                    const size = ty.BYTES_PER_ELEMENT;
+                    const bytes = new Uint8Array(memory.buffer.slice(ptr, ptr + len * size));
+                    if (!IS_LE && size > 1) {
+                        for (let i = 0; i < bytes.length; i += size)
+                            bytes.subarray(i, i + size).reverse();
+                    }
+                    return new ty(bytes.buffer);
+                }
+            "),
+
+            Intrinsic::TraceCall => self.src.js("
+                let TRACE_HOOK = null;
+
+                export function setTraceHook(hook) {
+                    TRACE_HOOK = hook;
+                }
+
+                export function trace_call(module, func, is_async, params, results, call) {
+                    if (TRACE_HOOK === null)
+                        return call();
+                    const start = performance.now();
+                    const finish = (results, error) => TRACE_HOOK({
+                        module,
+                        function: func,
+                        params,
+                        results,
+                        error,
+                        duration_us: Math.round((performance.now() - start) * 1000),
+                    });
+                    let ret;
+                    try {
+                        ret = call();
+                    } catch (e) {
+                        finish(undefined, e);
+                        throw e;
+                    }
+                    if (!is_async) {
+                        finish(results(ret), undefined);
+                        return ret;
+                    }
+                    return Promise.resolve(ret).then(
+                        val => {
+                            finish(results(val), undefined);
+                            return val;
+                        },
+                        e => {
+                            finish(undefined, e);
+                            throw e;
+                        },
+                    );
+                }
+            "),
         }
     }
 }
@@ -20,8 +20,13 @@ fn execute(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path) {
     let imports = wit_bindgen_gen_core::wit_parser::Interface::parse_file(imports).unwrap();
     let exports = wit_bindgen_gen_core::wit_parser::Interface::parse_file(exports).unwrap();
     let mut files = Default::default();
-    wit_bindgen_gen_js::Opts::default()
-        .build()
+    // The `tracing` test installs a trace hook and checks what it records.
+    let mut opts = wit_bindgen_gen_js::Opts::default();
+    if name.starts_with("tracing_") {
+        opts.tracing = true;
+        opts.tracing_redact = vec!["secret".to_string()];
+    }
+    opts.build()
         .generate_all(&[exports], &[imports], &mut files);
     for (file, contents) in files.iter() {
         fs::write(dir.join(file), contents).unwrap();
@@ -76,7 +81,10 @@ fn execute(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path) {
     path.push(env::current_dir().unwrap());
     path.push(dir.clone());
     println!("{:?}", std::env::join_paths(&path));
-    run(Command::new("node")
+    // `WIT_BINDGEN_NODE` can point at a wrapper script, for example one that
+    // runs node under emulation for big-endian testing.
+    let node = env::var("WIT_BINDGEN_NODE").unwrap_or_else(|_| "node".to_string());
+    run(Command::new(node)
         .arg("--experimental-wasi-unstable-preview1")
         .arg(dir.join("host.js"))
         .env("NODE_PATH", std::env::join_paths(&path).unwrap())
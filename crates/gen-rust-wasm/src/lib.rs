@@ -22,6 +22,7 @@ pub struct RustWasm {
     trait_name: String,
     i64_return_pointer_area_size: usize,
     sizes: SizeAlign,
+    memory64: bool,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -45,6 +46,11 @@ pub struct Opts {
     #[cfg_attr(feature = "structopt", structopt(skip))]
     pub symbol_namespace: String,
 
+    /// Whether or not to embed exported interfaces in custom sections of the
+    /// compiled module so tools like `wasmlink` can discover them.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub embed_interface: bool,
+
     /// The alias to use for the `wit_bindgen_rust` crate.
     ///
     /// This allows code generators to alias the `wit_bindgen_rust` crate
@@ -216,8 +222,16 @@ impl RustGenerator for RustWasm {
 
 impl Generator for RustWasm {
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
+        // Compiled Rust code can only address the default linear memory.
+        assert!(
+            iface.memory == 0,
+            "interface `{}` uses memory {}, but only memory 0 is supported",
+            iface.name,
+            iface.memory,
+        );
         let variant = Self::abi_variant(dir);
         self.in_import = variant == AbiVariant::GuestImport;
+        self.memory64 = iface.memory64;
         self.types.analyze(iface);
         self.trait_name = iface.name.to_camel_case();
         self.src
@@ -326,11 +340,11 @@ impl Generator for RustWasm {
         // automatically for runtime-required traits.
         if !self.in_import {
             let panic = "
-                #[cfg(not(target_arch = \"wasm32\"))]
+                #[cfg(not(target_family = \"wasm\"))]
                 {
-                    panic!(\"handles can only be used on wasm32\");
+                    panic!(\"handles can only be used on wasm\");
                 }
-                #[cfg(target_arch = \"wasm32\")]
+                #[cfg(target_family = \"wasm\")]
             ";
             self.src.push_str(&format!(
                 "
@@ -740,6 +754,28 @@ impl Generator for RustWasm {
             ));
         }
 
+        if !self.in_import && self.opts.embed_interface {
+            let wit = iface
+                .to_wit()
+                .unwrap_or_else(|e| panic!("failed to embed interface `{}`: {}", iface.name, e));
+            let escaped = wit
+                .bytes()
+                .flat_map(std::ascii::escape_default)
+                .map(char::from)
+                .collect::<String>();
+            src.push_str("#[cfg(target_family = \"wasm\")]\n");
+            src.push_str(&format!(
+                "#[link_section = \"{}{}\"]\n",
+                INTERFACE_SECTION_PREFIX, iface.name
+            ));
+            src.push_str("#[used]\n");
+            src.push_str(&format!(
+                "static WIT_INTERFACE: [u8; {}] = *b\"{}\";\n",
+                wit.len(),
+                escaped
+            ));
+        }
+
         // Close the opening `mod`.
         src.push_str("}\n");
 
@@ -797,6 +833,16 @@ impl FunctionBindgen<'_> {
         }
     }
 
+    /// The type of pointers and lengths passed through the canonical ABI,
+    /// which is `i64` when targeting 64-bit memories.
+    fn pointer_ty(&self) -> &'static str {
+        if self.gen.memory64 {
+            "i64"
+        } else {
+            "i32"
+        }
+    }
+
     fn emit_cleanup(&mut self) {
         for (ptr, layout) in mem::take(&mut self.cleanup) {
             self.push_str(&format!("std::alloc::dealloc({}, {});\n", ptr, layout));
@@ -822,10 +868,10 @@ impl FunctionBindgen<'_> {
         self.push_str(module);
         self.push_str("\")]\n");
         self.push_str("extern \"C\" {\n");
-        self.push_str("#[cfg_attr(target_arch = \"wasm32\", link_name = \"");
+        self.push_str("#[cfg_attr(target_family = \"wasm\", link_name = \"");
         self.push_str(name);
         self.push_str("\")]\n");
-        self.push_str("#[cfg_attr(not(target_arch = \"wasm32\"), link_name = \"");
+        self.push_str("#[cfg_attr(not(target_family = \"wasm\"), link_name = \"");
         self.push_str(module);
         self.push_str("_");
         self.push_str(name);
@@ -926,7 +972,11 @@ impl Bindgen for FunctionBindgen<'_> {
     fn i64_return_pointer_area(&mut self, amt: usize) -> String {
         assert!(amt <= self.gen.i64_return_pointer_area_size);
         let tmp = self.tmp();
-        self.push_str(&format!("let ptr{} = RET_AREA.as_mut_ptr() as i32;\n", tmp));
+        self.push_str(&format!(
+            "let ptr{} = RET_AREA.as_mut_ptr() as {};\n",
+            tmp,
+            self.pointer_ty()
+        ));
         format!("ptr{}", tmp)
     }
 
@@ -1169,8 +1219,9 @@ impl Bindgen for FunctionBindgen<'_> {
                     };
                     self.push_str(&format!("let {} = ({}).into_boxed_slice();\n", val, op0));
                 }
-                self.push_str(&format!("let {} = {}.as_ptr() as i32;\n", ptr, val));
-                self.push_str(&format!("let {} = {}.len() as i32;\n", len, val));
+                let ptr_ty = self.pointer_ty();
+                self.push_str(&format!("let {} = {}.as_ptr() as {};\n", ptr, val, ptr_ty));
+                self.push_str(&format!("let {} = {}.len() as {};\n", len, val, ptr_ty));
                 if realloc.is_some() {
                     self.push_str(&format!("core::mem::forget({});\n", val));
                 }
@@ -1208,8 +1259,9 @@ impl Bindgen for FunctionBindgen<'_> {
                 let result = format!("result{}", tmp);
                 let layout = format!("layout{}", tmp);
                 let len = format!("len{}", tmp);
+                let ptr_ty = self.pointer_ty();
                 self.push_str(&format!("let {} = {};\n", vec, operands[0]));
-                self.push_str(&format!("let {} = {}.len() as i32;\n", len, vec));
+                self.push_str(&format!("let {} = {}.len() as {};\n", len, vec, ptr_ty));
                 let size = self.gen.sizes.size(element);
                 let align = self.gen.sizes.align(element);
                 self.push_str(&format!(
@@ -1229,12 +1281,12 @@ impl Bindgen for FunctionBindgen<'_> {
                     vec
                 ));
                 self.push_str(&format!(
-                    "let base = {} as i32 + (i as i32) * {};\n",
-                    result, size,
+                    "let base = {} as {2} + (i as {2}) * {};\n",
+                    result, size, ptr_ty,
                 ));
                 self.push_str(&body);
                 self.push_str("}\n");
-                results.push(format!("{} as i32", result));
+                results.push(format!("{} as {}", result, ptr_ty));
                 results.push(len);
 
                 if realloc.is_none() {
@@ -5,7 +5,9 @@
 
 #include <assert.h>
 #include <cmath>
+#include <stdint.h>
 #include <stdlib.h>
+#include <string.h>
 
 #include "smw/abort.h"
 #include "smw/cx.h"
@@ -13,8 +15,10 @@
 #include "smw/wasm.h"
 
 #include "mozilla/UniquePtr.h"
+#include "mozilla/Vector.h"
 #include "jsapi.h"
 #include "js/Array.h"
+#include "js/BigInt.h"
 #include "js/Conversions.h"
 #include "js/ForOfIterator.h"
 #include "js/Modules.h"
@@ -38,6 +42,13 @@ static PersistentRootedValueVector* ARGS;
 // Used for holding returns from Wasm calls.
 static PersistentRootedValueVector* RETS;
 
+// The JS values backing the resources that the user module exports. A
+// resource's representation in the canonical ABI is its index in this vector.
+static PersistentRootedValueVector* RESOURCES;
+
+// Indices in `RESOURCES` that have been dropped and may be reused.
+static mozilla::Vector<uint32_t>* FREE_RESOURCES;
+
 void init_operands(JSContext* cx) {
     assert(!OPERANDS && "OPERANDS must only be initialized once");
     OPERANDS = new PersistentRootedValueVector(cx, cx);
@@ -56,6 +67,18 @@ void init_operands(JSContext* cx) {
     if (!RETS) {
         abort(cx, "failed to allocate RETS");
     }
+
+    assert(!RESOURCES && "RESOURCES must only be initialized once");
+    RESOURCES = new PersistentRootedValueVector(cx, cx);
+    if (!RESOURCES) {
+        abort(cx, "failed to allocate RESOURCES");
+    }
+
+    assert(!FREE_RESOURCES && "FREE_RESOURCES must only be initialized once");
+    FREE_RESOURCES = new mozilla::Vector<uint32_t>();
+    if (!FREE_RESOURCES) {
+        abort(cx, "failed to allocate FREE_RESOURCES");
+    }
 }
 
 PersistentRootedValueVector& operands() {
@@ -101,6 +124,11 @@ PersistentRootedValueVector& rets() {
     return *RETS;
 }
 
+PersistentRootedValueVector& resources() {
+    assert(RESOURCES && RESOURCES->initialized() && "RESOURCES must be initialized");
+    return *RESOURCES;
+}
+
 WASM_EXPORT
 void canonical_abi_free(void* ptr, size_t size, size_t align) {
     (void) size;
@@ -150,7 +178,7 @@ void SMW_push_arg(size_t i) {
 }
 
 WASM_EXPORT
-void SMW_call(char *funcName, size_t funcNameLen, size_t numResults, size_t dest) {
+void SMW_call(const char *funcName, size_t funcNameLen, size_t numResults, size_t dest) {
 #ifdef LOGGING
     SMW_LOG("SMW_call(funcName = %p \"", funcName);
     for (size_t i = 0; i < funcNameLen; i++) {
@@ -162,11 +190,11 @@ void SMW_call(char *funcName, size_t funcNameLen, size_t numResults, size_t dest
             dest);
 #endif
 
-    UniqueChars uniqFuncName(funcName);
-
+    // NB: `funcName` is an interned string owned by the glue module; do not
+    // free it.
     JSContext *cx = get_js_context();
 
-    JS::RootedString funcNameAtom(cx, JS_AtomizeStringN(cx, uniqFuncName.get(), funcNameLen));
+    JS::RootedString funcNameAtom(cx, JS_AtomizeStringN(cx, funcName, funcNameLen));
     if (!funcNameAtom) {
         abort(cx, "failed to atomize function name");
     }
@@ -449,4 +477,461 @@ void SMW_array_push(size_t array, size_t elem) {
     }
 }
 
+WASM_EXPORT
+int32_t SMW_i32_from_number(size_t i) {
+    SMW_LOG("SMW_i32_from_number(i = %zu)\n", i);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, operands()[i]);
+    int32_t x = 0;
+    if (!JS::ToInt32(cx, val, &x)) {
+        abort(cx, "failed to convert value to an integer");
+    }
+    return x;
+}
+
+WASM_EXPORT
+void SMW_s32_from_i32(int32_t x, size_t dest) {
+    SMW_LOG("SMW_s32_from_i32(x = %d, dest = %zu)\n", x, dest);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, JS::Int32Value(x));
+    save_operand(dest, val);
+}
+
+WASM_EXPORT
+int64_t SMW_i64_from_bigint(size_t i) {
+    SMW_LOG("SMW_i64_from_bigint(i = %zu)\n", i);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, operands()[i]);
+
+    // Accept plain numbers as well as `BigInt`s, since it is easy to
+    // accidentally pass a number where a 64-bit integer is expected.
+    if (val.isNumber()) {
+        double number = std::trunc(val.toNumber());
+        if (number < 0) {
+            return int64_t(number);
+        }
+        return int64_t(uint64_t(number));
+    }
+
+    JS::BigInt* bigint = JS::ToBigInt(cx, val);
+    if (!bigint) {
+        abort(cx, "failed to convert value to a BigInt");
+    }
+    // Both signed and unsigned 64-bit integers have the same bit pattern when
+    // truncated to 64 bits, so we don't need to know which one we're lowering.
+    return JS::ToBigInt64(bigint);
+}
+
+WASM_EXPORT
+void SMW_s64_from_i64(int64_t x, size_t dest) {
+    SMW_LOG("SMW_s64_from_i64(x = %lld, dest = %zu)\n", x, dest);
+
+    JSContext* cx = get_js_context();
+    JS::RootedBigInt bigint(cx, JS::NumberToBigInt(cx, x));
+    if (!bigint) {
+        abort(cx, "failed to allocate a BigInt");
+    }
+    JS::RootedValue val(cx, JS::BigIntValue(bigint));
+    save_operand(dest, val);
+}
+
+WASM_EXPORT
+void SMW_u64_from_i64(uint64_t x, size_t dest) {
+    SMW_LOG("SMW_u64_from_i64(x = %llu, dest = %zu)\n", x, dest);
+
+    JSContext* cx = get_js_context();
+    JS::RootedBigInt bigint(cx, JS::NumberToBigInt(cx, x));
+    if (!bigint) {
+        abort(cx, "failed to allocate a BigInt");
+    }
+    JS::RootedValue val(cx, JS::BigIntValue(bigint));
+    save_operand(dest, val);
+}
+
+WASM_EXPORT
+double SMW_f64_from_number(size_t i) {
+    SMW_LOG("SMW_f64_from_number(i = %zu)\n", i);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, operands()[i]);
+    double number = 0.0;
+    if (!JS::ToNumber(cx, val, &number)) {
+        abort(cx, "failed to convert value to number");
+    }
+    return number;
+}
+
+WASM_EXPORT
+void SMW_number_from_f64(double x, size_t dest) {
+    SMW_LOG("SMW_number_from_f64(x = %f, dest = %zu)\n", x, dest);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, JS::NumberValue(x));
+    save_operand(dest, val);
+}
+
+WASM_EXPORT
+uint32_t SMW_i32_from_char(size_t i) {
+    SMW_LOG("SMW_i32_from_char(i = %zu)\n", i);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, operands()[i]);
+    if (!val.isString()) {
+        abort(cx, "value is not a string");
+    }
+    JS::RootedString str(cx, val.toString());
+    JS::Rooted<JSLinearString*> linearStr(cx, JS_EnsureLinearString(cx, str));
+    if (!linearStr) {
+        abort(cx, "failed to linearize JS string");
+    }
+
+    size_t len = JS::GetLinearStringLength(linearStr);
+    if (len == 0) {
+        abort(cx, "expected a string containing a single character, found an empty string");
+    }
+    char16_t lead = JS::GetLinearStringCharAt(linearStr, 0);
+    if (lead >= 0xD800 && lead <= 0xDBFF && len == 2) {
+        char16_t trail = JS::GetLinearStringCharAt(linearStr, 1);
+        if (trail >= 0xDC00 && trail <= 0xDFFF) {
+            return 0x10000 + ((uint32_t(lead) - 0xD800) << 10) + (uint32_t(trail) - 0xDC00);
+        }
+    }
+    if (len != 1 || (lead >= 0xD800 && lead <= 0xDFFF)) {
+        abort(cx, "expected a string containing a single Unicode scalar value");
+    }
+    return lead;
+}
+
+WASM_EXPORT
+void SMW_char_from_i32(uint32_t c, size_t dest) {
+    SMW_LOG("SMW_char_from_i32(c = %u, dest = %zu)\n", c, dest);
+
+    JSContext* cx = get_js_context();
+    if (c > 0x10FFFF || (c >= 0xD800 && c <= 0xDFFF)) {
+        abort(cx, "invalid Unicode scalar value");
+    }
+
+    char16_t chars[2];
+    size_t len = 0;
+    if (c < 0x10000) {
+        chars[len++] = char16_t(c);
+    } else {
+        c -= 0x10000;
+        chars[len++] = char16_t(0xD800 + (c >> 10));
+        chars[len++] = char16_t(0xDC00 + (c & 0x3FF));
+    }
+
+    JS::RootedString str(cx, JS_NewUCStringCopyN(cx, chars, len));
+    if (!str) {
+        abort(cx, "failed to create JS string from character");
+    }
+    JS::RootedValue val(cx, JS::StringValue(str));
+    save_operand(dest, val);
+}
+
+/**
+ * The JS representation of a WIT variant. Must be kept in sync with
+ * `VariantRepr` in `wit_bindgen_gen_spidermonkey`.
+ */
+enum class VariantRepr : uint32_t {
+    // `true` or `false`.
+    Bool = 0,
+    // `null` or `undefined` for `none`, otherwise the payload itself.
+    Option = 1,
+    // The case name as a string.
+    Enum = 2,
+    // An object of the form `{ tag: "case-name", val: payload }`.
+    Tagged = 3,
+};
+
+/**
+ * Find the index of `tag` within the `numCases` NUL-terminated case names laid
+ * out back to back in `caseNames`.
+ */
+static uint32_t find_case(JSContext* cx, JS::HandleValue tag, const char* caseNames, size_t numCases) {
+    if (!tag.isString()) {
+        abort(cx, "variant tag is not a string");
+    }
+    JS::RootedString tagStr(cx, tag.toString());
+
+    const char* caseName = caseNames;
+    for (size_t i = 0; i < numCases; i++) {
+        bool match = false;
+        if (!JS_StringEqualsAscii(cx, tagStr, caseName, &match)) {
+            abort(cx, "failed to compare variant tag");
+        }
+        if (match) {
+            return uint32_t(i);
+        }
+        caseName += strlen(caseName) + 1;
+    }
+
+    // TODO: include the tag and the variant's name in this message.
+    abort(cx, "invalid variant tag");
+}
+
+WASM_EXPORT
+uint32_t SMW_variant_discriminant(size_t i, VariantRepr repr, const char* caseNames, size_t numCases) {
+    SMW_LOG("SMW_variant_discriminant(i = %zu, repr = %u, caseNames = %p, numCases = %zu)\n",
+            i,
+            uint32_t(repr),
+            caseNames,
+            numCases);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, operands()[i]);
+
+    switch (repr) {
+    case VariantRepr::Bool:
+        return JS::ToBoolean(val) ? 1 : 0;
+    case VariantRepr::Option:
+        return val.isNullOrUndefined() ? 0 : 1;
+    case VariantRepr::Enum:
+        return find_case(cx, val, caseNames, numCases);
+    case VariantRepr::Tagged: {
+        if (!val.isObject()) {
+            abort(cx, "variant value is not an object");
+        }
+        JS::RootedObject obj(cx, &val.toObject());
+        JS::RootedValue tag(cx);
+        if (!JS_GetProperty(cx, obj, "tag", &tag)) {
+            abort(cx, "failed to get variant's `tag` property");
+        }
+        return find_case(cx, tag, caseNames, numCases);
+    }
+    default:
+        abort(cx, "unknown variant representation");
+    }
+}
+
+WASM_EXPORT
+void SMW_variant_payload(size_t i, VariantRepr repr, size_t dest) {
+    SMW_LOG("SMW_variant_payload(i = %zu, repr = %u, dest = %zu)\n", i, uint32_t(repr), dest);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, operands()[i]);
+
+    switch (repr) {
+    case VariantRepr::Option: {
+        save_operand(dest, val);
+        break;
+    }
+    case VariantRepr::Tagged: {
+        assert(val.isObject() && "already checked by `SMW_variant_discriminant`");
+        JS::RootedObject obj(cx, &val.toObject());
+        JS::RootedValue payload(cx);
+        if (!JS_GetProperty(cx, obj, "val", &payload)) {
+            abort(cx, "failed to get variant's `val` property");
+        }
+        save_operand(dest, payload);
+        break;
+    }
+    default:
+        abort(cx, "variant representation does not have a payload");
+    }
+}
+
+WASM_EXPORT
+void SMW_variant_lift(VariantRepr repr,
+                      uint32_t discriminant,
+                      const char* caseName,
+                      size_t caseNameLen,
+                      size_t payload,
+                      size_t dest) {
+    SMW_LOG("SMW_variant_lift(repr = %u, discriminant = %u, caseName = %p, caseNameLen = %zu, payload = %zu, dest = %zu)\n",
+            uint32_t(repr),
+            discriminant,
+            caseName,
+            caseNameLen,
+            payload,
+            dest);
+
+    JSContext* cx = get_js_context();
+    // `SIZE_MAX` means that this case does not have a payload.
+    bool hasPayload = payload != SIZE_MAX;
+
+    switch (repr) {
+    case VariantRepr::Bool: {
+        JS::RootedValue val(cx, JS::BooleanValue(discriminant != 0));
+        save_operand(dest, val);
+        break;
+    }
+    case VariantRepr::Option: {
+        if (discriminant == 0) {
+            JS::RootedValue val(cx, JS::NullValue());
+            save_operand(dest, val);
+        } else {
+            assert(hasPayload && "`some` always has a payload");
+            JS::RootedValue val(cx, operands()[payload]);
+            save_operand(dest, val);
+        }
+        break;
+    }
+    case VariantRepr::Enum: {
+        JS::RootedString name(cx, JS_NewStringCopyN(cx, caseName, caseNameLen));
+        if (!name) {
+            abort(cx, "failed to create JS string for enum case");
+        }
+        JS::RootedValue val(cx, JS::StringValue(name));
+        save_operand(dest, val);
+        break;
+    }
+    case VariantRepr::Tagged: {
+        JS::RootedObject obj(cx, JS_NewPlainObject(cx));
+        if (!obj) {
+            abort(cx, "failed to allocate a new JS object");
+        }
+        JS::RootedString name(cx, JS_NewStringCopyN(cx, caseName, caseNameLen));
+        if (!name) {
+            abort(cx, "failed to create JS string for variant tag");
+        }
+        JS::RootedValue tag(cx, JS::StringValue(name));
+        if (!JS_DefineProperty(cx, obj, "tag", tag, JSPROP_ENUMERATE)) {
+            abort(cx, "failed to define variant's `tag` property");
+        }
+        if (hasPayload) {
+            JS::RootedValue val(cx, operands()[payload]);
+            if (!JS_DefineProperty(cx, obj, "val", val, JSPROP_ENUMERATE)) {
+                abort(cx, "failed to define variant's `val` property");
+            }
+        }
+        JS::RootedValue objVal(cx, JS::ObjectValue(*obj));
+        save_operand(dest, objVal);
+        break;
+    }
+    default:
+        abort(cx, "unknown variant representation");
+    }
+}
+
+WASM_EXPORT
+void SMW_new_object(size_t dest) {
+    SMW_LOG("SMW_new_object(dest = %zu)\n", dest);
+
+    JSContext* cx = get_js_context();
+    JS::RootedObject obj(cx, JS_NewPlainObject(cx));
+    if (!obj) {
+        abort(cx, "failed to allocate a new JS object");
+    }
+    JS::RootedValue objVal(cx, JS::ObjectValue(*obj));
+    save_operand(dest, objVal);
+}
+
+WASM_EXPORT
+void SMW_get_property(size_t obj, const char* name, size_t nameLen, size_t dest) {
+    SMW_LOG("SMW_get_property(obj = %zu, name = %p, nameLen = %zu, dest = %zu)\n",
+            obj,
+            name,
+            nameLen,
+            dest);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue objVal(cx, operands()[obj]);
+    if (!objVal.isObject()) {
+        abort(cx, "value is not an object");
+    }
+    JS::RootedObject jsObj(cx, &objVal.toObject());
+    JS::RootedValue val(cx);
+    if (!JS_GetProperty(cx, jsObj, name, &val)) {
+        abort(cx, "failed to get object property");
+    }
+    save_operand(dest, val);
+}
+
+WASM_EXPORT
+void SMW_set_property(size_t obj, const char* name, size_t nameLen, size_t val) {
+    SMW_LOG("SMW_set_property(obj = %zu, name = %p, nameLen = %zu, val = %zu)\n",
+            obj,
+            name,
+            nameLen,
+            val);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue objVal(cx, operands()[obj]);
+    assert(objVal.isObject());
+    JS::RootedObject jsObj(cx, &objVal.toObject());
+    JS::RootedValue jsVal(cx, operands()[val]);
+    if (!JS_DefineProperty(cx, jsObj, name, jsVal, JSPROP_ENUMERATE)) {
+        abort(cx, "failed to define object property");
+    }
+}
+
+WASM_EXPORT
+uint32_t SMW_resource_insert(size_t i) {
+    SMW_LOG("SMW_resource_insert(i = %zu)\n", i);
+
+    JSContext* cx = get_js_context();
+    JS::RootedValue val(cx, operands()[i]);
+
+    if (!FREE_RESOURCES->empty()) {
+        uint32_t rep = FREE_RESOURCES->popCopy();
+        resources()[rep].set(val);
+        return rep;
+    }
+
+    if (!resources().append(val)) {
+        abort(cx, "failed to append to the resources vector");
+    }
+    return uint32_t(resources().length() - 1);
+}
+
+WASM_EXPORT
+void SMW_resource_get(uint32_t rep, size_t dest) {
+    SMW_LOG("SMW_resource_get(rep = %u, dest = %zu)\n", rep, dest);
+
+    if (rep >= resources().length()) {
+        abort(get_js_context(), "invalid resource representation");
+    }
+    JS::RootedValue val(get_js_context(), resources()[rep]);
+    save_operand(dest, val);
+}
+
+WASM_EXPORT
+void SMW_resource_drop(uint32_t rep, const char* dropFuncName, size_t dropFuncNameLen) {
+    SMW_LOG("SMW_resource_drop(rep = %u, dropFuncName = %p, dropFuncNameLen = %zu)\n",
+            rep,
+            dropFuncName,
+            dropFuncNameLen);
+
+    JSContext* cx = get_js_context();
+    if (rep >= resources().length()) {
+        abort(cx, "invalid resource representation");
+    }
+
+    JS::RootedValue val(cx, resources()[rep]);
+    resources()[rep].setUndefined();
+    if (!FREE_RESOURCES->append(rep)) {
+        abort(cx, "failed to append to the free resources vector");
+    }
+
+    // If the user module exports a destructor for this resource, give it a
+    // chance to clean up after the value.
+    JS::RootedString funcNameAtom(cx, JS_AtomizeStringN(cx, dropFuncName, dropFuncNameLen));
+    if (!funcNameAtom) {
+        abort(cx, "failed to atomize function name");
+    }
+    JS::RootedObject module(cx, get_user_module());
+    JS::RootedValue exportVal(cx);
+    bool hasExport = false;
+    if (!JS::GetModuleExport(cx, module, funcNameAtom, &exportVal, &hasExport)) {
+        abort(cx, "failed to get module export");
+    }
+    if (!hasExport) {
+        return;
+    }
+
+    JS::RootedFunction exportFunc(cx, JS_ValueToFunction(cx, exportVal));
+    if (!exportFunc) {
+        abort(cx, "exported resource destructor is not a function");
+    }
+    JS::RootedObject thisObj(cx);
+    JS::RootedValue result(cx);
+    if (!JS::Call(cx, thisObj, exportFunc, JS::HandleValueArray(val), &result)) {
+        abort(cx, "calling resource destructor failed");
+    }
+}
+
 } // namespace smw
@@ -8,8 +8,8 @@ namespace smw {
 /**
  * Print the given error message and abort.
  */
-void abort(const char* msg);
-void abort(JSContext *cx, const char* msg);
+[[noreturn]] void abort(const char* msg);
+[[noreturn]] void abort(JSContext *cx, const char* msg);
 
 }
 
@@ -2,10 +2,7 @@
 
 #![deny(missing_docs)]
 
-mod data_segments;
-
-use data_segments::DataSegments;
-use heck::SnakeCase;
+use heck::{MixedCase, SnakeCase};
 use lazy_static::lazy_static;
 use std::borrow::Cow;
 use std::convert::TryFrom;
@@ -16,10 +13,15 @@ use wasm_encoder::Instruction;
 use wit_bindgen_gen_core::{
     wit_parser::{
         abi::{self, AbiVariant, WasmSignature, WasmType},
-        Docs, Function, Interface, Record, ResourceId, SizeAlign, Type, TypeId, Variant,
+        Docs, Function, Interface, Record, ResourceId, SizeAlign, Type, TypeDefKind, TypeId,
+        Variant,
     },
     Direction, Files, Generator,
 };
+use wit_bindgen_gen_wasm_glue::{
+    convert_ty, emit_wasm_instruction, is_nullable_option, mem_arg, DataSegments, EmbeddedModule,
+    GlueFunction, InstructionSink,
+};
 
 #[allow(missing_docs)]
 #[derive(Default, Debug, Clone)]
@@ -29,6 +31,15 @@ pub struct Opts {
     pub js: PathBuf,
     #[cfg_attr(feature = "structopt", structopt(long))]
     pub import_spidermonkey: bool,
+    /// The path to the `spidermonkey.wasm` to embed in the generated module,
+    /// unless `--import-spidermonkey` is given.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub spidermonkey_wasm: Option<PathBuf>,
+    /// Evaluate the JavaScript module at build time and snapshot the
+    /// initialized state into the generated module with Wizer.
+    #[cfg(feature = "wizer")]
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub pre_initialize: bool,
 }
 
 #[allow(missing_docs)]
@@ -36,10 +47,30 @@ impl Opts {
     pub fn build<'a>(self, js_source: impl Into<Cow<'a, str>>) -> SpiderMonkeyWasm<'a> {
         let mut builder = SpiderMonkeyWasm::new(self.js, js_source);
         builder.import_spidermonkey(self.import_spidermonkey);
+        #[cfg(feature = "wizer")]
+        builder.pre_initialize(self.pre_initialize);
         builder
     }
 }
 
+/// Pre-initialize a glue module generated by [`SpiderMonkeyWasm`] with Wizer.
+///
+/// This runs the module's `wizer.initialize` function, which initializes
+/// SpiderMonkey and evaluates the top level of the JavaScript, and then
+/// snapshots the resulting state into a new Wasm module. Instantiating the
+/// snapshot skips all of that work.
+///
+/// The glue module must embed `spidermonkey.wasm` rather than import it, and
+/// the JavaScript's top level must not call any imported WIT functions.
+#[cfg(feature = "wizer")]
+pub fn wizen(wasm: &[u8]) -> anyhow::Result<Vec<u8>> {
+    wizer::Wizer::new()
+        .allow_wasi(true)
+        .wasm_module_linking(true)
+        .wasm_multi_memory(true)
+        .run(wasm)
+}
+
 lazy_static! {
     /// Functions exported from `spidermonkey.wasm`
     static ref SMW_EXPORTS: Vec<(&'static str, WasmSignature)> = vec![
@@ -227,6 +258,157 @@ lazy_static! {
                 retptr: None,
             },
         ),
+        (
+            "SMW_i32_from_number",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_s32_from_i32",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_i64_from_bigint",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I64],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_s64_from_i64",
+            WasmSignature {
+                params: vec![WasmType::I64, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_u64_from_i64",
+            WasmSignature {
+                params: vec![WasmType::I64, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_f64_from_number",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::F64],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_number_from_f64",
+            WasmSignature {
+                params: vec![WasmType::F64, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_i32_from_char",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_char_from_i32",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_variant_discriminant",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_variant_payload",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_variant_lift",
+            WasmSignature {
+                params: vec![
+                    WasmType::I32,
+                    WasmType::I32,
+                    WasmType::I32,
+                    WasmType::I32,
+                    WasmType::I32,
+                    WasmType::I32,
+                ],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_new_object",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_get_property",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_set_property",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_resource_insert",
+            WasmSignature {
+                params: vec![WasmType::I32],
+                results: vec![WasmType::I32],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_resource_get",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
+        (
+            "SMW_resource_drop",
+            WasmSignature {
+                params: vec![WasmType::I32, WasmType::I32, WasmType::I32],
+                results: vec![],
+                retptr: None,
+            },
+        ),
         (
             "dump_i32",
             WasmSignature {
@@ -266,6 +448,11 @@ lazy_static! {
 /// * Evaluates the JavaScript source, compiling it to bytecode and initializing
 ///   globals and defining top-level functions in the process.
 ///
+/// When `spidermonkey.wasm` is embedded, the initialization function sticks
+/// to the Wasm features that Wizer can snapshot, and the generated module can
+/// be pre-initialized at build time with `wizen`. The resulting snapshot
+/// has already run `wizer.initialize` and no longer exports it.
+///
 /// ### Imports
 ///
 /// By the time an imported WIT function is called, we have the following
@@ -317,6 +504,37 @@ lazy_static! {
 ///   This is the user-written JavaScript function that is being exported. It
 ///   accepts and returns the JavaScript values that correspond to the interface
 ///   types used in the WIT signature.
+///
+/// ### JavaScript Values
+///
+/// WIT values are represented in JavaScript as follows:
+///
+/// * Integers and floats are numbers, except for 64-bit integers which are
+///   `BigInt`s.
+///
+/// * `char`s are strings containing a single Unicode scalar value.
+///
+/// * Records are plain objects with a property for each field, named in
+///   `camelCase`. Tuples are arrays.
+///
+/// * Flags are plain objects with a boolean property for each flag, named in
+///   `camelCase`.
+///
+/// * `bool`s are booleans. An `option<T>` is `null` (or `undefined`) or a `T`,
+///   unless `T` is itself an option, in which case it is represented like any
+///   other variant. Enums are their case name as a string. All other variants,
+///   including `expected<T, E>`, are objects of the form `{ tag: "case-name",
+///   val: payload }`.
+///
+/// * Handles to resources defined by an imported interface are the raw handle
+///   number. The JS module for the imported interface exports a
+///   `drop_${resource}` function to release a handle when it is no longer
+///   needed.
+///
+/// * Handles to resources defined by the exported interface may be any
+///   JavaScript value. When the last handle to a resource is dropped, the
+///   user's `drop_${resource}` export is called with that value, if it
+///   exists.
 pub struct SpiderMonkeyWasm<'a> {
     /// The filename to use for the JS.
     js_name: PathBuf,
@@ -327,10 +545,25 @@ pub struct SpiderMonkeyWasm<'a> {
     i64_return_pointer_area_size: usize,
 
     num_import_functions: Option<u32>,
+    num_import_glue_functions: Option<u32>,
     num_export_functions: Option<u32>,
 
     import_spidermonkey: bool,
 
+    /// The `spidermonkey.wasm` to embed, if we aren't importing it.
+    spidermonkey_wasm: Option<Cow<'a, [u8]>>,
+
+    /// When embedding `spidermonkey.wasm`, the function table slot where our
+    /// import glue functions are placed by an active element segment.
+    static_table_base: Option<u32>,
+
+    #[cfg(feature = "wizer")]
+    pre_initialize: bool,
+
+    /// Whether the interface we are currently generating bindings for is
+    /// imported or exported.
+    in_import: bool,
+
     /// Function types that we use in this Wasm module.
     types: wasm_encoder::TypeSection,
 
@@ -346,8 +579,13 @@ pub struct SpiderMonkeyWasm<'a> {
     /// thus far.
     import_glue_fns: Vec<wasm_encoder::Function>,
 
-    /// A map from `module_name -> func_name -> (index, num_args)`.
-    import_fn_name_to_index: HashMap<String, HashMap<String, (u32, u32)>>,
+    /// A map from `module_name -> func_name -> index` for every raw function
+    /// import, including the `canonical_abi` resource intrinsics.
+    import_fn_name_to_index: HashMap<String, HashMap<String, u32>>,
+
+    /// The JS modules we define in `wizer.initialize` for imported interfaces:
+    /// `module_name -> [(js_func_name, import_glue_fn, num_args)]`.
+    js_import_modules: HashMap<String, Vec<(String, u32, u32)>>,
 
     exports: wasm_encoder::ExportSection,
 
@@ -355,8 +593,19 @@ pub struct SpiderMonkeyWasm<'a> {
     /// thus far, and their type index.
     export_glue_fns: Vec<(wasm_encoder::Function, u32)>,
 
+    /// The static data for the glue module's own memory. This memory is
+    /// defined by a nested module, so these segments target its memory 0.
     data_segments: DataSegments,
 
+    /// Strings that are copied into `spidermonkey.wasm`'s memory once in
+    /// `wizer.initialize`, rather than on every use: `(data offset, len)`. The
+    /// pointer to the `i`th string is stored in global `FIRST_STRING_GLOBAL +
+    /// i`.
+    strings: Vec<(u32, u32)>,
+
+    /// A map from string to its index in `strings`.
+    string_to_index: HashMap<String, u32>,
+
     sizes: SizeAlign,
     function_names: Vec<(u32, String)>,
     local_names: Vec<(u32, wasm_encoder::NameMap)>,
@@ -373,16 +622,25 @@ impl<'a> SpiderMonkeyWasm<'a> {
             js,
             i64_return_pointer_area_size: 0,
             num_import_functions: None,
+            num_import_glue_functions: None,
             num_export_functions: None,
             import_spidermonkey: false,
+            spidermonkey_wasm: None,
+            static_table_base: None,
+            #[cfg(feature = "wizer")]
+            pre_initialize: false,
+            in_import: false,
             types: wasm_encoder::TypeSection::new(),
             wasm_sig_to_index: Default::default(),
             imports: wasm_encoder::ImportSection::new(),
             import_glue_fns: Default::default(),
             import_fn_name_to_index: Default::default(),
+            js_import_modules: Default::default(),
             exports: wasm_encoder::ExportSection::new(),
             export_glue_fns: Default::default(),
-            data_segments: DataSegments::new(1),
+            data_segments: DataSegments::new(0),
+            strings: Vec::new(),
+            string_to_index: Default::default(),
             sizes: Default::default(),
             function_names: Vec::new(),
             local_names: Vec::new(),
@@ -410,6 +668,27 @@ impl<'a> SpiderMonkeyWasm<'a> {
         self.import_spidermonkey = import;
     }
 
+    /// Provide the `spidermonkey.wasm` module to embed in the generated glue
+    /// module.
+    ///
+    /// This is required unless `spidermonkey.wasm` is imported.
+    pub fn spidermonkey_wasm(&mut self, wasm: impl Into<Cow<'a, [u8]>>) {
+        self.spidermonkey_wasm = Some(wasm.into());
+    }
+
+    /// Configure whether the generated glue module is pre-initialized with
+    /// [`wizen`].
+    ///
+    /// By default, the `wizer.initialize` function is exported and must be
+    /// called by the embedder before anything else. If `pre_initialize` is
+    /// `true`, then the JavaScript is evaluated at build time and the output
+    /// is the resulting snapshot instead. This requires embedding
+    /// `spidermonkey.wasm`.
+    #[cfg(feature = "wizer")]
+    pub fn pre_initialize(&mut self, pre_initialize: bool) {
+        self.pre_initialize = pre_initialize;
+    }
+
     fn intern_type(&mut self, wasm_sig: WasmSignature) -> u32 {
         if let Some(idx) = self.wasm_sig_to_index.get(&&wasm_sig) {
             return *idx;
@@ -427,12 +706,63 @@ impl<'a> SpiderMonkeyWasm<'a> {
         idx
     }
 
+    /// Add a raw function import of `module.name` and return its function
+    /// index.
+    fn import_function(&mut self, module: &str, name: &str, wasm_sig: WasmSignature) -> u32 {
+        let type_index = self.intern_type(wasm_sig);
+        let index = self.wit_import(self.imports.len());
+        self.imports.import(
+            module,
+            Some(name),
+            wasm_encoder::EntityType::Function(type_index),
+        );
+
+        let existing = self
+            .import_fn_name_to_index
+            .entry(module.to_string())
+            .or_default()
+            .insert(name.to_string(), index);
+        assert!(existing.is_none(), "duplicate import: {}.{}", module, name);
+
+        self.function_names
+            .push((index, format!("{}.{}", module, name)));
+        index
+    }
+
+    /// Get the function index of the given `canonical_abi` resource
+    /// intrinsic.
+    fn canonical_abi_import(&self, name: &str) -> u32 {
+        *self
+            .import_fn_name_to_index
+            .get("canonical_abi")
+            .and_then(|funcs| funcs.get(name))
+            .unwrap_or_else(|| panic!("unknown `canonical_abi` import: {}", name))
+    }
+
+    /// Get the global holding a pointer to a copy of `s` in
+    /// `spidermonkey.wasm`'s memory, along with the length of `s`.
+    ///
+    /// The copy is made once, in `wizer.initialize`, and lives forever.
+    fn string_global(&mut self, s: &str) -> (u32, u32) {
+        let len = u32::try_from(s.len()).unwrap();
+        if let Some(i) = self.string_to_index.get(s) {
+            return (FIRST_STRING_GLOBAL + *i, len);
+        }
+
+        let i = u32::try_from(self.strings.len()).unwrap();
+        let offset = self.data_segments.add(s.as_bytes().iter().copied());
+        self.strings.push((offset, len));
+        self.string_to_index.insert(s.to_string(), i);
+        (FIRST_STRING_GLOBAL + i, len)
+    }
+
     fn link_spidermonkey_wasm(
         &mut self,
         modules: &mut wasm_encoder::ModuleSection,
         instances: &mut wasm_encoder::InstanceSection,
         aliases: &mut wasm_encoder::AliasSection,
-    ) {
+    ) -> u32 {
+        let mut forwarded_instances = 0;
         if self.import_spidermonkey {
             // Import an instance that exports all the expected
             // `spidermonkey.wasm` things.
@@ -467,18 +797,66 @@ impl<'a> SpiderMonkeyWasm<'a> {
                 wasm_encoder::EntityType::Instance(instance_type_index),
             );
         } else {
-            // Embded `spidermonkey.wasm` in the modules section and then
-            // instantiate it. This will involve adding its imports to our
-            // import section and fowarding them along.
-            let _ = (modules, instances);
-            todo!()
+            // Embed `spidermonkey.wasm` in the modules section and then
+            // instantiate it. Its imports are grouped by module into instance
+            // imports of our own, which we forward along. Importing instances
+            // rather than functions leaves our function index space as-is.
+            let wasm = self.spidermonkey_wasm.take().unwrap_or_else(|| {
+                panic!(
+                    "`spidermonkey.wasm` must be provided with \
+                     `SpiderMonkeyWasm::spidermonkey_wasm` unless it is imported"
+                )
+            });
+            let embedded =
+                EmbeddedModule::new("spidermonkey.wasm", &wasm, self.wit_import_glue_fns_len())
+                    .unwrap_or_else(|e| panic!("failed to embed `spidermonkey.wasm`: {:?}", e));
+            self.static_table_base = Some(embedded.table_base);
+
+            let mut smw_imports: Vec<(String, Vec<(String, WasmSignature)>)> = Vec::new();
+            for (module, name, sig) in embedded.imports {
+                match smw_imports.iter_mut().find(|(m, _)| *m == module) {
+                    Some((_, funcs)) => funcs.push((name, sig)),
+                    None => smw_imports.push((module, vec![(name, sig)])),
+                }
+            }
+
+            let first_instance = u32::try_from(self.import_fn_name_to_index.len()).unwrap();
+            let mut args = Vec::new();
+            for (i, (module, funcs)) in smw_imports.iter().enumerate() {
+                let exports: Vec<_> = funcs
+                    .iter()
+                    .map(|(name, sig)| {
+                        let idx = self.intern_type(sig.clone());
+                        (name.as_str(), wasm_encoder::EntityType::Function(idx))
+                    })
+                    .collect();
+                let instance_type_index = self.types.len();
+                self.types.instance(exports);
+                self.imports.import(
+                    module,
+                    None,
+                    wasm_encoder::EntityType::Instance(instance_type_index),
+                );
+                args.push((
+                    module.as_str(),
+                    wasm_encoder::Export::Instance(first_instance + u32::try_from(i).unwrap()),
+                ));
+            }
+
+            modules.module(&embedded.module);
+            instances.instantiate(0, args);
+            forwarded_instances = smw_imports.len();
         }
 
         // Regardless whether we imported an instance or instantiated an embedded
         // module, we now have an instance of `spidermonkey.wasm`. Alias its
         // exported functions and exported memory into this module's index
-        // spaces.
-        let instance_index = u32::try_from(self.import_fn_name_to_index.len()).unwrap();
+        // spaces. Each module we import functions from (including
+        // `canonical_abi`) is an implicit instance that precedes it, as are the
+        // instances we import to satisfy an embedded `spidermonkey.wasm`'s
+        // imports.
+        let instance_index =
+            u32::try_from(self.import_fn_name_to_index.len() + forwarded_instances).unwrap();
         aliases.instance_export(instance_index, wasm_encoder::ItemKind::Memory, "memory");
         aliases.instance_export(
             instance_index,
@@ -490,6 +868,36 @@ impl<'a> SpiderMonkeyWasm<'a> {
             let idx = self.spidermonkey_import(name);
             self.function_names.push((idx, name.to_string()));
         }
+
+        instance_index
+    }
+
+    /// Define the glue module's own memory and its static data.
+    ///
+    /// Rather than defining the memory directly, we define it in a nested
+    /// module, instantiate that, and alias its memory. This way the data
+    /// segments target memory 0 and are encoded the same with or without the
+    /// bulk memory proposal, which Wizer disables when snapshotting.
+    fn define_glue_memory(
+        &mut self,
+        modules: &mut wasm_encoder::ModuleSection,
+        instances: &mut wasm_encoder::InstanceSection,
+        aliases: &mut wasm_encoder::AliasSection,
+        instance_index: u32,
+    ) {
+        let mut mems = wasm_encoder::MemorySection::new();
+        mems.memory(self.data_segments.memory_type());
+        let mut exports = wasm_encoder::ExportSection::new();
+        exports.export("memory", wasm_encoder::Export::Memory(0));
+        let mut glue_memory = wasm_encoder::Module::new();
+        glue_memory
+            .section(&mems)
+            .section(&exports)
+            .section(&self.data_segments.take_data());
+
+        modules.module(&glue_memory);
+        instances.instantiate(modules.len() - 1, None);
+        aliases.instance_export(instance_index, wasm_encoder::ItemKind::Memory, "memory");
     }
 
     /// Malloc `size` bytes and save the result to `local`.
@@ -534,28 +942,74 @@ impl<'a> SpiderMonkeyWasm<'a> {
     }
 
     /// Copy data from the root glue module's linear memory into
-    /// `spidermonkey.wasm`'s linear memory:
+    /// `spidermonkey.wasm`'s linear memory, one byte at a time:
     ///
     /// ```wat
-    /// (memory.copy 0 1 (local.get ${to_local})
-    ///                  (i32.const ${from_offset})
-    ///                  (i32.const ${len}))
+    /// (local.set ${index_local} (i32.const 0))
+    /// (block
+    ///   (loop
+    ///     (br_if 1 (i32.ge_u (local.get ${index_local}) (i32.const ${len})))
+    ///     (i32.store8 0 (i32.add (local.get ${to_local}) (local.get ${index_local}))
+    ///                   (i32.load8_u 1 offset=${from_offset} (local.get ${index_local})))
+    ///     (local.set ${index_local} (i32.add (local.get ${index_local}) (i32.const 1)))
+    ///     (br 0)))
     /// ```
-    fn copy_to_smw<'b, F>(&self, func: &mut F, from_offset: u32, to_local: u32, len: u32)
-    where
+    ///
+    /// This only runs in `wizer.initialize`, and we avoid `memory.copy` there
+    /// because Wizer can't snapshot modules that use bulk memory operations.
+    fn copy_to_smw<'b, F>(
+        &self,
+        func: &mut F,
+        from_offset: u32,
+        to_local: u32,
+        len: u32,
+        index_local: u32,
+    ) where
         F: InstructionSink<'b>,
     {
+        // []
+        func.instruction(Instruction::I32Const(0));
+        // [i32]
+        func.instruction(Instruction::LocalSet(index_local));
+        // []
+        func.instruction(Instruction::Block(wasm_encoder::BlockType::Empty));
+        func.instruction(Instruction::Loop(wasm_encoder::BlockType::Empty));
+        // []
+        func.instruction(Instruction::LocalGet(index_local));
+        // [i32]
+        func.instruction(Instruction::I32Const(len as _));
+        // [i32 i32]
+        func.instruction(Instruction::I32GeU);
+        // [i32]
+        func.instruction(Instruction::BrIf(1));
         // []
         func.instruction(Instruction::LocalGet(to_local));
         // [i32]
-        func.instruction(Instruction::I32Const(from_offset as _));
+        func.instruction(Instruction::LocalGet(index_local));
         // [i32 i32]
-        func.instruction(Instruction::I32Const(len as _));
-        // [i32 i32 i32]
-        func.instruction(Instruction::MemoryCopy {
-            src: GLUE_MEMORY,
-            dst: SM_MEMORY,
-        });
+        func.instruction(Instruction::I32Add);
+        // [i32]
+        func.instruction(Instruction::LocalGet(index_local));
+        // [i32 i32]
+        func.instruction(Instruction::I32Load8_U(wasm_encoder::MemArg {
+            offset: from_offset.into(),
+            align: 0,
+            memory_index: GLUE_MEMORY,
+        }));
+        // [i32 i32]
+        func.instruction(Instruction::I32Store8(sm_mem_arg(0)));
+        // []
+        func.instruction(Instruction::LocalGet(index_local));
+        // [i32]
+        func.instruction(Instruction::I32Const(1));
+        // [i32 i32]
+        func.instruction(Instruction::I32Add);
+        // [i32]
+        func.instruction(Instruction::LocalSet(index_local));
+        // []
+        func.instruction(Instruction::Br(0));
+        func.instruction(Instruction::End);
+        func.instruction(Instruction::End);
         // []
     }
 
@@ -570,6 +1024,98 @@ impl<'a> SpiderMonkeyWasm<'a> {
         // []
     }
 
+    /// Define the JS glue function for dropping a handle to an imported
+    /// resource, and export it from the interface's JS module as
+    /// `drop_${resource}`:
+    ///
+    /// ```wat
+    /// (func (param $cx i32) (param $argc i32) (param $vp i32) (result i32)
+    ///   (call $SMW_fill_operands (local.get $argc) (local.get $vp))
+    ///   (call $canonical_abi.resource_drop_${resource}
+    ///     (call $SMW_i32_from_u32 (i32.const 0)))
+    ///   (call $SMW_clear_operands)
+    ///   (i32.const 1))
+    /// ```
+    fn define_resource_drop_import_glue(&mut self, iface: &Interface, name: &str) {
+        let drop_index = self.canonical_abi_import(&format!("resource_drop_{}", name));
+        let glue_index = u32::try_from(self.import_glue_fns.len()).unwrap();
+        self.js_import_modules
+            .entry(iface.name.clone())
+            .or_default()
+            .push((format!("drop_{}", name.to_snake_case()), glue_index, 1));
+
+        let mut glue = wasm_encoder::Function::new(vec![]);
+        // []
+        glue.instruction(&Instruction::LocalGet(1));
+        // [i32]
+        glue.instruction(&Instruction::LocalGet(2));
+        // [i32 i32]
+        glue.instruction(&Instruction::Call(
+            self.spidermonkey_import("SMW_fill_operands"),
+        ));
+        // []
+        glue.instruction(&Instruction::I32Const(0));
+        // [i32]
+        glue.instruction(&Instruction::Call(
+            self.spidermonkey_import("SMW_i32_from_u32"),
+        ));
+        // [i32]
+        glue.instruction(&Instruction::Call(drop_index));
+        // []
+        self.clear_js_operands(&mut glue);
+        // []
+        glue.instruction(&Instruction::I32Const(1));
+        // [i32]
+        glue.instruction(&Instruction::End);
+
+        self.import_glue_fns.push(glue);
+    }
+
+    /// Define and export the `canonical_abi_drop_${resource}` function for an
+    /// exported resource, which is called when the last handle to the resource
+    /// is dropped:
+    ///
+    /// ```wat
+    /// (func (param $rep i32)
+    ///   (call $SMW_resource_drop (local.get $rep)
+    ///                            (global.get ${drop_func_name})
+    ///                            (i32.const ${drop_func_name.len()})))
+    /// ```
+    fn define_resource_drop_export_glue(&mut self, name: &str) {
+        let export_fn_index = self.wit_export(u32::try_from(self.export_glue_fns.len()).unwrap());
+        let export_name = format!("canonical_abi_drop_{}", name);
+        self.exports.export(
+            &export_name,
+            wasm_encoder::Export::Function(export_fn_index),
+        );
+        self.function_names.push((export_fn_index, export_name));
+
+        let type_index = self.intern_type(WasmSignature {
+            params: vec![WasmType::I32],
+            results: vec![],
+            retptr: None,
+        });
+
+        let (drop_func_name, drop_func_name_len) =
+            self.string_global(&format!("drop_{}", name.to_snake_case()));
+
+        let mut glue = wasm_encoder::Function::new(vec![]);
+        // []
+        glue.instruction(&Instruction::LocalGet(0));
+        // [i32]
+        glue.instruction(&Instruction::GlobalGet(drop_func_name));
+        // [i32 i32]
+        glue.instruction(&Instruction::I32Const(drop_func_name_len as i32));
+        // [i32 i32 i32]
+        glue.instruction(&Instruction::Call(
+            self.spidermonkey_import("SMW_resource_drop"),
+        ));
+        // []
+        glue.instruction(&Instruction::End);
+
+        self.export_glue_fns.push((glue, type_index));
+    }
+
     fn define_wizer_initialize(
         &mut self,
         funcs: &mut wasm_encoder::FunctionSection,
@@ -594,7 +1140,7 @@ impl<'a> SpiderMonkeyWasm<'a> {
         });
         funcs.function(ty_index);
 
-        let locals = vec![(7, wasm_encoder::ValType::I32)];
+        let locals = vec![(9, wasm_encoder::ValType::I32)];
         let js_name_local = 0;
         let js_local = 1;
         let module_name_local = 2;
@@ -602,6 +1148,8 @@ impl<'a> SpiderMonkeyWasm<'a> {
         let table_size_local = 4;
         let func_name_local = 5;
         let ret_ptr_local = 6;
+        let string_local = 7;
+        let copy_index_local = 8;
 
         let mut local_names = wasm_encoder::NameMap::new();
         local_names.append(js_name_local, "js_name");
@@ -611,6 +1159,8 @@ impl<'a> SpiderMonkeyWasm<'a> {
         local_names.append(table_size_local, "table_size");
         local_names.append(func_name_local, "func_name");
         local_names.append(ret_ptr_local, "ret_ptr");
+        local_names.append(string_local, "string");
+        local_names.append(copy_index_local, "copy_index");
         self.local_names.push((wizer_init_index, local_names));
 
         let mut wizer_init = wasm_encoder::Function::new(locals);
@@ -626,14 +1176,27 @@ impl<'a> SpiderMonkeyWasm<'a> {
         self.malloc_static_size(&mut wizer_init, js_len, js_local);
 
         // Copy the data into the freshly allocated regions.
-        self.copy_to_smw(&mut wizer_init, js_name_offset, js_name_local, js_name_len);
-        self.copy_to_smw(&mut wizer_init, js_offset, js_local, js_len);
-
+        self.copy_to_smw(
+            &mut wizer_init,
+            js_name_offset,
+            js_name_local,
+            js_name_len,
+            copy_index_local,
+        );
+        self.copy_to_smw(
+            &mut wizer_init,
+            js_offset,
+            js_local,
+            js_len,
+            copy_index_local,
+        );
+
         // Allocate space in the `spidermonkey.wasm` memory for the return
-        // pointer area and save it to the return pointer global.
+        // pointer area and save it to the return pointer global. The area's
+        // size is given in `i64`s.
         self.malloc_static_size(
             &mut wizer_init,
-            u32::try_from(self.i64_return_pointer_area_size).unwrap(),
+            u32::try_from(self.i64_return_pointer_area_size * 8).unwrap(),
             ret_ptr_local,
         );
         // []
@@ -642,6 +1205,20 @@ impl<'a> SpiderMonkeyWasm<'a> {
         wizer_init.instruction(&Instruction::GlobalSet(RET_PTR_GLOBAL));
         // []
 
+        // Copy each of our static strings into `spidermonkey.wasm`'s memory
+        // and save the pointer to its global.
+        for (i, (offset, len)) in self.strings.clone().into_iter().enumerate() {
+            self.malloc_static_size(&mut wizer_init, len, string_local);
+            self.copy_to_smw(&mut wizer_init, offset, string_local, len, copy_index_local);
+            // []
+            wizer_init.instruction(&Instruction::LocalGet(string_local));
+            // [i32]
+            wizer_init.instruction(&Instruction::GlobalSet(
+                FIRST_STRING_GLOBAL + u32::try_from(i).unwrap(),
+            ));
+            // []
+        }
+
         // Call `SMW_initialize_engine`:
         //
         //     (call $SMW_initialize_engine)
@@ -652,9 +1229,8 @@ impl<'a> SpiderMonkeyWasm<'a> {
         // module will export each of our generated glue functions for that WIT
         // module.
         let smw_new_module_builder = self.spidermonkey_import("SMW_new_module_builder");
-        let import_fn_name_to_index =
-            std::mem::replace(&mut self.import_fn_name_to_index, Default::default());
-        for (module, funcs) in &import_fn_name_to_index {
+        let js_import_modules = std::mem::replace(&mut self.js_import_modules, Default::default());
+        for (module, funcs) in &js_import_modules {
             // Malloc space for the module name.
             self.malloc_static_size(
                 &mut wizer_init,
@@ -669,6 +1245,7 @@ impl<'a> SpiderMonkeyWasm<'a> {
                 module_offset,
                 module_name_local,
                 u32::try_from(module.len()).unwrap(),
+                copy_index_local,
             );
 
             // Call `SMW_new_module_builder`, passing it the module name:
@@ -687,38 +1264,43 @@ impl<'a> SpiderMonkeyWasm<'a> {
                 .instruction(&Instruction::LocalSet(module_builder_local));
             // []
 
-            // Grow enough space in the function table for the functions we will
-            // add to it. Check for failure to allocate and trap if so.
-            //
-            //     (table.grow (ref.null) (i32.const ${funcs.len()}))
-            //     local.tee ${table_size}
-            //     i32.const -1
-            //     i32.eq
-            //     if
-            //       unreachable
-            //     end
-            wizer_init
-                // []
-                .instruction(&Instruction::RefNull(wasm_encoder::ValType::FuncRef))
-                // [funcref]
-                .instruction(&Instruction::I32Const(i32::try_from(funcs.len()).unwrap()))
-                // [funcref i32]
-                .instruction(&Instruction::TableGrow { table: 0 })
-                // [i32]
-                .instruction(&Instruction::LocalTee(table_size_local))
-                // [i32]
-                .instruction(&Instruction::I32Const(-1))
-                // [i32 i32]
-                .instruction(&Instruction::I32Eq)
-                // [i32]
-                .instruction(&Instruction::If(wasm_encoder::BlockType::Empty))
-                // []
-                .instruction(&Instruction::Unreachable)
+            // When `spidermonkey.wasm` is embedded, our import glue functions
+            // were already placed in the function table by an active element
+            // segment. Otherwise, we have to put them there ourselves.
+            if self.static_table_base.is_none() {
+                // Grow enough space in the function table for the functions we will
+                // add to it. Check for failure to allocate and trap if so.
+                //
+                //     (table.grow (ref.null) (i32.const ${funcs.len()}))
+                //     local.tee ${table_size}
+                //     i32.const -1
+                //     i32.eq
+                //     if
+                //       unreachable
+                //     end
+                wizer_init
+                    // []
+                    .instruction(&Instruction::RefNull(wasm_encoder::ValType::FuncRef))
+                    // [funcref]
+                    .instruction(&Instruction::I32Const(i32::try_from(funcs.len()).unwrap()))
+                    // [funcref i32]
+                    .instruction(&Instruction::TableGrow { table: 0 })
+                    // [i32]
+                    .instruction(&Instruction::LocalTee(table_size_local))
+                    // [i32]
+                    .instruction(&Instruction::I32Const(-1))
+                    // [i32 i32]
+                    .instruction(&Instruction::I32Eq)
+                    // [i32]
+                    .instruction(&Instruction::If(wasm_encoder::BlockType::Empty))
+                    // []
+                    .instruction(&Instruction::Unreachable)
+                    // []
+                    .instruction(&Instruction::End);
                 // []
-                .instruction(&Instruction::End);
-            // []
+            }
 
-            for (i, (func, (func_index, num_args))) in funcs.iter().enumerate() {
+            for (i, (func, glue_index, num_args)) in funcs.iter().enumerate() {
                 // Malloc space for the function's name.
                 self.malloc_static_size(
                     &mut wizer_init,
@@ -733,26 +1315,29 @@ impl<'a> SpiderMonkeyWasm<'a> {
                     func_name_offset,
                     func_name_local,
                     u32::try_from(func.len()).unwrap(),
+                    copy_index_local,
                 );
 
-                // Set `table[orig_size + i]` to our synthesized import glue
-                // function:
-                //
-                //     (table.set (i32.add (i32.const ${i}) (local.get ${table_size}))
-                //                (ref.func ${func_index}))
-                let glue_func_index = self.wit_import_glue_fn(*func_index);
-                wizer_init
+                if self.static_table_base.is_none() {
+                    // Set `table[orig_size + i]` to our synthesized import glue
+                    // function:
+                    //
+                    //     (table.set (i32.add (i32.const ${i}) (local.get ${table_size}))
+                    //                (ref.func ${glue_func_index}))
+                    let glue_func_index = self.wit_import_glue_fn(*glue_index);
+                    wizer_init
+                        // []
+                        .instruction(&Instruction::I32Const(i32::try_from(i).unwrap()))
+                        // [i32]
+                        .instruction(&Instruction::LocalGet(table_size_local))
+                        // [i32 i32]
+                        .instruction(&Instruction::I32Add)
+                        // [i32]
+                        .instruction(&Instruction::RefFunc(glue_func_index))
+                        // [i32 funcref]
+                        .instruction(&Instruction::TableSet { table: 0 });
                     // []
-                    .instruction(&Instruction::I32Const(i32::try_from(i).unwrap()))
-                    // [i32]
-                    .instruction(&Instruction::LocalGet(table_size_local))
-                    // [i32 i32]
-                    .instruction(&Instruction::I32Add)
-                    // [i32]
-                    .instruction(&Instruction::RefFunc(glue_func_index))
-                    // [i32 funcref]
-                    .instruction(&Instruction::TableSet { table: 0 });
-                // []
+                }
 
                 // Call `SMW_module_builder_add_export` passing the index of the
                 // function that we just inserted into the table:
@@ -762,6 +1347,10 @@ impl<'a> SpiderMonkeyWasm<'a> {
                 //                                          (i32.const ${func.len()})
                 //                                          (i32.add (i32.const ${i}) (local.get ${table_size}))
                 //                                          (i32.const ${num_args}))
+                //
+                // or, if the function was placed in the table statically,
+                // passing `(i32.const ${static_table_base + glue_index})` as its
+                // index instead.
                 let smw_module_builder_add_export =
                     self.spidermonkey_import("SMW_module_builder_add_export");
                 wizer_init
@@ -770,13 +1359,25 @@ impl<'a> SpiderMonkeyWasm<'a> {
                     // [i32]
                     .instruction(&Instruction::LocalGet(func_name_local))
                     // [i32 i32]
-                    .instruction(&Instruction::I32Const(i32::try_from(func.len()).unwrap()))
-                    // [i32 i32 i32]
-                    .instruction(&Instruction::I32Const(i32::try_from(i).unwrap()))
-                    // [i32 i32 i32 i32]
-                    .instruction(&Instruction::LocalGet(table_size_local))
-                    // [i32 i32 i32 i32 i32]
-                    .instruction(&Instruction::I32Add)
+                    .instruction(&Instruction::I32Const(i32::try_from(func.len()).unwrap()));
+                // [i32 i32 i32]
+                match self.static_table_base {
+                    Some(base) => {
+                        wizer_init.instruction(&Instruction::I32Const(
+                            i32::try_from(base + *glue_index).unwrap(),
+                        ));
+                    }
+                    None => {
+                        wizer_init
+                            // [i32 i32 i32]
+                            .instruction(&Instruction::I32Const(i32::try_from(i).unwrap()))
+                            // [i32 i32 i32 i32]
+                            .instruction(&Instruction::LocalGet(table_size_local))
+                            // [i32 i32 i32 i32 i32]
+                            .instruction(&Instruction::I32Add);
+                    }
+                }
+                wizer_init
                     // [i32 i32 i32 i32]
                     .instruction(&Instruction::I32Const(i32::try_from(*num_args).unwrap()))
                     // [i32 i32 i32 i32 i32]
@@ -829,6 +1430,9 @@ impl<'a> SpiderMonkeyWasm<'a> {
 // ```text
 // |wit imports...|spidermonkey.wasm imports...|import glue...|export glue...|wizer.initialize|
 // ```
+//
+// The WIT imports include the `canonical_abi` resource intrinsics, and the
+// import and export glue include the glue for dropping resources.
 impl SpiderMonkeyWasm<'_> {
     /// Get the number of imported WIT functions.
     fn wit_import_functions_len(&self) -> u32 {
@@ -858,6 +1462,12 @@ impl SpiderMonkeyWasm<'_> {
         self.wit_import_functions_len() + u32::try_from(SMW_EXPORTS.len()).unwrap()
     }
 
+    /// Get the number of synthesized glue functions for WIT imports.
+    fn wit_import_glue_fns_len(&self) -> u32 {
+        self.num_import_glue_functions
+            .expect("must call `preprocess_all` before generating bindings")
+    }
+
     /// Get the range of indices for our synthesized glue functions for WIT
     /// imports.
     fn wit_import_glue_fn_range(&self) -> Range<u32> {
@@ -870,10 +1480,10 @@ impl SpiderMonkeyWasm<'_> {
     /// import.
     fn wit_import_glue_fn(&self, i: u32) -> u32 {
         assert!(
-            i < self.wit_import_functions_len(),
+            i < self.wit_import_glue_fns_len(),
             "{} < {}",
             i,
-            self.wit_import_functions_len()
+            self.wit_import_glue_fns_len()
         );
         let start = self.wit_import_glue_fns_start();
         start + i
@@ -881,7 +1491,7 @@ impl SpiderMonkeyWasm<'_> {
 
     /// Get the function index where WIT export glue functions start.
     fn wit_export_start(&self) -> u32 {
-        self.wit_import_glue_fns_start() + self.wit_import_functions_len()
+        self.wit_import_glue_fns_start() + self.wit_import_glue_fns_len()
     }
 
     fn wit_exports_len(&self) -> u32 {
@@ -906,10 +1516,61 @@ impl Generator for SpiderMonkeyWasm<'_> {
             exports.len() <= 1,
             "only one exported interface is currently supported"
         );
-        self.num_import_functions =
-            Some(u32::try_from(imports.iter().map(|i| i.functions.len()).sum::<usize>()).unwrap());
+        let num_functions =
+            |ifaces: &[Interface]| -> usize { ifaces.iter().map(|i| i.functions.len()).sum() };
+        let num_resources =
+            |ifaces: &[Interface]| -> usize { ifaces.iter().map(|i| i.resources.len()).sum() };
+
+        // Imported resources need a `resource_drop_*` intrinsic and exported
+        // resources need `resource_{new,get}_*` intrinsics. Imported resources
+        // additionally get a JS glue function for dropping handles, and
+        // exported resources get a `canonical_abi_drop_*` export.
+        self.num_import_functions = Some(
+            u32::try_from(
+                num_functions(imports) + num_resources(imports) + 2 * num_resources(exports),
+            )
+            .unwrap(),
+        );
+        self.num_import_glue_functions =
+            Some(u32::try_from(num_functions(imports) + num_resources(imports)).unwrap());
         self.num_export_functions =
-            Some(u32::try_from(exports.iter().map(|i| i.functions.len()).sum::<usize>()).unwrap());
+            Some(u32::try_from(num_functions(exports) + num_resources(exports)).unwrap());
+
+        // Import all of the `canonical_abi` intrinsics up front, so that they
+        // are contiguous in the imports section.
+        let drop_sig = WasmSignature {
+            params: vec![WasmType::I32],
+            results: vec![],
+            retptr: None,
+        };
+        let new_get_sig = WasmSignature {
+            params: vec![WasmType::I32],
+            results: vec![WasmType::I32],
+            retptr: None,
+        };
+        for iface in imports {
+            for (_, resource) in iface.resources.iter() {
+                self.import_function(
+                    "canonical_abi",
+                    &format!("resource_drop_{}", resource.name),
+                    drop_sig.clone(),
+                );
+            }
+        }
+        for iface in exports {
+            for (_, resource) in iface.resources.iter() {
+                self.import_function(
+                    "canonical_abi",
+                    &format!("resource_new_{}", resource.name),
+                    new_get_sig.clone(),
+                );
+                self.import_function(
+                    "canonical_abi",
+                    &format!("resource_get_{}", resource.name),
+                    new_get_sig.clone(),
+                );
+            }
+        }
 
         // Figure out what the maximum return pointer area we will need is.
         for (iface, variant) in imports
@@ -932,88 +1593,125 @@ impl Generator for SpiderMonkeyWasm<'_> {
     }
 
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
+        // Canonical ABI values live in `spidermonkey.wasm`'s memory, which is the
+        // only memory an interface may use.
+        assert!(
+            iface.memory == 0,
+            "interface `{}` uses memory {}, but only memory 0 is supported",
+            iface.name,
+            iface.memory,
+        );
+        assert!(
+            !iface.memory64,
+            "interface `{}` uses a 64-bit memory, which is not supported",
+            iface.name,
+        );
+        for (_, ty) in iface.types.iter() {
+            match ty.kind {
+                TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => panic!(
+                    "interface `{}` uses buffers, which are not supported",
+                    iface.name,
+                ),
+                _ => {}
+            }
+        }
         self.sizes.fill(Self::abi_variant(dir), iface);
+        self.in_import = dir == Direction::Import;
     }
 
+    // JavaScript is dynamically typed, so there are no type definitions to
+    // emit. Records, variants, lists, etc. are handled by the glue for each
+    // function that uses them.
+
     fn type_record(
         &mut self,
-        iface: &Interface,
-        id: TypeId,
-        name: &str,
-        record: &Record,
-        docs: &Docs,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _record: &Record,
+        _docs: &Docs,
     ) {
-        let _ = (iface, id, name, record, docs);
-        todo!()
     }
 
     fn type_variant(
         &mut self,
-        iface: &Interface,
-        id: TypeId,
-        name: &str,
-        variant: &Variant,
-        docs: &Docs,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _variant: &Variant,
+        _docs: &Docs,
     ) {
-        let _ = (iface, id, name, variant, docs);
-        todo!()
     }
 
     fn type_resource(&mut self, iface: &Interface, ty: ResourceId) {
-        let _ = (iface, ty);
-        todo!()
+        let name = &iface.resources[ty].name;
+        if self.in_import {
+            self.define_resource_drop_import_glue(iface, name);
+        } else {
+            self.define_resource_drop_export_glue(name);
+        }
     }
 
-    fn type_alias(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
-        let _ = (iface, id, name, ty, docs);
-        todo!()
+    fn type_alias(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
     }
 
-    fn type_list(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
-        let _ = (iface, id, name, ty, docs);
-        todo!()
+    fn type_list(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
     }
 
     fn type_pointer(
         &mut self,
-        iface: &Interface,
-        id: TypeId,
-        name: &str,
-        const_: bool,
-        ty: &Type,
-        docs: &Docs,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _const_: bool,
+        _ty: &Type,
+        _docs: &Docs,
     ) {
-        let _ = (iface, id, name, const_, ty, docs);
-        todo!()
+        unreachable!("we do not support the preview1 ABI")
     }
 
-    fn type_builtin(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
-        let _ = (iface, id, name, name, ty, docs);
-        todo!()
+    fn type_builtin(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
     }
 
     fn type_push_buffer(
         &mut self,
-        iface: &Interface,
-        id: TypeId,
-        name: &str,
-        ty: &Type,
-        docs: &Docs,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
     ) {
-        let _ = (iface, id, name, name, ty, docs);
-        todo!()
     }
 
     fn type_pull_buffer(
         &mut self,
-        iface: &Interface,
-        id: TypeId,
-        name: &str,
-        ty: &Type,
-        docs: &Docs,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
     ) {
-        let _ = (iface, id, name, name, ty, docs);
-        todo!()
     }
 
     fn import(&mut self, iface: &Interface, func: &Function) {
@@ -1025,26 +1723,18 @@ impl Generator for SpiderMonkeyWasm<'_> {
 
         // Add the raw Wasm import.
         let wasm_sig = iface.wasm_signature(AbiVariant::GuestImport, func);
-        let type_index = self.intern_type(wasm_sig.clone());
-        let import_fn_index = self.wit_import(self.imports.len());
-        self.imports.import(
-            &iface.name,
-            Some(&func.name),
-            wasm_encoder::EntityType::Function(type_index),
-        );
+        self.import_function(&iface.name, &func.name, wasm_sig.clone());
 
-        let existing = self
-            .import_fn_name_to_index
+        // Export the glue function from the JS module for this interface.
+        let glue_index = u32::try_from(self.import_glue_fns.len()).unwrap();
+        self.js_import_modules
             .entry(iface.name.clone())
             .or_default()
-            .insert(
-                func.name.clone(),
-                (import_fn_index, u32::try_from(func.params.len()).unwrap()),
-            );
-        assert!(existing.is_none());
-
-        self.function_names
-            .push((import_fn_index, format!("{}.{}", iface.name, func.name)));
+            .push((
+                func.name.to_snake_case(),
+                glue_index,
+                u32::try_from(func.params.len()).unwrap(),
+            ));
 
         let mut bindgen = Bindgen::new(self, &wasm_sig, func, abi::LiftLower::LowerArgsLiftResults);
         iface.call(
@@ -1066,7 +1756,7 @@ impl Generator for SpiderMonkeyWasm<'_> {
 
         let wasm_sig = iface.wasm_signature(AbiVariant::GuestExport, func);
         let type_index = self.intern_type(wasm_sig.clone());
-        let export_fn_index = self.wit_export(self.exports.len());
+        let export_fn_index = self.wit_export(u32::try_from(self.export_glue_fns.len()).unwrap());
         self.exports
             .export(&func.name, wasm_encoder::Export::Function(export_fn_index));
         self.function_names
@@ -1093,13 +1783,12 @@ impl Generator for SpiderMonkeyWasm<'_> {
         let mut modules = wasm_encoder::ModuleSection::new();
         let mut instances = wasm_encoder::InstanceSection::new();
         let mut aliases = wasm_encoder::AliasSection::new();
-        let mut mems = wasm_encoder::MemorySection::new();
         let mut funcs = wasm_encoder::FunctionSection::new();
         let mut globals = wasm_encoder::GlobalSection::new();
         let mut elems = wasm_encoder::ElementSection::new();
         let mut code = wasm_encoder::CodeSection::new();
 
-        self.link_spidermonkey_wasm(&mut modules, &mut instances, &mut aliases);
+        let smw_instance = self.link_spidermonkey_wasm(&mut modules, &mut instances, &mut aliases);
 
         // Define the return pointer global.
         globals.global(
@@ -1110,6 +1799,18 @@ impl Generator for SpiderMonkeyWasm<'_> {
             &Instruction::I32Const(0),
         );
 
+        // Define the globals for our static strings. These are initialized in
+        // `wizer.initialize`.
+        for _ in &self.strings {
+            globals.global(
+                wasm_encoder::GlobalType {
+                    val_type: wasm_encoder::ValType::I32,
+                    mutable: true,
+                },
+                &Instruction::I32Const(0),
+            );
+        }
+
         // Re-export `spidermonkey.wasm`'s memory and canonical ABI functions.
         self.exports
             .export("memory", wasm_encoder::Export::Memory(SM_MEMORY));
@@ -1155,12 +1856,25 @@ impl Generator for SpiderMonkeyWasm<'_> {
 
         // We will use `ref.func` to get a reference to each of our synthesized
         // import glue functions, so we need to declare them as reference-able.
+        //
+        // If `spidermonkey.wasm` is embedded, we instead reserved space for
+        // them at the end of its function table and fill those slots with an
+        // active element segment, which keeps `wizer.initialize` free of
+        // reference types instructions so that Wizer can snapshot it.
         let func_indices: Vec<u32> = self.wit_import_glue_fn_range().collect();
         if !func_indices.is_empty() {
-            elems.declared(
-                wasm_encoder::ValType::FuncRef,
-                wasm_encoder::Elements::Functions(&func_indices),
-            );
+            match self.static_table_base {
+                Some(base) => elems.active(
+                    None,
+                    &Instruction::I32Const(i32::try_from(base).unwrap()),
+                    wasm_encoder::ValType::FuncRef,
+                    wasm_encoder::Elements::Functions(&func_indices),
+                ),
+                None => elems.declared(
+                    wasm_encoder::ValType::FuncRef,
+                    wasm_encoder::Elements::Functions(&func_indices),
+                ),
+            };
         }
 
         let js_name = self.js_name.display().to_string();
@@ -1176,14 +1890,15 @@ impl Generator for SpiderMonkeyWasm<'_> {
             u32::try_from(self.js.len()).unwrap(),
         );
 
-        module.section(&self.types).section(&self.imports);
-
-        if !self.import_spidermonkey {
-            module.section(&modules).section(&instances);
-        }
+        // The glue memory's instance is created right after
+        // `spidermonkey.wasm`'s, whether that was imported or instantiated.
+        self.define_glue_memory(&mut modules, &mut instances, &mut aliases, smw_instance + 1);
 
-        mems.memory(self.data_segments.memory_type());
-        let data = self.data_segments.take_data();
+        module
+            .section(&self.types)
+            .section(&self.imports)
+            .section(&modules)
+            .section(&instances);
 
         // Fill out the `names` section to assist in debugging the generated
         // wasm.
@@ -1214,15 +1929,14 @@ impl Generator for SpiderMonkeyWasm<'_> {
         module
             .section(&aliases)
             .section(&funcs)
-            .section(&mems)
             .section(&globals)
             .section(&self.exports)
             .section(&elems)
             .section(&code)
-            .section(&data)
             .section(&names);
 
-        let wasm = module.finish();
+        #[allow(unused_mut)]
+        let mut wasm = module.finish();
 
         let js_file_stem = self.js_name.file_stem().unwrap_or_else(|| {
             panic!(
@@ -1238,37 +1952,61 @@ impl Generator for SpiderMonkeyWasm<'_> {
         });
         let wasm_name = format!("{}.wasm", js_file_stem);
 
-        files.push(&wasm_name, &wasm);
-    }
-}
-
-trait InstructionSink<'a> {
-    fn instruction(&mut self, inst: wasm_encoder::Instruction<'a>);
-}
-
-impl<'a> InstructionSink<'a> for wasm_encoder::Function {
-    fn instruction(&mut self, inst: wasm_encoder::Instruction<'a>) {
-        wasm_encoder::Function::instruction(self, &inst);
-    }
-}
+        #[cfg(feature = "wizer")]
+        if self.pre_initialize {
+            assert!(
+                !self.import_spidermonkey,
+                "pre-initialization requires embedding `spidermonkey.wasm`"
+            );
+            wasm = wizen(&wasm)
+                .unwrap_or_else(|e| panic!("failed to pre-initialize {}: {:?}", wasm_name, e));
+        }
 
-impl<'a> InstructionSink<'a> for Vec<wasm_encoder::Instruction<'a>> {
-    fn instruction(&mut self, inst: wasm_encoder::Instruction<'a>) {
-        self.push(inst);
+        files.push(&wasm_name, &wasm);
     }
 }
 
 const RET_PTR_GLOBAL: u32 = 0;
+const FIRST_STRING_GLOBAL: u32 = 1;
 
 const SM_MEMORY: u32 = 0;
 const GLUE_MEMORY: u32 = 1;
 
-fn convert_ty(ty: WasmType) -> wasm_encoder::ValType {
-    match ty {
-        WasmType::I32 => wasm_encoder::ValType::I32,
-        WasmType::I64 => wasm_encoder::ValType::I64,
-        WasmType::F32 => wasm_encoder::ValType::F32,
-        WasmType::F64 => wasm_encoder::ValType::F64,
+/// How a variant is represented in JavaScript.
+///
+/// This must be kept in sync with `VariantRepr` in `bindgen.cpp`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VariantRepr {
+    /// `true` or `false`.
+    Bool = 0,
+    /// `null` or `undefined` for `none`, otherwise the payload itself.
+    Option = 1,
+    /// The case name as a string.
+    Enum = 2,
+    /// An object of the form `{ tag: "case-name", val: payload }`.
+    Tagged = 3,
+}
+
+impl VariantRepr {
+    fn new(iface: &Interface, variant: &Variant, name: Option<&str>) -> Self {
+        if variant.is_bool() {
+            VariantRepr::Bool
+        } else if variant.is_enum() && name.is_some() {
+            VariantRepr::Enum
+        } else if is_nullable_option(iface, variant) {
+            VariantRepr::Option
+        } else {
+            VariantRepr::Tagged
+        }
+    }
+
+    /// Does this representation name the case of a variant value, rather than
+    /// implying it from the value's shape?
+    fn has_case_names(self) -> bool {
+        match self {
+            VariantRepr::Bool | VariantRepr::Option => false,
+            VariantRepr::Enum | VariantRepr::Tagged => true,
+        }
     }
 }
 
@@ -1288,6 +2026,10 @@ struct Bindgen<'a, 'b> {
     /// The Wasm local for our current iteration's base pointer, if any.
     iter_base_pointer: Vec<u32>,
 
+    /// The JS operands for the payloads of the variant cases whose blocks we
+    /// are currently lowering.
+    payloads: Vec<u32>,
+
     /// Allocations to free after the call.
     ///
     /// `(local holding pointer, local holding length, alignment)`
@@ -1332,6 +2074,7 @@ impl<'a, 'b> Bindgen<'a, 'b> {
             block_results: vec![],
             iter_elem: vec![],
             iter_base_pointer: vec![],
+            payloads: vec![],
             to_free: vec![],
         }
     }
@@ -1371,6 +2114,174 @@ impl<'a, 'b> Bindgen<'a, 'b> {
         Operand::Js(js)
     }
 
+    /// Blocks are pushed and finished in order, one per variant case, so pop
+    /// the last `n` of them back off in that same order.
+    fn pop_blocks(&mut self, n: usize) -> Vec<(Vec<Instruction<'a>>, Vec<Operand>)> {
+        let mut blocks: Vec<_> = (0..n).map(|_| self.pop_block()).collect();
+        blocks.reverse();
+        blocks
+    }
+
+    /// Convert the JS value `js` into a new Wasm local of type `ty` by calling
+    /// the given `spidermonkey.wasm` intrinsic and then applying `convert` to
+    /// its result.
+    fn js_to_wasm(
+        &mut self,
+        js: u32,
+        intrinsic: &str,
+        convert: Vec<Instruction<'a>>,
+        ty: wasm_encoder::ValType,
+    ) -> Operand {
+        let local = self.new_local(ty);
+
+        // []
+        self.inst(Instruction::I32Const(js as i32));
+        // [i32]
+        self.inst(Instruction::Call(self.gen.spidermonkey_import(intrinsic)));
+        // [T]
+        for inst in convert {
+            self.inst(inst);
+        }
+        // [ty]
+        self.inst(Instruction::LocalSet(local));
+        // []
+
+        Operand::Wasm(local)
+    }
+
+    /// Convert the Wasm value in `local` into a new JS value by applying
+    /// `convert` to it and then calling the given `spidermonkey.wasm`
+    /// intrinsic.
+    fn wasm_to_js(
+        &mut self,
+        local: u32,
+        convert: Vec<Instruction<'a>>,
+        intrinsic: &str,
+    ) -> Operand {
+        let result = self.next_js();
+
+        // []
+        self.inst(Instruction::LocalGet(local));
+        // [T]
+        for inst in convert {
+            self.inst(inst);
+        }
+        // [U]
+        self.inst(Instruction::I32Const(result.unwrap_js() as i32));
+        // [U i32]
+        self.inst(Instruction::Call(self.gen.spidermonkey_import(intrinsic)));
+        // []
+
+        result
+    }
+
+    /// Push the pointer and length of the interned string `s` onto the Wasm
+    /// stack.
+    fn push_string(&mut self, s: &str) {
+        let (global, len) = self.gen.string_global(s);
+        // []
+        self.inst(Instruction::GlobalGet(global));
+        // [i32]
+        self.inst(Instruction::I32Const(len as i32));
+        // [i32 i32]
+    }
+
+    /// Get the `name` property of the JS object `obj` and save it to `dest`.
+    fn get_property(&mut self, obj: u32, name: &str, dest: u32) {
+        // []
+        self.inst(Instruction::I32Const(obj as i32));
+        // [i32]
+        self.push_string(name);
+        // [i32 i32 i32]
+        self.inst(Instruction::I32Const(dest as i32));
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::Call(
+            self.gen.spidermonkey_import("SMW_get_property"),
+        ));
+        // []
+    }
+
+    /// Set the `name` property of the JS object `obj` to the JS value `val`.
+    fn set_property(&mut self, obj: u32, name: &str, val: u32) {
+        // []
+        self.inst(Instruction::I32Const(obj as i32));
+        // [i32]
+        self.push_string(name);
+        // [i32 i32 i32]
+        self.inst(Instruction::I32Const(val as i32));
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::Call(
+            self.gen.spidermonkey_import("SMW_set_property"),
+        ));
+        // []
+    }
+
+    /// Push the discriminant of the JS value `js`, whose variant is represented
+    /// with `repr` and has the given cases, onto the Wasm stack.
+    fn variant_discriminant(&mut self, js: u32, repr: VariantRepr, case_names: &[&str]) {
+        // []
+        self.inst(Instruction::I32Const(js as i32));
+        // [i32]
+        self.inst(Instruction::I32Const(repr as i32));
+        // [i32 i32]
+        if repr.has_case_names() {
+            // The case names are laid out back to back, each terminated by a
+            // NUL, so only the pointer to the first is needed.
+            let names: String = case_names.iter().map(|n| format!("{}\0", n)).collect();
+            let (global, _len) = self.gen.string_global(&names);
+            self.inst(Instruction::GlobalGet(global));
+            // [i32 i32 i32]
+            self.inst(Instruction::I32Const(case_names.len() as i32));
+        } else {
+            self.inst(Instruction::I32Const(0));
+            // [i32 i32 i32]
+            self.inst(Instruction::I32Const(0));
+        }
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::Call(
+            self.gen.spidermonkey_import("SMW_variant_discriminant"),
+        ));
+        // [i32]
+    }
+
+    /// Lift a variant case, represented with `repr`, into the JS value `dest`.
+    ///
+    /// `discriminant` pushes the case's discriminant onto the Wasm stack, and
+    /// `payload` is the JS operand holding the case's lifted payload, if any.
+    fn variant_lift(
+        &mut self,
+        repr: VariantRepr,
+        discriminant: Instruction<'a>,
+        case_name: &str,
+        payload: Option<u32>,
+        dest: u32,
+    ) {
+        // []
+        self.inst(Instruction::I32Const(repr as i32));
+        // [i32]
+        self.inst(discriminant);
+        // [i32 i32]
+        if repr.has_case_names() {
+            self.push_string(case_name);
+        } else {
+            self.inst(Instruction::I32Const(0));
+            self.inst(Instruction::I32Const(0));
+        }
+        // [i32 i32 i32 i32]
+        self.inst(Instruction::I32Const(match payload {
+            Some(js) => js as i32,
+            // `SIZE_MAX` means there is no payload.
+            None => -1,
+        }));
+        // [i32 i32 i32 i32 i32]
+        self.inst(Instruction::I32Const(dest as i32));
+        // [i32 i32 i32 i32 i32 i32]
+        self.inst(Instruction::Call(
+            self.gen.spidermonkey_import("SMW_variant_lift"),
+        ));
+        // []
+    }
+
     /// Finish generating these bindings and return the encoded Wasm function.
     fn finish(self) -> wasm_encoder::Function {
         // TODO: Coalesce contiguous locals of the same type here into the
@@ -1389,6 +2300,16 @@ impl<'a, 'b> Bindgen<'a, 'b> {
     }
 }
 
+impl<'a> GlueFunction<'a> for Bindgen<'a, '_> {
+    fn inst(&mut self, inst: Instruction<'a>) {
+        Bindgen::inst(self, inst);
+    }
+
+    fn new_local(&mut self, ty: wasm_encoder::ValType) -> u32 {
+        Bindgen::new_local(self, ty)
+    }
+}
+
 /// Operands are locals that either hold the value directly or refer to an index
 /// in `bindgen.cpp`'s JS operand vector depending on if we're dealing with a JS
 /// or Wasm value:
@@ -1438,11 +2359,7 @@ fn pop_js(operands: &mut Vec<Operand>) -> u32 {
 }
 
 fn sm_mem_arg(offset: u32) -> wasm_encoder::MemArg {
-    wasm_encoder::MemArg {
-        offset: offset as u64,
-        align: 0,
-        memory_index: SM_MEMORY,
-    }
+    mem_arg(offset, SM_MEMORY)
 }
 
 impl abi::Bindgen for Bindgen<'_, '_> {
@@ -1450,7 +2367,7 @@ impl abi::Bindgen for Bindgen<'_, '_> {
 
     fn emit(
         &mut self,
-        _iface: &Interface,
+        iface: &Interface,
         inst: &abi::Instruction<'_>,
         operands: &mut Vec<Self::Operand>,
         results: &mut Vec<Self::Operand>,
@@ -1463,107 +2380,199 @@ impl abi::Bindgen for Bindgen<'_, '_> {
                     abi::LiftLower::LowerArgsLiftResults => Operand::Js(nth),
                 });
             }
-            abi::Instruction::I32Const { val: _ } => todo!(),
-            abi::Instruction::Bitcasts { casts: _ } => todo!(),
-            abi::Instruction::ConstZero { tys: _ } => todo!(),
-            abi::Instruction::I32Load { offset } => {
-                let addr = pop_wasm(operands);
-                let local = self.new_local(wasm_encoder::ValType::I32);
-
-                // []
-                self.inst(Instruction::LocalGet(addr));
-                // [i32]
-                self.inst(Instruction::I32Load(sm_mem_arg((*offset as u32).into())));
-                // [i32]
-                self.inst(Instruction::LocalSet(local));
-                // []
-
-                results.push(Operand::Wasm(local));
+            abi::Instruction::I32Const { .. }
+            | abi::Instruction::Bitcasts { .. }
+            | abi::Instruction::ConstZero { .. }
+            | abi::Instruction::I32Load { .. }
+            | abi::Instruction::I32Load8U { .. }
+            | abi::Instruction::I32Load8S { .. }
+            | abi::Instruction::I32Load16U { .. }
+            | abi::Instruction::I32Load16S { .. }
+            | abi::Instruction::I64Load { .. }
+            | abi::Instruction::F32Load { .. }
+            | abi::Instruction::F64Load { .. }
+            | abi::Instruction::I32Store { .. }
+            | abi::Instruction::I32Store8 { .. }
+            | abi::Instruction::I32Store16 { .. }
+            | abi::Instruction::I64Store { .. }
+            | abi::Instruction::F32Store { .. }
+            | abi::Instruction::F64Store { .. } => {
+                let locals: Vec<_> = operands.drain(..).map(|op| op.unwrap_wasm()).collect();
+                let new_locals = emit_wasm_instruction(self, inst, &locals, SM_MEMORY);
+                results.extend(new_locals.into_iter().map(Operand::Wasm));
             }
-            abi::Instruction::I32Load8U { offset: _ } => todo!(),
-            abi::Instruction::I32Load8S { offset: _ } => todo!(),
-            abi::Instruction::I32Load16U { offset: _ } => todo!(),
-            abi::Instruction::I32Load16S { offset: _ } => todo!(),
-            abi::Instruction::I64Load { offset: _ } => todo!(),
-            abi::Instruction::F32Load { offset: _ } => todo!(),
-            abi::Instruction::F64Load { offset: _ } => todo!(),
-            abi::Instruction::I32Store { offset } => {
-                let addr = pop_wasm(operands);
-                let val = pop_wasm(operands);
-
-                // []
-                self.inst(Instruction::LocalGet(addr));
-                // [i32]
-                self.inst(Instruction::LocalGet(val));
-                // [i32 i32]
-                self.inst(Instruction::I32Store(sm_mem_arg((*offset as u32).into())));
-                // []
+            abi::Instruction::I32FromChar => {
+                let js = pop_js(operands);
+                let result =
+                    self.js_to_wasm(js, "SMW_i32_from_char", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::I64FromU64 | abi::Instruction::I64FromS64 => {
+                let js = pop_js(operands);
+                let result = self.js_to_wasm(
+                    js,
+                    "SMW_i64_from_bigint",
+                    vec![],
+                    wasm_encoder::ValType::I64,
+                );
+                results.push(result);
             }
-            abi::Instruction::I32Store8 { offset: _ } => todo!(),
-            abi::Instruction::I32Store16 { offset: _ } => todo!(),
-            abi::Instruction::I64Store { offset: _ } => todo!(),
-            abi::Instruction::F32Store { offset: _ } => todo!(),
-            abi::Instruction::F64Store { offset: _ } => todo!(),
-            abi::Instruction::I32FromChar => todo!(),
-            abi::Instruction::I64FromU64 => todo!(),
-            abi::Instruction::I64FromS64 => todo!(),
             abi::Instruction::I32FromU32 => {
                 let js = pop_js(operands);
-                let local = self.new_local(wasm_encoder::ValType::I32);
-
-                // []
-                self.inst(Instruction::I32Const(js as i32));
-                // [i32]
-                self.inst(Instruction::Call(
-                    self.gen.spidermonkey_import("SMW_i32_from_u32"),
-                ));
-                // [i32]
-                self.inst(Instruction::LocalSet(local));
-                // []
+                let result =
+                    self.js_to_wasm(js, "SMW_i32_from_u32", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            // `ToInt32` wraps modulo 2^32, so truncating its result handles
+            // all of the smaller integer types too.
+            abi::Instruction::I32FromS32
+            | abi::Instruction::I32FromU16
+            | abi::Instruction::I32FromS16
+            | abi::Instruction::I32FromU8
+            | abi::Instruction::I32FromS8
+            | abi::Instruction::I32FromUsize
+            | abi::Instruction::I32FromChar8 => {
+                let js = pop_js(operands);
+                let result = self.js_to_wasm(
+                    js,
+                    "SMW_i32_from_number",
+                    vec![],
+                    wasm_encoder::ValType::I32,
+                );
+                results.push(result);
+            }
+            abi::Instruction::F32FromIf32 => {
+                let js = pop_js(operands);
+                let result = self.js_to_wasm(
+                    js,
+                    "SMW_f64_from_number",
+                    vec![Instruction::F32DemoteF64],
+                    wasm_encoder::ValType::F32,
+                );
+                results.push(result);
+            }
+            abi::Instruction::F64FromIf64 => {
+                let js = pop_js(operands);
+                let result = self.js_to_wasm(
+                    js,
+                    "SMW_f64_from_number",
+                    vec![],
+                    wasm_encoder::ValType::F64,
+                );
+                results.push(result);
+            }
 
-                results.push(Operand::Wasm(local));
+            abi::Instruction::S8FromI32 => {
+                let local = pop_wasm(operands);
+                let result =
+                    self.wasm_to_js(local, vec![Instruction::I32Extend8S], "SMW_s32_from_i32");
+                results.push(result);
             }
-            abi::Instruction::I32FromS32 => todo!(),
-            abi::Instruction::I32FromU16 => todo!(),
-            abi::Instruction::I32FromS16 => todo!(),
-            abi::Instruction::I32FromU8 => todo!(),
-            abi::Instruction::I32FromS8 => todo!(),
-            abi::Instruction::I32FromUsize => todo!(),
-            abi::Instruction::I32FromChar8 => todo!(),
-            abi::Instruction::F32FromIf32 => todo!(),
-            abi::Instruction::F64FromIf64 => todo!(),
-            abi::Instruction::S8FromI32 => todo!(),
-            abi::Instruction::U8FromI32 => todo!(),
-            abi::Instruction::S16FromI32 => todo!(),
-            abi::Instruction::U16FromI32 => todo!(),
-            abi::Instruction::S32FromI32 => todo!(),
-            abi::Instruction::U32FromI32 => {
+            abi::Instruction::U8FromI32 | abi::Instruction::Char8FromI32 => {
                 let local = pop_wasm(operands);
-                let result = self.next_js();
+                let result = self.wasm_to_js(
+                    local,
+                    vec![Instruction::I32Const(0xff), Instruction::I32And],
+                    "SMW_u32_from_i32",
+                );
+                results.push(result);
+            }
+            abi::Instruction::S16FromI32 => {
+                let local = pop_wasm(operands);
+                let result =
+                    self.wasm_to_js(local, vec![Instruction::I32Extend16S], "SMW_s32_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::U16FromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(
+                    local,
+                    vec![Instruction::I32Const(0xffff), Instruction::I32And],
+                    "SMW_u32_from_i32",
+                );
+                results.push(result);
+            }
+            abi::Instruction::S32FromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(local, vec![], "SMW_s32_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::U32FromI32 | abi::Instruction::UsizeFromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(local, vec![], "SMW_u32_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::S64FromI64 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(local, vec![], "SMW_s64_from_i64");
+                results.push(result);
+            }
+            abi::Instruction::U64FromI64 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(local, vec![], "SMW_u64_from_i64");
+                results.push(result);
+            }
+            abi::Instruction::CharFromI32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(local, vec![], "SMW_char_from_i32");
+                results.push(result);
+            }
+            abi::Instruction::If32FromF32 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(
+                    local,
+                    vec![Instruction::F64PromoteF32],
+                    "SMW_number_from_f64",
+                );
+                results.push(result);
+            }
+            abi::Instruction::If64FromF64 => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(local, vec![], "SMW_number_from_f64");
+                results.push(result);
+            }
 
-                // []
-                self.inst(Instruction::LocalGet(local));
-                // [i32]
-                self.inst(Instruction::I32Const(result.unwrap_js() as i32));
-                // [i32 i32]
-                self.inst(Instruction::Call(
-                    self.gen.spidermonkey_import("SMW_u32_from_i32"),
-                ));
-                // []
+            // Handles to imported resources are plain numbers in JS.
+            abi::Instruction::I32FromBorrowedHandle { ty: _ } => {
+                let js = pop_js(operands);
+                let result =
+                    self.js_to_wasm(js, "SMW_i32_from_u32", vec![], wasm_encoder::ValType::I32);
+                results.push(result);
+            }
+            abi::Instruction::HandleOwnedFromI32 { ty: _ } => {
+                let local = pop_wasm(operands);
+                let result = self.wasm_to_js(local, vec![], "SMW_u32_from_i32");
+                results.push(result);
+            }
 
+            // Exported resources can be any JS value, which we keep in
+            // `bindgen.cpp`'s resource table and identify by their index in
+            // that table when creating a handle with the canonical ABI.
+            abi::Instruction::I32FromOwnedHandle { ty } => {
+                let js = pop_js(operands);
+                let resource_new = self
+                    .gen
+                    .canonical_abi_import(&format!("resource_new_{}", iface.resources[*ty].name));
+                let result = self.js_to_wasm(
+                    js,
+                    "SMW_resource_insert",
+                    vec![Instruction::Call(resource_new)],
+                    wasm_encoder::ValType::I32,
+                );
+                results.push(result);
+            }
+            abi::Instruction::HandleBorrowedFromI32 { ty } => {
+                let local = pop_wasm(operands);
+                let resource_get = self
+                    .gen
+                    .canonical_abi_import(&format!("resource_get_{}", iface.resources[*ty].name));
+                let result = self.wasm_to_js(
+                    local,
+                    vec![Instruction::Call(resource_get)],
+                    "SMW_resource_get",
+                );
                 results.push(result);
             }
-            abi::Instruction::S64FromI64 => todo!(),
-            abi::Instruction::U64FromI64 => todo!(),
-            abi::Instruction::CharFromI32 => todo!(),
-            abi::Instruction::If32FromF32 => todo!(),
-            abi::Instruction::If64FromF64 => todo!(),
-            abi::Instruction::Char8FromI32 => todo!(),
-            abi::Instruction::UsizeFromI32 => todo!(),
-            abi::Instruction::I32FromBorrowedHandle { ty: _ } => todo!(),
-            abi::Instruction::I32FromOwnedHandle { ty: _ } => todo!(),
-            abi::Instruction::HandleOwnedFromI32 { ty: _ } => todo!(),
-            abi::Instruction::HandleBorrowedFromI32 { ty: _ } => todo!(),
             abi::Instruction::ListCanonLower { element, realloc } => {
                 let js = pop_js(operands);
                 let ptr = self.new_local(wasm_encoder::ValType::I32);
@@ -1642,9 +2651,19 @@ impl abi::Bindgen for Bindgen<'_, '_> {
                 self.inst(Instruction::LocalSet(length));
                 // []
 
-                // `malloc` space for the result.
+                // `malloc` space for the result: `length * size` bytes.
+                //
+                // []
+                self.inst(Instruction::LocalGet(length));
+                // [i32]
+                self.inst(Instruction::I32Const(u32::try_from(size).unwrap() as _));
+                // [i32 i32]
+                self.inst(Instruction::I32Mul);
+                // [i32]
+                self.inst(Instruction::LocalSet(ptr));
+                // []
                 self.gen
-                    .malloc_dynamic_size(self.blocks.last_mut().unwrap(), length, ptr);
+                    .malloc_dynamic_size(self.blocks.last_mut().unwrap(), ptr, ptr);
 
                 // Create a new block and loop. The block is so we can branch to
                 // it to exit out of the loop.
@@ -1908,53 +2927,386 @@ impl abi::Bindgen for Bindgen<'_, '_> {
                 results.push(Operand::Wasm(iter_base_pointer));
             }
 
-            abi::Instruction::BufferPayloadName => todo!(),
-            abi::Instruction::BufferLowerPtrLen { push: _, ty: _ } => todo!(),
-            abi::Instruction::BufferLowerHandle { push: _, ty: _ } => todo!(),
-            abi::Instruction::BufferLiftPtrLen { push: _, ty: _ } => todo!(),
-            abi::Instruction::BufferLiftHandle { push: _, ty: _ } => todo!(),
-            abi::Instruction::RecordLower {
-                record: _,
-                name: _,
-                ty: _,
-            } => todo!(),
-            abi::Instruction::RecordLift {
-                record: _,
-                name: _,
-                ty: _,
-            } => todo!(),
-            abi::Instruction::FlagsLower {
-                record: _,
-                name: _,
-                ty: _,
-            } => todo!(),
-            abi::Instruction::FlagsLower64 {
-                record: _,
-                name: _,
-                ty: _,
-            } => todo!(),
-            abi::Instruction::FlagsLift {
-                record: _,
-                name: _,
-                ty: _,
-            } => todo!(),
-            abi::Instruction::FlagsLift64 {
-                record: _,
-                name: _,
-                ty: _,
-            } => todo!(),
-            abi::Instruction::VariantPayloadName => todo!(),
+            abi::Instruction::BufferPayloadName
+            | abi::Instruction::BufferLowerPtrLen { .. }
+            | abi::Instruction::BufferLowerHandle { .. }
+            | abi::Instruction::BufferLiftPtrLen { .. }
+            | abi::Instruction::BufferLiftHandle { .. } => {
+                unreachable!("buffers are not supported")
+            }
+            abi::Instruction::RecordLower { record, .. } => {
+                let js = pop_js(operands);
+                for (i, field) in record.fields.iter().enumerate() {
+                    let dest = self.next_js();
+                    if record.is_tuple() {
+                        // Tuples are represented as arrays.
+                        //
+                        // []
+                        self.inst(Instruction::I32Const(js as i32));
+                        // [i32]
+                        self.inst(Instruction::I32Const(i as i32));
+                        // [i32 i32]
+                        self.inst(Instruction::I32Const(dest.unwrap_js() as i32));
+                        // [i32 i32 i32]
+                        self.inst(Instruction::Call(
+                            self.gen.spidermonkey_import("SMW_get_array_element"),
+                        ));
+                        // []
+                    } else {
+                        self.get_property(js, &field.name.to_mixed_case(), dest.unwrap_js());
+                    }
+                    results.push(dest);
+                }
+            }
+            abi::Instruction::RecordLift { record, .. } => {
+                let fields: Vec<_> = operands.drain(..).map(|op| op.unwrap_js()).collect();
+                let result = self.next_js().unwrap_js();
+                if record.is_tuple() {
+                    // []
+                    self.inst(Instruction::I32Const(result as i32));
+                    // [i32]
+                    self.inst(Instruction::Call(
+                        self.gen.spidermonkey_import("SMW_new_array"),
+                    ));
+                    // []
+                    for js in fields {
+                        // []
+                        self.inst(Instruction::I32Const(result as i32));
+                        // [i32]
+                        self.inst(Instruction::I32Const(js as i32));
+                        // [i32 i32]
+                        self.inst(Instruction::Call(
+                            self.gen.spidermonkey_import("SMW_array_push"),
+                        ));
+                        // []
+                    }
+                } else {
+                    // []
+                    self.inst(Instruction::I32Const(result as i32));
+                    // [i32]
+                    self.inst(Instruction::Call(
+                        self.gen.spidermonkey_import("SMW_new_object"),
+                    ));
+                    // []
+                    for (field, js) in record.fields.iter().zip(fields) {
+                        self.set_property(result, &field.name.to_mixed_case(), js);
+                    }
+                }
+                results.push(Operand::Js(result));
+            }
+
+            // Flags are represented as objects with a boolean property for
+            // each flag.
+            abi::Instruction::FlagsLower { record, .. } => {
+                let js = pop_js(operands);
+                let flag = self.next_js().unwrap_js();
+                let locals: Vec<_> = (0..record.num_i32s())
+                    .map(|_| self.new_local(wasm_encoder::ValType::I32))
+                    .collect();
+                for local in &locals {
+                    // []
+                    self.inst(Instruction::I32Const(0));
+                    // [i32]
+                    self.inst(Instruction::LocalSet(*local));
+                    // []
+                }
+                for (i, field) in record.fields.iter().enumerate() {
+                    let local = locals[i / 32];
+                    self.get_property(js, &field.name.to_mixed_case(), flag);
+                    self.variant_discriminant(flag, VariantRepr::Bool, &[]);
+                    // [i32]
+                    self.inst(Instruction::I32Const((i % 32) as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Shl);
+                    // [i32]
+                    self.inst(Instruction::LocalGet(local));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Or);
+                    // [i32]
+                    self.inst(Instruction::LocalSet(local));
+                    // []
+                }
+                results.extend(locals.into_iter().map(Operand::Wasm));
+            }
+            abi::Instruction::FlagsLower64 { record, .. } => {
+                let js = pop_js(operands);
+                let flag = self.next_js().unwrap_js();
+                let local = self.new_local(wasm_encoder::ValType::I64);
+
+                // []
+                self.inst(Instruction::I64Const(0));
+                // [i64]
+                self.inst(Instruction::LocalSet(local));
+                // []
+                for (i, field) in record.fields.iter().enumerate() {
+                    self.get_property(js, &field.name.to_mixed_case(), flag);
+                    self.variant_discriminant(flag, VariantRepr::Bool, &[]);
+                    // [i32]
+                    self.inst(Instruction::I64ExtendI32U);
+                    // [i64]
+                    self.inst(Instruction::I64Const(i as i64));
+                    // [i64 i64]
+                    self.inst(Instruction::I64Shl);
+                    // [i64]
+                    self.inst(Instruction::LocalGet(local));
+                    // [i64 i64]
+                    self.inst(Instruction::I64Or);
+                    // [i64]
+                    self.inst(Instruction::LocalSet(local));
+                    // []
+                }
+                results.push(Operand::Wasm(local));
+            }
+            abi::Instruction::FlagsLift { record, .. } => {
+                let locals: Vec<_> = operands.drain(..).map(|op| op.unwrap_wasm()).collect();
+                let flag = self.next_js().unwrap_js();
+                let result = self.next_js().unwrap_js();
+
+                // Trap if any bits are set that don't correspond to a flag.
+                for (i, local) in locals.iter().enumerate() {
+                    let num_flags = (record.fields.len() - i * 32).min(32);
+                    if num_flags == 32 {
+                        continue;
+                    }
+                    // []
+                    self.inst(Instruction::LocalGet(*local));
+                    // [i32]
+                    self.inst(Instruction::I32Const(!((1u32 << num_flags) - 1) as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32And);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    self.inst(Instruction::Unreachable);
+                    self.inst(Instruction::End);
+                    // []
+                }
+
+                // []
+                self.inst(Instruction::I32Const(result as i32));
+                // [i32]
+                self.inst(Instruction::Call(
+                    self.gen.spidermonkey_import("SMW_new_object"),
+                ));
+                // []
+                for (i, field) in record.fields.iter().enumerate() {
+                    let local = locals[i / 32];
+                    let is_set = self.new_local(wasm_encoder::ValType::I32);
+
+                    // []
+                    self.inst(Instruction::LocalGet(local));
+                    // [i32]
+                    self.inst(Instruction::I32Const((i % 32) as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32ShrU);
+                    // [i32]
+                    self.inst(Instruction::I32Const(1));
+                    // [i32 i32]
+                    self.inst(Instruction::I32And);
+                    // [i32]
+                    self.inst(Instruction::LocalSet(is_set));
+                    // []
+
+                    self.variant_lift(
+                        VariantRepr::Bool,
+                        Instruction::LocalGet(is_set),
+                        "",
+                        None,
+                        flag,
+                    );
+                    self.set_property(result, &field.name.to_mixed_case(), flag);
+                }
+
+                results.push(Operand::Js(result));
+            }
+            abi::Instruction::FlagsLift64 { record, .. } => {
+                let local = pop_wasm(operands);
+                let flag = self.next_js().unwrap_js();
+                let result = self.next_js().unwrap_js();
+
+                // Trap if any bits are set that don't correspond to a flag.
+                if record.fields.len() < 64 {
+                    // []
+                    self.inst(Instruction::LocalGet(local));
+                    // [i64]
+                    self.inst(Instruction::I64Const(
+                        !((1u64 << record.fields.len()) - 1) as i64,
+                    ));
+                    // [i64 i64]
+                    self.inst(Instruction::I64And);
+                    // [i64]
+                    self.inst(Instruction::I64Const(0));
+                    // [i64 i64]
+                    self.inst(Instruction::I64Neq);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    self.inst(Instruction::Unreachable);
+                    self.inst(Instruction::End);
+                    // []
+                }
+
+                // []
+                self.inst(Instruction::I32Const(result as i32));
+                // [i32]
+                self.inst(Instruction::Call(
+                    self.gen.spidermonkey_import("SMW_new_object"),
+                ));
+                // []
+                for (i, field) in record.fields.iter().enumerate() {
+                    let is_set = self.new_local(wasm_encoder::ValType::I32);
+
+                    // []
+                    self.inst(Instruction::LocalGet(local));
+                    // [i64]
+                    self.inst(Instruction::I64Const(i as i64));
+                    // [i64 i64]
+                    self.inst(Instruction::I64ShrU);
+                    // [i64]
+                    self.inst(Instruction::I32WrapI64);
+                    // [i32]
+                    self.inst(Instruction::I32Const(1));
+                    // [i32 i32]
+                    self.inst(Instruction::I32And);
+                    // [i32]
+                    self.inst(Instruction::LocalSet(is_set));
+                    // []
+
+                    self.variant_lift(
+                        VariantRepr::Bool,
+                        Instruction::LocalGet(is_set),
+                        "",
+                        None,
+                        flag,
+                    );
+                    self.set_property(result, &field.name.to_mixed_case(), flag);
+                }
+
+                results.push(Operand::Js(result));
+            }
+
+            abi::Instruction::VariantPayloadName => {
+                let payload = self.next_js();
+                self.payloads.push(payload.unwrap_js());
+                results.push(payload);
+            }
             abi::Instruction::VariantLower {
-                variant: _,
-                name: _,
+                variant,
+                name,
                 ty: _,
-                results: _,
-            } => todo!(),
+                results: result_types,
+            } => {
+                let js = pop_js(operands);
+                let blocks = self.pop_blocks(variant.cases.len());
+                let payloads = self
+                    .payloads
+                    .split_off(self.payloads.len() - variant.cases.len());
+                let repr = VariantRepr::new(iface, variant, *name);
+
+                let discriminant = self.new_local(wasm_encoder::ValType::I32);
+                let case_names: Vec<_> = variant.cases.iter().map(|c| c.name.as_str()).collect();
+                self.variant_discriminant(js, repr, &case_names);
+                // [i32]
+                self.inst(Instruction::LocalSet(discriminant));
+                // []
+
+                let locals: Vec<_> = result_types
+                    .iter()
+                    .map(|ty| self.new_local(convert_ty(*ty)))
+                    .collect();
+
+                // Select the block for the discriminant's case with a chain
+                // of `if`/`else`s, trapping if none of them match.
+                for (i, ((case, (block, block_results)), payload)) in
+                    variant.cases.iter().zip(blocks).zip(payloads).enumerate()
+                {
+                    // []
+                    self.inst(Instruction::LocalGet(discriminant));
+                    // [i32]
+                    self.inst(Instruction::I32Const(i as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Eq);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    // []
+
+                    if case.ty.is_some() {
+                        // []
+                        self.inst(Instruction::I32Const(js as i32));
+                        // [i32]
+                        self.inst(Instruction::I32Const(repr as i32));
+                        // [i32 i32]
+                        self.inst(Instruction::I32Const(payload as i32));
+                        // [i32 i32 i32]
+                        self.inst(Instruction::Call(
+                            self.gen.spidermonkey_import("SMW_variant_payload"),
+                        ));
+                        // []
+                    }
+
+                    self.current_block().extend(block);
+
+                    assert_eq!(block_results.len(), locals.len());
+                    for (local, result) in locals.iter().zip(block_results) {
+                        // []
+                        self.inst(Instruction::LocalGet(result.unwrap_wasm()));
+                        // [T]
+                        self.inst(Instruction::LocalSet(*local));
+                        // []
+                    }
+
+                    self.inst(Instruction::Else);
+                }
+                self.inst(Instruction::Unreachable);
+                for _ in &variant.cases {
+                    self.inst(Instruction::End);
+                }
+
+                results.extend(locals.into_iter().map(Operand::Wasm));
+            }
             abi::Instruction::VariantLift {
-                variant: _,
-                name: _,
+                variant,
+                name,
                 ty: _,
-            } => todo!(),
+            } => {
+                let discriminant = pop_wasm(operands);
+                let blocks = self.pop_blocks(variant.cases.len());
+                let repr = VariantRepr::new(iface, variant, *name);
+                let result = self.next_js().unwrap_js();
+
+                // Select the block for the discriminant's case with a chain
+                // of `if`/`else`s, trapping if none of them match.
+                for (i, (case, (block, block_results))) in
+                    variant.cases.iter().zip(blocks).enumerate()
+                {
+                    // []
+                    self.inst(Instruction::LocalGet(discriminant));
+                    // [i32]
+                    self.inst(Instruction::I32Const(i as i32));
+                    // [i32 i32]
+                    self.inst(Instruction::I32Eq);
+                    // [i32]
+                    self.inst(Instruction::If(wasm_encoder::BlockType::Empty));
+                    // []
+
+                    self.current_block().extend(block);
+
+                    let payload = block_results.first().map(|op| op.unwrap_js());
+                    self.variant_lift(
+                        repr,
+                        Instruction::I32Const(i as i32),
+                        &case.name,
+                        payload,
+                        result,
+                    );
+
+                    self.inst(Instruction::Else);
+                }
+                self.inst(Instruction::Unreachable);
+                for _ in &variant.cases {
+                    self.inst(Instruction::End);
+                }
+
+                results.push(Operand::Js(result));
+            }
             abi::Instruction::CallWasm { module, name, sig } => {
                 // Push the Wasm arguments.
                 //
@@ -1971,9 +3323,8 @@ impl abi::Bindgen for Bindgen<'_, '_> {
                     .get(*module)
                     .unwrap()
                     .get(*name)
-                    .unwrap()
-                    .0;
-                self.inst(Instruction::Call(func_index));
+                    .unwrap();
+                self.inst(Instruction::Call(*func_index));
                 // [R...]
 
                 // Allocate locals for the results and pop the return values off
@@ -2023,34 +3374,6 @@ impl abi::Bindgen for Bindgen<'_, '_> {
                     // []
                 }
 
-                // TODO: Rather than `malloc`ing the name for each call, we
-                // should pre-`malloc` them in the `wizer.initialize` function,
-                // add a global to the glue module that points to the
-                // pre-`malloc`ed name for each exported function, and then
-                // reuse those that pre-`malloc`ed name on each call.
-
-                // Malloc space for the function name.
-                let func_name_local = self.new_local(wasm_encoder::ValType::I32);
-                self.gen.malloc_static_size(
-                    self.blocks.last_mut().unwrap(),
-                    u32::try_from(func.name.len()).unwrap() + 1,
-                    func_name_local,
-                );
-
-                // Copy the function name from the glue Wasm module's memory
-                // into the `malloc`ed space in the `spidermonkey.wasm`'s
-                // memory.
-                let func_name_offset = self
-                    .gen
-                    .data_segments
-                    .add(func.name.to_snake_case().as_bytes().iter().copied());
-                self.gen.copy_to_smw(
-                    self.blocks.last_mut().unwrap(),
-                    func_name_offset,
-                    func_name_local,
-                    u32::try_from(func.name.len()).unwrap(),
-                );
-
                 let first_result = if func.results.is_empty() {
                     // If there aren't any function results, then this argument
                     // to `SMW_call` is going to be ignored. Use a highly
@@ -2064,14 +3387,11 @@ impl abi::Bindgen for Bindgen<'_, '_> {
                     js.unwrap_js()
                 };
 
-                // Make the call.
+                // Make the call. The function's name was copied into
+                // `spidermonkey.wasm`'s memory once, in `wizer.initialize`.
                 //
                 // []
-                self.inst(Instruction::LocalGet(func_name_local));
-                // [i32]
-                self.inst(Instruction::I32Const(
-                    i32::try_from(func.name.len()).unwrap(),
-                ));
+                self.push_string(&func.name.to_snake_case());
                 // [i32 i32]
                 self.inst(Instruction::I32Const(
                     u32::try_from(func.results.len()).unwrap() as _,
@@ -2161,7 +3481,7 @@ impl abi::Bindgen for Bindgen<'_, '_> {
     }
 
     fn allocate_typed_space(&mut self, _iface: &Interface, _ty: TypeId) -> Self::Operand {
-        todo!()
+        unreachable!("we do not support the preview1 ABI")
     }
 
     fn i64_return_pointer_area(&mut self, amt: usize) -> Self::Operand {
@@ -2186,7 +3506,7 @@ impl abi::Bindgen for Bindgen<'_, '_> {
     }
 
     fn sizes(&self) -> &SizeAlign {
-        todo!()
+        &self.gen.sizes
     }
 
     fn is_list_canonical(&self, _iface: &Interface, _ty: &Type) -> bool {
@@ -2,19 +2,39 @@ use std::path::Path;
 
 mod imports {
     test_helpers::codegen_spidermonkey_import!(
-        // TODO: should support more of the `*.wit` test suite
-        "strings.wit"
-        "simple-lists.wit"
+        "char.wit"
+        "conventions.wit"
+        "empty.wit"
+        "flags.wit"
+        "floats.wit"
+        "integers.wit"
+        "lists.wit"
+        "records.wit"
+        "resource.wit"
         "simple-functions.wit"
+        "simple-lists.wit"
+        "smoke.wit"
+        "strings.wit"
+        "variants.wit"
     );
 }
 
 mod exports {
     test_helpers::codegen_spidermonkey_export!(
-        // TODO: should support more of the `*.wit` test suite
-        "strings.wit"
-        "simple-lists.wit"
+        "char.wit"
+        "conventions.wit"
+        "empty.wit"
+        "flags.wit"
+        "floats.wit"
+        "integers.wit"
+        "lists.wit"
+        "records.wit"
+        "resource.wit"
         "simple-functions.wit"
+        "simple-lists.wit"
+        "smoke.wit"
+        "strings.wit"
+        "variants.wit"
     );
 }
 
@@ -0,0 +1,65 @@
+use std::path::Path;
+use wit_bindgen_gen_core::{wit_parser::Interface, Files, Generator};
+use wit_bindgen_gen_spidermonkey::SpiderMonkeyWasm;
+
+const WITS: &[&str] = &[
+    "char.wit",
+    "conventions.wit",
+    "empty.wit",
+    "flags.wit",
+    "floats.wit",
+    "integers.wit",
+    "lists.wit",
+    "records.wit",
+    "resource.wit",
+    "simple-functions.wit",
+    "simple-lists.wit",
+    "smoke.wit",
+    "strings.wit",
+    "variants.wit",
+];
+
+fn stub_spidermonkey() -> Vec<u8> {
+    wat::parse_file(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spidermonkey-stub.wat"))
+        .unwrap()
+}
+
+fn generate(imports: &[Interface], exports: &[Interface], smw: Vec<u8>) -> Vec<u8> {
+    let mut gen = SpiderMonkeyWasm::new("foo.js", "");
+    gen.spidermonkey_wasm(smw);
+    let mut files = Files::default();
+    gen.generate_all(imports, exports, &mut files);
+    let (_, wasm) = files
+        .iter()
+        .find(|(name, _)| *name == "foo.wasm")
+        .expect("should generate `foo.wasm`");
+    wasm.to_vec()
+}
+
+fn verify(wasm: &[u8]) {
+    let mut validator = wasmparser::Validator::new();
+    validator.wasm_features(wasmparser::WasmFeatures {
+        bulk_memory: true,
+        module_linking: true,
+        multi_memory: true,
+        ..wasmparser::WasmFeatures::default()
+    });
+    validator.validate_all(wasm).expect("wasm isn't valid");
+}
+
+#[test]
+fn embedded() {
+    for wit in WITS {
+        let path = Path::new("../../tests/codegen").join(wit);
+        let import = Interface::parse_file(&path).unwrap();
+        verify(&generate(&[import], &[], stub_spidermonkey()));
+        let export = Interface::parse_file(&path).unwrap();
+        verify(&generate(&[], &[export], stub_spidermonkey()));
+    }
+}
+
+#[test]
+#[should_panic(expected = "failed to embed `spidermonkey.wasm`")]
+fn embedded_not_wasm() {
+    generate(&[], &[], b"not a wasm module".to_vec());
+}
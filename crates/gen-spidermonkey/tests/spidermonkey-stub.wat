@@ -0,0 +1,50 @@
+;; A stand-in for `spidermonkey.wasm` with the same imports and exports but
+;; none of the behavior, for checking the glue module that embeds it.
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "random_get" (func (param i32 i32) (result i32)))
+  (memory (export "memory") 2)
+  (table (export "__indirect_function_table") 1 funcref)
+  (func (export "_initialize"))
+  (func (export "canonical_abi_free") (param i32 i32 i32))
+  (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) (i32.const 0))
+  (func (export "SMW_initialize_engine"))
+  (func (export "SMW_new_module_builder") (param i32 i32) (result i32) (i32.const 0))
+  (func (export "SMW_module_builder_add_export") (param i32 i32 i32 i32 i32))
+  (func (export "SMW_finish_module_builder") (param i32))
+  (func (export "SMW_eval_module") (param i32 i32 i32))
+  (func (export "SMW_malloc") (param i32) (result i32) (i32.const 0))
+  (func (export "SMW_fill_operands") (param i32 i32))
+  (func (export "SMW_clear_operands"))
+  (func (export "SMW_push_arg") (param i32))
+  (func (export "SMW_call") (param i32 i32 i32 i32))
+  (func (export "SMW_push_return_value") (param i32))
+  (func (export "SMW_finish_returns") (param i32 i32))
+  (func (export "SMW_i32_from_u32") (param i32) (result i32) (i32.const 0))
+  (func (export "SMW_u32_from_i32") (param i32 i32))
+  (func (export "SMW_string_canon_lower") (param i32 i32))
+  (func (export "SMW_string_canon_lift") (param i32 i32 i32))
+  (func (export "SMW_spread_into_array") (param i32) (result i32) (i32.const 0))
+  (func (export "SMW_get_array_element") (param i32 i32 i32))
+  (func (export "SMW_array_push") (param i32 i32))
+  (func (export "SMW_new_array") (param i32))
+  (func (export "SMW_i32_from_number") (param i32) (result i32) (i32.const 0))
+  (func (export "SMW_s32_from_i32") (param i32 i32))
+  (func (export "SMW_i64_from_bigint") (param i32) (result i64) (i64.const 0))
+  (func (export "SMW_s64_from_i64") (param i64 i32))
+  (func (export "SMW_u64_from_i64") (param i64 i32))
+  (func (export "SMW_f64_from_number") (param i32) (result f64) (f64.const 0))
+  (func (export "SMW_number_from_f64") (param f64 i32))
+  (func (export "SMW_i32_from_char") (param i32) (result i32) (i32.const 0))
+  (func (export "SMW_char_from_i32") (param i32 i32))
+  (func (export "SMW_variant_discriminant") (param i32 i32 i32 i32) (result i32) (i32.const 0))
+  (func (export "SMW_variant_payload") (param i32 i32 i32))
+  (func (export "SMW_variant_lift") (param i32 i32 i32 i32 i32 i32))
+  (func (export "SMW_new_object") (param i32))
+  (func (export "SMW_get_property") (param i32 i32 i32 i32))
+  (func (export "SMW_set_property") (param i32 i32 i32 i32))
+  (func (export "SMW_resource_insert") (param i32) (result i32) (i32.const 0))
+  (func (export "SMW_resource_get") (param i32 i32))
+  (func (export "SMW_resource_drop") (param i32 i32 i32))
+  (func (export "dump_i32") (param i32) (result i32) (i32.const 0))
+)
@@ -3,6 +3,7 @@
 
 use std::convert::TryFrom;
 
+/// The static data of a glue module, which lives in a single memory.
 #[derive(Debug)]
 pub struct DataSegments {
     data: wasm_encoder::DataSection,
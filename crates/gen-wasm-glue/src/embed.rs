@@ -0,0 +1,166 @@
+//! Embedding an interpreter's Wasm module inside the generated glue module.
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::convert::TryFrom;
+use wit_bindgen_gen_core::wit_parser::abi::{WasmSignature, WasmType};
+
+const TYPE_SECTION: u8 = 1;
+const IMPORT_SECTION: u8 = 2;
+const TABLE_SECTION: u8 = 4;
+
+/// An interpreter's Wasm module, such as `spidermonkey.wasm`, prepared for
+/// embedding in the glue module's `module` section.
+pub struct EmbeddedModule {
+    /// The re-encoded module.
+    pub module: wasm_encoder::Module,
+
+    /// The module's function imports, which the glue module must
+    /// import itself and forward along: `(module, name, signature)`.
+    pub imports: Vec<(String, String, WasmSignature)>,
+
+    /// The first of the function table slots that were reserved for the glue
+    /// module's own use.
+    pub table_base: u32,
+}
+
+impl EmbeddedModule {
+    /// Prepare the given Wasm module, named `name` in error messages, for
+    /// embedding, growing its function table's initial size by
+    /// `reserved_table_slots`.
+    ///
+    /// Fails if `wasm` is malformed or uses features that cannot be forwarded
+    /// through the glue module, such as non-function imports.
+    ///
+    /// Reserving table slots up front lets the glue module place its import
+    /// glue functions into the table with an active element segment, rather
+    /// than growing the table and setting its entries at runtime.
+    pub fn new(name: &str, wasm: &[u8], reserved_table_slots: u32) -> Result<EmbeddedModule> {
+        ensure!(
+            wasm.len() >= 8 && wasm[..4] == *b"\0asm",
+            "`{}` is not a Wasm module",
+            name
+        );
+
+        let mut module = wasm_encoder::Module::new();
+        let mut types = Vec::new();
+        let mut imports = Vec::new();
+        let mut table_base = None;
+
+        let mut reader = wasmparser::BinaryReader::new_with_offset(&wasm[8..], 8);
+        while !reader.eof() {
+            let id = u8::try_from(reader.read_u8()?)?;
+            let len = reader.read_var_u32()?;
+            let offset = reader.original_position();
+            let data = reader
+                .read_bytes(usize::try_from(len)?)
+                .with_context(|| format!("failed to read a section of `{}`", name))?;
+
+            match id {
+                TYPE_SECTION => {
+                    for ty in wasmparser::TypeSectionReader::new(data, offset)? {
+                        types.push(match ty? {
+                            wasmparser::TypeDef::Func(f) => Some(f),
+                            _ => None,
+                        });
+                    }
+                }
+                IMPORT_SECTION => {
+                    for import in wasmparser::ImportSectionReader::new(data, offset)? {
+                        let import = import?;
+                        let ty = match import.ty {
+                            wasmparser::ImportSectionEntryType::Function(ty) => types
+                                .get(usize::try_from(ty)?)
+                                .and_then(|ty| ty.as_ref())
+                                .ok_or_else(|| {
+                                    anyhow!(
+                                        "`{}` imports a function with invalid type {}",
+                                        name,
+                                        ty
+                                    )
+                                })?,
+                            _ => bail!(
+                                "`{}` may only import functions; found non-function import {}.{}",
+                                name,
+                                import.module,
+                                import.field.unwrap_or_default()
+                            ),
+                        };
+                        let field = import.field.ok_or_else(|| {
+                            anyhow!(
+                                "`{}` may not import whole instances: {}",
+                                name,
+                                import.module
+                            )
+                        })?;
+                        imports.push((
+                            import.module.to_string(),
+                            field.to_string(),
+                            WasmSignature {
+                                params: ty
+                                    .params
+                                    .iter()
+                                    .map(|ty| convert_ty(name, *ty))
+                                    .collect::<Result<_>>()?,
+                                results: ty
+                                    .returns
+                                    .iter()
+                                    .map(|ty| convert_ty(name, *ty))
+                                    .collect::<Result<_>>()?,
+                                retptr: None,
+                            },
+                        ));
+                    }
+                }
+                TABLE_SECTION => {
+                    // Reserve our slots at the end of the first table, which
+                    // is `__indirect_function_table`.
+                    let mut tables = wasm_encoder::TableSection::new();
+                    for (i, table) in wasmparser::TableSectionReader::new(data, offset)?
+                        .into_iter()
+                        .enumerate()
+                    {
+                        let table = table?;
+                        ensure!(
+                            table.element_type == wasmparser::Type::FuncRef,
+                            "`{}` may only define `funcref` tables",
+                            name
+                        );
+                        let mut ty = wasm_encoder::TableType {
+                            element_type: wasm_encoder::ValType::FuncRef,
+                            minimum: table.initial,
+                            maximum: table.maximum,
+                        };
+                        if i == 0 {
+                            table_base = Some(table.initial);
+                            ty.minimum += reserved_table_slots;
+                            ty.maximum = ty.maximum.map(|max| max.max(ty.minimum));
+                        }
+                        tables.table(ty);
+                    }
+                    module.section(&tables);
+                    continue;
+                }
+                _ => {}
+            }
+
+            module.section(&wasm_encoder::RawSection { id, data });
+        }
+
+        Ok(EmbeddedModule {
+            module,
+            imports,
+            table_base: table_base
+                .ok_or_else(|| anyhow!("`{}` does not define a function table", name))?,
+        })
+    }
+}
+
+fn convert_ty(name: &str, ty: wasmparser::Type) -> Result<WasmType> {
+    Ok(match ty {
+        wasmparser::Type::I32 => WasmType::I32,
+        wasmparser::Type::I64 => WasmType::I64,
+        wasmparser::Type::F32 => WasmType::F32,
+        wasmparser::Type::F64 => WasmType::F64,
+        ty => bail!("unsupported type in `{}` import: {:?}", name, ty),
+    })
+}
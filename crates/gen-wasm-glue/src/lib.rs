@@ -0,0 +1,291 @@
+//! Support shared by the bindings generators that emit a Wasm glue module
+//! around an interpreter compiled to Wasm, such as `spidermonkey.wasm` and
+//! `cpython.wasm`.
+//!
+//! The glue code that these generators emit is mostly specific to the
+//! interpreter's embedding API, but the parts of the canonical ABI that only
+//! deal with Wasm values, like loads, stores and bitcasts, are the same for
+//! every interpreter and are emitted by [`emit_wasm_instruction`].
+
+#![deny(missing_docs)]
+
+mod data_segments;
+mod embed;
+
+pub use data_segments::DataSegments;
+pub use embed::EmbeddedModule;
+
+use wasm_encoder::{Instruction, ValType};
+use wit_bindgen_gen_core::wit_parser::{
+    abi::{self, WasmType},
+    Interface, Type, TypeDefKind, Variant,
+};
+
+/// Something that Wasm instructions can be appended to.
+pub trait InstructionSink<'a> {
+    /// Append `inst`.
+    fn instruction(&mut self, inst: Instruction<'a>);
+}
+
+impl<'a> InstructionSink<'a> for wasm_encoder::Function {
+    fn instruction(&mut self, inst: Instruction<'a>) {
+        wasm_encoder::Function::instruction(self, &inst);
+    }
+}
+
+impl<'a> InstructionSink<'a> for Vec<Instruction<'a>> {
+    fn instruction(&mut self, inst: Instruction<'a>) {
+        self.push(inst);
+    }
+}
+
+/// A glue function that is being generated.
+pub trait GlueFunction<'a> {
+    /// Append `inst` to the block that is currently being generated.
+    fn inst(&mut self, inst: Instruction<'a>);
+
+    /// Create a new Wasm local of type `ty` and return its index.
+    fn new_local(&mut self, ty: ValType) -> u32;
+}
+
+/// Convert a canonical ABI Wasm type into its `wasm_encoder` equivalent.
+pub fn convert_ty(ty: WasmType) -> ValType {
+    match ty {
+        WasmType::I32 => ValType::I32,
+        WasmType::I64 => ValType::I64,
+        WasmType::F32 => ValType::F32,
+        WasmType::F64 => ValType::F64,
+    }
+}
+
+/// The `MemArg` for accessing `offset` in the given memory.
+pub fn mem_arg(offset: u32, memory: u32) -> wasm_encoder::MemArg {
+    wasm_encoder::MemArg {
+        offset: offset as u64,
+        align: 0,
+        memory_index: memory,
+    }
+}
+
+/// Get the resulting type and the instructions that implement the given
+/// bitcast, or `None` if it is a no-op.
+pub fn bitcast(cast: &abi::Bitcast) -> Option<(ValType, Vec<Instruction<'static>>)> {
+    Some(match cast {
+        abi::Bitcast::None => return None,
+        abi::Bitcast::F32ToF64 => (ValType::F64, vec![Instruction::F64PromoteF32]),
+        abi::Bitcast::F64ToF32 => (ValType::F32, vec![Instruction::F32DemoteF64]),
+        abi::Bitcast::F32ToI32 => (ValType::I32, vec![Instruction::I32ReinterpretF32]),
+        abi::Bitcast::I32ToF32 => (ValType::F32, vec![Instruction::F32ReinterpretI32]),
+        abi::Bitcast::F64ToI64 => (ValType::I64, vec![Instruction::I64ReinterpretF64]),
+        abi::Bitcast::I64ToF64 => (ValType::F64, vec![Instruction::F64ReinterpretI64]),
+        abi::Bitcast::I32ToI64 => (ValType::I64, vec![Instruction::I64ExtendI32U]),
+        abi::Bitcast::I64ToI32 => (ValType::I32, vec![Instruction::I32WrapI64]),
+        abi::Bitcast::F32ToI64 => (
+            ValType::I64,
+            vec![Instruction::I32ReinterpretF32, Instruction::I64ExtendI32U],
+        ),
+        abi::Bitcast::I64ToF32 => (
+            ValType::F32,
+            vec![Instruction::I32WrapI64, Instruction::F32ReinterpretI32],
+        ),
+    })
+}
+
+/// Is this variant an `option<T>` that can be represented as either the
+/// interpreter's null value or the `T` itself? This is the case unless `T` is
+/// itself such an option, since otherwise `none` and `some(none)` would be
+/// indistinguishable.
+pub fn is_nullable_option(iface: &Interface, variant: &Variant) -> bool {
+    match variant.as_option() {
+        Some(Type::Id(id)) => match &iface.types[*id].kind {
+            TypeDefKind::Variant(v) => !is_nullable_option(iface, v),
+            _ => true,
+        },
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Emit one of the instructions that only operate on Wasm values: constants,
+/// bitcasts, loads and stores.
+///
+/// `operands` are the locals holding the instruction's operands, and the
+/// locals holding its results are returned. Loads and stores access the
+/// interpreter's memory, `memory`, rather than the memory named by the
+/// instruction, which is the memory's index in the guest module.
+pub fn emit_wasm_instruction<'a, F>(
+    func: &mut F,
+    inst: &abi::Instruction<'_>,
+    operands: &[u32],
+    memory: u32,
+) -> Vec<u32>
+where
+    F: GlueFunction<'a> + ?Sized,
+{
+    let mem_arg = |offset: &i32| mem_arg(*offset as u32, memory);
+
+    match inst {
+        abi::Instruction::I32Const { val } => {
+            let local = func.new_local(ValType::I32);
+
+            // []
+            func.inst(Instruction::I32Const(*val));
+            // [i32]
+            func.inst(Instruction::LocalSet(local));
+            // []
+
+            vec![local]
+        }
+        abi::Instruction::Bitcasts { casts } => casts
+            .iter()
+            .zip(operands)
+            .map(|(cast, local)| {
+                let (ty, insts) = match bitcast(cast) {
+                    Some(b) => b,
+                    None => return *local,
+                };
+                let result = func.new_local(ty);
+
+                // []
+                func.inst(Instruction::LocalGet(*local));
+                // [T]
+                for inst in insts {
+                    func.inst(inst);
+                }
+                // [U]
+                func.inst(Instruction::LocalSet(result));
+                // []
+
+                result
+            })
+            .collect(),
+        abi::Instruction::ConstZero { tys } => tys
+            .iter()
+            .map(|ty| {
+                // Locals are not necessarily zero here, since the current
+                // block might be a loop body, so explicitly zero them.
+                let local = func.new_local(convert_ty(*ty));
+
+                // []
+                func.inst(match ty {
+                    WasmType::I32 => Instruction::I32Const(0),
+                    WasmType::I64 => Instruction::I64Const(0),
+                    WasmType::F32 => Instruction::F32Const(0.0),
+                    WasmType::F64 => Instruction::F64Const(0.0),
+                });
+                // [T]
+                func.inst(Instruction::LocalSet(local));
+                // []
+
+                local
+            })
+            .collect(),
+
+        abi::Instruction::I32Load { offset } => load(
+            func,
+            operands,
+            Instruction::I32Load(mem_arg(offset)),
+            ValType::I32,
+        ),
+        abi::Instruction::I32Load8U { offset } => load(
+            func,
+            operands,
+            Instruction::I32Load8_U(mem_arg(offset)),
+            ValType::I32,
+        ),
+        abi::Instruction::I32Load8S { offset } => load(
+            func,
+            operands,
+            Instruction::I32Load8_S(mem_arg(offset)),
+            ValType::I32,
+        ),
+        abi::Instruction::I32Load16U { offset } => load(
+            func,
+            operands,
+            Instruction::I32Load16_U(mem_arg(offset)),
+            ValType::I32,
+        ),
+        abi::Instruction::I32Load16S { offset } => load(
+            func,
+            operands,
+            Instruction::I32Load16_S(mem_arg(offset)),
+            ValType::I32,
+        ),
+        abi::Instruction::I64Load { offset } => load(
+            func,
+            operands,
+            Instruction::I64Load(mem_arg(offset)),
+            ValType::I64,
+        ),
+        abi::Instruction::F32Load { offset } => load(
+            func,
+            operands,
+            Instruction::F32Load(mem_arg(offset)),
+            ValType::F32,
+        ),
+        abi::Instruction::F64Load { offset } => load(
+            func,
+            operands,
+            Instruction::F64Load(mem_arg(offset)),
+            ValType::F64,
+        ),
+
+        abi::Instruction::I32Store { offset } => {
+            store(func, operands, Instruction::I32Store(mem_arg(offset)))
+        }
+        abi::Instruction::I32Store8 { offset } => {
+            store(func, operands, Instruction::I32Store8(mem_arg(offset)))
+        }
+        abi::Instruction::I32Store16 { offset } => {
+            store(func, operands, Instruction::I32Store16(mem_arg(offset)))
+        }
+        abi::Instruction::I64Store { offset } => {
+            store(func, operands, Instruction::I64Store(mem_arg(offset)))
+        }
+        abi::Instruction::F32Store { offset } => {
+            store(func, operands, Instruction::F32Store(mem_arg(offset)))
+        }
+        abi::Instruction::F64Store { offset } => {
+            store(func, operands, Instruction::F64Store(mem_arg(offset)))
+        }
+
+        _ => panic!("not a Wasm-only instruction: {:?}", inst),
+    }
+}
+
+/// Load a value of type `ty` from the address in `operands` into a new local.
+fn load<'a, F>(func: &mut F, operands: &[u32], load: Instruction<'a>, ty: ValType) -> Vec<u32>
+where
+    F: GlueFunction<'a> + ?Sized,
+{
+    let addr = operands[0];
+    let local = func.new_local(ty);
+
+    // []
+    func.inst(Instruction::LocalGet(addr));
+    // [i32]
+    func.inst(load);
+    // [ty]
+    func.inst(Instruction::LocalSet(local));
+    // []
+
+    vec![local]
+}
+
+/// Store the value in `operands` to the address in `operands`.
+fn store<'a, F>(func: &mut F, operands: &[u32], store: Instruction<'a>) -> Vec<u32>
+where
+    F: GlueFunction<'a> + ?Sized,
+{
+    let (val, addr) = (operands[0], operands[1]);
+
+    // []
+    func.inst(Instruction::LocalGet(addr));
+    // [i32]
+    func.inst(Instruction::LocalGet(val));
+    // [i32 T]
+    func.inst(store);
+    // []
+
+    vec![]
+}
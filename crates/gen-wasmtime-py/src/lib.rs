@@ -4,7 +4,10 @@ use std::mem;
 use wit_bindgen_gen_core::wit_parser::abi::{
     AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType, WitxInstruction,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator, Ns};
+use wit_bindgen_gen_core::{
+    abi_export_name, contains_named_type, wit_parser::*, AbiExport, AbiExportKind, Direction,
+    Files, Generator, Ns,
+};
 
 #[derive(Default)]
 pub struct WasmtimePy {
@@ -18,7 +21,9 @@ pub struct WasmtimePy {
     needs_store: bool,
     needs_load: bool,
     needs_validate_guest_char: bool,
+    needs_validate_flags: bool,
     needs_expected: bool,
+    needs_some: bool,
     needs_i32_to_f32: bool,
     needs_f32_to_i32: bool,
     needs_i64_to_f64: bool,
@@ -30,6 +35,7 @@ pub struct WasmtimePy {
     needs_push_buffer: bool,
     needs_pull_buffer: bool,
     needs_t_typevar: bool,
+    needs_trace_call: bool,
     pyimports: BTreeMap<String, Option<BTreeSet<String>>>,
 }
 
@@ -50,7 +56,9 @@ struct Import {
 struct Exports {
     freestanding_funcs: Vec<Source>,
     resource_funcs: BTreeMap<ResourceId, Vec<Source>>,
-    fields: BTreeMap<String, &'static str>,
+    // Maps the name of each field to the export it's initialized from and
+    // its type.
+    fields: BTreeMap<String, (String, &'static str)>,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -58,6 +66,23 @@ struct Exports {
 pub struct Opts {
     #[cfg_attr(feature = "structopt", structopt(long = "no-typescript"))]
     pub no_typescript: bool,
+
+    /// Whether or not to report each call to the hook set with
+    /// `set_trace_hook`, recording its parameters, results, duration and
+    /// errors. Durations are whole microseconds in `duration_us`.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub tracing: bool,
+
+    /// Names of types whose values are recorded as `<redacted>` rather than
+    /// with their contents when tracing, including within other types.
+    #[cfg_attr(feature = "structopt", structopt(long = "tracing-redact"))]
+    pub tracing_redact: Vec<String>,
+
+    /// Renames the exports used for the canonical ABI, which are `memory`,
+    /// `canonical_abi_realloc` and `canonical_abi_free` by default. Each entry
+    /// is `kind=name`, or `iface:kind=name` to rename it for one interface.
+    #[cfg_attr(feature = "structopt", structopt(long = "abi-export"))]
+    pub abi_exports: Vec<AbiExport>,
 }
 
 impl Opts {
@@ -108,11 +133,13 @@ impl WasmtimePy {
             );
         }
         if self.needs_store {
-            // TODO: this uses native endianness
+            // Note that `__ctype_le__` is the little-endian variant of `ty`,
+            // regardless of the host's byte order.
             self.pyimport("ctypes", None);
             self.src.push_str(
                 "
                     def _store(ty: Any, mem: wasmtime.Memory, store: wasmtime.Storelike, base: int, offset: int, val: Any) -> None:
+                        ty = ty.__ctype_le__
                         ptr = (base & 0xffffffff) + offset
                         if ptr + ctypes.sizeof(ty) > mem.data_len(store):
                             raise IndexError('out-of-bounds store')
@@ -125,11 +152,11 @@ impl WasmtimePy {
             );
         }
         if self.needs_load {
-            // TODO: this uses native endianness
             self.pyimport("ctypes", None);
             self.src.push_str(
                 "
                     def _load(ty: Any, mem: wasmtime.Memory, store: wasmtime.Storelike, base: int, offset: int) -> Any:
+                        ty = ty.__ctype_le__
                         ptr = (base & 0xffffffff) + offset
                         if ptr + ctypes.sizeof(ty) > mem.data_len(store):
                             raise IndexError('out-of-bounds store')
@@ -151,6 +178,16 @@ impl WasmtimePy {
                 ",
             );
         }
+        if self.needs_validate_flags {
+            self.src.push_str(
+                "
+                    def _validate_flags(flags: int, mask: int) -> int:
+                        if flags & ~mask != 0:
+                            raise TypeError('flags have extraneous bits set')
+                        return flags
+                ",
+            );
+        }
         if self.needs_expected {
             self.pyimport("dataclasses", "dataclass");
             self.pyimport("typing", "TypeVar");
@@ -171,6 +208,18 @@ impl WasmtimePy {
                 ",
             );
         }
+        if self.needs_some {
+            self.pyimport("dataclasses", "dataclass");
+            self.pyimport("typing", "Generic");
+            self.needs_t_typevar = true;
+            self.src.push_str(
+                "
+                    @dataclass
+                    class Some(Generic[T]):
+                        value: T
+                ",
+            );
+        }
         if self.needs_i32_to_f32 || self.needs_f32_to_i32 {
             self.pyimport("ctypes", None);
             self.src
@@ -182,8 +231,8 @@ impl WasmtimePy {
                 self.src.push_str(
                     "
                         def _i32_to_f32(i: int) -> float:
-                            _i32_to_f32_i32[0] = i     # type: ignore
-                            return _i32_to_f32_f32[0]  # type: ignore
+                            _i32_to_f32_i32[0] = i
+                            return float(_i32_to_f32_f32[0])
                     ",
                 );
             }
@@ -191,8 +240,8 @@ impl WasmtimePy {
                 self.src.push_str(
                     "
                         def _f32_to_i32(i: float) -> int:
-                            _i32_to_f32_f32[0] = i    # type: ignore
-                            return _i32_to_f32_i32[0] # type: ignore
+                            _i32_to_f32_f32[0] = i
+                            return int(_i32_to_f32_i32[0])
                     ",
                 );
             }
@@ -208,8 +257,8 @@ impl WasmtimePy {
                 self.src.push_str(
                     "
                         def _i64_to_f64(i: int) -> float:
-                            _i64_to_f64_i64[0] = i    # type: ignore
-                            return _i64_to_f64_f64[0] # type: ignore
+                            _i64_to_f64_i64[0] = i
+                            return float(_i64_to_f64_f64[0])
                     ",
                 );
             }
@@ -217,8 +266,8 @@ impl WasmtimePy {
                 self.src.push_str(
                     "
                         def _f64_to_i64(i: float) -> int:
-                            _i64_to_f64_f64[0] = i    # type: ignore
-                            return _i64_to_f64_i64[0] # type: ignore
+                            _i64_to_f64_f64[0] = i
+                            return int(_i64_to_f64_i64[0])
                     ",
                 );
             }
@@ -264,11 +313,10 @@ impl WasmtimePy {
         if self.needs_list_canon_lift {
             self.pyimport("ctypes", None);
             self.pyimport("typing", "List");
-            // TODO: this is doing a native-endian read, not a little-endian
-            // read
             self.src.push_str(
                 "
                     def _list_canon_lift(ptr: int, len: int, size: int, ty: Any, mem: wasmtime.Memory ,store: wasmtime.Storelike) -> Any:
+                        ty = ty.__ctype_le__
                         ptr = ptr & 0xffffffff
                         len = len & 0xffffffff
                         if ptr + len * size > mem.data_len(store):
@@ -289,11 +337,10 @@ impl WasmtimePy {
             self.pyimport("typing", "Tuple");
             // TODO: is there a faster way to memcpy other than iterating over
             // the input list?
-            // TODO: this is doing a native-endian write, not a little-endian
-            // write
             self.src.push_str(
                 "
                     def _list_canon_lower(list: Any, ty: Any, size: int, align: int, realloc: wasmtime.Func, mem: wasmtime.Memory, store: wasmtime.Storelike) -> Tuple[int, int]:
+                        ty = ty.__ctype_le__
                         total_size = size * len(list)
                         ptr = realloc(store, 0, 0, align, total_size)
                         assert(isinstance(ptr, int))
@@ -370,7 +417,7 @@ impl WasmtimePy {
             self.src.push_str(
                 "
                     class PushBuffer(Generic[T]):
-                        def __init__(self, ptr: int, len: int, size: int, write: Callable) -> None:
+                        def __init__(self, ptr: int, len: int, size: int, write: Callable[[T, int], None]) -> None:
                             self.ptr = ptr
                             self.len = len
                             self.size = size
@@ -398,7 +445,7 @@ impl WasmtimePy {
             self.src.push_str(
                 "
                     class PullBuffer(Generic[T]):
-                        def __init__(self, ptr: int, len: int, size: int, read: Callable) -> None:
+                        def __init__(self, ptr: int, len: int, size: int, read: Callable[[int], T]) -> None:
                             self.len = len
                             self.ptr = ptr
                             self.size = size
@@ -411,12 +458,109 @@ impl WasmtimePy {
                             if self.len == 0:
                                 return None
                             self.len -= 1
-                            ret: T = self.read(self.ptr)
+                            ret = self.read(self.ptr)
                             self.ptr += self.size
                             return ret
                 ",
             )
         }
+        if self.needs_trace_call {
+            self.pyimport("dataclasses", "dataclass");
+            self.pyimport("typing", "TypeVar");
+            self.pyimport("typing", "Callable");
+            self.pyimport("typing", "Dict");
+            self.pyimport("typing", "Optional");
+            self.pyimport("time", None);
+            self.needs_t_typevar = true;
+            self.src.push_str(
+                "
+                    @dataclass
+                    class TraceRecord:
+                        module: str
+                        function: str
+                        params: Dict[str, Any]
+                        results: Optional[Dict[str, Any]]
+                        error: Optional[BaseException]
+                        duration_us: int
+
+                    _TRACE_HOOK: Optional[Callable[[TraceRecord], None]] = None
+
+                    def set_trace_hook(hook: Optional[Callable[[TraceRecord], None]]) -> None:
+                        global _TRACE_HOOK
+                        _TRACE_HOOK = hook
+
+                    def _trace_call(module: str, func: str, params: Dict[str, Any], results: Callable[[T], Dict[str, Any]], call: Callable[[], T]) -> T:
+                        hook = _TRACE_HOOK
+                        if hook is None:
+                            return call()
+                        start = time.perf_counter()
+                        try:
+                            ret = call()
+                        except BaseException as e:
+                            hook(TraceRecord(module, func, params, None, e, _elapsed_us(start)))
+                            raise
+                        hook(TraceRecord(module, func, params, results(ret), None, _elapsed_us(start)))
+                        return ret
+
+                    def _elapsed_us(start: float) -> int:
+                        return round((time.perf_counter() - start) * 1_000_000)
+                ",
+            );
+        }
+    }
+
+    /// Returns the value recorded in traces for `value` of type `ty`.
+    fn trace_value(&self, iface: &Interface, ty: &Type, value: &str) -> String {
+        if contains_named_type(iface, ty, &self.opts.tracing_redact) {
+            "'<redacted>'".to_string()
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Returns the arguments to `_trace_call` for `func` recording `params`,
+    /// which are followed by the function performing the call.
+    fn trace_call(&mut self, iface: &Interface, func: &Function, params: &[String]) -> String {
+        self.needs_trace_call = true;
+        let params = func
+            .params
+            .iter()
+            .zip(params)
+            .map(|((name, ty), value)| {
+                format!(
+                    "'{}': {}",
+                    name.to_snake_case(),
+                    self.trace_value(iface, ty, value)
+                )
+            })
+            .collect::<Vec<_>>();
+        let results = func
+            .results
+            .iter()
+            .enumerate()
+            .map(|(i, (name, ty))| {
+                let key = if !name.is_empty() {
+                    name.to_snake_case()
+                } else if func.results.len() == 1 {
+                    "result".to_string()
+                } else {
+                    format!("result{}", i)
+                };
+                let value = if func.results.len() == 1 {
+                    "ret".to_string()
+                } else {
+                    format!("ret[{}]", i)
+                };
+                format!("'{}': {}", key, self.trace_value(iface, ty, &value))
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "'{}', '{}', {{{}}}, lambda ret: {{{}}}",
+            iface.name,
+            func.name,
+            params.join(", "),
+            results.join(", "),
+        )
     }
 
     fn type_string(&mut self, iface: &Interface, ty: &Type) -> String {
@@ -474,10 +618,7 @@ impl WasmtimePy {
                         if v.is_bool() {
                             self.src.push_str("bool");
                         } else if let Some(t) = v.as_option() {
-                            self.pyimport("typing", "Optional");
-                            self.src.push_str("Optional[");
-                            self.print_ty(iface, t);
-                            self.src.push_str("]");
+                            self.print_option(iface, v, t);
                         } else if let Some((ok, err)) = v.as_expected() {
                             self.needs_expected = true;
                             self.src.push_str("Expected[");
@@ -506,6 +647,45 @@ impl WasmtimePy {
         }
     }
 
+    /// Prints `option<T>` as `Optional[T]`, unless `T` can itself be `None`
+    /// in which case the payload is wrapped in `Some` to keep the two
+    /// distinguishable.
+    fn print_option(&mut self, iface: &Interface, variant: &Variant, ty: &Type) {
+        self.pyimport("typing", "Optional");
+        self.src.push_str("Optional[");
+        if self.is_nullable_option(iface, variant) {
+            self.print_ty(iface, ty);
+        } else {
+            self.needs_some = true;
+            self.src.push_str("Some[");
+            self.print_ty(iface, ty);
+            self.src.push_str("]");
+        }
+        self.src.push_str("]");
+    }
+
+    /// Returns whether `variant` is an option whose payload can be represented
+    /// directly as `Optional[T]`, which is the case unless `None` is already a
+    /// valid value of the payload.
+    fn is_nullable_option(&self, iface: &Interface, variant: &Variant) -> bool {
+        match variant.as_option() {
+            Some(ty) => !self.can_be_none(iface, ty),
+            None => false,
+        }
+    }
+
+    fn can_be_none(&self, iface: &Interface, ty: &Type) -> bool {
+        match ty {
+            Type::Id(id) => match &iface.types[*id].kind {
+                TypeDefKind::Type(t) => self.can_be_none(iface, t),
+                TypeDefKind::Variant(v) => v.as_option().is_some(),
+                TypeDefKind::Record(r) => r.is_tuple() && r.fields.is_empty(),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
     fn print_tuple<'a>(&mut self, iface: &Interface, types: impl IntoIterator<Item = &'a Type>) {
         let types = types.into_iter().collect::<Vec<_>>();
         if types.is_empty() {
@@ -635,17 +815,26 @@ impl WasmtimePy {
             self.print_ty(iface, ty);
         }
         self.src.push_str(") -> ");
+        self.print_results(iface, func);
+        params
+    }
+
+    fn print_results(&mut self, iface: &Interface, func: &Function) {
         match func.results.len() {
             0 => self.src.push_str("None"),
             1 => self.print_ty(iface, &func.results[0].1),
             _ => self.print_tuple(iface, func.results.iter().map(|p| &p.1)),
         }
-        params
     }
 }
 
 impl Generator for WasmtimePy {
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
+        assert!(
+            !iface.memory64,
+            "interface `{}` uses a 64-bit memory, which is not supported",
+            iface.name,
+        );
         let variant = Self::abi_variant(dir);
         self.sizes.fill(variant, iface);
         self.in_import = variant == AbiVariant::GuestImport;
@@ -664,15 +853,17 @@ impl Generator for WasmtimePy {
             self.src.push_str(&format!("{} = ", name.to_camel_case()));
             self.print_tuple(iface, record.fields.iter().map(|f| &f.ty));
         } else if record.is_flags() {
-            self.pyimport("enum", "Flag");
-            self.pyimport("enum", "auto");
+            self.pyimport("enum", "IntFlag");
             self.src
-                .push_str(&format!("class {}(Flag):\n", name.to_camel_case()));
+                .push_str(&format!("class {}(IntFlag):\n", name.to_camel_case()));
             self.indent();
-            for field in record.fields.iter() {
+            for (i, field) in record.fields.iter().enumerate() {
                 self.docs(&field.docs);
-                self.src
-                    .push_str(&format!("{} = auto()\n", field.name.to_shouty_snake_case()));
+                self.src.push_str(&format!(
+                    "{} = 1 << {}\n",
+                    field.name.to_shouty_snake_case(),
+                    i
+                ));
             }
             if record.fields.is_empty() {
                 self.src.push_str("pass\n");
@@ -731,11 +922,9 @@ impl Generator for WasmtimePy {
             }
             self.deindent();
         } else if let Some(t) = variant.as_option() {
-            self.pyimport("typing", "Optional");
-            self.src
-                .push_str(&format!("{} = Optional[", name.to_camel_case()));
-            self.print_ty(iface, t);
-            self.src.push_str("]\n");
+            self.src.push_str(&format!("{} = ", name.to_camel_case()));
+            self.print_option(iface, variant, t);
+            self.src.push_str("\n");
         } else if let Some((ok, err)) = variant.as_expected() {
             self.needs_expected = true;
             self.src
@@ -896,9 +1085,13 @@ impl Generator for WasmtimePy {
             ..
         } = f;
 
+        let memory =
+            abi_export_name(&self.opts.abi_exports, iface, AbiExportKind::Memory).to_string();
+        let realloc =
+            abi_export_name(&self.opts.abi_exports, iface, AbiExportKind::Realloc).to_string();
+        let free = abi_export_name(&self.opts.abi_exports, iface, AbiExportKind::Free).to_string();
         if needs_memory {
-            // TODO: hardcoding "memory"
-            self.src.push_str("m = caller[\"memory\"]\n");
+            self.src.push_str(&format!("m = caller[\"{}\"]\n", memory));
             self.src
                 .push_str("assert(isinstance(m, wasmtime.Memory))\n");
             self.pyimport("typing", "cast");
@@ -906,16 +1099,16 @@ impl Generator for WasmtimePy {
             locals.insert("memory").unwrap();
         }
 
-        if let Some(name) = needs_realloc {
+        if needs_realloc.is_some() {
             self.src
-                .push_str(&format!("realloc = caller[\"{}\"]\n", name));
+                .push_str(&format!("realloc = caller[\"{}\"]\n", realloc));
             self.src
                 .push_str("assert(isinstance(realloc, wasmtime.Func))\n");
             locals.insert("realloc").unwrap();
         }
 
-        if let Some(name) = needs_free {
-            self.src.push_str(&format!("free = caller[\"{}\"]\n", name));
+        if needs_free.is_some() {
+            self.src.push_str(&format!("free = caller[\"{}\"]\n", free));
             self.src
                 .push_str("assert(isinstance(free, wasmtime.Func))\n");
             locals.insert("free").unwrap();
@@ -974,6 +1167,17 @@ impl Generator for WasmtimePy {
         self.src.push_str(":\n");
         self.indent();
 
+        let trace = if self.opts.tracing {
+            let trace = self.trace_call(iface, func, &params);
+            self.src.push_str("def _call() -> ");
+            self.print_results(iface, func);
+            self.src.push_str(":\n");
+            self.indent();
+            Some(trace)
+        } else {
+            None
+        };
+
         let src_object = match &func.kind {
             FunctionKind::Freestanding => "self".to_string(),
             FunctionKind::Static { .. } => "obj".to_string(),
@@ -997,7 +1201,6 @@ impl Generator for WasmtimePy {
             ..
         } = f;
         if needs_memory {
-            // TODO: hardcoding "memory"
             self.src
                 .push_str(&format!("memory = {}._memory;\n", src_object));
         }
@@ -1018,24 +1221,39 @@ impl Generator for WasmtimePy {
             ));
         }
         self.src.push_str(&src);
+        if let Some(trace) = trace {
+            self.deindent();
+            self.src
+                .push_str(&format!("return _trace_call({}, _call)\n", trace));
+        }
         self.deindent();
 
+        let abi_exports = &self.opts.abi_exports;
+        let export = |kind| abi_export_name(abi_exports, iface, kind).to_string();
         let exports = self
             .guest_exports
             .entry(iface.name.to_string())
             .or_insert_with(Exports::default);
         if needs_memory {
-            exports
-                .fields
-                .insert("memory".to_string(), "wasmtime.Memory");
+            exports.fields.insert(
+                "memory".to_string(),
+                (export(AbiExportKind::Memory), "wasmtime.Memory"),
+            );
         }
         if let Some(name) = &needs_realloc {
-            exports.fields.insert(name.clone(), "wasmtime.Func");
+            exports.fields.insert(
+                name.clone(),
+                (export(AbiExportKind::Realloc), "wasmtime.Func"),
+            );
         }
         if let Some(name) = &needs_free {
-            exports.fields.insert(name.clone(), "wasmtime.Func");
+            exports
+                .fields
+                .insert(name.clone(), (export(AbiExportKind::Free), "wasmtime.Func"));
         }
-        exports.fields.insert(func.name.clone(), "wasmtime.Func");
+        exports
+            .fields
+            .insert(func.name.clone(), (func.name.clone(), "wasmtime.Func"));
 
         let func_body = mem::replace(&mut self.src, prev);
         let dst = match &func.kind {
@@ -1251,7 +1469,7 @@ impl Generator for WasmtimePy {
             self.indent();
 
             self.src.push_str("instance: wasmtime.Instance\n");
-            for (name, ty) in exports.fields.iter() {
+            for (name, (_, ty)) in exports.fields.iter() {
                 self.src
                     .push_str(&format!("_{}: {}\n", name.to_snake_case(), ty));
             }
@@ -1302,14 +1520,14 @@ impl Generator for WasmtimePy {
                 .push_str("self.instance = linker.instantiate(store, module)\n");
             self.src
                 .push_str("exports = self.instance.exports(store)\n");
-            for (name, ty) in exports.fields.iter() {
+            for (name, (export, ty)) in exports.fields.iter() {
                 self.src.push_str(&format!(
                     "
-                        {snake} = exports['{name}']
+                        {snake} = exports['{export}']
                         assert(isinstance({snake}, {ty}))
                         self._{snake} = {snake}
                     ",
-                    name = name,
+                    export = export,
                     snake = name.to_snake_case(),
                     ty = ty,
                 ));
@@ -1622,11 +1840,31 @@ impl Bindgen for FunctionBindgen<'_> {
                     results.push(format!("{}({})", name.to_camel_case(), operands.join(", ")));
                 }
             }
-            Instruction::FlagsLift { name, .. } | Instruction::FlagsLift64 { name, .. } => {
-                results.push(format!("{}({})", name.to_camel_case(), operands[0]));
+            // Flags coming from wasm are signed integers, so they're converted
+            // to their unsigned representation before validating that no
+            // unknown bits are set.
+            Instruction::FlagsLift { name, record, .. } => {
+                self.gen.needs_validate_flags = true;
+                let mask = (1u64 << record.fields.len()) - 1;
+                results.push(format!(
+                    "{}(_validate_flags({} & 0xffffffff, {}))",
+                    name.to_camel_case(),
+                    operands[0],
+                    mask,
+                ));
+            }
+            Instruction::FlagsLift64 { name, record, .. } => {
+                self.gen.needs_validate_flags = true;
+                let mask = (1u128 << record.fields.len()) - 1;
+                results.push(format!(
+                    "{}(_validate_flags({} & 0xffffffffffffffff, {}))",
+                    name.to_camel_case(),
+                    operands[0],
+                    mask,
+                ));
             }
             Instruction::FlagsLower { .. } | Instruction::FlagsLower64 { .. } => {
-                results.push(format!("({}).value", operands[0]));
+                results.push(format!("int({})", operands[0]));
             }
 
             Instruction::VariantPayloadName => {
@@ -1701,7 +1939,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     self.src.indent(2);
 
                     if case.ty.is_some() {
-                        if variant.as_option().is_some() {
+                        if self.gen.is_nullable_option(iface, variant) {
                             self.src
                                 .push_str(&format!("{} = {}\n", payload, operands[0]));
                         } else {
@@ -1781,10 +2019,15 @@ impl Bindgen for FunctionBindgen<'_> {
                         if case.ty.is_none() {
                             assert!(block_results.is_empty());
                             self.src.push_str(&format!("{} = None\n", result));
-                        } else {
+                        } else if self.gen.is_nullable_option(iface, variant) {
                             assert!(block_results.len() == 1);
                             self.src
                                 .push_str(&format!("{} = {}\n", result, block_results[0]));
+                        } else {
+                            assert!(block_results.len() == 1);
+                            self.gen.needs_some = true;
+                            self.src
+                                .push_str(&format!("{} = Some({})\n", result, block_results[0]));
                         }
                     } else {
                         self.src.push_str(&format!(
@@ -2010,12 +2253,15 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.src.push_str(&format!("{} = {}\n", ptr, operands[1]));
                 self.src.push_str(&format!("{} = {}\n", len, operands[2]));
                 let size = self.gen.sizes.size(ty);
+                let elem = self.gen.type_string(iface, ty);
                 if *push {
                     self.gen.needs_push_buffer = true;
                     assert!(block_results.is_empty());
                     let write = self.locals.tmp("write_val");
-                    self.src
-                        .push_str(&format!("def {}(e, {}): # type: ignore\n", write, base));
+                    self.src.push_str(&format!(
+                        "def {}(e: {}, {}: int) -> None:\n",
+                        write, elem, base
+                    ));
                     self.src.indent(2);
                     self.src.push_str(&block);
                     self.src.deindent(2);
@@ -2025,7 +2271,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     assert_eq!(block_results.len(), 1);
                     let read = self.locals.tmp("read_val");
                     self.src
-                        .push_str(&format!("def {}({}): # type: ignore\n", read, base));
+                        .push_str(&format!("def {}({}: int) -> {}:\n", read, base, elem));
                     self.src.indent(2);
                     self.src.push_str(&block);
                     self.src.push_str(&format!("return {}\n", block_results[0]));
@@ -2112,6 +2358,15 @@ impl Bindgen for FunctionBindgen<'_> {
                 }
             }
             Instruction::CallInterface { module: _, func } => {
+                // Parameters are recorded when tracing as well as passed to
+                // the call, so they're only evaluated once here.
+                if self.gen.opts.tracing {
+                    for op in operands.iter_mut() {
+                        let param = self.locals.tmp("param");
+                        self.src.push_str(&format!("{} = {}\n", param, op));
+                        *op = param;
+                    }
+                }
                 for i in 0..func.results.len() {
                     if i > 0 {
                         self.src.push_str(", ");
@@ -2123,6 +2378,11 @@ impl Bindgen for FunctionBindgen<'_> {
                 if func.results.len() > 0 {
                     self.src.push_str(" = ");
                 }
+                if self.gen.opts.tracing {
+                    let trace = self.gen.trace_call(iface, func, operands);
+                    self.src
+                        .push_str(&format!("_trace_call({}, lambda: ", trace));
+                }
                 match &func.kind {
                     FunctionKind::Freestanding | FunctionKind::Static { .. } => {
                         self.src.push_str(&format!(
@@ -2140,6 +2400,9 @@ impl Bindgen for FunctionBindgen<'_> {
                         ));
                     }
                 }
+                if self.gen.opts.tracing {
+                    self.src.push_str(")");
+                }
                 self.src.push_str("\n");
             }
 
@@ -14,6 +14,13 @@ fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
     fs::create_dir_all(&dir.join("imports")).unwrap();
     fs::create_dir_all(&dir.join("exports")).unwrap();
 
+    // The `tracing` test installs a trace hook and checks what it records.
+    let mut opts = wit_bindgen_gen_wasmtime_py::Opts::default();
+    if name.starts_with("tracing_") {
+        opts.tracing = true;
+        opts.tracing_redact = vec!["secret".to_string()];
+    }
+
     println!("OUT_DIR = {:?}", dir);
     println!("Generating bindings...");
     // We call `generate_all` with exports from the imports.wit file, and
@@ -21,9 +28,7 @@ fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
     // implementing the host side of these APIs.
     let iface = wit_bindgen_gen_core::wit_parser::Interface::parse_file(imports).unwrap();
     let mut files = Default::default();
-    wit_bindgen_gen_wasmtime_py::Opts::default()
-        .build()
-        .generate_all(&[], &[iface], &mut files);
+    opts.clone().build().generate_all(&[], &[iface], &mut files);
     for (file, contents) in files.iter() {
         fs::write(dir.join("imports").join(file), contents).unwrap();
     }
@@ -31,9 +36,7 @@ fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
 
     let iface = wit_bindgen_gen_core::wit_parser::Interface::parse_file(exports).unwrap();
     let mut files = Default::default();
-    wit_bindgen_gen_wasmtime_py::Opts::default()
-        .build()
-        .generate_all(&[iface], &[], &mut files);
+    opts.clone().build().generate_all(&[iface], &[], &mut files);
     for (file, contents) in files.iter() {
         fs::write(dir.join("exports").join(file), contents).unwrap();
     }
@@ -48,8 +51,11 @@ fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
             .arg(out_dir.join("mypycache").join(name)),
     );
 
+    // `WIT_BINDGEN_PYTHON` can point at a wrapper script, for example one that
+    // runs python under emulation for big-endian testing.
+    let python = env::var("WIT_BINDGEN_PYTHON").unwrap_or_else(|_| "python3".to_string());
     exec(
-        Command::new("python3")
+        Command::new(python)
             .env("PYTHONPATH", &dir)
             .arg(py)
             .arg(wasm),
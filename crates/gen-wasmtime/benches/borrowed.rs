@@ -0,0 +1,162 @@
+//! Benchmarks passing lists from wasm to host functions.
+//!
+//! The wasm module calls host functions taking a `list<u32>`, a `list<string>` and a `list<list<u32>>`.
+//! The generated bindings hand these to the host as views of linear memory, so the `borrowed` host reads
+//! them in place while the `owned` host first copies them into a `Vec<u32>`, `Vec<String>` and
+//! `Vec<Vec<u32>>`, which is what owned bindings would have to do.
+//!
+//! Run with `cargo bench -p wit-bindgen-gen-wasmtime`.
+
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, BenchmarkId, Criterion,
+    Throughput,
+};
+use wasmtime::{Engine, Linker, Module, Store};
+use wit_bindgen_wasmtime::Le;
+
+wit_bindgen_wasmtime::export!({
+    src["bench"]: "
+        sum: function(a: list<u32>) -> u32
+        total-len: function(a: list<string>) -> u32
+        nested-sum: function(a: list<list<u32>>) -> u32
+    ",
+});
+
+const LENGTHS: &[u32] = &[16, 1024, 16 * 1024];
+
+// The strings of the `list<string>` and the lists of the `list<list<u32>>` all point at these bytes,
+// which live after the list's `(ptr, len)` pairs.
+const ELEMENT: &[u8] = b"hello, world!!!!";
+
+const WAT: &str = r#"
+    (module
+        (import "bench" "sum" (func $sum (param i32 i32) (result i32)))
+        (import "bench" "total-len" (func $total_len (param i32 i32) (result i32)))
+        (import "bench" "nested-sum" (func $nested_sum (param i32 i32) (result i32)))
+        (memory (export "memory") 4)
+
+        (func (export "sum") (param $len i32) (result i32)
+            (call $sum (i32.const 0) (local.get $len)))
+
+        (func (export "total-len") (param $len i32) (result i32)
+            (call $total_len (i32.const 0) (local.get $len)))
+
+        (func (export "nested-sum") (param $len i32) (result i32)
+            (call $nested_sum (i32.const 0) (local.get $len)))
+    )
+"#;
+
+fn sum(a: impl Iterator<Item = u32>) -> u32 {
+    a.fold(0, u32::wrapping_add)
+}
+
+struct Borrowed;
+
+impl bench::Bench for Borrowed {
+    fn sum(&mut self, a: &[Le<u32>]) -> u32 {
+        sum(a.iter().map(|x| x.get()))
+    }
+
+    fn total_len(&mut self, a: Vec<&str>) -> u32 {
+        a.iter().map(|s| s.len() as u32).sum()
+    }
+
+    fn nested_sum(&mut self, a: Vec<&[Le<u32>]>) -> u32 {
+        sum(a.iter().flat_map(|a| a.iter().map(|x| x.get())))
+    }
+}
+
+struct Owned;
+
+impl bench::Bench for Owned {
+    fn sum(&mut self, a: &[Le<u32>]) -> u32 {
+        let a = a.iter().map(|x| x.get()).collect::<Vec<u32>>();
+        sum(a.into_iter())
+    }
+
+    fn total_len(&mut self, a: Vec<&str>) -> u32 {
+        let a = a.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        a.iter().map(|s| s.len() as u32).sum()
+    }
+
+    fn nested_sum(&mut self, a: Vec<&[Le<u32>]>) -> u32 {
+        let a = a
+            .iter()
+            .map(|a| a.iter().map(|x| x.get()).collect())
+            .collect::<Vec<Vec<u32>>>();
+        sum(a.into_iter().flatten())
+    }
+}
+
+/// Benchmarks calling `export` with a list of `len` elements, where `element_len` is the length of each
+/// element's own list, if the elements are lists.
+fn bench_host<T: bench::Bench>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    module: &Module,
+    name: &str,
+    host: T,
+    export: &str,
+    len: u32,
+    element_len: Option<u32>,
+) {
+    let mut linker = Linker::new(module.engine());
+    bench::add_to_linker(&mut linker, |s| s).unwrap();
+    let mut store = Store::new(module.engine(), host);
+    let instance = linker.instantiate(&mut store, module).unwrap();
+
+    if let Some(element_len) = element_len {
+        let mut data = Vec::new();
+        for _ in 0..len {
+            data.extend_from_slice(&(len * 8).to_le_bytes());
+            data.extend_from_slice(&element_len.to_le_bytes());
+        }
+        data.extend_from_slice(ELEMENT);
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        memory.write(&mut store, 0, &data).unwrap();
+    }
+
+    let run = instance
+        .get_typed_func::<u32, u32, _>(&mut store, export)
+        .unwrap();
+    group.bench_with_input(BenchmarkId::new(name, len), &len, |b, len| {
+        b.iter(|| run.call(&mut store, *len).unwrap())
+    });
+}
+
+fn bench_borrowed(c: &mut Criterion) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT).unwrap();
+
+    for (export, element_len) in [
+        ("sum", None),
+        ("total-len", Some(ELEMENT.len() as u32)),
+        ("nested-sum", Some(ELEMENT.len() as u32 / 4)),
+    ] {
+        let mut group = c.benchmark_group(export);
+        for len in LENGTHS {
+            group.throughput(Throughput::Elements(u64::from(*len)));
+            bench_host(
+                &mut group,
+                &module,
+                "borrowed",
+                Borrowed,
+                export,
+                *len,
+                element_len,
+            );
+            bench_host(
+                &mut group,
+                &module,
+                "owned",
+                Owned,
+                export,
+                *len,
+                element_len,
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_borrowed);
+criterion_main!(benches);
@@ -5,9 +5,12 @@ use std::mem;
 use std::process::{Command, Stdio};
 use std::str::FromStr;
 use wit_bindgen_gen_core::wit_parser::abi::{
-    Abi, AbiVariant, Bindgen, Instruction, LiftLower, WasmType, WitxInstruction,
+    Abi, AbiVariant, Bindgen, Instruction, LiftLower, ValuePath, WasmType, WitxInstruction,
+};
+use wit_bindgen_gen_core::{
+    abi_export_name, contains_named_type, wit_parser::*, AbiExport, AbiExportKind, Direction,
+    Files, Generator, Source, TypeInfo, Types,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator, Source, TypeInfo, Types};
 use wit_bindgen_gen_rust::{
     int_repr, to_rust_ident, wasm_type, FnSig, RustFunctionGenerator, RustGenerator, TypeMode,
 };
@@ -22,7 +25,7 @@ pub struct Wasmtime {
     needs_invalid_variant: bool,
     needs_validate_flags: bool,
     needs_raw_mem: bool,
-    needs_bad_int: bool,
+    needs_int_from_i32: bool,
     needs_copy_slice: bool,
     needs_buffer_glue: bool,
     needs_le: bool,
@@ -66,10 +69,17 @@ pub struct Opts {
     #[cfg_attr(feature = "structopt", structopt(long))]
     pub rustfmt: bool,
 
-    /// Whether or not to emit `tracing` macro calls on function entry/exit.
+    /// Whether or not to emit `tracing` spans and events for each call,
+    /// recording its parameters, results, duration and errors. Durations are
+    /// whole microseconds in the `duration_us` field.
     #[cfg_attr(feature = "structopt", structopt(long))]
     pub tracing: bool,
 
+    /// Names of types whose values are recorded as `<redacted>` rather than
+    /// with their contents when tracing, including within other types.
+    #[cfg_attr(feature = "structopt", structopt(long = "tracing-redact"))]
+    pub tracing_redact: Vec<String>,
+
     /// Indicates which functions should be `async`: `all`, `none`, or a
     /// comma-separated list.
     #[cfg_attr(
@@ -82,6 +92,47 @@ pub struct Opts {
     /// custom trait-defined error. Applicable for import bindings.
     #[cfg_attr(feature = "structopt", structopt(long))]
     pub custom_error: bool,
+
+    /// Only generate the types and host traits of the interfaces, without any
+    /// of the glue which depends on `wasmtime`.
+    ///
+    /// The generated code only needs `wit-bindgen-wasmtime` with its
+    /// `wasmtime` feature disabled, unless an interface uses buffers. The glue
+    /// is generated separately with `trait_module`.
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long, conflicts_with = "trait-module")
+    )]
+    pub trait_only: bool,
+
+    /// Path to the module containing code previously generated with
+    /// `trait_only`, such as `crate::host`.
+    ///
+    /// Only the `wasmtime` glue is generated, and the types and traits are
+    /// re-exported from that module instead. With `custom_error` the methods
+    /// converting errors to traps move to a separate `{Name}Errors` trait.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub trait_module: Option<String>,
+
+    /// Whether exports enforce the `wit_bindgen_wasmtime::Limits` configured
+    /// with their `set_limits` method on each call. Applicable for export
+    /// bindings.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub limits: bool,
+
+    /// Whether bindings reach into the store's data through generated traits
+    /// implemented by it, rather than through closures: exports access their
+    /// auxiliary data through `Has{Name}Data` instead of `get_state`, and
+    /// imports access their host implementation through `Has{Name}` instead
+    /// of `get`.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub has_data: bool,
+
+    /// Renames the exports used for the canonical ABI, which are `memory`,
+    /// `canonical_abi_realloc` and `canonical_abi_free` by default. Each entry
+    /// is `kind=name`, or `iface:kind=name` to rename it for one interface.
+    #[cfg_attr(feature = "structopt", structopt(long = "abi-export"))]
+    pub abi_exports: Vec<AbiExport>,
 }
 
 #[derive(Debug, Clone)]
@@ -129,6 +180,10 @@ impl FromStr for Async {
 
 impl Opts {
     pub fn build(self) -> Wasmtime {
+        assert!(
+            !self.trait_only || self.trait_module.is_none(),
+            "`trait_only` and `trait_module` cannot be used together"
+        );
         let mut r = Wasmtime::new();
         r.opts = self;
         r
@@ -168,7 +223,156 @@ impl Wasmtime {
         }
     }
 
+    /// Whether the types and traits of interfaces are generated, rather than
+    /// re-exported from a module generated with `trait_only`.
+    fn defines_types(&self) -> bool {
+        self.opts.trait_module.is_none()
+    }
+
+    /// Returns the expression accessing the host implementation of the
+    /// `name` imports from the store data `data`, either with the `get`
+    /// closure or through the `Has{Name}` trait.
+    fn import_state(&self, name: &str, data: &str) -> String {
+        if self.opts.has_data {
+            format!("{}.{}()", data, name.to_snake_case())
+        } else {
+            format!("get({})", data)
+        }
+    }
+
+    /// Returns the expression accessing the auxiliary data of the `name`
+    /// exports from the store data `data`, either with the `get_state` closure
+    /// or through the `Has{Name}Data` trait.
+    fn export_state(&self, name: &str, get_state: &str, data: &str) -> String {
+        if self.opts.has_data {
+            format!("{}.{}_data()", data, name.to_snake_case())
+        } else {
+            format!("{}({})", get_state, data)
+        }
+    }
+
+    /// Returns the `tracing` field recording `value` of type `ty`.
+    fn trace_field(&self, iface: &Interface, name: &str, ty: &Type, value: &str) -> String {
+        if contains_named_type(iface, ty, &self.opts.tracing_redact) {
+            format!("{} = \"<redacted>\",\n", name)
+        } else {
+            format!(
+                "{} = wit_bindgen_wasmtime::tracing::field::debug({}),\n",
+                name, value
+            )
+        }
+    }
+
+    /// Starts the span tracing a call of `func`, returning where its body
+    /// begins in the source for `finish_trace`.
+    fn start_trace(&mut self, iface: &Interface, func: &Function, is_async: bool) -> usize {
+        self.src.push_str(&format!(
+            "
+                let span = wit_bindgen_wasmtime::tracing::span!(
+                    wit_bindgen_wasmtime::tracing::Level::TRACE,
+                    \"wit-bindgen abi\",
+                    module = \"{}\",
+                    function = \"{}\",
+                );
+            ",
+            iface.name, func.name,
+        ));
+        if !is_async {
+            self.src.push_str("let _enter = span.enter();\n");
+        }
+        self.src.len()
+    }
+
+    /// Wraps the body of the call emitted since `start` to record its
+    /// duration and outcome, with `results` being the fields recorded from the
+    /// successful result matching `pattern`.
+    fn finish_trace(src: &mut Source, start: usize, is_async: bool, pattern: &str, results: &str) {
+        let body = src[start..].to_string();
+        src.as_mut_string().truncate(start);
+        src.push_str("let trace_start = std::time::Instant::now();\n");
+        if is_async {
+            src.push_str(&format!(
+                "let result: Result<_, wasmtime::Trap> = wit_bindgen_wasmtime::tracing::Instrument::instrument(async {{
+                    {}
+                }}, span.clone()).await;\n",
+                body
+            ));
+        } else {
+            src.push_str(&format!(
+                "let result: Result<_, wasmtime::Trap> = (|| {{
+                    {}
+                }})();\n",
+                body
+            ));
+        }
+        src.push_str(&format!(
+            "
+                let duration_us = trace_start.elapsed().as_micros() as u64;
+                match &result {{
+                    Ok({}) => wit_bindgen_wasmtime::tracing::event!(
+                        wit_bindgen_wasmtime::tracing::Level::TRACE,
+                        duration_us = duration_us,
+                        {}
+                    ),
+                    Err(e) => wit_bindgen_wasmtime::tracing::event!(
+                        wit_bindgen_wasmtime::tracing::Level::TRACE,
+                        duration_us = duration_us,
+                        error = %e,
+                    ),
+                }}
+                result
+            ",
+            pattern, results
+        ));
+    }
+
+    /// Returns the pattern matching the results of `func` and the `tracing`
+    /// fields recording them.
+    fn trace_results(&self, iface: &Interface, func: &Function) -> (String, String) {
+        let mut fields = String::new();
+        let mut names = Vec::new();
+        for (i, (name, ty)) in func.results.iter().enumerate() {
+            let var = if func.results.len() == 1 {
+                "ret".to_string()
+            } else {
+                format!("ret{}", i)
+            };
+            let name = trace_result_name(func, i, name);
+            fields.push_str(&self.trace_field(iface, &format!("results.{}", name), ty, &var));
+            names.push(var);
+        }
+        let pattern = match names.len() {
+            0 => "_".to_string(),
+            1 => names.pop().unwrap(),
+            _ => format!("({})", names.join(", ")),
+        };
+        (pattern, fields)
+    }
+
+    /// Returns the trait methods converting custom errors to traps or to the
+    /// error types of functions.
+    fn error_conversions(&self) -> String {
+        let mut src = String::new();
+        if self.needs_custom_error_to_trap {
+            src.push_str("fn error_to_trap(&mut self, err: Self::Error) -> wasmtime::Trap;\n");
+        }
+        for ty in self.needs_custom_error_to_types.iter() {
+            src.push_str(&format!(
+                "fn error_to_{}(&mut self, err: Self::Error) -> Result<{}, wasmtime::Trap>;\n",
+                ty.to_snake_case(),
+                ty.to_camel_case(),
+            ));
+        }
+        src
+    }
+
     fn print_intrinsics(&mut self) {
+        if self.needs_le && self.defines_types() {
+            self.push_str("use wit_bindgen_wasmtime::Le;\n");
+        }
+        if self.opts.trait_only {
+            return;
+        }
         if self.needs_raw_mem {
             self.push_str("use wit_bindgen_wasmtime::rt::RawMem;\n");
         }
@@ -178,16 +382,12 @@ impl Wasmtime {
         if self.needs_invalid_variant {
             self.push_str("use wit_bindgen_wasmtime::rt::invalid_variant;\n");
         }
-        if self.needs_bad_int {
-            self.push_str("use core::convert::TryFrom;\n");
-            self.push_str("use wit_bindgen_wasmtime::rt::bad_int;\n");
+        if self.needs_int_from_i32 {
+            self.push_str("use wit_bindgen_wasmtime::rt::int_from_i32;\n");
         }
         if self.needs_validate_flags {
             self.push_str("use wit_bindgen_wasmtime::rt::validate_flags;\n");
         }
-        if self.needs_le {
-            self.push_str("use wit_bindgen_wasmtime::Le;\n");
-        }
         if self.needs_copy_slice {
             self.push_str("use wit_bindgen_wasmtime::rt::copy_slice;\n");
         }
@@ -380,8 +580,18 @@ impl Generator for Wasmtime {
         self.trait_name = iface.name.to_camel_case();
         self.src
             .push_str(&format!("pub mod {} {{\n", iface.name.to_snake_case()));
-        self.src
-            .push_str("#[allow(unused_imports)]\nuse wit_bindgen_wasmtime::{wasmtime, anyhow};\n");
+        if !self.opts.trait_only {
+            self.src.push_str(
+                "#[allow(unused_imports)]\nuse wit_bindgen_wasmtime::{wasmtime, anyhow};\n",
+            );
+        }
+        if let Some(path) = &self.opts.trait_module {
+            self.src.push_str(&format!(
+                "pub use {}::{}::*;\n",
+                path,
+                iface.name.to_snake_case()
+            ));
+        }
         self.sizes.fill(variant, iface);
     }
 
@@ -393,6 +603,9 @@ impl Generator for Wasmtime {
         record: &Record,
         docs: &Docs,
     ) {
+        if !self.defines_types() {
+            return;
+        }
         if record.is_flags() {
             self.src
                 .push_str("wit_bindgen_wasmtime::bitflags::bitflags! {\n");
@@ -491,6 +704,9 @@ impl Generator for Wasmtime {
         variant: &Variant,
         docs: &Docs,
     ) {
+        if !self.defines_types() {
+            return;
+        }
         self.print_typedef_variant(iface, id, name, variant, docs);
     }
 
@@ -505,23 +721,38 @@ impl Generator for Wasmtime {
         }
 
         self.exported_resources.insert(ty);
+        if !self.defines_types() {
+            return;
+        }
 
         // ... otherwise for exports we generate a newtype wrapper around an
-        // `i32` to manage the resultt.
+        // `i32` to manage the resultt. The glue generated with `trait_module`
+        // lives in a different module, so it needs access to the index too.
         let tyname = name.to_camel_case();
         self.rustdoc(&iface.resources[ty].docs);
         self.src.push_str("#[derive(Debug)]\n");
+        let vis = if self.opts.trait_only {
+            "#[doc(hidden)] pub "
+        } else {
+            ""
+        };
         self.src.push_str(&format!(
-            "pub struct {}(wit_bindgen_wasmtime::rt::ResourceIndex);\n",
-            tyname
+            "pub struct {}({}wit_bindgen_wasmtime::rt::ResourceIndex);\n",
+            tyname, vis
         ));
     }
 
     fn type_alias(&mut self, iface: &Interface, id: TypeId, _name: &str, ty: &Type, docs: &Docs) {
+        if !self.defines_types() {
+            return;
+        }
         self.print_typedef_alias(iface, id, ty, docs);
     }
 
     fn type_list(&mut self, iface: &Interface, id: TypeId, _name: &str, ty: &Type, docs: &Docs) {
+        if !self.defines_types() {
+            return;
+        }
         self.print_type_list(iface, id, ty, docs);
     }
 
@@ -534,6 +765,9 @@ impl Generator for Wasmtime {
         ty: &Type,
         docs: &Docs,
     ) {
+        if !self.defines_types() {
+            return;
+        }
         self.rustdoc(docs);
         let mutbl = if const_ { "const" } else { "mut" };
         self.src
@@ -543,6 +777,9 @@ impl Generator for Wasmtime {
     }
 
     fn type_builtin(&mut self, iface: &Interface, _id: TypeId, name: &str, ty: &Type, docs: &Docs) {
+        if !self.defines_types() {
+            return;
+        }
         self.rustdoc(docs);
         self.src
             .push_str(&format!("pub type {}", name.to_camel_case()));
@@ -559,6 +796,9 @@ impl Generator for Wasmtime {
         ty: &Type,
         docs: &Docs,
     ) {
+        if !self.defines_types() {
+            return;
+        }
         self.print_typedef_buffer(iface, id, true, ty, docs);
     }
 
@@ -570,6 +810,9 @@ impl Generator for Wasmtime {
         ty: &Type,
         docs: &Docs,
     ) {
+        if !self.defines_types() {
+            return;
+        }
         self.print_typedef_buffer(iface, id, false, ty, docs);
     }
 
@@ -600,7 +843,7 @@ impl Generator for Wasmtime {
         let params = (0..sig.params.len())
             .map(|i| format!("arg{}", i))
             .collect::<Vec<_>>();
-        let mut f = FunctionBindgen::new(self, is_dtor, params);
+        let mut f = FunctionBindgen::new(self, &func.name, is_dtor, params);
         f.func_takes_all_memory = func.abi == Abi::Preview1
             && func
                 .params
@@ -701,48 +944,46 @@ impl Generator for Wasmtime {
             false
         };
 
-        if self.opts.tracing {
-            self.src.push_str(&format!(
-                "
-                    let span = wit_bindgen_wasmtime::tracing::span!(
-                        wit_bindgen_wasmtime::tracing::Level::TRACE,
-                        \"wit-bindgen abi\",
-                        module = \"{}\",
-                        function = \"{}\",
-                    );
-                    let _enter = span.enter();
-                ",
-                iface.name, func.name,
-            ));
-        }
+        let trace_start = if self.opts.tracing {
+            Some(self.start_trace(iface, func, is_async))
+        } else {
+            None
+        };
         self.src.push_str(&closures);
 
         for (name, func) in needs_functions {
             self.src.push_str(&format!(
                 "
-                    let func = get_func(&mut caller, \"{name}\")?;
+                    let func = get_func(&mut caller, \"{export}\")?;
                     let func_{name} = func.typed::<{cvt}, _>(&caller)?;
                 ",
                 name = name,
-                cvt = func.cvt(),
+                export = abi_export_name(&self.opts.abi_exports, iface, func.kind()),
+                cvt = func.cvt(iface),
             ));
             self.needs_get_func = true;
         }
 
         if needs_memory || needs_borrow_checker {
-            self.src
-                .push_str("let memory = &get_memory(&mut caller, \"memory\")?;\n");
+            self.src.push_str(&format!(
+                "let memory = &get_memory(&mut caller, \"{}\")?;\n",
+                abi_export_name(&self.opts.abi_exports, iface, AbiExportKind::Memory),
+            ));
             self.needs_get_memory = true;
         }
 
         if needs_borrow_checker {
-            self.src.push_str(
+            self.src.push_str(&format!(
                 "let (mem, data) = memory.data_and_store_mut(&mut caller);
                 let mut _bc = wit_bindgen_wasmtime::BorrowChecker::new(mem);
-                let host = get(data);\n",
-            );
+                let host = {};\n",
+                self.import_state(&self.trait_name, "data"),
+            ));
         } else {
-            self.src.push_str("let host = get(caller.data_mut());\n");
+            self.src.push_str(&format!(
+                "let host = {};\n",
+                self.import_state(&self.trait_name, "caller.data_mut()"),
+            ));
         }
 
         if self.all_needed_handles.len() > 0 {
@@ -751,6 +992,12 @@ impl Generator for Wasmtime {
 
         self.src.push_str(&String::from(src));
 
+        // Parameters and results are recorded around the call to the host,
+        // so only the outcome is recorded here.
+        if let Some(start) = trace_start {
+            Self::finish_trace(&mut self.src, start, is_async, "_", "");
+        }
+
         if is_async {
             self.src.push_str("})\n");
         }
@@ -788,6 +1035,33 @@ impl Generator for Wasmtime {
         self.print_results(iface, func);
         self.push_str(", wasmtime::Trap> {\n");
 
+        let trace_start = if self.opts.tracing {
+            let start = self.start_trace(iface, func, is_async);
+            let mut params = String::new();
+            for (name, ty) in func.params.iter() {
+                let name = to_rust_ident(name);
+                params.push_str(&self.trace_field(
+                    iface,
+                    &format!("params.{}", name),
+                    ty,
+                    &format!("&{}", name),
+                ));
+            }
+            if !params.is_empty() {
+                self.src.push_str(&format!(
+                    "wit_bindgen_wasmtime::tracing::event!(
+                        wit_bindgen_wasmtime::tracing::Level::TRACE,
+                        {}
+                    );\n",
+                    params
+                ));
+            }
+            let (pattern, results) = self.trace_results(iface, func);
+            Some((start, pattern, results))
+        } else {
+            None
+        };
+
         let is_dtor = self.types.is_preview1_dtor_func(func);
         if is_dtor {
             assert_eq!(func.results.len(), 0, "destructors cannot have results");
@@ -797,7 +1071,7 @@ impl Generator for Wasmtime {
             .iter()
             .map(|(name, _)| to_rust_ident(name).to_string())
             .collect();
-        let mut f = FunctionBindgen::new(self, is_dtor, params);
+        let mut f = FunctionBindgen::new(self, &func.name, is_dtor, params);
         iface.call(
             AbiVariant::GuestExport,
             LiftLower::LowerArgsLiftResults,
@@ -818,15 +1092,24 @@ impl Generator for Wasmtime {
             .guest_exports
             .entry(iface.name.to_string())
             .or_insert_with(Exports::default);
+        if self.opts.limits {
+            exports.fields.insert(
+                "limits".to_string(),
+                (
+                    "wit_bindgen_wasmtime::Limits".to_string(),
+                    "wit_bindgen_wasmtime::Limits::default()".to_string(),
+                ),
+            );
+        }
         for (name, func) in needs_functions {
             self.src
                 .push_str(&format!("let func_{0} = &self.{0};\n", name));
             let get = format!(
                 "instance.get_typed_func::<{}, _>(&mut store, \"{}\")?",
-                func.cvt(),
-                name
+                func.cvt(iface),
+                abi_export_name(&self.opts.abi_exports, iface, func.kind()),
             );
-            exports.fields.insert(name, (func.ty(), get));
+            exports.fields.insert(name, (func.ty(iface), get));
         }
 
         self.src.push_str(&closures);
@@ -838,13 +1121,15 @@ impl Generator for Wasmtime {
                 "memory".to_string(),
                 (
                     "wasmtime::Memory".to_string(),
-                    "instance
-                        .get_memory(&mut store, \"memory\")
-                         .ok_or_else(|| {
-                             anyhow::anyhow!(\"`memory` export not a memory\")
-                         })?
-                    "
-                    .to_string(),
+                    format!(
+                        "instance
+                            .get_memory(&mut store, \"{0}\")
+                            .ok_or_else(|| {{
+                                anyhow::anyhow!(\"`{0}` export not a memory\")
+                            }})?
+                        ",
+                        abi_export_name(&self.opts.abi_exports, iface, AbiExportKind::Memory),
+                    ),
                 ),
             );
         }
@@ -855,7 +1140,14 @@ impl Generator for Wasmtime {
                 .push_str("let mut buffer_transaction = self.buffer_glue.transaction();\n");
         }
 
+        if self.opts.limits {
+            self.src.push_str("self.limits.begin_call(&mut caller)?;\n");
+        }
+
         self.src.push_str(&String::from(src));
+        if let Some((start, pattern, results)) = trace_start {
+            Self::finish_trace(&mut self.src, start, is_async, &pattern, &results);
+        }
         self.src.push_str("}\n");
         let func_body = mem::replace(&mut self.src, prev);
         if !is_dtor {
@@ -890,59 +1182,63 @@ impl Generator for Wasmtime {
     }
 
     fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
+        let error_conversions = self.error_conversions();
         for (module, funcs) in sorted_iter(&self.guest_imports) {
             let module_camel = module.to_camel_case();
             let is_async = !self.opts.async_.is_none();
-            if is_async {
-                self.src.push_str("#[wit_bindgen_wasmtime::async_trait]\n");
-            }
-            self.src.push_str("pub trait ");
-            self.src.push_str(&module_camel);
-            self.src.push_str(": Sized ");
-            if is_async {
-                self.src.push_str(" + Send");
-            }
-            self.src.push_str("{\n");
-            if self.all_needed_handles.len() > 0 {
-                for handle in self.all_needed_handles.iter() {
-                    self.src.push_str("type ");
-                    self.src.push_str(&handle.to_camel_case());
-                    self.src.push_str(": std::fmt::Debug");
-                    if is_async {
-                        self.src.push_str(" + Send + Sync");
+            if self.defines_types() {
+                if is_async {
+                    self.src.push_str("#[wit_bindgen_wasmtime::async_trait]\n");
+                }
+                self.src.push_str("pub trait ");
+                self.src.push_str(&module_camel);
+                self.src.push_str(": Sized ");
+                if is_async {
+                    self.src.push_str(" + Send");
+                }
+                self.src.push_str("{\n");
+                if self.all_needed_handles.len() > 0 {
+                    for handle in self.all_needed_handles.iter() {
+                        self.src.push_str("type ");
+                        self.src.push_str(&handle.to_camel_case());
+                        self.src.push_str(": std::fmt::Debug");
+                        if is_async {
+                            self.src.push_str(" + Send + Sync");
+                        }
+                        self.src.push_str(";\n");
                     }
-                    self.src.push_str(";\n");
                 }
-            }
-            if self.opts.custom_error {
-                self.src.push_str("type Error;\n");
-                if self.needs_custom_error_to_trap {
-                    self.src.push_str(
-                        "fn error_to_trap(&mut self, err: Self::Error) -> wasmtime::Trap;\n",
-                    );
+                if self.opts.custom_error {
+                    self.src.push_str("type Error;\n");
+                    // The conversions to traps depend on `wasmtime`, so with
+                    // `trait_only` they're generated with the glue instead.
+                    if !self.opts.trait_only {
+                        self.src.push_str(&error_conversions);
+                    }
+                }
+                for f in funcs {
+                    self.src.push_str(&f.trait_signature);
+                    self.src.push_str(";\n\n");
                 }
-                for ty in self.needs_custom_error_to_types.iter() {
+                for handle in self.all_needed_handles.iter() {
                     self.src.push_str(&format!(
-                        "fn error_to_{}(&mut self, err: Self::Error) -> Result<{}, wasmtime::Trap>;\n",
-                        ty.to_snake_case(),
-                        ty.to_camel_case(),
+                        "fn drop_{}(&mut self, state: Self::{}) {{
+                            drop(state);
+                        }}\n",
+                        handle.to_snake_case(),
+                        handle.to_camel_case(),
                     ));
                 }
-            }
-            for f in funcs {
-                self.src.push_str(&f.trait_signature);
-                self.src.push_str(";\n\n");
-            }
-            for handle in self.all_needed_handles.iter() {
+                self.src.push_str("}\n");
+            } else if self.opts.custom_error {
                 self.src.push_str(&format!(
-                    "fn drop_{}(&mut self, state: Self::{}) {{
-                        drop(state);
-                    }}\n",
-                    handle.to_snake_case(),
-                    handle.to_camel_case(),
+                    "pub trait {0}Errors: {0} {{\n{1}}}\n",
+                    module_camel, error_conversions,
                 ));
             }
-            self.src.push_str("}\n");
+            if self.opts.trait_only {
+                continue;
+            }
 
             if self.all_needed_handles.len() > 0 {
                 self.src.push_str("\npub struct ");
@@ -972,19 +1268,56 @@ impl Generator for Wasmtime {
             }
         }
 
+        // Only the types and the host trait are generated with `trait_only`,
+        // everything else needs `wasmtime`.
+        if self.opts.trait_only {
+            self.guest_imports.clear();
+            self.guest_exports.clear();
+        }
+
         for (module, funcs) in mem::take(&mut self.guest_imports) {
             let module_camel = module.to_camel_case();
             let is_async = !self.opts.async_.is_none();
-            self.push_str("\npub fn add_to_linker<T, U>(linker: &mut wasmtime::Linker<T>");
-            self.push_str(", get: impl Fn(&mut T) -> ");
-            if self.all_needed_handles.is_empty() {
-                self.push_str("&mut U");
+            let mut host_bound = module_camel.clone();
+            if !self.defines_types() && self.opts.custom_error {
+                host_bound.push_str(&format!(" + {}Errors", module_camel));
+            }
+            if self.opts.has_data {
+                let host = if self.all_needed_handles.is_empty() {
+                    "&mut Self::Host".to_string()
+                } else {
+                    format!("(&mut Self::Host, &mut {}Tables<Self::Host>)", module_camel)
+                };
+                self.push_str(&format!(
+                    "
+                        /// Implemented by the data of a `Store<T>` to provide
+                        /// the host implementation of the wasm imports.
+                        pub trait Has{0} {{
+                            type Host: {1};
+
+                            fn {2}(&mut self) -> {3};
+                        }}
+                    ",
+                    module_camel,
+                    host_bound,
+                    module.to_snake_case(),
+                    host,
+                ));
+                self.push_str("\npub fn add_to_linker<T>(linker: &mut wasmtime::Linker<T>)");
+                self.push_str(" -> anyhow::Result<()> \n");
+                self.push_str(&format!("where T: Has{}", module_camel));
             } else {
-                self.push_str(&format!("(&mut U, &mut {}Tables<U>)", module_camel));
+                self.push_str("\npub fn add_to_linker<T, U>(linker: &mut wasmtime::Linker<T>");
+                self.push_str(", get: impl Fn(&mut T) -> ");
+                if self.all_needed_handles.is_empty() {
+                    self.push_str("&mut U");
+                } else {
+                    self.push_str(&format!("(&mut U, &mut {}Tables<U>)", module_camel));
+                }
+                self.push_str("+ Send + Sync + Copy + 'static) -> anyhow::Result<()> \n");
+                self.push_str("where U: ");
+                self.push_str(&host_bound);
             }
-            self.push_str("+ Send + Sync + Copy + 'static) -> anyhow::Result<()> \n");
-            self.push_str("where U: ");
-            self.push_str(&module_camel);
             if is_async {
                 self.push_str(", T: Send,");
             }
@@ -1013,7 +1346,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_drop_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, handle: u32| {{
-                                let (host, tables) = get(caller.data_mut());
+                                let (host, tables) = {state};
                                 let handle = tables
                                     .{snake}_table
                                     .remove(handle)
@@ -1026,6 +1359,7 @@ impl Generator for Wasmtime {
                         )?;\n",
                         name = handle,
                         snake = handle.to_snake_case(),
+                        state = self.import_state(&module, "caller.data_mut()"),
                     ));
                 }
             }
@@ -1063,13 +1397,31 @@ impl Generator for Wasmtime {
             }
             self.push_str("}\n");
 
+            if self.opts.has_data {
+                self.push_str(&format!(
+                    "
+                        /// Implemented by the data of a `Store<T>` to provide
+                        /// the auxiliary data of the wasm exports.
+                        pub trait Has{0}Data {{
+                            fn {1}_data(&mut self) -> &mut {0}Data;
+                        }}
+                    ",
+                    name,
+                    name.to_snake_case(),
+                ));
+            }
+
             self.push_str("pub struct ");
             self.push_str(&name);
             self.push_str("<T> {\n");
-            self.push_str(&format!(
-                "get_state: Box<dyn Fn(&mut T) -> &mut {}Data + Send + Sync>,\n",
-                name
-            ));
+            if self.opts.has_data {
+                self.push_str("_data: std::marker::PhantomData<fn() -> T>,\n");
+            } else {
+                self.push_str(&format!(
+                    "get_state: Box<dyn Fn(&mut T) -> &mut {}Data + Send + Sync>,\n",
+                    name
+                ));
+            }
             for (name, (ty, _)) in exports.fields.iter() {
                 self.push_str(name);
                 self.push_str(": ");
@@ -1080,13 +1432,39 @@ impl Generator for Wasmtime {
             //     self.push_str("buffer_glue: wit_bindgen_wasmtime::imports::BufferGlue,");
             // }
             self.push_str("}\n");
-            let bound = if self.opts.async_.is_none() {
-                ""
+            let mut bounds = Vec::new();
+            if self.opts.has_data {
+                bounds.push(format!("Has{}Data", name));
+            }
+            if !self.opts.async_.is_none() {
+                bounds.push("Send".to_string());
+            }
+            let bound = if bounds.is_empty() {
+                String::new()
             } else {
-                ": Send"
+                format!(": {}", bounds.join(" + "))
             };
             self.push_str(&format!("impl<T{}> {}<T> {{\n", bound, name));
 
+            // Without `has_data` the auxiliary data is accessed through a
+            // `get_state` closure passed to each constructor.
+            let (state_docs, state_param, state_arg) = if self.opts.has_data {
+                (String::new(), String::new(), "")
+            } else {
+                (
+                    "///
+                    /// The `get_state` closure is required to access the
+                    /// auxiliary data necessary for these wasm exports from
+                    /// the general store's state."
+                        .to_string(),
+                    format!(
+                        "get_state: impl Fn(&mut T) -> &mut {}Data + Send + Sync + Copy + 'static,",
+                        name
+                    ),
+                    "get_state",
+                )
+            };
+
             if self.exported_resources.len() == 0 {
                 self.push_str("#[allow(unused_variables)]\n");
             }
@@ -1094,16 +1472,13 @@ impl Generator for Wasmtime {
                 "
                     /// Adds any intrinsics, if necessary for this exported wasm
                     /// functionality to the `linker` provided.
-                    ///
-                    /// The `get_state` closure is required to access the
-                    /// auxiliary data necessary for these wasm exports from
-                    /// the general store's state.
+                    {}
                     pub fn add_to_linker(
                         linker: &mut wasmtime::Linker<T>,
-                        get_state: impl Fn(&mut T) -> &mut {}Data + Send + Sync + Copy + 'static,
+                        {}
                     ) -> anyhow::Result<()> {{
                 ",
-                name,
+                state_docs, state_param,
             ));
             for r in self.exported_resources.iter() {
                 let (func_wrap, call, wait, prefix, suffix) = if self.opts.async_.is_none() {
@@ -1123,7 +1498,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_drop_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, idx: u32| {prefix}{{
-                                let state = get_state(caller.data_mut());
+                                let state = {state};
                                 let resource_idx = state.index_slab{idx}.remove(idx)?;
                                 let wasm = match state.resource_slab{idx}.drop(resource_idx) {{
                                     Some(wasm) => wasm,
@@ -1138,7 +1513,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_clone_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, idx: u32| {{
-                                let state = get_state(caller.data_mut());
+                                let state = {state};
                                 let resource_idx = state.index_slab{idx}.get(idx)?;
                                 state.resource_slab{idx}.clone(resource_idx)?;
                                 Ok(state.index_slab{idx}.insert(resource_idx))
@@ -1148,7 +1523,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_get_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, idx: u32| {{
-                                let state = get_state(caller.data_mut());
+                                let state = {state};
                                 let resource_idx = state.index_slab{idx}.get(idx)?;
                                 Ok(state.resource_slab{idx}.get(resource_idx))
                             }},
@@ -1157,7 +1532,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_new_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, val: i32| {{
-                                let state = get_state(caller.data_mut());
+                                let state = {state};
                                 let resource_idx = state.resource_slab{idx}.insert(val);
                                 Ok(state.index_slab{idx}.insert(resource_idx))
                             }},
@@ -1165,6 +1540,7 @@ impl Generator for Wasmtime {
                     ",
                     name = iface.resources[*r].name,
                     idx = r.index(),
+                    state = self.export_state(&name, "get_state", "caller.data_mut()"),
                     func_wrap = func_wrap,
                     call = call,
                     wait = wait,
@@ -1231,22 +1607,24 @@ impl Generator for Wasmtime {
                     /// instantiate the `module` otherwise using `linker`, and
                     /// both an instance of this structure and the underlying
                     /// `wasmtime::Instance` will be returned.
-                    ///
-                    /// The `get_state` parameter is used to access the
-                    /// auxiliary state necessary for these wasm exports from
-                    /// the general store state `T`.
-                    pub {}fn instantiate(
+                    {state_docs}
+                    pub {async_fn}fn instantiate(
                         mut store: impl wasmtime::AsContextMut<Data = T>,
                         module: &wasmtime::Module,
                         linker: &mut wasmtime::Linker<T>,
-                        get_state: impl Fn(&mut T) -> &mut {}Data + Send + Sync + Copy + 'static,
+                        {state_param}
                     ) -> anyhow::Result<(Self, wasmtime::Instance)> {{
-                        Self::add_to_linker(linker, get_state)?;
-                        let instance = linker.instantiate{}(&mut store, module){}?;
-                        Ok((Self::new(store, &instance,get_state)?, instance))
+                        Self::add_to_linker(linker, {state_arg})?;
+                        let instance = linker.instantiate{instantiate}(&mut store, module){wait}?;
+                        Ok((Self::new(store, &instance, {state_arg})?, instance))
                     }}
                 ",
-                async_fn, name, instantiate, wait,
+                async_fn = async_fn,
+                instantiate = instantiate,
+                wait = wait,
+                state_docs = state_docs,
+                state_param = state_param,
+                state_arg = state_arg,
             ));
 
             self.push_str(&format!(
@@ -1262,10 +1640,10 @@ impl Generator for Wasmtime {
                     pub fn new(
                         mut store: impl wasmtime::AsContextMut<Data = T>,
                         instance: &wasmtime::Instance,
-                        get_state: impl Fn(&mut T) -> &mut {}Data + Send + Sync + Copy + 'static,
+                        {}
                     ) -> anyhow::Result<Self> {{
                 ",
-                name,
+                state_param,
             ));
             self.push_str("let mut store = store.as_context_mut();\n");
             assert!(!self.needs_get_func);
@@ -1279,12 +1657,13 @@ impl Generator for Wasmtime {
             for r in self.exported_resources.iter() {
                 self.src.push_str(&format!(
                     "
-                        get_state(store.data_mut()).dtor{} = \
+                        {}.dtor{} = \
                             Some(instance.get_typed_func::<i32, (), _>(\
                                 &mut store, \
                                 \"canonical_abi_drop_{}\", \
                             )?);\n
                     ",
+                    self.export_state(&name, "get_state", "store.data_mut()"),
                     r.index(),
                     iface.resources[*r].name,
                 ));
@@ -1296,10 +1675,25 @@ impl Generator for Wasmtime {
                 self.push_str(name);
                 self.push_str(",\n");
             }
-            self.push_str("get_state: Box::new(get_state),\n");
+            if self.opts.has_data {
+                self.push_str("_data: std::marker::PhantomData,\n");
+            } else {
+                self.push_str("get_state: Box::new(get_state),\n");
+            }
             self.push_str("\n})\n");
             self.push_str("}\n");
 
+            if self.opts.limits {
+                self.push_str(
+                    "
+                        /// Sets the limits enforced on each call into wasm.
+                        pub fn set_limits(&mut self, limits: wit_bindgen_wasmtime::Limits) {
+                            self.limits = limits;
+                        }
+                    ",
+                );
+            }
+
             for func in exports.funcs.iter() {
                 self.push_str(func);
             }
@@ -1310,6 +1704,14 @@ impl Generator for Wasmtime {
                 } else {
                     ("async ", "call_async", ".await")
                 };
+                let (begin_call, call_error) = if self.opts.limits {
+                    (
+                        "self.limits.begin_call(&mut store)?;",
+                        ".map_err(|e| self.limits.call_error(&mut store, e))",
+                    )
+                } else {
+                    ("", "")
+                };
                 self.src.push_str(&format!(
                     "
                         /// Drops the host-owned handle to the resource
@@ -1325,12 +1727,14 @@ impl Generator for Wasmtime {
                             val: {name_camel},
                         ) -> Result<(), wasmtime::Trap> {{
                             let mut store = store.as_context_mut();
-                            let data = (self.get_state)(store.data_mut());
+                            let data = {state};
                             let wasm = match data.resource_slab{idx}.drop(val.0) {{
                                 Some(val) => val,
                                 None => return Ok(()),
                             }};
-                            data.dtor{idx}.unwrap().{call}(&mut store, wasm){wait}?;
+                            let dtor = data.dtor{idx}.unwrap();
+                            {begin_call}
+                            dtor.{call}(&mut store, wasm){wait}{call_error}?;
                             Ok(())
                         }}
                     ",
@@ -1340,6 +1744,9 @@ impl Generator for Wasmtime {
                     async = async_fn,
                     call = call,
                     wait = wait,
+                    begin_call = begin_call,
+                    call_error = call_error,
+                    state = self.export_state(&name, "(self.get_state)", "store.data_mut()"),
                 ));
             }
 
@@ -1388,6 +1795,11 @@ struct FunctionBindgen<'a> {
     // Destination where source code is pushed onto for this function
     src: Source,
 
+    // The name of the function, and the path to the parameter or result value
+    // being translated, used to give context to errors.
+    func_name: &'a str,
+    path: Vec<String>,
+
     // Whether or not this function is a preview1 dtor
     is_dtor: bool,
 
@@ -1427,10 +1839,17 @@ struct FunctionBindgen<'a> {
     needs_functions: HashMap<String, NeededFunction>,
 }
 
-impl FunctionBindgen<'_> {
-    fn new(gen: &mut Wasmtime, is_dtor: bool, params: Vec<String>) -> FunctionBindgen<'_> {
+impl<'a> FunctionBindgen<'a> {
+    fn new(
+        gen: &'a mut Wasmtime,
+        func_name: &'a str,
+        is_dtor: bool,
+        params: Vec<String>,
+    ) -> FunctionBindgen<'a> {
         FunctionBindgen {
             gen,
+            func_name,
+            path: Vec::new(),
             block_storage: Vec::new(),
             blocks: Vec::new(),
             src: Source::default(),
@@ -1469,7 +1888,8 @@ impl FunctionBindgen<'_> {
                     self.push_str(
                         "let (caller_memory, data) = memory.data_and_store_mut(&mut caller);\n",
                     );
-                    self.push_str("let (_, _tables) = get(data);\n");
+                    let state = self.gen.import_state(&self.gen.trait_name, "data");
+                    self.push_str(&format!("let (_, _tables) = {};\n", state));
                 } else {
                     self.push_str("let caller_memory = memory.data_mut(&mut caller);\n");
                 }
@@ -1489,12 +1909,44 @@ impl FunctionBindgen<'_> {
             ("call_async", ".await")
         };
         self.push_str(&format!(
-            "func_{}.{}(&mut caller, {}){}?;\n",
-            name, method, args, suffix
+            "func_{}.{}(&mut caller, {}){}{}?;\n",
+            name,
+            method,
+            args,
+            suffix,
+            self.limits_call_error(),
         ));
         self.caller_memory_available = false; // invalidated by call
     }
 
+    /// Returns the expression accessing the auxiliary data of the exports
+    /// being called.
+    fn export_state(&self) -> String {
+        self.gen.export_state(
+            &self.gen.trait_name,
+            "(self.get_state)",
+            "caller.as_context_mut().data_mut()",
+        )
+    }
+
+    /// Returns the code to replace the error of a call into wasm when it runs
+    /// out of fuel, if limits are enforced.
+    fn limits_call_error(&self) -> &'static str {
+        if self.gen.opts.limits && !self.gen.in_import {
+            ".map_err(|e| self.limits.call_error(&mut caller, e))"
+        } else {
+            ""
+        }
+    }
+
+    /// Checks the size of a list allocated in wasm against the limits, if
+    /// they're enforced.
+    fn limits_check_alloc(&mut self, size: String) {
+        if self.gen.opts.limits && !self.gen.in_import {
+            self.push_str(&format!("self.limits.check_alloc({})?;\n", size));
+        }
+    }
+
     fn type_string(&mut self, iface: &Interface, ty: &Type, mode: TypeMode) -> String {
         let start = self.gen.src.len();
         self.gen.print_ty(iface, ty, mode);
@@ -1507,9 +1959,10 @@ impl FunctionBindgen<'_> {
         let mem = self.memory_src();
         self.gen.needs_raw_mem = true;
         let tmp = self.tmp();
+        let context = self.in_func();
         self.push_str(&format!(
-            "let load{} = {}.load::<{}>({} + {})?;\n",
-            tmp, mem, ty, operands[0], offset
+            "let load{} = {}.load::<{}>({} + {}){}?;\n",
+            tmp, mem, ty, operands[0], offset, context
         ));
         format!("load{}", tmp)
     }
@@ -1517,11 +1970,37 @@ impl FunctionBindgen<'_> {
     fn store(&mut self, offset: i32, method: &str, extra: &str, operands: &[String]) {
         let mem = self.memory_src();
         self.gen.needs_raw_mem = true;
+        let context = self.in_func();
         self.push_str(&format!(
-            "{}.store({} + {}, wit_bindgen_wasmtime::rt::{}({}){})?;\n",
-            mem, operands[1], offset, method, operands[0], extra
+            "{}.store({} + {}, wit_bindgen_wasmtime::rt::{}({}){}){}?;\n",
+            mem, operands[1], offset, method, operands[0], extra, context
         ));
     }
+
+    /// Returns a `map_err` call which wraps a `GuestError` with the name of
+    /// this function and the path to the value currently being translated.
+    fn in_func(&self) -> String {
+        format!(".map_err(|e| e{})", self.guest_error_context())
+    }
+
+    /// Returns the `GuestError::in_func` call for the value currently being
+    /// translated, such as `.in_func("f", "points[].x")`.
+    ///
+    /// Values without a path are those read from or written to the return
+    /// pointer area of a function, and are reported as its `result`.
+    fn guest_error_context(&self) -> String {
+        let mut location = String::new();
+        for part in self.path.iter() {
+            if !location.is_empty() && !part.starts_with('[') {
+                location.push('.');
+            }
+            location.push_str(part);
+        }
+        if location.is_empty() {
+            location.push_str("result");
+        }
+        format!(".in_func({:?}, {:?})", self.func_name, location)
+    }
 }
 
 impl RustFunctionGenerator for FunctionBindgen<'_> {
@@ -1590,6 +2069,20 @@ impl Bindgen for FunctionBindgen<'_> {
         iface.all_bits_valid(ty)
     }
 
+    fn enter_value(&mut self, path: ValuePath<'_>) {
+        self.path.push(match path {
+            ValuePath::Named("") => "result".to_string(),
+            ValuePath::Named(name) | ValuePath::Field(name) | ValuePath::Case(name) => {
+                name.to_string()
+            }
+            ValuePath::Element => "[]".to_string(),
+        });
+    }
+
+    fn exit_value(&mut self) {
+        self.path.pop();
+    }
+
     fn emit(
         &mut self,
         iface: &Interface,
@@ -1605,8 +2098,13 @@ impl Bindgen for FunctionBindgen<'_> {
         };
 
         let mut try_from = |cvt: &str, operands: &[String], results: &mut Vec<String>| {
-            self.gen.needs_bad_int = true;
-            let result = format!("{}::try_from({}).map_err(bad_int)?", cvt, operands[0]);
+            self.gen.needs_int_from_i32 = true;
+            let result = format!(
+                "int_from_i32::<{}>({}){}?",
+                cvt,
+                operands[0],
+                self.in_func()
+            );
             results.push(result);
         };
 
@@ -1666,7 +2164,7 @@ impl Bindgen for FunctionBindgen<'_> {
 
             Instruction::CharFromI32 => {
                 self.gen.needs_char_from_i32 = true;
-                results.push(format!("char_from_i32({})?", operands[0]));
+                results.push(format!("char_from_i32({}){}?", operands[0], self.in_func()));
             }
 
             Instruction::Bitcasts { casts } => {
@@ -1706,9 +2204,10 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.push_str(&format!(
                     "
                         let obj{tmp} = {op};
-                        (self.get_state)(caller.as_context_mut().data_mut()).resource_slab{idx}.clone(obj{tmp}.0)?;
-                        let handle{tmp} = (self.get_state)(caller.as_context_mut().data_mut()).index_slab{idx}.insert(obj{tmp}.0);
+                        {state}.resource_slab{idx}.clone(obj{tmp}.0)?;
+                        let handle{tmp} = {state}.index_slab{idx}.insert(obj{tmp}.0);
                     ",
+                    state = self.export_state(),
                     tmp = tmp,
                     idx = ty.index(),
                     op = operands[0],
@@ -1719,8 +2218,9 @@ impl Bindgen for FunctionBindgen<'_> {
             Instruction::HandleOwnedFromI32 { ty } => {
                 let tmp = self.tmp();
                 self.push_str(&format!(
-                    "let handle{} = (self.get_state)(caller.as_context_mut().data_mut()).index_slab{}.remove({} as u32)?;\n",
+                    "let handle{} = {}.index_slab{}.remove({} as u32)?;\n",
                     tmp,
+                    self.export_state(),
                     ty.index(),
                     operands[0],
                 ));
@@ -1762,10 +2262,11 @@ impl Bindgen for FunctionBindgen<'_> {
                         {name}::all().bits() as i64,
                         \"{name}\",
                         |b| {name} {{ bits: b as {ty} }}
-                    )?",
+                    ){context}?",
                     flags,
                     name = name.to_camel_case(),
                     ty = int_repr(repr),
+                    context = self.in_func(),
                 ));
             }
 
@@ -1819,9 +2320,11 @@ impl Bindgen for FunctionBindgen<'_> {
                         unimplemented!()
                     }
                 });
-                result.push_str("_ => return Err(invalid_variant(\"");
+                result.push_str("d => return Err(invalid_variant(\"");
                 result.push_str(&variant_name);
-                result.push_str("\")),\n");
+                result.push_str("\", i64::from(d))");
+                result.push_str(&self.guest_error_context());
+                result.push_str(".into()),\n");
                 result.push_str("}");
                 results.push(result);
                 self.gen.needs_invalid_variant = true;
@@ -1849,23 +2352,29 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.push_str(&format!("let {} = {};\n", val, operands[0]));
 
                 // ... and then realloc space for the result in the guest module
+                self.limits_check_alloc(format!("{}.len() * {}", val, size));
                 let ptr = format!("ptr{}", tmp);
+                let ptr_ty = wasm_type(iface.wasm_pointer());
                 self.push_str(&format!("let {} = ", ptr));
                 self.call_intrinsic(
                     realloc,
-                    format!("(0, 0, {}, ({}.len() as i32) * {})", align, val, size),
+                    format!(
+                        "(0, 0, {}, ({}.len() as {}) * {})",
+                        align, val, ptr_ty, size
+                    ),
                 );
 
                 // ... and then copy over the result.
                 let mem = self.memory_src();
+                let context = self.in_func();
                 self.push_str(&format!(
-                    "{}.store_many({}, {}.as_ref())?;\n",
-                    mem, ptr, val
+                    "{}.store_many({}, {}.as_ref()){}?;\n",
+                    mem, ptr, val, context
                 ));
                 self.gen.needs_raw_mem = true;
                 self.needs_memory = true;
                 results.push(ptr);
-                results.push(format!("{}.len() as i32", val));
+                results.push(format!("{}.len() as {}", val, ptr_ty));
             }
 
             Instruction::ListCanonLift { element, free, .. } => match free {
@@ -1874,34 +2383,36 @@ impl Bindgen for FunctionBindgen<'_> {
                     self.gen.needs_copy_slice = true;
                     self.needs_functions
                         .insert(free.to_string(), NeededFunction::Free);
-                    let (stringify, align) = match element {
-                        Type::Char => (true, 1),
-                        _ => (false, self.gen.sizes.align(element)),
+                    let (stringify, size, align) = match element {
+                        Type::Char => (true, 1, 1),
+                        _ => (
+                            false,
+                            self.gen.sizes.size(element),
+                            self.gen.sizes.align(element),
+                        ),
                     };
                     let tmp = self.tmp();
                     self.push_str(&format!("let ptr{} = {};\n", tmp, operands[0]));
                     self.push_str(&format!("let len{} = {};\n", tmp, operands[1]));
-                    let result = format!(
-                        "
-                                copy_slice(
-                                    &mut caller,
-                                    memory,
-                                    func_{},
-                                    ptr{tmp}, len{tmp}, {}
-                                )?
-                            ",
+                    let context = self.in_func();
+                    self.push_str(&format!(
+                        "let data{tmp} = copy_slice(memory.data(&caller), ptr{tmp}, len{tmp}){}?;\n",
+                        context,
+                        tmp = tmp,
+                    ));
+                    self.call_intrinsic(
                         free,
-                        align,
-                        tmp = tmp
+                        format!("(ptr{tmp}, len{tmp} * {}, {})", size, align, tmp = tmp),
                     );
                     if stringify {
                         results.push(format!(
-                            "String::from_utf8({})
-                                    .map_err(|_| wasmtime::Trap::new(\"invalid utf-8\"))?",
-                            result
+                            "String::from_utf8(data{})
+                                .map_err(|e| wit_bindgen_wasmtime::GuestError::from(e.utf8_error()){})?",
+                            tmp,
+                            self.guest_error_context(),
                         ));
                     } else {
-                        results.push(result);
+                        results.push(format!("data{}", tmp));
                     }
                 }
                 None => {
@@ -1913,7 +2424,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     let tmp = self.tmp();
                     self.push_str(&format!("let ptr{} = {};\n", tmp, operands[0]));
                     self.push_str(&format!("let len{} = {};\n", tmp, operands[1]));
-                    let slice = format!("_bc.{}(ptr{1}, len{1})?", method, tmp);
+                    let slice = format!("_bc.{}(ptr{1}, len{1}){2}?", method, tmp, self.in_func());
                     results.push(slice);
                 }
             },
@@ -1932,10 +2443,12 @@ impl Bindgen for FunctionBindgen<'_> {
 
                 // first store our vec-to-lower in a temporary since we'll
                 // reference it multiple times.
+                let ptr_ty = wasm_type(iface.wasm_pointer());
                 self.push_str(&format!("let {} = {};\n", vec, operands[0]));
-                self.push_str(&format!("let {} = {}.len() as i32;\n", len, vec));
+                self.push_str(&format!("let {} = {}.len() as {};\n", len, vec, ptr_ty));
 
                 // ... then realloc space for the result in the guest module
+                self.limits_check_alloc(format!("{}.len() * {}", vec, size));
                 self.push_str(&format!("let {} = ", result));
                 self.call_intrinsic(realloc, format!("(0, 0, {}, {} * {})", align, len, size));
 
@@ -1945,7 +2458,10 @@ impl Bindgen for FunctionBindgen<'_> {
                     "for (i, e) in {}.into_iter().enumerate() {{\n",
                     vec
                 ));
-                self.push_str(&format!("let base = {} + (i as i32) * {};\n", result, size));
+                self.push_str(&format!(
+                    "let base = {} + (i as {}) * {};\n",
+                    result, ptr_ty, size
+                ));
                 self.push_str(&body);
                 self.push_str("}");
 
@@ -2008,7 +2524,12 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.push_str(&format!("let len{} = {};\n", tmp, operands[2]));
                 if iface.all_bits_valid(ty) {
                     let method = if *push { "slice_mut" } else { "slice" };
-                    results.push(format!("_bc.{}(ptr{1}, len{1})?", method, tmp));
+                    results.push(format!(
+                        "_bc.{}(ptr{1}, len{1}){2}?",
+                        method,
+                        tmp,
+                        self.in_func()
+                    ));
                 } else {
                     let size = self.gen.sizes.size(ty);
                     let closure = format!("closure{}", tmp);
@@ -2111,6 +2632,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 if self.gen.opts.async_.includes(name) {
                     self.push_str(".await");
                 }
+                self.push_str(self.limits_call_error());
                 self.push_str("?;\n");
                 self.after_call = true;
                 self.caller_memory_available = false; // invalidated by call
@@ -2126,12 +2648,14 @@ impl Bindgen for FunctionBindgen<'_> {
                 if self.gen.opts.tracing && func.params.len() > 0 {
                     self.push_str("wit_bindgen_wasmtime::tracing::event!(\n");
                     self.push_str("wit_bindgen_wasmtime::tracing::Level::TRACE,\n");
-                    for (i, (name, _ty)) in func.params.iter().enumerate() {
-                        self.push_str(&format!(
-                            "{} = wit_bindgen_wasmtime::tracing::field::debug(&param{}),\n",
-                            to_rust_ident(name),
-                            i
-                        ));
+                    for (i, (name, ty)) in func.params.iter().enumerate() {
+                        let field = self.gen.trace_field(
+                            iface,
+                            &format!("params.{}", to_rust_ident(name)),
+                            ty,
+                            &format!("&param{}", i),
+                        );
+                        self.push_str(&field);
                     }
                     self.push_str(");\n");
                 }
@@ -2188,11 +2712,17 @@ impl Bindgen for FunctionBindgen<'_> {
                 if self.gen.opts.tracing && func.results.len() > 0 {
                     self.push_str("wit_bindgen_wasmtime::tracing::event!(\n");
                     self.push_str("wit_bindgen_wasmtime::tracing::Level::TRACE,\n");
-                    for name in results.iter() {
-                        self.push_str(&format!(
-                            "{} = wit_bindgen_wasmtime::tracing::field::debug(&{0}),\n",
-                            name,
-                        ));
+                    for (i, ((name, ty), result)) in
+                        func.results.iter().zip(results.iter()).enumerate()
+                    {
+                        let name = trace_result_name(func, i, name);
+                        let field = self.gen.trace_field(
+                            iface,
+                            &format!("results.{}", name),
+                            ty,
+                            &format!("&{}", result),
+                        );
+                        self.push_str(&field);
                     }
                     self.push_str(");\n");
                 }
@@ -2261,15 +2791,23 @@ impl Bindgen for FunctionBindgen<'_> {
 }
 
 impl NeededFunction {
-    fn cvt(&self) -> &'static str {
+    fn kind(&self) -> AbiExportKind {
         match self {
-            NeededFunction::Realloc => "(i32, i32, i32, i32), i32",
-            NeededFunction::Free => "(i32, i32, i32), ()",
+            NeededFunction::Realloc => AbiExportKind::Realloc,
+            NeededFunction::Free => AbiExportKind::Free,
         }
     }
 
-    fn ty(&self) -> String {
-        format!("wasmtime::TypedFunc<{}>", self.cvt())
+    fn cvt(&self, iface: &Interface) -> String {
+        let ptr = wasm_type(iface.wasm_pointer());
+        match self {
+            NeededFunction::Realloc => format!("({0}, {0}, {0}, {0}), {0}", ptr),
+            NeededFunction::Free => format!("({0}, {0}, {0}), ()", ptr),
+        }
+    }
+
+    fn ty(&self, iface: &Interface) -> String {
+        format!("wasmtime::TypedFunc<{}>", self.cvt(iface))
     }
 }
 
@@ -2278,3 +2816,15 @@ fn sorted_iter<K: Ord, V>(map: &HashMap<K, V>) -> impl Iterator<Item = (&K, &V)>
     list.sort_by_key(|p| p.0);
     list.into_iter()
 }
+
+/// Returns the name of the `i`th result of `func`, named `name`, as recorded
+/// when tracing.
+fn trace_result_name(func: &Function, i: usize, name: &str) -> String {
+    if !name.is_empty() {
+        to_rust_ident(name)
+    } else if func.results.len() == 1 {
+        "result".to_string()
+    } else {
+        format!("result{}", i)
+    }
+}
@@ -107,3 +107,352 @@ mod custom_errors {
         custom_error: true,
     });
 }
+
+mod trait_only {
+    mod host {
+        wit_bindgen_wasmtime::export!({
+            src["x"]: "
+                record point { x: u32, y: u32 }
+                flags perms { read, write }
+                resource blob
+                foo: function(p: point, l: list<u32>) -> perms
+                bar: function(h: blob) -> expected<string, u32>
+            ",
+            custom_error: true,
+            trait_only: true,
+        });
+    }
+
+    mod glue {
+        wit_bindgen_wasmtime::export!({
+            src["x"]: "
+                record point { x: u32, y: u32 }
+                flags perms { read, write }
+                resource blob
+                foo: function(p: point, l: list<u32>) -> perms
+                bar: function(h: blob) -> expected<string, u32>
+            ",
+            custom_error: true,
+            trait_module: "crate::trait_only::host",
+        });
+    }
+
+    struct Me;
+
+    impl host::x::X for Me {
+        type Blob = ();
+        type Error = ();
+
+        fn foo(
+            &mut self,
+            p: host::x::Point,
+            l: &[wit_bindgen_wasmtime::Le<u32>],
+        ) -> Result<host::x::Perms, Self::Error> {
+            let _ = (p.x + p.y, l);
+            Ok(host::x::Perms::READ)
+        }
+
+        fn bar(&mut self, h: &Self::Blob) -> Result<Result<String, u32>, Self::Error> {
+            let _ = h;
+            Ok(Ok(String::new()))
+        }
+    }
+
+    impl glue::x::XErrors for Me {
+        fn error_to_trap(&mut self, err: Self::Error) -> wit_bindgen_wasmtime::wasmtime::Trap {
+            let _ = err;
+            wit_bindgen_wasmtime::wasmtime::Trap::new("error")
+        }
+    }
+
+    fn add(linker: &mut wit_bindgen_wasmtime::wasmtime::Linker<(Me, glue::x::XTables<Me>)>) {
+        glue::x::add_to_linker(linker, |s| (&mut s.0, &mut s.1)).unwrap();
+    }
+}
+
+mod limits {
+    wit_bindgen_wasmtime::import!({
+        src["x"]: "
+            resource y {
+                z: function(a: list<string>) -> string
+            }
+            w: function(a: list<list<u32>>) -> list<u8>
+        ",
+        limits: true,
+    });
+}
+
+mod has_data {
+    wit_bindgen_wasmtime::import!({
+        src["x"]: "
+            resource y {
+                z: function() -> string
+            }
+            w: function(a: list<u32>) -> y
+        ",
+        has_data: true,
+    });
+
+    wit_bindgen_wasmtime::import!({
+        src["v"]: "
+            u: function(a: string) -> string
+        ",
+        has_data: true,
+        async: *,
+    });
+
+    #[derive(Default)]
+    struct Data {
+        x: x::XData,
+        v: v::VData,
+    }
+
+    impl x::HasXData for Data {
+        fn x_data(&mut self) -> &mut x::XData {
+            &mut self.x
+        }
+    }
+
+    impl v::HasVData for Data {
+        fn v_data(&mut self) -> &mut v::VData {
+            &mut self.v
+        }
+    }
+
+    fn instantiate(
+        store: &mut wit_bindgen_wasmtime::wasmtime::Store<Data>,
+        module: &wit_bindgen_wasmtime::wasmtime::Module,
+        linker: &mut wit_bindgen_wasmtime::wasmtime::Linker<Data>,
+    ) -> wit_bindgen_wasmtime::anyhow::Result<x::X<Data>> {
+        let (x, _) = x::X::instantiate(&mut *store, module, linker)?;
+        let y = x.w(&mut *store, &[1])?;
+        x.y_z(&mut *store, &y)?;
+        x.drop_y(&mut *store, y)?;
+        Ok(x)
+    }
+
+    async fn instantiate_async(
+        store: &mut wit_bindgen_wasmtime::wasmtime::Store<Data>,
+        module: &wit_bindgen_wasmtime::wasmtime::Module,
+        linker: &mut wit_bindgen_wasmtime::wasmtime::Linker<Data>,
+    ) -> wit_bindgen_wasmtime::anyhow::Result<String> {
+        let (v, _) = v::V::instantiate(&mut *store, module, linker).await?;
+        Ok(v.u(&mut *store, "").await?)
+    }
+
+    wit_bindgen_wasmtime::export!({
+        src["hs"]: "
+            resource r
+            q: function(a: list<u32>) -> r
+            p: function(a: r) -> string
+        ",
+        has_data: true,
+    });
+
+    wit_bindgen_wasmtime::export!({
+        src["ha"]: "
+            o: function(a: string) -> string
+        ",
+        has_data: true,
+        async: *,
+    });
+
+    struct Host;
+
+    impl hs::Hs for Host {
+        type R = u32;
+
+        fn q(&mut self, a: &[wit_bindgen_wasmtime::Le<u32>]) -> u32 {
+            a.len() as u32
+        }
+
+        fn p(&mut self, a: &u32) -> String {
+            a.to_string()
+        }
+    }
+
+    #[wit_bindgen_wasmtime::async_trait]
+    impl ha::Ha for Host {
+        async fn o(&mut self, a: &str) -> String {
+            a.to_string()
+        }
+    }
+
+    // Host implementations of several interfaces compose on one store type,
+    // alongside the auxiliary data of exports.
+    struct HostData {
+        host: Host,
+        hs_tables: hs::HsTables<Host>,
+        x: x::XData,
+    }
+
+    impl hs::HasHs for HostData {
+        type Host = Host;
+
+        fn hs(&mut self) -> (&mut Host, &mut hs::HsTables<Host>) {
+            (&mut self.host, &mut self.hs_tables)
+        }
+    }
+
+    impl ha::HasHa for HostData {
+        type Host = Host;
+
+        fn ha(&mut self) -> &mut Host {
+            &mut self.host
+        }
+    }
+
+    impl x::HasXData for HostData {
+        fn x_data(&mut self) -> &mut x::XData {
+            &mut self.x
+        }
+    }
+
+    fn add_to_linker(
+        linker: &mut wit_bindgen_wasmtime::wasmtime::Linker<HostData>,
+    ) -> wit_bindgen_wasmtime::anyhow::Result<()> {
+        hs::add_to_linker(linker)?;
+        ha::add_to_linker(linker)?;
+        x::X::add_to_linker(linker)
+    }
+}
+
+mod tracing_redact {
+    mod host {
+        wit_bindgen_wasmtime::export!({
+            src["x"]: "
+                record secret { a: u32 }
+                f: function(a: string, b: option<secret>) -> list<secret>
+                g: function(a: list<u8>) -> (b: secret, c: u32)
+            ",
+            tracing_redact: ["secret"],
+            async: ["g"],
+        });
+    }
+
+    mod guest {
+        wit_bindgen_wasmtime::import!({
+            src["x"]: "
+                record secret { a: u32 }
+                f: function(a: string, b: option<secret>) -> list<secret>
+                g: function(a: list<u8>) -> (b: secret, c: u32)
+            ",
+            tracing_redact: ["secret"],
+            async: ["g"],
+        });
+    }
+}
+
+mod borrowed {
+    wit_bindgen_wasmtime::export!({
+        src["x"]: "
+            record point { x: u32, y: u32 }
+            record named { name: string, tags: list<string>, points: list<point> }
+            f: function(a: list<u32>, b: string, c: list<point>)
+            g: function(a: list<list<u32>>, b: list<string>, c: named, d: option<list<named>>)
+            h: function(a: list<u8>, b: expected<list<s16>, string>) -> list<u8>
+            n: function(a: list<list<list<u32>>>, b: list<list<string>>, c: option<list<list<u8>>>)
+        ",
+        async: ["h"],
+    });
+
+    struct Me;
+
+    // Everything lifted from guest memory is handed out as a view of that
+    // memory rather than copied. This is the existing `TypeMode::LeafBorrowed`
+    // behavior for exports; these signatures pin it down. Nested lists borrow
+    // their innermost lists, while the outer levels are `Vec`s of views as
+    // every element's pointer and length has to be checked first.
+    #[wit_bindgen_wasmtime::async_trait]
+    impl x::X for Me {
+        fn f(
+            &mut self,
+            a: &[wit_bindgen_wasmtime::Le<u32>],
+            b: &str,
+            c: &[wit_bindgen_wasmtime::Le<x::Point>],
+        ) {
+            let _ = (a, b, c);
+        }
+
+        fn g(
+            &mut self,
+            a: Vec<&[wit_bindgen_wasmtime::Le<u32>]>,
+            b: Vec<&str>,
+            c: x::Named<'_>,
+            d: Option<Vec<x::Named<'_>>>,
+        ) {
+            let _: (&str, Vec<&str>, &[wit_bindgen_wasmtime::Le<x::Point>]) =
+                (c.name, c.tags, c.points);
+            let _ = (a, b, d);
+        }
+
+        async fn h(
+            &mut self,
+            a: &[u8],
+            b: Result<&[wit_bindgen_wasmtime::Le<i16>], &str>,
+        ) -> Vec<u8> {
+            let _ = b;
+            a.to_vec()
+        }
+
+        fn n(
+            &mut self,
+            a: Vec<Vec<&[wit_bindgen_wasmtime::Le<u32>]>>,
+            b: Vec<Vec<&str>>,
+            c: Option<Vec<&[u8]>>,
+        ) {
+            let _ = (a, b, c);
+        }
+    }
+}
+
+mod abi_exports {
+    wit_bindgen_wasmtime::import!({
+        src["x"]: "
+            f: function(a: string) -> list<string>
+        ",
+        abi_exports: ["memory=memory1", "realloc=cabi_realloc", "x:free=cabi_free"],
+    });
+
+    wit_bindgen_wasmtime::export!({
+        src["y"]: "
+            f: function(a: list<u32>) -> string
+        ",
+        abi_exports: ["y:memory=memory1", "realloc=cabi_realloc"],
+    });
+}
+
+mod memory {
+    wit_bindgen_wasmtime::import!({
+        src["x"]: "
+            f: function(a: string) -> list<string>
+        ",
+        memory: 1,
+        abi_exports: ["memory=memory1"],
+    });
+
+    wit_bindgen_wasmtime::export!({
+        src["y"]: "
+            f: function(a: list<u32>) -> string
+        ",
+        memory: 2,
+        abi_exports: ["memory=mem2"],
+    });
+}
+
+mod memory64 {
+    wit_bindgen_wasmtime::import!({
+        src["x"]: "
+            record point { x: u32, name: string }
+            f: function(a: string, b: list<point>) -> list<string>
+        ",
+        memory64: true,
+    });
+
+    wit_bindgen_wasmtime::export!({
+        src["y"]: "
+            f: function(a: list<u32>) -> tuple<string, u8>
+        ",
+        memory64: true,
+    });
+}
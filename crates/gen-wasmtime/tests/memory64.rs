@@ -0,0 +1,73 @@
+use anyhow::Result;
+use wasmtime::{Config, Engine, Linker, Module, Store};
+
+wit_bindgen_wasmtime::export!({
+    src["host"]: "concat: function(a: string, b: list<u32>) -> string",
+    memory64: true,
+});
+
+wit_bindgen_wasmtime::import!({
+    src["guest"]: "run: function(a: string, b: list<u32>) -> string",
+    memory64: true,
+});
+
+// A guest that forwards `run` to the host's `concat`, returning through the
+// same return area. Its allocator hands out addresses above 4GiB so that any
+// pointer truncated to 32 bits along the way reads the wrong memory.
+const GUEST: &str = r#"
+(module
+  (import "host" "concat" (func $concat (param i64 i64 i64 i64 i64)))
+  (memory (export "memory") i64 65537)
+  (global $next (mut i64) (i64.const 0x1_0000_0000))
+  (func (export "canonical_abi_realloc") (param i64 i64 i64 i64) (result i64)
+    (local $ret i64)
+    (local.set $ret
+      (i64.and
+        (i64.add (global.get $next) (i64.sub (local.get 2) (i64.const 1)))
+        (i64.sub (i64.const 0) (local.get 2))))
+    (global.set $next (i64.add (local.get $ret) (local.get 3)))
+    (local.get $ret))
+  (func (export "canonical_abi_free") (param i64 i64 i64))
+  (func (export "run") (param i64 i64 i64 i64) (result i64)
+    (call $concat
+      (local.get 0) (local.get 1) (local.get 2) (local.get 3) (i64.const 16))
+    (i64.const 16)))
+"#;
+
+#[derive(Default)]
+struct MyHost;
+
+impl host::Host for MyHost {
+    fn concat(&mut self, a: &str, b: &[wit_bindgen_wasmtime::Le<u32>]) -> String {
+        let b = b.iter().map(|x| x.get()).collect::<Vec<_>>();
+        format!("{} {:?}", a, b)
+    }
+}
+
+#[derive(Default)]
+struct Context {
+    host: MyHost,
+    guest: guest::GuestData,
+}
+
+#[test]
+fn round_trip() -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_memory64(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, GUEST)?;
+
+    let mut linker = Linker::new(&engine);
+    host::add_to_linker(&mut linker, |cx: &mut Context| &mut cx.host)?;
+
+    let mut store = Store::new(&engine, Context::default());
+    let (guest, _instance) =
+        guest::Guest::instantiate(&mut store, &module, &mut linker, |cx| &mut cx.guest)?;
+
+    assert_eq!(
+        guest.run(&mut store, "hello", &[1, 2, 3])?,
+        "hello [1, 2, 3]"
+    );
+    assert_eq!(guest.run(&mut store, "", &[])?, " []");
+    Ok(())
+}
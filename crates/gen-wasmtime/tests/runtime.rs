@@ -33,7 +33,22 @@ fn instantiate<I: Default, E: Default, T>(
         &mut Linker<Context<I, E>>,
     ) -> Result<(T, Instance)>,
 ) -> Result<(T, Store<Context<I, E>>)> {
-    let engine = Engine::new(&default_config()?)?;
+    instantiate_with_config(&default_config()?, wasm, add_imports, mk_exports)
+}
+
+/// Same as `instantiate`, but with an engine created from `config`, for tests
+/// which need features like fuel consumption.
+fn instantiate_with_config<I: Default, E: Default, T>(
+    config: &Config,
+    wasm: &str,
+    add_imports: impl FnOnce(&mut Linker<Context<I, E>>) -> Result<()>,
+    mk_exports: impl FnOnce(
+        &mut Store<Context<I, E>>,
+        &Module,
+        &mut Linker<Context<I, E>>,
+    ) -> Result<(T, Instance)>,
+) -> Result<(T, Store<Context<I, E>>)> {
+    let engine = Engine::new(config)?;
     let module = Module::from_file(&engine, wasm)?;
 
     let mut linker = Linker::new(&engine);
@@ -97,7 +112,72 @@ fn instantiate_smw<I: Default, E: Default, T>(
     println!("instantiating input wasm...");
     let (exports, instance) = mk_exports(&mut store, &module, &mut linker)?;
 
-    println!("running wizer.initialize");
+    // Glue modules that were pre-initialized with Wizer have already run
+    // `wizer.initialize` and no longer export it.
+    if instance
+        .get_export(&mut store, "wizer.initialize")
+        .is_some()
+    {
+        println!("running wizer.initialize");
+        let init = instance.get_typed_func::<(), (), _>(&mut store, "wizer.initialize")?;
+        init.call(&mut store, ())
+            .context("failed to call wizer.initialize")?;
+    }
+    Ok((exports, store))
+}
+
+#[cfg(feature = "wasm-cpython")]
+fn instantiate_cpw<I: Default, E: Default, T>(
+    wasm: &str,
+    add_imports: impl FnOnce(&mut Linker<Context<I, E>>) -> Result<()>,
+    mk_exports: impl FnOnce(
+        &mut Store<Context<I, E>>,
+        &Module,
+        &mut Linker<Context<I, E>>,
+    ) -> Result<(T, Instance)>,
+) -> Result<(T, Store<Context<I, E>>)> {
+    let mut config = default_config()?;
+    config.wasm_module_linking(true);
+    config.wasm_multi_memory(true);
+    let engine = Engine::new(&config)?;
+
+    let wasm = std::fs::read(wasm).context(format!("failed to read {}", wasm))?;
+    let cpw = std::fs::read("../gen-cpython/cpython-wasm/cpython.wasm")
+        .context("failed to read `cpython.wasm`")?;
+    let module = Module::new_with_name(&engine, &wasm, "wasm.wasm")?;
+    let cpw = Module::new_with_name(&engine, &cpw, "cpython.wasm")?;
+
+    let mut linker = Linker::new(&engine);
+    add_imports(&mut linker)?;
+    wasmtime_wasi::add_to_linker(&mut linker, |cx| &mut cx.wasi)?;
+
+    // `cpython.wasm` loads the Python standard library from the filesystem.
+    let stdlib = wasmtime_wasi::sync::Dir::open_ambient_dir(
+        "../gen-cpython/cpython-wasm/lib/python3.11",
+        wasmtime_wasi::sync::ambient_authority(),
+    )
+    .context("failed to open the Python standard library directory")?;
+    let wasi = wasmtime_wasi::sync::WasiCtxBuilder::new()
+        .inherit_stdio()
+        .preopened_dir(stdlib, "/lib/python3.11")?
+        .build();
+
+    let mut store = Store::new(
+        &engine,
+        Context {
+            wasi,
+            imports: I::default(),
+            exports: E::default(),
+        },
+    );
+
+    let cpw_instance = linker
+        .instantiate(&mut store, &cpw)
+        .context("failed to instantiate `cpython.wasm`")?;
+    linker.define_name("cpython", cpw_instance)?;
+
+    let (exports, instance) = mk_exports(&mut store, &module, &mut linker)?;
+
     let init = instance.get_typed_func::<(), (), _>(&mut store, "wizer.initialize")?;
     init.call(&mut store, ())
         .context("failed to call wizer.initialize")?;
@@ -442,8 +442,9 @@ def_instruction! {
         /// representation of the interface types value in the native language
         /// exactly matches the canonical ABI definition of the type.
         ///
-        /// This will consume two `i32` values from the stack, a pointer and a
-        /// length, and then produces an interface value list. If the `free`
+        /// This will consume two `i32` values from the stack, or `i64` values
+        /// for 64-bit memories, a pointer and a length, and then produces an
+        /// interface value list. If the `free`
         /// field is set to `Some` then the pointer/length should be considered
         /// an owned allocation and need to be deallocated by the receiver. If
         /// it is set to `None` then a view is provided but it does not need to
@@ -462,8 +463,9 @@ def_instruction! {
 
         /// Lifts a list which into an interface types value.
         ///
-        /// This will consume two `i32` values from the stack, a pointer and a
-        /// length, and then produces an interface value list. Note that the
+        /// This will consume two `i32` values from the stack, or `i64` values
+        /// for 64-bit memories, a pointer and a length, and then produces an
+        /// interface value list. Note that the
         /// pointer/length popped are **owned** and need to be deallocated with
         /// the wasm `free` function when the list is no longer needed.
         ///
@@ -747,6 +749,23 @@ pub enum LiftLower {
     LowerArgsLiftResults,
 }
 
+/// One step in the path from a function's parameters or results to the value
+/// currently being lifted or lowered, as reported by [`Bindgen::enter_value`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValuePath<'a> {
+    /// A parameter or result of the function, by name.
+    ///
+    /// Results may have an empty name if the function has a single unnamed
+    /// result.
+    Named(&'a str),
+    /// A field of a record.
+    Field(&'a str),
+    /// The payload of a variant case.
+    Case(&'a str),
+    /// An element of a list.
+    Element,
+}
+
 /// We use a different ABI for wasm importing functions exported by the host
 /// than for wasm exporting functions imported by the host.
 ///
@@ -854,6 +873,21 @@ pub trait Bindgen {
     /// "canonical" form for lists. This dictates whether the `ListCanonLower`
     /// and `ListCanonLift` instructions are used or not.
     fn is_list_canonical(&self, iface: &Interface, element: &Type) -> bool;
+
+    /// Indicates that the instructions emitted until the matching
+    /// `exit_value` translate the value at `path`, relative to the value
+    /// entered previously.
+    ///
+    /// Calls nest, so the sequence of entered paths locates the value within
+    /// the function's parameters or results. Code generators can use this to
+    /// describe where an invalid value was found; by default it's ignored.
+    fn enter_value(&mut self, path: ValuePath<'_>) {
+        let _ = path;
+    }
+
+    /// Indicates that the value entered with the last `enter_value` has been
+    /// translated.
+    fn exit_value(&mut self) {}
 }
 
 impl Interface {
@@ -1005,6 +1039,16 @@ impl Interface {
         }
     }
 
+    /// The WebAssembly type of pointers and lengths passed through the
+    /// canonical ABI, which depends on whether `memory64` is set.
+    pub fn wasm_pointer(&self) -> WasmType {
+        if self.memory64 {
+            WasmType::I64
+        } else {
+            WasmType::I32
+        }
+    }
+
     /// Get the WebAssembly type signature for this interface function
     ///
     /// The first entry returned is the list of parameters and the second entry
@@ -1079,10 +1123,10 @@ impl Interface {
                 retptr = Some(mem::take(&mut results));
                 match variant {
                     AbiVariant::GuestImport => {
-                        params.push(WasmType::I32);
+                        params.push(self.wasm_pointer());
                     }
                     AbiVariant::GuestExport => {
-                        results.push(WasmType::I32);
+                        results.push(self.wasm_pointer());
                     }
                 }
             }
@@ -1141,8 +1185,8 @@ impl Interface {
                 }
 
                 TypeDefKind::List(_) => {
-                    result.push(WasmType::I32);
-                    result.push(WasmType::I32);
+                    result.push(self.wasm_pointer());
+                    result.push(self.wasm_pointer());
                 }
 
                 TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
@@ -1228,6 +1272,16 @@ impl Interface {
                 panic!("the preview1 ABI only supports import modes");
             }
         }
+        if self.memory64 {
+            // WASI and the async machinery of the canonical ABI, which passes
+            // callbacks and contexts as `i32`, are both 32-bit only.
+            if Abi::Preview1 == func.abi {
+                panic!("the preview1 ABI does not support 64-bit memories");
+            }
+            if func.is_async {
+                panic!("async functions do not support 64-bit memories");
+            }
+        }
         Generator::new(self, func.abi, variant, lift_lower, bindgen).call(func);
     }
 }
@@ -1522,9 +1576,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     .collect::<Vec<_>>()
             })
             .collect::<Vec<_>>();
-        for (operands, (_, ty)) in operands.into_iter().rev().zip(tys) {
+        for (operands, (name, ty)) in operands.into_iter().rev().zip(tys) {
             self.stack.extend(operands);
-            self.lift(ty);
+            self.in_value(ValuePath::Named(name), |me| me.lift(ty));
         }
     }
 
@@ -1536,12 +1590,20 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             .stack
             .drain(self.stack.len() - tys.len()..)
             .collect::<Vec<_>>();
-        for (operand, (_, ty)) in operands.into_iter().zip(tys) {
+        for (operand, (name, ty)) in operands.into_iter().zip(tys) {
             self.stack.push(operand);
-            self.lower(ty, nargs.as_mut());
+            self.in_value(ValuePath::Named(name), |me| me.lower(ty, nargs.as_mut()));
         }
     }
 
+    /// Runs `f` to translate the value at `path`, reporting the path to the
+    /// bindings generator.
+    fn in_value(&mut self, path: ValuePath<'_>, f: impl FnOnce(&mut Self)) {
+        self.bindgen.enter_value(path);
+        f(self);
+        self.bindgen.exit_value();
+    }
+
     /// Assumes `types.len()` values are on the stack and stores them all into
     /// the return pointer of this function, specified in the last argument.
     ///
@@ -1686,7 +1748,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             self.emit(&IterElem { element });
                             self.emit(&IterBasePointer);
                             let addr = self.stack.pop().unwrap();
-                            self.write_to_memory(element, addr, 0);
+                            self.in_value(ValuePath::Element, |me| {
+                                me.write_to_memory(element, addr, 0)
+                            });
                             self.finish_block(0);
                             self.emit(&ListLower { element, realloc });
                         }
@@ -1745,7 +1809,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             .collect::<Vec<_>>();
                         for (field, value) in record.fields.iter().zip(values) {
                             self.stack.push(value);
-                            self.lower(&field.ty, None);
+                            self.in_value(ValuePath::Field(&field.name), |me| {
+                                me.lower(&field.ty, None)
+                            });
                         }
                     }
                 },
@@ -1837,7 +1903,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             // Using the payload of this block we lower the type to
                             // raw wasm values.
                             self.stack.push(payload_name.clone());
-                            self.lower(ty, None);
+                            self.in_value(ValuePath::Case(&case.name), |me| me.lower(ty, None));
 
                             // Determine the types of all the wasm values we just
                             // pushed, and record how many. If we pushed too few
@@ -1998,7 +2064,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             self.push_block();
                             self.emit(&IterBasePointer);
                             let addr = self.stack.pop().unwrap();
-                            self.read_from_memory(element, addr, 0);
+                            self.in_value(ValuePath::Element, |me| {
+                                me.read_from_memory(element, addr, 0)
+                            });
                             self.finish_block(1);
                             self.emit(&ListLift {
                                 element,
@@ -2061,7 +2129,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             self.iface
                                 .push_wasm(self.abi, self.variant, &field.ty, &mut temp);
                             self.stack.extend(args.drain(..temp.len()));
-                            self.lift(&field.ty);
+                            self.in_value(ValuePath::Field(&field.name), |me| me.lift(&field.ty));
                         }
                         self.emit(&RecordLift {
                             record,
@@ -2164,7 +2232,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             }
 
                             // Then recursively lift this variant's payload.
-                            self.lift(ty);
+                            self.in_value(ValuePath::Case(&case.name), |me| me.lift(ty));
                         }
                         self.finish_block(case.ty.is_some() as usize);
                     }
@@ -2201,15 +2269,16 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     self.lower_and_emit(ty, addr, &I32Store { offset });
                 }
 
-                // After lowering the list there's two i32 values on the stack
-                // which we write into memory, writing the pointer into the low address
-                // and the length into the high address.
+                // After lowering the list there's two pointer-sized values on
+                // the stack which we write into memory, writing the pointer
+                // into the low address and the length into the high address.
                 TypeDefKind::List(_) => {
                     self.lower(ty, None);
+                    let size = self.bindgen.sizes().pointer_size() as i32;
                     self.stack.push(addr.clone());
-                    self.emit(&I32Store { offset: offset + 4 });
+                    self.store_pointer(offset + size);
                     self.stack.push(addr);
-                    self.emit(&I32Store { offset });
+                    self.store_pointer(offset);
                 }
 
                 // Lower the buffer to its raw values, and then write the values
@@ -2266,11 +2335,13 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         .zip(&record.fields)
                     {
                         self.stack.push(op);
-                        self.write_to_memory(
-                            &field.ty,
-                            addr.clone(),
-                            offset + (field_offset as i32),
-                        );
+                        self.in_value(ValuePath::Field(&field.name), |me| {
+                            me.write_to_memory(
+                                &field.ty,
+                                addr.clone(),
+                                offset + (field_offset as i32),
+                            )
+                        });
                     }
                 }
 
@@ -2289,7 +2360,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         self.store_intrepr(offset, v.tag);
                         if let Some(ty) = &case.ty {
                             self.stack.push(payload_name.clone());
-                            self.write_to_memory(ty, addr.clone(), payload_offset);
+                            self.in_value(ValuePath::Case(&case.name), |me| {
+                                me.write_to_memory(ty, addr.clone(), payload_offset)
+                            });
                         }
                         self.finish_block(0);
                     }
@@ -2334,10 +2407,11 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                 // Read the pointer/len and then perform the standard lifting
                 // proceses.
                 TypeDefKind::List(_) => {
+                    let size = self.bindgen.sizes().pointer_size() as i32;
                     self.stack.push(addr.clone());
-                    self.emit(&I32Load { offset });
+                    self.load_pointer(offset);
                     self.stack.push(addr);
-                    self.emit(&I32Load { offset: offset + 4 });
+                    self.load_pointer(offset + size);
                     self.lift(ty);
                 }
 
@@ -2386,11 +2460,13 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         .into_iter()
                         .zip(&record.fields)
                     {
-                        self.read_from_memory(
-                            &field.ty,
-                            addr.clone(),
-                            offset + (field_offset as i32),
-                        );
+                        self.in_value(ValuePath::Field(&field.name), |me| {
+                            me.read_from_memory(
+                                &field.ty,
+                                addr.clone(),
+                                offset + (field_offset as i32),
+                            )
+                        });
                     }
                     self.emit(&RecordLift {
                         record,
@@ -2411,7 +2487,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     for case in variant.cases.iter() {
                         self.push_block();
                         if let Some(ty) = &case.ty {
-                            self.read_from_memory(ty, addr.clone(), payload_offset);
+                            self.in_value(ValuePath::Case(&case.name), |me| {
+                                me.read_from_memory(ty, addr.clone(), payload_offset)
+                            });
                         }
                         self.finish_block(case.ty.is_some() as usize);
                     }
@@ -2449,7 +2527,25 @@ impl<'a, B: Bindgen> Generator<'a, B> {
         });
     }
 
+    fn load_pointer(&mut self, offset: i32) {
+        self.emit(&match self.iface.wasm_pointer() {
+            WasmType::I64 => Instruction::I64Load { offset },
+            _ => Instruction::I32Load { offset },
+        });
+    }
+
+    fn store_pointer(&mut self, offset: i32) {
+        self.emit(&match self.iface.wasm_pointer() {
+            WasmType::I64 => Instruction::I64Store { offset },
+            _ => Instruction::I32Store { offset },
+        });
+    }
+
     fn translate_buffer(&mut self, push: bool, ty: &Type) {
+        assert!(
+            !self.iface.memory64,
+            "buffers do not support 64-bit memories"
+        );
         let do_write = match self.lift_lower {
             // For declared items, input/output is defined in the context of
             // what the callee will do. The callee will read input buffers,
@@ -717,6 +717,21 @@ impl<'a> Value<'a> {
     }
 }
 
+/// Returns whether `name` can be written as a bare identifier, as opposed to
+/// needing to be quoted because it's a keyword, for example.
+pub fn is_bare_id(name: &str) -> bool {
+    let mut tokens = match Tokenizer::new(name) {
+        Ok(tokens) => tokens,
+        Err(_) => return false,
+    };
+    match tokens.next() {
+        Ok(Some((span, Token::Id))) => {
+            span.end as usize == name.len() && tokens.parse_id(span).is_ok()
+        }
+        _ => false,
+    }
+}
+
 fn parse_id<'a>(tokens: &mut Tokenizer<'a>) -> Result<Id<'a>> {
     match tokens.next()? {
         Some((span, Token::Id)) => Ok(Id {
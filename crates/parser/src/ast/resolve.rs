@@ -82,6 +82,8 @@ impl Resolver {
             interfaces: Default::default(),
             functions: mem::take(&mut self.functions),
             globals: mem::take(&mut self.globals),
+            memory: 0,
+            memory64: false,
         })
     }
 
@@ -7,9 +7,16 @@ use std::path::{Path, PathBuf};
 
 pub mod abi;
 mod ast;
+mod print;
 mod sizealign;
 pub use sizealign::*;
 
+/// The prefix of the name of a WebAssembly custom section which embeds an
+/// interface exported by the module, in the `*.wit` text format.
+///
+/// The rest of the section's name is the name of the interface.
+pub const INTERFACE_SECTION_PREFIX: &str = "wit-interface:";
+
 #[derive(Debug)]
 pub struct Interface {
     pub name: String,
@@ -21,6 +28,17 @@ pub struct Interface {
     pub interface_lookup: HashMap<String, InterfaceId>,
     pub functions: Vec<Function>,
     pub globals: Vec<Global>,
+    /// The index of the linear memory through which the canonical ABI passes
+    /// values for this interface, for modules with multiple memories.
+    ///
+    /// This is 0 after parsing and can be changed before generating bindings.
+    pub memory: u32,
+    /// Whether that linear memory is a 64-bit memory, in which case pointers
+    /// and lengths in the canonical ABI are `i64` rather than `i32`.
+    ///
+    /// This is `false` after parsing and can be changed before generating
+    /// bindings.
+    pub memory64: bool,
 }
 
 pub type TypeId = Id<TypeDef>;
@@ -301,6 +319,16 @@ impl Interface {
         Interface::parse_with(path, &contents, |path| load_fs(parent, path))
     }
 
+    /// Prints this interface in the `*.wit` text format.
+    ///
+    /// The printed interface is self-contained: types and resources used from
+    /// other interfaces are defined in it directly. Documentation comments
+    /// are not printed, and interfaces using constructs which only exist in
+    /// `*.witx` can't be printed.
+    pub fn to_wit(&self) -> Result<String> {
+        print::Printer::new(self)?.print()
+    }
+
     pub fn parse_with(
         filename: impl AsRef<Path>,
         contents: &str,
@@ -0,0 +1,330 @@
+use crate::abi::Abi;
+use crate::*;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Prints an interface in the `*.wit` text format.
+///
+/// Types and resources used from other interfaces are defined inline so the
+/// output can be parsed on its own. Documentation comments are not printed.
+pub(crate) struct Printer<'a> {
+    iface: &'a Interface,
+    type_names: HashMap<TypeId, &'a str>,
+    resource_names: HashMap<ResourceId, &'a str>,
+    dst: String,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(iface: &'a Interface) -> Result<Printer<'a>> {
+        // Prefer the names types and resources are known by in this interface
+        // (which may be aliases introduced by `use`) over their original names.
+        // Resources are also registered as unnamed types which are printed as
+        // handles.
+        let mut type_names = HashMap::new();
+        let mut lookup = iface.type_lookup.iter().collect::<Vec<_>>();
+        lookup.sort();
+        for (name, id) in lookup {
+            if iface.types[*id].name.is_some() {
+                type_names.entry(*id).or_insert(name.as_str());
+            }
+        }
+        for (id, ty) in iface.types.iter() {
+            if let Some(name) = &ty.name {
+                type_names.entry(id).or_insert(name.as_str());
+            }
+        }
+
+        let mut resource_names = HashMap::new();
+        let mut lookup = iface.resource_lookup.iter().collect::<Vec<_>>();
+        lookup.sort();
+        for (name, id) in lookup {
+            resource_names.entry(*id).or_insert(name.as_str());
+        }
+        for (id, resource) in iface.resources.iter() {
+            resource_names.entry(id).or_insert(resource.name.as_str());
+        }
+
+        let mut names = HashSet::new();
+        for name in type_names.values().chain(resource_names.values()) {
+            if !names.insert(*name) {
+                bail!("interface defines `{}` more than once", name);
+            }
+        }
+
+        Ok(Printer {
+            iface,
+            type_names,
+            resource_names,
+            dst: String::new(),
+        })
+    }
+
+    pub fn print(mut self) -> Result<String> {
+        let iface = self.iface;
+
+        for (id, _) in iface.resources.iter() {
+            let name = self.resource_names[&id];
+            self.dst.push_str("resource ");
+            self.id(name);
+
+            let funcs = iface
+                .functions
+                .iter()
+                .filter_map(|f| match &f.kind {
+                    FunctionKind::Static { resource, .. } if *resource == id => Some((f, true)),
+                    FunctionKind::Method { resource, .. } if *resource == id => Some((f, false)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if funcs.is_empty() {
+                self.dst.push('\n');
+                continue;
+            }
+
+            self.dst.push_str(" {\n");
+            for (func, statik) in funcs {
+                self.dst.push_str("  ");
+                if statik {
+                    self.dst.push_str("static ");
+                }
+                // Methods implicitly take `self` as their first parameter.
+                self.function(func, if statik { 0 } else { 1 })?;
+            }
+            self.dst.push_str("}\n");
+        }
+
+        for (id, ty) in iface.types.iter() {
+            if ty.name.is_some() {
+                self.type_def(id, ty)?;
+            }
+        }
+
+        for func in iface.functions.iter() {
+            if let FunctionKind::Freestanding = func.kind {
+                self.function(func, 0)?;
+            }
+        }
+
+        for global in iface.globals.iter() {
+            self.id(&global.name);
+            self.dst.push_str(": ");
+            self.ty(&global.ty)?;
+            self.dst.push('\n');
+        }
+
+        Ok(self.dst)
+    }
+
+    fn type_def(&mut self, id: TypeId, ty: &TypeDef) -> Result<()> {
+        let name = self.type_names[&id];
+        match &ty.kind {
+            TypeDefKind::Record(r) if !r.is_tuple() => {
+                let flags = match r.kind {
+                    RecordKind::Flags(None) => true,
+                    RecordKind::Flags(Some(_)) => {
+                        bail!("flags `{}` has an explicit representation", name)
+                    }
+                    _ => false,
+                };
+                self.dst.push_str(if flags { "flags " } else { "record " });
+                self.id(name);
+                self.dst.push_str(" {\n");
+                for field in r.fields.iter() {
+                    self.dst.push_str("  ");
+                    self.id(&field.name);
+                    if !flags {
+                        self.dst.push_str(": ");
+                        self.ty(&field.ty)?;
+                    }
+                    self.dst.push_str(",\n");
+                }
+                self.dst.push_str("}\n");
+            }
+            TypeDefKind::Variant(v)
+                if !v.is_bool() && v.as_option().is_none() && v.as_expected().is_none() =>
+            {
+                if v.tag != Variant::infer_tag(v.cases.len()) {
+                    bail!("variant `{}` has an explicit tag", name);
+                }
+                let union = v
+                    .cases
+                    .iter()
+                    .enumerate()
+                    .all(|(i, c)| c.name == i.to_string() && c.ty.is_some());
+                self.dst.push_str(if union {
+                    "union "
+                } else if v.is_enum() {
+                    "enum "
+                } else {
+                    "variant "
+                });
+                self.id(name);
+                self.dst.push_str(" {\n");
+                for case in v.cases.iter() {
+                    self.dst.push_str("  ");
+                    if union {
+                        self.ty(case.ty.as_ref().unwrap())?;
+                    } else {
+                        self.id(&case.name);
+                        if let Some(ty) = &case.ty {
+                            self.dst.push('(');
+                            self.ty(ty)?;
+                            self.dst.push(')');
+                        }
+                    }
+                    self.dst.push_str(",\n");
+                }
+                self.dst.push_str("}\n");
+            }
+            kind => {
+                self.dst.push_str("type ");
+                self.id(name);
+                self.dst.push_str(" = ");
+                self.type_def_kind(name, kind)?;
+                self.dst.push('\n');
+            }
+        }
+        Ok(())
+    }
+
+    fn type_def_kind(&mut self, name: &str, kind: &TypeDefKind) -> Result<()> {
+        match kind {
+            TypeDefKind::Type(t) => self.ty(t)?,
+            TypeDefKind::List(Type::Char) => self.dst.push_str("string"),
+            TypeDefKind::List(t) => self.generic("list", t)?,
+            TypeDefKind::PushBuffer(t) => self.generic("push-buffer", t)?,
+            TypeDefKind::PullBuffer(t) => self.generic("pull-buffer", t)?,
+            TypeDefKind::Record(r) if r.is_tuple() => {
+                self.dst.push_str("tuple<");
+                for (i, field) in r.fields.iter().enumerate() {
+                    if i > 0 {
+                        self.dst.push_str(", ");
+                    }
+                    self.ty(&field.ty)?;
+                }
+                self.dst.push('>');
+            }
+            TypeDefKind::Variant(v) if v.is_bool() => self.dst.push_str("bool"),
+            TypeDefKind::Variant(v) if v.as_option().is_some() => {
+                self.generic("option", v.as_option().unwrap())?
+            }
+            TypeDefKind::Variant(v) if v.as_expected().is_some() => {
+                let (ok, err) = v.as_expected().unwrap();
+                self.dst.push_str("expected<");
+                self.opt_ty(ok)?;
+                self.dst.push_str(", ");
+                self.opt_ty(err)?;
+                self.dst.push('>');
+            }
+            TypeDefKind::Record(_) | TypeDefKind::Variant(_) => {
+                bail!("anonymous type in `{}` cannot be represented", name)
+            }
+            TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
+                bail!("pointer types cannot be represented")
+            }
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, func: &Function, skip_params: usize) -> Result<()> {
+        if func.abi != Abi::Canonical {
+            bail!("function `{}` does not use the canonical ABI", func.name);
+        }
+        self.id(func.item_name());
+        self.dst.push_str(": ");
+        if func.is_async {
+            self.dst.push_str("async ");
+        }
+        self.dst.push_str("function(");
+        for (i, (name, ty)) in func.params.iter().skip(skip_params).enumerate() {
+            if i > 0 {
+                self.dst.push_str(", ");
+            }
+            self.id(name);
+            self.dst.push_str(": ");
+            self.ty(ty)?;
+        }
+        self.dst.push(')');
+        match func.results.as_slice() {
+            [] => {}
+            [(name, ty)] if name.is_empty() => {
+                self.dst.push_str(" -> ");
+                self.ty(ty)?;
+            }
+            results => {
+                self.dst.push_str(" -> (");
+                for (i, (name, ty)) in results.iter().enumerate() {
+                    if i > 0 {
+                        self.dst.push_str(", ");
+                    }
+                    if !name.is_empty() {
+                        self.id(name);
+                        self.dst.push_str(": ");
+                    }
+                    self.ty(ty)?;
+                }
+                self.dst.push(')');
+            }
+        }
+        self.dst.push('\n');
+        Ok(())
+    }
+
+    fn generic(&mut self, name: &str, ty: &Type) -> Result<()> {
+        self.dst.push_str(name);
+        self.dst.push('<');
+        self.ty(ty)?;
+        self.dst.push('>');
+        Ok(())
+    }
+
+    fn opt_ty(&mut self, ty: Option<&Type>) -> Result<()> {
+        match ty {
+            Some(ty) => self.ty(ty),
+            None => {
+                self.dst.push('_');
+                Ok(())
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) -> Result<()> {
+        match ty {
+            Type::U8 => self.dst.push_str("u8"),
+            Type::U16 => self.dst.push_str("u16"),
+            Type::U32 => self.dst.push_str("u32"),
+            Type::U64 => self.dst.push_str("u64"),
+            Type::S8 => self.dst.push_str("s8"),
+            Type::S16 => self.dst.push_str("s16"),
+            Type::S32 => self.dst.push_str("s32"),
+            Type::S64 => self.dst.push_str("s64"),
+            Type::F32 => self.dst.push_str("f32"),
+            Type::F64 => self.dst.push_str("f64"),
+            Type::Char => self.dst.push_str("char"),
+            Type::CChar | Type::Usize => bail!("witx-only type cannot be represented"),
+            Type::Handle(id) => {
+                let name = self.resource_names[id];
+                self.dst.push_str("handle ");
+                self.id(name);
+            }
+            Type::Id(id) => match self.type_names.get(id).copied() {
+                Some(name) => self.id(name),
+                None => {
+                    let iface = self.iface;
+                    self.type_def_kind("<anonymous>", &iface.types[*id].kind)?
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn id(&mut self, name: &str) {
+        if ast::is_bare_id(name) {
+            self.dst.push_str(name);
+        } else {
+            self.dst.push('"');
+            self.dst.push_str(name);
+            self.dst.push('"');
+        }
+    }
+}
@@ -4,11 +4,13 @@ use crate::{Int, Interface, Record, RecordKind, Type, TypeDef, TypeDefKind, Vari
 #[derive(Default)]
 pub struct SizeAlign {
     map: Vec<(usize, usize)>,
+    memory64: bool,
 }
 
 impl SizeAlign {
     pub fn fill(&mut self, variant: AbiVariant, iface: &Interface) {
         self.map = vec![(0, 0); iface.types.len()];
+        self.memory64 = iface.memory64;
         for ty in iface.topological_types() {
             let pair = self.calculate(variant, &iface.types[ty]);
             self.map[ty.index()] = pair;
@@ -18,7 +20,7 @@ impl SizeAlign {
     fn calculate(&self, variant: AbiVariant, ty: &TypeDef) -> (usize, usize) {
         match &ty.kind {
             TypeDefKind::Type(t) => (self.size(t), self.align(t)),
-            TypeDefKind::List(_) => (8, 4),
+            TypeDefKind::List(_) => (2 * self.pointer_size(), self.pointer_size()),
             TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => (4, 4),
             TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => match variant {
                 AbiVariant::GuestImport => (12, 4),
@@ -82,6 +84,16 @@ impl SizeAlign {
         }
     }
 
+    /// The size and alignment of pointers and lengths, which is 4 for 32-bit
+    /// memories and 8 for 64-bit memories.
+    pub fn pointer_size(&self) -> usize {
+        if self.memory64 {
+            8
+        } else {
+            4
+        }
+    }
+
     pub fn field_offsets(&self, record: &Record) -> Vec<usize> {
         let mut cur = 0;
         record
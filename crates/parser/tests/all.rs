@@ -115,6 +115,7 @@ impl Runner<'_> {
             }
         } else {
             let instance = result?;
+            check_round_trip(&instance)?;
             to_json(&instance)
         };
 
@@ -153,6 +154,47 @@ impl Runner<'_> {
     }
 }
 
+/// Checks that printing an interface as `*.wit` text yields an interface with
+/// the same ABI when parsed again.
+fn check_round_trip(i: &Interface) -> Result<()> {
+    let wit = i.to_wit()?;
+    let printed = Interface::parse(&i.name, &wit)
+        .context(format!("failed to parse printed interface:\n{}", wit))?;
+
+    let names = |i: &Interface| {
+        i.functions
+            .iter()
+            .map(|f| f.name.clone())
+            .collect::<Vec<_>>()
+    };
+    let mut expected = names(i);
+    let mut actual = names(&printed);
+    expected.sort();
+    actual.sort();
+    if expected != actual {
+        bail!(
+            "printed interface has functions {:?} instead of {:?}",
+            actual,
+            expected
+        );
+    }
+
+    for f in i.functions.iter() {
+        let g = printed.functions.iter().find(|g| g.name == f.name).unwrap();
+        for variant in [abi::AbiVariant::GuestImport, abi::AbiVariant::GuestExport] {
+            if i.wasm_signature(variant, f) != printed.wasm_signature(variant, g) {
+                bail!(
+                    "printed function `{}` has a different signature:\n{}",
+                    f.name,
+                    wit
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn to_json(i: &Interface) -> String {
     #[derive(Serialize)]
     struct Interface {
@@ -50,6 +50,8 @@ mod kw {
     syn::custom_keyword!(paths);
     syn::custom_keyword!(unchecked);
     syn::custom_keyword!(multi_module);
+    syn::custom_keyword!(embed_interface);
+    syn::custom_keyword!(memory64);
 }
 
 impl Parse for Opts {
@@ -61,14 +63,20 @@ impl Parse for Opts {
             let content;
             syn::braced!(content in input);
             let mut interfaces = Vec::new();
+            let mut memory64 = false;
             let fields = Punctuated::<ConfigField, Token![,]>::parse_terminated(&content)?;
             for field in fields.into_pairs() {
                 match field.into_value() {
                     ConfigField::Unchecked => opts.unchecked = true,
                     ConfigField::MultiModule => opts.multi_module = true,
+                    ConfigField::EmbedInterface => opts.embed_interface = true,
+                    ConfigField::Memory64 => memory64 = true,
                     ConfigField::Interfaces(v) => interfaces = v,
                 }
             }
+            for iface in interfaces.iter_mut() {
+                iface.memory64 = memory64;
+            }
             if interfaces.is_empty() {
                 return Err(Error::new(
                     call_site,
@@ -100,6 +108,8 @@ enum ConfigField {
     Interfaces(Vec<Interface>),
     Unchecked,
     MultiModule,
+    EmbedInterface,
+    Memory64,
 }
 
 impl Parse for ConfigField {
@@ -135,6 +145,12 @@ impl Parse for ConfigField {
         } else if l.peek(kw::multi_module) {
             input.parse::<kw::multi_module>()?;
             Ok(ConfigField::MultiModule)
+        } else if l.peek(kw::embed_interface) {
+            input.parse::<kw::embed_interface>()?;
+            Ok(ConfigField::EmbedInterface)
+        } else if l.peek(kw::memory64) {
+            input.parse::<kw::memory64>()?;
+            Ok(ConfigField::Memory64)
         } else {
             Err(l.error())
         }
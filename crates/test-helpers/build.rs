@@ -163,6 +163,87 @@ fn main() {
         }
     }
 
+    #[cfg(feature = "wasm-spidermonkey-wizer")]
+    {
+        let smw_path = "../gen-spidermonkey/spidermonkey-wasm/spidermonkey.wasm";
+        println!("cargo:rerun-if-changed={}", smw_path);
+        let smw = fs::read(smw_path).expect("failed to read `spidermonkey.wasm`");
+
+        for test_dir in fs::read_dir("../../tests/runtime").unwrap() {
+            let test_dir = test_dir.unwrap().path();
+            let js_impl = test_dir.join("wasm.js");
+            if !js_impl.exists() {
+                continue;
+            }
+
+            let import = Interface::parse_file(&test_dir.join("imports.wit")).unwrap();
+            let export = Interface::parse_file(&test_dir.join("exports.wit")).unwrap();
+            let mut files = Default::default();
+            let js = fs::read_to_string(&js_impl).unwrap();
+            let mut gen = wit_bindgen_gen_spidermonkey::SpiderMonkeyWasm::new("wasm.js", &js);
+            gen.spidermonkey_wasm(&smw[..]);
+            gen.pre_initialize(true);
+            gen.generate_all(&[import], &[export], &mut files);
+
+            let out_dir = out_dir.join(format!(
+                "js-wizer-{}",
+                test_dir.file_name().unwrap().to_str().unwrap()
+            ));
+            drop(fs::remove_dir_all(&out_dir));
+            fs::create_dir(&out_dir).unwrap();
+            for (file, contents) in files.iter() {
+                let dst = out_dir.join(file);
+                fs::write(dst, contents).unwrap();
+            }
+
+            wasms.push((
+                "spidermonkey_wizer",
+                test_dir.file_stem().unwrap().to_str().unwrap().to_string(),
+                out_dir.join("wasm.wasm").to_str().unwrap().to_string(),
+            ));
+        }
+    }
+
+    if cfg!(feature = "wasm-cpython") {
+        for test_dir in fs::read_dir("../../tests/runtime").unwrap() {
+            let test_dir = test_dir.unwrap().path();
+            let py_impl = test_dir.join("wasm.py");
+            if !py_impl.exists() {
+                continue;
+            }
+            let imports = test_dir.join("imports.wit");
+            let exports = test_dir.join("exports.wit");
+            println!("cargo:rerun-if-changed={}", imports.display());
+            println!("cargo:rerun-if-changed={}", exports.display());
+            println!("cargo:rerun-if-changed={}", py_impl.display());
+
+            let import = Interface::parse_file(&test_dir.join("imports.wit")).unwrap();
+            let export = Interface::parse_file(&test_dir.join("exports.wit")).unwrap();
+            let mut files = Default::default();
+            let py = fs::read_to_string(&py_impl).unwrap();
+            let mut gen = wit_bindgen_gen_cpython::CPythonWasm::new("wasm.py", &py);
+            gen.import_cpython(true);
+            gen.generate_all(&[import], &[export], &mut files);
+
+            let out_dir = out_dir.join(format!(
+                "py-{}",
+                test_dir.file_name().unwrap().to_str().unwrap()
+            ));
+            drop(fs::remove_dir_all(&out_dir));
+            fs::create_dir(&out_dir).unwrap();
+            for (file, contents) in files.iter() {
+                let dst = out_dir.join(file);
+                fs::write(dst, contents).unwrap();
+            }
+
+            wasms.push((
+                "cpython",
+                test_dir.file_stem().unwrap().to_str().unwrap().to_string(),
+                out_dir.join("wasm.wasm").to_str().unwrap().to_string(),
+            ));
+        }
+    }
+
     let src = format!("const WASMS: &[(&str, &str, &str)] = &{:?};", wasms);
     std::fs::write(out_dir.join("wasms.rs"), src).unwrap();
 }
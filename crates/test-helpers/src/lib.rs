@@ -52,6 +52,7 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
                 || {
                     let mut opts = wit_bindgen_gen_rust_wasm::Opts::default();
                     opts.unchecked = true;
+                    opts.embed_interface = true;
                     opts.symbol_namespace = "unchecked".to_string();
                     opts.build()
                 },
@@ -353,6 +354,26 @@ pub fn codegen_spidermonkey_export(input: TokenStream) -> TokenStream {
     })
 }
 
+#[proc_macro]
+#[cfg(feature = "wit-bindgen-gen-cpython")]
+pub fn codegen_cpython_import(input: TokenStream) -> TokenStream {
+    gen_verify(input, Direction::Import, "import", || {
+        let mut gen = wit_bindgen_gen_cpython::CPythonWasm::new("foo.py", "");
+        gen.import_cpython(true);
+        gen
+    })
+}
+
+#[proc_macro]
+#[cfg(feature = "wit-bindgen-gen-cpython")]
+pub fn codegen_cpython_export(input: TokenStream) -> TokenStream {
+    gen_verify(input, Direction::Export, "export", || {
+        let mut gen = wit_bindgen_gen_cpython::CPythonWasm::new("foo.py", "");
+        gen.import_cpython(true);
+        gen
+    })
+}
+
 fn generate_tests<G>(
     input: TokenStream,
     dir: &str,
@@ -0,0 +1,74 @@
+//! Benchmarks passing lists between linked modules.
+//!
+//! The `main` module calls `get` to receive a list from the `a` module and then passes it back with `put`.
+//! Both modules import their memory and allocator from the `alloc` module: when `alloc` is shared, the
+//! returned list is moved to `main` without being copied; otherwise each module has its own memory and
+//! the lists are copied with a single `memory.copy` in both directions.
+//!
+//! Run with `cargo bench -p test-modules`.
+
+use anyhow::Result;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::collections::HashMap;
+use wasmlink::{InstancePolicy, Linker, Module};
+
+const LENGTHS: &[u32] = &[1024, 64 * 1024, 1024 * 1024];
+
+fn link(policy: InstancePolicy) -> Result<Vec<u8>> {
+    let main = wat::parse_str(include_str!("lists/main.wat"))?;
+    let a = wat::parse_str(include_str!("lists/a.wat"))?;
+    let alloc = wat::parse_str(include_str!("lists/alloc.wat"))?;
+
+    let main = Module::new("main", &main, [])?;
+
+    let mut imports = HashMap::new();
+    imports.insert(
+        "a",
+        Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse(
+                "a",
+                include_str!("lists/a.wit"),
+            )?],
+        )?,
+    );
+    imports.insert("alloc", Module::new("alloc", &alloc, [])?);
+
+    Linker::new(test_modules::profile()?)
+        .with_instance_policy(policy)
+        .link(&main, &imports)
+}
+
+fn bench_lists(c: &mut Criterion) {
+    use wasmtime::{Config, Engine, Instance, Module, Store};
+
+    let mut config = Config::new();
+    config.wasm_module_linking(true);
+    config.wasm_multi_memory(true);
+    let engine = Engine::new(&config).unwrap();
+
+    let mut group = c.benchmark_group("lists");
+    for (name, policy) in [
+        ("isolated", InstancePolicy::Isolated),
+        ("shared", InstancePolicy::Shared),
+    ] {
+        let module = Module::new(&engine, &link(policy).unwrap()).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let run = instance
+            .get_typed_func::<(u32, u32), (), _>(&mut store, "run")
+            .unwrap();
+
+        for len in LENGTHS {
+            group.throughput(Throughput::Bytes(u64::from(*len)));
+            group.bench_with_input(BenchmarkId::new(name, len), len, |b, len| {
+                b.iter(|| run.call(&mut store, (*len, 1)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lists);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+(module
+  (import "alloc" "memory" (memory 40))
+  (import "alloc" "canonical_abi_realloc" (func $realloc (param i32 i32 i32 i32) (result i32)))
+  (import "alloc" "canonical_abi_free" (func $free (param i32 i32 i32)))
+  (export "memory" (memory 0))
+  (export "canonical_abi_realloc" (func $realloc))
+  (export "canonical_abi_free" (func $free))
+  (func (export "get") (param $len i32) (result i32)
+    i32.const 16
+    i32.const 0
+    i32.const 0
+    i32.const 1
+    local.get $len
+    call $realloc
+    i32.store
+    i32.const 16
+    local.get $len
+    i32.store offset=4
+    i32.const 16)
+  (func (export "put") (param $ptr i32) (param $len i32)
+    local.get $ptr
+    local.get $len
+    i32.const 1
+    call $free))
@@ -0,0 +1,29 @@
+(module
+  (memory (export "memory") 40)
+  (global $top (mut i32) (i32.const 1024))
+  (func (export "canonical_abi_realloc") (param $old i32) (param $old_size i32) (param $align i32) (param $size i32) (result i32)
+    (local $ptr i32)
+    global.get $top
+    local.get $align
+    i32.add
+    i32.const 1
+    i32.sub
+    i32.const 0
+    local.get $align
+    i32.sub
+    i32.and
+    local.tee $ptr
+    local.get $size
+    i32.add
+    global.set $top
+    local.get $ptr)
+  (func (export "canonical_abi_free") (param $ptr i32) (param $size i32) (param $align i32)
+    local.get $ptr
+    local.get $size
+    i32.add
+    global.get $top
+    i32.eq
+    if
+      local.get $ptr
+      global.set $top
+    end))
@@ -0,0 +1,35 @@
+(module
+  (import "alloc" "memory" (memory 40))
+  (import "alloc" "canonical_abi_realloc" (func $realloc (param i32 i32 i32 i32) (result i32)))
+  (import "alloc" "canonical_abi_free" (func $free (param i32 i32 i32)))
+  (import "a" "get" (func $get (param i32 i32)))
+  (import "a" "put" (func $put (param i32 i32)))
+  (export "memory" (memory 0))
+  (export "canonical_abi_realloc" (func $realloc))
+  (export "canonical_abi_free" (func $free))
+  (func (export "run") (param $len i32) (param $iterations i32)
+    (local $ptr i32)
+    block
+      loop
+        local.get $iterations
+        i32.eqz
+        br_if 1
+        local.get $len
+        i32.const 0
+        call $get
+        i32.const 0
+        i32.load
+        local.tee $ptr
+        local.get $len
+        call $put
+        local.get $ptr
+        local.get $len
+        i32.const 1
+        call $free
+        local.get $iterations
+        i32.const 1
+        i32.sub
+        local.set $iterations
+        br 0
+      end
+    end))
@@ -1,3 +1,20 @@
+use anyhow::Result;
+use wasmlink::Profile;
+
+/// Creates the link profile for the test modules.
+///
+/// The profile provides the WASI snapshot preview1 functions, which the
+/// `wasm32-wasi` test modules import.
+pub fn profile() -> Result<Profile> {
+    let interface = wit_parser::Interface::parse_file(
+        "../../tests/codegen/wasi_snapshot_preview1/wasi-snapshot-preview1.witx",
+    )?;
+
+    let mut profile = Profile::new();
+    profile.add_interface("wasi_snapshot_preview1", &interface)?;
+    Ok(profile)
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -6,7 +23,7 @@ mod tests {
         fs,
         path::{Path, PathBuf},
     };
-    use wasmlink::{Linker, Module, Profile};
+    use wasmlink::{Linker, Module};
     use wasmtime_wasi::WasiCtxBuilder;
 
     fn module_path(name: &str) -> PathBuf {
@@ -53,7 +70,7 @@ mod tests {
             })
             .collect::<Result<HashMap<_, _>>>()?;
 
-        let linker = Linker::new(Profile::new());
+        let linker = Linker::new(crate::profile()?);
         linker.link(&main_module, &import_modules)
     }
 
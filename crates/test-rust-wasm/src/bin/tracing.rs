@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/tracing/wasm.rs");
+
+fn main() {}
@@ -0,0 +1,127 @@
+use serde_json::{json, Value};
+use std::fmt::Write;
+use wasmlink::{LinkPlan, Signature};
+
+fn signature_to_json(signature: &Signature) -> Value {
+    json!({
+        "params": signature.params,
+        "results": signature.results,
+        "retptr": signature.retptr,
+    })
+}
+
+/// Converts a link plan to JSON.
+pub fn to_json(plan: &LinkPlan) -> Value {
+    json!({
+        "modules": plan.modules.iter().map(|m| json!({
+            "name": m.name,
+            "imports": m.imports,
+            "must_adapt": m.must_adapt,
+            "string_encoding": m.string_encoding.to_string(),
+            "functions": m.functions.iter().map(|f| json!({
+                "interface": f.interface,
+                "name": f.name,
+                "must_adapt": f.must_adapt,
+                "import_signature": signature_to_json(&f.import_signature),
+                "export_type": signature_to_json(&f.export_type),
+            })).collect::<Vec<_>>(),
+            "resources": m.resources.iter().map(|r| json!({
+                "name": r.name,
+                "id": r.id,
+                "drop_callback": r.drop_callback,
+            })).collect::<Vec<_>>(),
+            "profile_imports": m.profile_imports.iter().map(|i| json!({
+                "module": i.module,
+                "field": i.field,
+                "type": signature_to_json(&i.ty),
+            })).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+        "needs_runtime": plan.needs_runtime,
+    })
+}
+
+/// Converts a link plan to human-readable text.
+pub fn to_text(plan: &LinkPlan) -> String {
+    let mut text = String::new();
+
+    for module in &plan.modules {
+        writeln!(
+            text,
+            "module `{}` ({}, {} strings)",
+            module.name,
+            if module.must_adapt {
+                "adapted"
+            } else {
+                "not adapted"
+            },
+            module.string_encoding
+        )
+        .unwrap();
+
+        if !module.imports.is_empty() {
+            writeln!(
+                text,
+                "  imports: {}",
+                module
+                    .imports
+                    .iter()
+                    .map(|i| format!("`{}`", i))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .unwrap();
+        }
+
+        if !module.functions.is_empty() {
+            writeln!(text, "  functions:").unwrap();
+            for f in &module.functions {
+                writeln!(
+                    text,
+                    "    `{}` from interface `{}` ({})",
+                    f.name,
+                    f.interface,
+                    if f.must_adapt {
+                        "adapted"
+                    } else {
+                        "not adapted"
+                    }
+                )
+                .unwrap();
+                writeln!(text, "      import signature: {}", f.import_signature).unwrap();
+                writeln!(text, "      export type: {}", f.export_type).unwrap();
+            }
+        }
+
+        if !module.resources.is_empty() {
+            writeln!(text, "  resources:").unwrap();
+            for r in &module.resources {
+                writeln!(
+                    text,
+                    "    `{}` (id {}) dropped by `{}`",
+                    r.name, r.id, r.drop_callback
+                )
+                .unwrap();
+            }
+        }
+
+        if !module.profile_imports.is_empty() {
+            writeln!(text, "  profile imports:").unwrap();
+            for i in &module.profile_imports {
+                writeln!(text, "    `{}` from `{}`: {}", i.field, i.module, i.ty).unwrap();
+            }
+        }
+    }
+
+    writeln!(
+        text,
+        "resource runtime: {}",
+        if plan.needs_runtime {
+            "linked"
+        } else {
+            "not linked"
+        }
+    )
+    .unwrap();
+
+    text
+}
@@ -2,10 +2,16 @@
 
 #![deny(missing_docs)]
 
+mod inspect;
+
 use anyhow::{bail, Context, Result};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 use structopt::{clap::AppSettings, StructOpt};
-use wasmlink::{Linker, Module, Profile};
+use wasmlink::{InstancePolicy, Linker, Module, Profile, StringEncoding};
 
 fn parse_module(s: &str) -> Result<(String, PathBuf)> {
     match s.split_once('=') {
@@ -21,55 +27,123 @@ fn parse_interface(s: &str) -> Result<(String, PathBuf)> {
     }
 }
 
-/// WebAssembly module linker.
+fn parse_profile(s: &str) -> (Option<String>, PathBuf) {
+    match s.split_once('=') {
+        Some((name, path)) => (Some(name.into()), path.into()),
+        None => (None, s.into()),
+    }
+}
+
+fn parse_instance_policy(s: &str) -> Result<InstancePolicy> {
+    match s {
+        "isolated" => Ok(InstancePolicy::Isolated),
+        "shared" => Ok(InstancePolicy::Shared),
+        _ => bail!("expected `isolated` or `shared`"),
+    }
+}
+
+fn parse_string_encoding(s: &str) -> Result<(Option<String>, StringEncoding)> {
+    match s.split_once('=') {
+        Some((name, encoding)) => Ok((Some(name.into()), encoding.parse()?)),
+        None => Ok((None, s.parse()?)),
+    }
+}
+
+fn load_profile(profile: &mut Profile, name: Option<&str>, path: &Path) -> Result<()> {
+    if !path.is_file() {
+        bail!("profile file `{}` does not exist", path.display());
+    }
+
+    match path.extension().and_then(OsStr::to_str) {
+        Some("wit") | Some("witx") | Some("md") => {
+            let interface = wit_parser::Interface::parse_file(path)?;
+
+            profile.add_interface(name.unwrap_or(&interface.name), &interface)
+        }
+        _ => {
+            if name.is_some() {
+                bail!(
+                    "profile module `{}` cannot be given a name as it names its own imports",
+                    path.display()
+                );
+            }
+
+            profile.add_module(&wat::parse_file(path)?)
+        }
+    }
+    .with_context(|| format!("failed to load profile `{}`", path.display()))
+}
+
+/// The modules, interfaces, and profiles to link.
 #[derive(Debug, StructOpt)]
-#[structopt(name = "wasmlink", version = env!("CARGO_PKG_VERSION"), global_settings = &[
-    AppSettings::VersionlessSubcommands,
-    AppSettings::ColoredHelp,
-    AppSettings::ArgRequiredElseHelp,
-])]
-pub struct App {
+pub struct Inputs {
     /// A transitive imported module to the module being linked.
     #[structopt(long = "module", short = "m", value_name = "NAME=MODULE", parse(try_from_str = parse_module), required = true, min_values = 1)]
     pub modules: Vec<(String, PathBuf)>,
 
     /// The path to an interface definition file for an imported module.
+    ///
+    /// Defaults to the interfaces embedded in the imported module's custom sections, if any.
     #[structopt(long = "interface", short = "i", value_name = "NAME=INTERFACE", parse(try_from_str = parse_interface))]
     pub interfaces: Vec<(String, PathBuf)>,
 
-    /// The name of the target profile to use for the link.
-    #[structopt(long, short = "p", value_name = "PROFILE")]
-    pub profile: String,
+    /// A target profile file describing the host functions provided to linked modules.
+    ///
+    /// Interface files (`.wit`, `.witx`, or `.md`) provide their functions from a module with the given name,
+    /// defaulting to the interface's name; any other file is a module whose function imports are provided.
+    #[structopt(long = "profile", short = "p", value_name = "[NAME=]PROFILE", parse(from_str = parse_profile))]
+    pub profiles: Vec<(Option<String>, PathBuf)>,
 
-    /// The path of the output linked module; defaults to replacing the given module.
-    #[structopt(long, short = "o", value_name = "OUTPUT", parse(from_os_str))]
-    pub output: Option<PathBuf>,
+    /// The string encoding (`utf8` or `utf16`) of a linked module's interfaces.
+    ///
+    /// Without a name, the encoding applies to the module being linked. Modules default to `utf8`;
+    /// the adapters transcode strings passed between modules with different encodings.
+    #[structopt(long = "string-encoding", short = "e", value_name = "[NAME=]ENCODING", parse(try_from_str = parse_string_encoding))]
+    pub string_encodings: Vec<(Option<String>, StringEncoding)>,
 
     /// The module to link.
     #[structopt(index = 1, value_name = "MODULE", parse(from_os_str))]
-    pub module: PathBuf,
+    pub module: Option<PathBuf>,
 }
 
-impl App {
-    /// Executes the application.
-    pub fn execute(self) -> Result<()> {
+impl Inputs {
+    fn module_path(&self) -> Result<&Path> {
+        match &self.module {
+            Some(path) => Ok(path),
+            None => bail!("a module to link must be specified"),
+        }
+    }
+
+    /// Loads the inputs and calls the given function with the module, its imports, and the profile.
+    fn load<T>(
+        &self,
+        f: impl FnOnce(&Module, &HashMap<&str, Module>, Profile) -> Result<T>,
+    ) -> Result<T> {
         if self.modules.is_empty() {
             bail!("at least one import module must be specified");
         }
 
-        let module_bytes = wat::parse_file(&self.module)
-            .with_context(|| format!("failed to parse module `{}`", self.module.display()))?;
+        let mut string_encodings = self
+            .string_encodings
+            .iter()
+            .cloned()
+            .collect::<HashMap<_, _>>();
+
+        let module_path = self.module_path()?;
+        let module_bytes = wat::parse_file(module_path)
+            .with_context(|| format!("failed to parse module `{}`", module_path.display()))?;
 
         let module = Module::new(
-            self.module.file_name().unwrap().to_str().unwrap(),
+            module_path.file_name().unwrap().to_str().unwrap(),
             &module_bytes,
             [],
         )
-        .with_context(|| format!("failed to parse module `{}`", self.module.display()))?;
+        .with_context(|| format!("failed to parse module `{}`", module_path.display()))?
+        .with_string_encoding(string_encodings.remove(&None).unwrap_or_default());
 
         let import_bytes = self
             .modules
-            .into_iter()
+            .iter()
             .map(|(name, path)| {
                 if !path.is_file() {
                     bail!(
@@ -88,14 +162,14 @@ impl App {
 
         let mut import_interfaces = self
             .interfaces
-            .into_iter()
+            .iter()
             .map(|(name, path)| {
                 if !path.is_file() {
                     bail!("interface file `{}` does not exist", path.display());
                 }
 
                 Ok((
-                    name,
+                    name.as_str(),
                     wit_parser::Interface::parse_file(&path).with_context(|| {
                         format!("failed to parse interface file `{}`", path.display())
                     })?,
@@ -106,20 +180,154 @@ impl App {
         let import_modules: HashMap<&str, Module> = import_bytes
             .iter()
             .map(|(name, bytes)| {
-                let name = name.as_ref();
+                let name = name.as_str();
+                let encoding = string_encodings
+                    .remove(&Some(name.to_string()))
+                    .unwrap_or_default();
                 Ok((
                     name,
-                    Module::new(name, bytes, import_interfaces.remove(name))?,
+                    Module::new(name, bytes, import_interfaces.remove(name))?
+                        .with_string_encoding(encoding),
                 ))
             })
             .collect::<Result<HashMap<_, _>>>()?;
 
-        // TODO: do something with the profile option
+        if let Some(name) = string_encodings.keys().flatten().next() {
+            bail!(
+                "a string encoding was given for module `{}` but it is not an import module",
+                name
+            );
+        }
 
-        let linker = Linker::new(Profile::new());
+        let mut profile = Profile::new();
+        for (name, path) in &self.profiles {
+            load_profile(&mut profile, name.as_deref(), path)?;
+        }
+
+        f(&module, &import_modules, profile)
+    }
+}
+
+/// Explains how a module would be linked without linking it.
+///
+/// Prints the module graph, the functions that are adapted with their signatures, the resources with their drop
+/// callbacks, and the imports satisfied by the profiles.
+#[derive(Debug, StructOpt)]
+pub struct InspectCommand {
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub inputs: Inputs,
+
+    /// Print the link plan as JSON.
+    #[structopt(long)]
+    pub json: bool,
+}
+
+impl InspectCommand {
+    /// Executes the command.
+    pub fn execute(self) -> Result<()> {
+        let plan = self
+            .inputs
+            .load(|module, imports, profile| Linker::new(profile).inspect(module, imports))?;
+
+        if self.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&inspect::to_json(&plan))?
+            );
+        } else {
+            print!("{}", inspect::to_text(&plan));
+        }
+
+        Ok(())
+    }
+}
+
+/// The wasmlink subcommands.
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Explains how a module would be linked without linking it.
+    Inspect(InspectCommand),
+}
+
+/// WebAssembly module linker.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "wasmlink", version = env!("CARGO_PKG_VERSION"), global_settings = &[
+    AppSettings::VersionlessSubcommands,
+    AppSettings::ColoredHelp,
+    AppSettings::ArgRequiredElseHelp,
+], settings = &[AppSettings::SubcommandsNegateReqs, AppSettings::ArgsNegateSubcommands])]
+pub struct App {
+    #[structopt(subcommand)]
+    #[allow(missing_docs)]
+    pub command: Option<Command>,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub inputs: Inputs,
+
+    /// How to instantiate a module imported by more than one module.
+    ///
+    /// `isolated` gives each importing module its own instance; `shared` instantiates the module once.
+    /// Modules adapted by an interface are always instantiated per importing module.
+    #[structopt(long, value_name = "POLICY", default_value = "isolated", possible_values = &["isolated", "shared"], parse(try_from_str = parse_instance_policy))]
+    pub instances: InstancePolicy,
+
+    /// Fuse the linked modules into a single core WebAssembly module.
+    ///
+    /// The fused module does not require the module linking proposal, but it requires the multi-memory proposal
+    /// if more than one linked module defines a memory.
+    #[structopt(long)]
+    pub fused: bool,
+
+    /// Output the linked module in the WebAssembly text format.
+    ///
+    /// Without an output path, the text is printed to stdout rather than replacing the given module.
+    #[structopt(long)]
+    pub wat: bool,
+
+    /// The path of the output linked module; defaults to replacing the given module.
+    #[structopt(long, short = "o", value_name = "OUTPUT", parse(from_os_str))]
+    pub output: Option<PathBuf>,
+}
+
+impl App {
+    /// Executes the application.
+    pub fn execute(self) -> Result<()> {
+        if let Some(Command::Inspect(command)) = self.command {
+            return command.execute();
+        }
+
+        let bytes = self.inputs.load(|module, imports, profile| {
+            let linker = Linker::new(profile).with_instance_policy(self.instances);
+
+            if self.fused {
+                linker.link_fused(module, imports)
+            } else {
+                linker.link(module, imports)
+            }
+        })?;
+
+        if self.wat {
+            let text = wasmprinter::print_bytes(&bytes)
+                .context("failed to print the linked module as text")?;
+
+            return match &self.output {
+                Some(output) => std::fs::write(output, text).with_context(|| {
+                    format!("failed to write to output module `{}`", output.display())
+                }),
+                None => {
+                    print!("{}", text);
+                    Ok(())
+                }
+            };
+        }
 
-        let output = self.output.as_ref().unwrap_or(&self.module);
-        std::fs::write(output, linker.link(&module, &import_modules)?)
+        let output = match &self.output {
+            Some(output) => output,
+            None => self.inputs.module_path()?,
+        };
+        std::fs::write(output, bytes)
             .with_context(|| format!("failed to write to output module `{}`", output.display()))?;
 
         Ok(())
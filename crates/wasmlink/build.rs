@@ -1,4 +1,7 @@
-use std::process::{Command, Stdio};
+use std::{
+    env,
+    process::{Command, Stdio},
+};
 
 fn main() {
     println!("cargo:rerun-if-changed=runtime/src");
@@ -9,6 +12,10 @@ fn main() {
 
     cmd.args(&["build", "--target=wasm32-unknown-unknown"]);
 
+    if env::var_os("CARGO_FEATURE_RUNTIME_LEAK_CHECK").is_some() {
+        cmd.args(&["--features", "leak-check"]);
+    }
+
     if !cfg!(debug_assertions) {
         cmd.arg("--release");
         cmd.env("CARGO_PROFILE_RELEASE_LTO", "true");
@@ -1,86 +1,154 @@
 use std::mem;
 
+// A handle is the index of its slab entry in the low bits and the generation of
+// the entry in the high bits. Bumping the generation when an entry is removed
+// means a stale handle is detected even after its entry is reused.
+//
+// Handles are 32-bit, so this limits a slab to 2^24 entries, and therefore to
+// 2^24 live handles. An entry is retired rather than reused once its 8-bit
+// generation is exhausted, so at most 2^32 handles are ever given out.
+const INDEX_BITS: u32 = 24;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+
 pub struct Slab<T> {
     storage: Vec<Entry<T>>,
     next: usize,
+    len: usize,
 }
 
 enum Entry<T> {
-    Full(T),
-    Empty { next: usize },
+    Full {
+        item: T,
+        generation: u8,
+    },
+    Empty {
+        next: usize,
+        generation: u8,
+    },
+    /// An entry that has been removed with every generation, and so is never
+    /// reused; wrapping its generation around would make stale handles valid
+    /// again.
+    Retired,
+}
+
+/// The reason a handle does not refer to an item of a slab.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HandleError {
+    /// The handle was never returned by the slab.
+    Invalid,
+    /// The item the handle refers to has already been removed.
+    Removed,
 }
 
 impl<T> Slab<T> {
+    pub const fn new() -> Self {
+        Slab {
+            storage: Vec::new(),
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Gets the number of items in the slab.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn insert(&mut self, item: T) -> u32 {
         if self.next == self.storage.len() {
+            if self.next > INDEX_MASK as usize {
+                too_many_handles_trap();
+            }
+
             self.storage.push(Entry::Empty {
                 next: self.next + 1,
+                generation: 0,
             });
         }
-        let ret = self.next as u32;
-        let entry = Entry::Full(item);
-        self.next = match mem::replace(&mut self.storage[self.next], entry) {
-            Entry::Empty { next } => next,
+        let index = self.next;
+        let generation = match self.storage[index] {
+            Entry::Empty { next, generation } => {
+                self.next = next;
+                generation
+            }
             _ => unreachable!(),
         };
-        return ret;
+        self.storage[index] = Entry::Full { item, generation };
+        self.len += 1;
+        index as u32 | (generation as u32) << INDEX_BITS
     }
 
-    pub fn get(&self, idx: u32) -> Option<&T> {
-        match self.storage.get(idx as usize)? {
-            Entry::Full(b) => Some(b),
-            Entry::Empty { .. } => None,
+    pub fn get(&self, handle: u32) -> Result<&T, HandleError> {
+        match self.storage.get((handle & INDEX_MASK) as usize) {
+            Some(Entry::Full { item, generation }) if Self::matches(handle, *generation) => {
+                Ok(item)
+            }
+            Some(_) => Err(HandleError::Removed),
+            None => Err(HandleError::Invalid),
         }
     }
 
-    pub fn get_mut(&mut self, idx: u32) -> Option<&mut T> {
-        match self.storage.get_mut(idx as usize)? {
-            Entry::Full(b) => Some(b),
-            Entry::Empty { .. } => None,
+    pub fn get_mut(&mut self, handle: u32) -> Result<&mut T, HandleError> {
+        match self.storage.get_mut((handle & INDEX_MASK) as usize) {
+            Some(Entry::Full { item, generation }) if Self::matches(handle, *generation) => {
+                Ok(item)
+            }
+            Some(_) => Err(HandleError::Removed),
+            None => Err(HandleError::Invalid),
         }
     }
 
-    pub fn remove(&mut self, idx: u32) -> Option<T> {
-        let slot = self.storage.get_mut(idx as usize)?;
-        match mem::replace(slot, Entry::Empty { next: self.next }) {
-            Entry::Full(b) => {
-                self.next = idx as usize;
-                Some(b)
-            }
-            Entry::Empty { next } => {
-                *slot = Entry::Empty { next };
-                None
+    pub fn remove(&mut self, handle: u32) -> Result<T, HandleError> {
+        self.get(handle)?;
+
+        let index = (handle & INDEX_MASK) as usize;
+        let empty = match ((handle >> INDEX_BITS) as u8).checked_add(1) {
+            Some(generation) => Entry::Empty {
+                next: self.next,
+                generation,
+            },
+            None => Entry::Retired,
+        };
+        let reused = matches!(empty, Entry::Empty { .. });
+        match mem::replace(&mut self.storage[index], empty) {
+            Entry::Full { item, .. } => {
+                if reused {
+                    self.next = index;
+                }
+                self.len -= 1;
+                Ok(item)
             }
+            _ => unreachable!(),
         }
     }
+
+    fn matches(handle: u32, generation: u8) -> bool {
+        (handle >> INDEX_BITS) as u8 == generation
+    }
 }
 
 impl<T> Default for Slab<T> {
     fn default() -> Slab<T> {
-        Slab {
-            storage: Vec::new(),
-            next: 0,
-        }
+        Slab::new()
     }
 }
 
-#[derive(Default)]
-pub struct IndexSlab {
-    slab: Slab<ResourceIndex>,
-}
-
-impl IndexSlab {
-    pub fn insert(&mut self, resource: ResourceIndex) -> u32 {
-        self.slab.insert(resource)
-    }
-
-    pub fn get(&self, slab_idx: u32) -> Option<ResourceIndex> {
-        self.slab.get(slab_idx).cloned()
-    }
-
-    pub fn remove(&mut self, slab_idx: u32) -> Option<ResourceIndex> {
-        self.slab.remove(slab_idx)
-    }
+/// A handle given out to a module, which refers to a resource of a particular type.
+///
+/// Handles of every resource type share one slab so that a handle can't be
+/// mistaken for a handle to a resource of another type.
+struct Handle {
+    /// The id of the resource's type, not of a module.
+    ///
+    /// The linker numbers resource types across all of the linked modules, so
+    /// the type also determines the module that defines it; checking the type
+    /// therefore also rejects handles owned by another module.
+    id: u32,
+    resource: ResourceIndex,
 }
 
 #[derive(Default)]
@@ -126,85 +194,241 @@ impl ResourceSlab {
     }
 }
 
+struct Runtime {
+    handles: Slab<Handle>,
+    resources: Vec<ResourceSlab>,
+}
+
+impl Runtime {
+    fn resources(&mut self, id: u32) -> &mut ResourceSlab {
+        let id = id as usize;
+        if self.resources.len() <= id {
+            self.resources.resize_with(id + 1, Default::default);
+        }
+
+        &mut self.resources[id]
+    }
+
+    fn insert(&mut self, id: u32, res: u32) -> u32 {
+        let resource = self.resources(id).insert(res);
+        self.handles.insert(Handle { id, resource })
+    }
+
+    fn get(&mut self, id: u32, idx: u32) -> u32 {
+        let resource = self.handle(id, idx);
+        self.resources(id).get(resource)
+    }
+
+    fn clone(&mut self, id: u32, idx: u32) -> u32 {
+        let resource = self.handle(id, idx);
+        self.resources(id).clone(resource);
+        self.handles.insert(Handle { id, resource })
+    }
+
+    /// Removes the handle, returning the resource to drop if it was the
+    /// resource's last handle.
+    fn remove(&mut self, id: u32, idx: u32) -> Option<u32> {
+        let resource = self.handle(id, idx);
+        self.handles.remove(idx).unwrap();
+        self.resources(id).drop(resource)
+    }
+
+    fn handle(&self, id: u32, handle: u32) -> ResourceIndex {
+        let handle = match self.handles.get(handle) {
+            Ok(handle) => handle,
+            Err(HandleError::Invalid) => invalid_handle_trap(),
+            Err(HandleError::Removed) => dropped_handle_trap(),
+        };
+
+        if handle.id != id {
+            foreign_handle_trap();
+        }
+
+        handle.resource
+    }
+}
+
 // Currently we assume the runtime cannot be used from multiple threads
 // because we don't support Wasm threading.
 // In the future this may need to be properly synchronized.
-static mut SLABS: Vec<(IndexSlab, ResourceSlab)> = Vec::new();
+static mut RUNTIME: Runtime = Runtime {
+    handles: Slab::new(),
+    resources: Vec::new(),
+};
+
+fn runtime() -> &'static mut Runtime {
+    unsafe { &mut *std::ptr::addr_of_mut!(RUNTIME) }
+}
+
+// The trap functions below are never inlined so that the reason for a trap
+// shows up by name in the backtrace. Each indicates that the problem comes
+// from user code and *not* the runtime.
 
-// This exists to indicate to users that an invalid handle value was passed
-// Thus the problem comes from user code and *not* the runtime
+/// A handle value that was never given out was passed to the runtime.
+#[inline(never)]
 fn invalid_handle_trap() -> ! {
-    unreachable!()
+    unreachable!("invalid handle")
 }
 
-#[no_mangle]
-pub extern "C" fn resource_insert(id: usize, res: u32) -> u32 {
-    let (indexes, resources) = unsafe {
-        if SLABS.len() <= id {
-            SLABS.resize_with(id + 1, Default::default);
-        }
+/// A handle was used after it was dropped, such as by dropping it twice.
+#[inline(never)]
+fn dropped_handle_trap() -> ! {
+    unreachable!("dropped handle")
+}
 
-        SLABS.get_mut(id).unwrap()
-    };
+/// A handle to a resource of one type was passed to the resource functions of
+/// another type, such as one defined by another module.
+#[inline(never)]
+fn foreign_handle_trap() -> ! {
+    unreachable!("foreign handle")
+}
 
-    let index = resources.insert(res);
-    indexes.insert(index)
+/// More handles are live at once than the handle representation supports, or
+/// so many handles have been given out that every slab entry is retired.
+#[inline(never)]
+fn too_many_handles_trap() -> ! {
+    unreachable!("too many handles")
 }
 
 #[no_mangle]
-pub extern "C" fn resource_get(id: usize, idx: u32) -> u32 {
-    let (indexes, resources) = unsafe {
-        if SLABS.len() <= id {
-            SLABS.resize_with(id + 1, Default::default);
-        }
+pub extern "C" fn resource_insert(id: u32, res: u32) -> u32 {
+    runtime().insert(id, res)
+}
 
-        SLABS.get(id).unwrap()
-    };
+#[no_mangle]
+pub extern "C" fn resource_get(id: u32, idx: u32) -> u32 {
+    runtime().get(id, idx)
+}
+
+#[no_mangle]
+pub extern "C" fn resource_clone(id: u32, idx: u32) -> u32 {
+    runtime().clone(id, idx)
+}
 
-    let res_idx = indexes
-        .get(idx)
-        .ok_or_else(|| invalid_handle_trap())
-        .unwrap();
-    resources.get(res_idx)
+#[no_mangle]
+pub extern "C" fn resource_remove(id: u32, idx: u32) -> u64 {
+    // The return value's upper 32-bits is a flag to denote if the resource is still alive.
+    // If the upper 32-bits are 0, the lower 32-bits are expected to be the resource to drop.
+    match runtime().remove(id, idx) {
+        Some(wasm) => wasm as u64,
+        None => 1u64 << 32,
+    }
 }
 
+/// Gets the number of handles that have not been dropped.
+///
+/// Only exported by leak checking builds of the runtime; a host calls it
+/// when tearing down an instance to report leaked handles.
+#[cfg(feature = "leak-check")]
 #[no_mangle]
-pub extern "C" fn resource_clone(id: usize, idx: u32) -> u32 {
-    let (indexes, resources) = unsafe {
-        if SLABS.len() <= id {
-            SLABS.resize_with(id + 1, Default::default);
+pub extern "C" fn resource_leaks() -> u32 {
+    runtime().handles.len() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_runtime() -> Runtime {
+        Runtime {
+            handles: Slab::new(),
+            resources: Vec::new(),
         }
+    }
 
-        SLABS.get_mut(id).unwrap()
-    };
+    #[test]
+    fn slab_reuses_entries_with_a_new_generation() {
+        let mut slab = Slab::new();
+        let a = slab.insert("a");
+        assert_eq!(slab.len(), 1);
+        assert_eq!(slab.remove(a), Ok("a"));
+        assert!(slab.is_empty());
+
+        let b = slab.insert("b");
+        assert_eq!(b & INDEX_MASK, a & INDEX_MASK);
+        assert_ne!(b, a);
+        assert_eq!(slab.get(a), Err(HandleError::Removed));
+        assert_eq!(slab.remove(a), Err(HandleError::Removed));
+        assert_eq!(slab.get(b), Ok(&"b"));
+    }
 
-    let res_idx = indexes
-        .get(idx)
-        .ok_or_else(|| invalid_handle_trap())
-        .unwrap();
-    resources.clone(res_idx);
-    indexes.insert(res_idx)
-}
+    #[test]
+    fn slab_rejects_handles_it_never_gave_out() {
+        let mut slab = Slab::new();
+        let a = slab.insert(1);
+        assert_eq!(slab.get(a + 1), Err(HandleError::Invalid));
+        assert_eq!(slab.get_mut(a + 1), Err(HandleError::Invalid));
+        assert_eq!(slab.remove(a + 1), Err(HandleError::Invalid));
+        *slab.get_mut(a).unwrap() += 1;
+        assert_eq!(slab.get(a), Ok(&2));
+    }
 
-#[no_mangle]
-pub extern "C" fn resource_remove(id: usize, idx: u32) -> u64 {
-    let (indexes, resources) = unsafe {
-        if SLABS.len() <= id {
-            SLABS.resize_with(id + 1, Default::default);
+    #[test]
+    fn slab_retires_entries_with_exhausted_generations() {
+        let mut slab = Slab::new();
+        let first = slab.insert(());
+        let mut handle = first;
+        for generation in 1..=u8::MAX as u32 {
+            slab.remove(handle).unwrap();
+            handle = slab.insert(());
+            assert_eq!(handle, generation << INDEX_BITS);
         }
+        slab.remove(handle).unwrap();
+
+        // The entry isn't reused, so the first handle stays stale.
+        let next = slab.insert(());
+        assert_eq!(next, 1);
+        assert_eq!(slab.get(first), Err(HandleError::Removed));
+        assert_eq!(slab.get(handle), Err(HandleError::Removed));
+        assert_eq!(slab.remove(first), Err(HandleError::Removed));
+        assert_eq!(slab.len(), 1);
+    }
 
-        SLABS.get_mut(id).unwrap()
-    };
+    #[test]
+    fn runtime_drops_resources_with_their_last_handle() {
+        let mut runtime = new_runtime();
+        let a = runtime.insert(0, 100);
+        let b = runtime.clone(0, a);
+        assert_ne!(a, b);
+        assert_eq!(runtime.get(0, a), 100);
+        assert_eq!(runtime.get(0, b), 100);
+
+        assert_eq!(runtime.remove(0, a), None);
+        assert_eq!(runtime.get(0, b), 100);
+        assert_eq!(runtime.remove(0, b), Some(100));
+        assert!(runtime.handles.is_empty());
+    }
 
-    let res_idx = indexes
-        .remove(idx)
-        .ok_or_else(|| invalid_handle_trap())
-        .unwrap();
+    #[test]
+    fn runtime_keeps_resource_types_apart() {
+        let mut runtime = new_runtime();
+        let a = runtime.insert(0, 100);
+        let b = runtime.insert(1, 200);
+        assert_eq!(runtime.get(0, a), 100);
+        assert_eq!(runtime.get(1, b), 200);
+    }
 
-    // The return value's upper 32-bits is a flag to denote if the resource is still alive.
-    // If the upper 32-bits are 0, the lower 32-bits are expected to be the resource to drop.
-    match resources.drop(res_idx) {
-        Some(wasm) => wasm as u64,
-        None => 1u64 << 32,
+    #[test]
+    #[should_panic(expected = "foreign handle")]
+    fn runtime_traps_on_foreign_handles() {
+        let mut runtime = new_runtime();
+        let a = runtime.insert(0, 100);
+        runtime.get(1, a);
+    }
+
+    #[test]
+    #[should_panic(expected = "dropped handle")]
+    fn runtime_traps_on_dropped_handles() {
+        let mut runtime = new_runtime();
+        let a = runtime.insert(0, 100);
+        runtime.remove(0, a);
+        runtime.remove(0, a);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid handle")]
+    fn runtime_traps_on_invalid_handles() {
+        let mut runtime = new_runtime();
+        runtime.get(0, 0);
     }
 }
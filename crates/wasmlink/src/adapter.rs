@@ -1,14 +1,16 @@
 use crate::{
     adapter::call::CallAdapter,
     linker::{to_val_type, CANONICAL_ABI_MODULE_NAME},
+    names::Names,
     resources::Resources,
-    Module,
+    Module, StringEncoding,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::collections::{BTreeMap, HashMap};
-use wasmparser::{FuncType, Type};
+use wasmparser::{FuncType, ImportSectionEntryType, MemoryType, ResizableLimits, Type};
 
 mod call;
+mod transcode;
 
 pub const RUNTIME_MODULE_NAME: &str = "$runtime";
 pub const PARENT_MODULE_NAME: &str = "$parent";
@@ -33,6 +35,19 @@ lazy_static::lazy_static! {
             returns: Box::new([])
         }
     };
+    pub static ref TRANSCODE_FUNC_TYPE: FuncType = {
+        FuncType {
+            params: Box::new([Type::I32, Type::I32, Type::I32]),
+            returns: Box::new([Type::I32])
+        }
+    };
+}
+
+fn to_limits(limits: &ResizableLimits) -> wasm_encoder::Limits {
+    wasm_encoder::Limits {
+        min: limits.initial,
+        max: limits.maximum,
+    }
 }
 
 /// Responsible for adapting a WebAssembly module.
@@ -51,29 +66,55 @@ impl<'a> ModuleAdapter<'a> {
     }
 
     /// Adapts the module and returns the resulting encoded module.
+    ///
+    /// The adapter assumes the importing module uses the same string encoding as the module.
     pub fn adapt(&self) -> Result<wasm_encoder::Module> {
+        self.adapt_for(self.module.string_encoding, false, &[])
+    }
+
+    /// Adapts the module for the given importing modules that use the given string encoding.
+    ///
+    /// Strings are transcoded by the adapter if the encodings differ.
+    ///
+    /// If the importing modules share the module's memory and allocator, returned lists are passed
+    /// to the importer without being copied. Lists passed to the module are still copied as the
+    /// module takes ownership of them.
+    ///
+    /// The names of the importing modules are only used to name the adapter functions.
+    pub fn adapt_for(
+        &self,
+        parent_encoding: StringEncoding,
+        shares_memory: bool,
+        importers: &[&str],
+    ) -> Result<wasm_encoder::Module> {
         if !self.module.must_adapt {
             return Ok(self.module.encode());
         }
 
+        let transcode = self.needs_transcoding(parent_encoding);
+
         let mut module = wasm_encoder::Module::new();
         let mut types = HashMap::new();
         let mut parent_realloc_index = None;
         let mut num_imported_funcs = 0;
+        let mut num_imported_tables = 0;
+        let mut num_imported_memories = 0;
         let mut num_aliased_funcs = 0;
         let mut num_adapted_func_aliases = 0;
         let mut num_defined_funcs = 0;
         let mut implicit_instances = BTreeMap::new();
         let mut resource_functions = HashMap::new();
 
-        self.write_type_section(&mut module, &mut types);
+        self.write_type_section(&mut module, &mut types, transcode);
         self.write_import_section(
             &mut module,
             &types,
             &mut num_imported_funcs,
+            &mut num_imported_tables,
+            &mut num_imported_memories,
             &mut parent_realloc_index,
             &mut implicit_instances,
-        );
+        )?;
         self.write_module_section(&mut module)?;
         self.write_instance_section(&mut module, &implicit_instances);
         self.write_alias_section(
@@ -84,26 +125,40 @@ impl<'a> ModuleAdapter<'a> {
             &mut num_adapted_func_aliases,
             &mut resource_functions,
         );
-        self.write_function_section(&mut module, &types, &mut num_defined_funcs);
+        self.write_function_section(&mut module, &types, transcode, &mut num_defined_funcs);
         self.write_export_section(
             &mut module,
             num_imported_funcs,
+            num_imported_memories,
             num_aliased_funcs,
             num_adapted_func_aliases,
         );
-        self.write_element_section(&mut module, num_imported_funcs, num_adapted_func_aliases);
+        self.write_element_section(
+            &mut module,
+            num_imported_funcs,
+            num_imported_tables,
+            num_adapted_func_aliases,
+        );
         self.write_code_section(
             &mut module,
             parent_realloc_index,
             num_imported_funcs,
+            num_imported_memories,
+            num_aliased_funcs,
+            parent_encoding,
+            shares_memory,
             &resource_functions,
         );
-
-        // TODO: write a names section for the adapted module?
+        self.write_names_section(&mut module, parent_encoding, importers);
 
         Ok(module)
     }
 
+    /// Determines if adapting for a parent with the given string encoding requires transcoding.
+    pub(crate) fn needs_transcoding(&self, parent_encoding: StringEncoding) -> bool {
+        self.module.has_strings && parent_encoding != self.module.string_encoding
+    }
+
     pub(crate) fn encode_shim(&self) -> Option<wasm_encoder::Module> {
         if !self.module.must_adapt {
             return None;
@@ -115,6 +170,7 @@ impl<'a> ModuleAdapter<'a> {
         let mut tables = wasm_encoder::TableSection::new();
         let mut exports = wasm_encoder::ExportSection::new();
         let mut code = wasm_encoder::CodeSection::new();
+        let mut names = Names::new(format!("{}-shim", self.module.name));
         let mut func_count = 0;
         let mut index = 0u32;
 
@@ -154,6 +210,10 @@ impl<'a> ModuleAdapter<'a> {
                 func.instruction(wasm_encoder::Instruction::End);
 
                 code.function(&func);
+                names.function(
+                    func_count - interface.inner().functions.len() as u32 + func_index as u32,
+                    format!("{}-shim::{}", self.module.name, f.name),
+                );
             }
         }
 
@@ -164,6 +224,7 @@ impl<'a> ModuleAdapter<'a> {
             &mut functions,
             &mut exports,
             &mut code,
+            &mut names,
         );
 
         let table_len = func_count + self.resources.exported_count();
@@ -184,10 +245,15 @@ impl<'a> ModuleAdapter<'a> {
         module.section(&tables);
         module.section(&exports);
         module.section(&code);
+        names.write(&mut module);
 
         Some(module)
     }
 
+    pub(crate) fn resources(&self) -> &Resources<'a> {
+        &self.resources
+    }
+
     pub(crate) fn aliases(&self) -> impl Iterator<Item = &str> {
         self.module
             .interfaces
@@ -201,6 +267,7 @@ impl<'a> ModuleAdapter<'a> {
         &self,
         module: &mut wasm_encoder::Module,
         types: &mut HashMap<&'a FuncType, u32>,
+        transcode: bool,
     ) {
         let mut section = wasm_encoder::TypeSection::new();
 
@@ -213,11 +280,7 @@ impl<'a> ModuleAdapter<'a> {
                 if i.module == CANONICAL_ABI_MODULE_NAME {
                     None
                 } else {
-                    Some(
-                        self.module
-                            .import_func_type(i)
-                            .expect("expected import to be a function"),
-                    )
+                    self.module.import_func_type(i)
                 }
             })
             .chain(
@@ -255,44 +318,87 @@ impl<'a> ModuleAdapter<'a> {
             });
         }
 
+        if transcode {
+            let index = types.len() as u32;
+            types.entry(&TRANSCODE_FUNC_TYPE).or_insert_with(|| {
+                section.function(
+                    TRANSCODE_FUNC_TYPE.params.iter().map(to_val_type),
+                    TRANSCODE_FUNC_TYPE.returns.iter().map(to_val_type),
+                );
+                index
+            });
+        }
+
         self.resources
             .write_adapter_type_section(types, &mut section);
 
         module.section(&section);
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn write_import_section(
         &self,
         module: &mut wasm_encoder::Module,
         types: &HashMap<&'a FuncType, u32>,
         num_imported_funcs: &mut u32,
+        num_imported_tables: &mut u32,
+        num_imported_memories: &mut u32,
         parent_realloc_index: &mut Option<u32>,
         implicit_instances: &mut BTreeMap<&'a str, u32>,
-    ) {
+    ) -> Result<()> {
         let mut section = wasm_encoder::ImportSection::new();
 
-        for (import_module, import_field, entity) in self.module.imports.iter().filter_map(|i| {
-            // The adapter will implement canonical ABI imports
-            if i.module == CANONICAL_ABI_MODULE_NAME {
-                None
-            } else {
-                let ty = self
-                    .module
-                    .import_func_type(i)
-                    .expect("import should be a function");
-                Some((
-                    i.module,
-                    i.field,
-                    wasm_encoder::EntityType::Function(types[ty]),
-                ))
-            }
-        }) {
-            *num_imported_funcs += 1;
+        // Forward the original module's imports, including any tables, memories, or globals
+        for import in self
+            .module
+            .imports
+            .iter()
+            .filter(|i| i.module != CANONICAL_ABI_MODULE_NAME)
+        {
+            let entity = match import.ty {
+                ImportSectionEntryType::Function(_) => {
+                    *num_imported_funcs += 1;
+                    let ty = self
+                        .module
+                        .import_func_type(import)
+                        .expect("import should be a function");
+                    wasm_encoder::EntityType::Function(types[ty])
+                }
+                ImportSectionEntryType::Table(ty) => {
+                    *num_imported_tables += 1;
+                    wasm_encoder::EntityType::Table(wasm_encoder::TableType {
+                        element_type: to_val_type(&ty.element_type),
+                        limits: to_limits(&ty.limits),
+                    })
+                }
+                ImportSectionEntryType::Memory(MemoryType::M32 {
+                    limits,
+                    shared: false,
+                }) => {
+                    *num_imported_memories += 1;
+                    wasm_encoder::EntityType::Memory(wasm_encoder::MemoryType {
+                        limits: to_limits(&limits),
+                    })
+                }
+                ImportSectionEntryType::Memory(_) => bail!(
+                    "module `{}` imports memory `{}` from module `{}` but only non-shared 32-bit memories can be imported by an adapted module",
+                    self.module.name,
+                    import.field.unwrap_or(""),
+                    import.module
+                ),
+                ImportSectionEntryType::Global(ty) => {
+                    wasm_encoder::EntityType::Global(wasm_encoder::GlobalType {
+                        val_type: to_val_type(&ty.content_type),
+                        mutable: ty.mutable,
+                    })
+                }
+                _ => unreachable!("module imports should have been validated"),
+            };
 
-            section.import(import_module, import_field, entity);
+            section.import(import.module, import.field, entity);
 
             let index = implicit_instances.len() as u32;
-            implicit_instances.entry(import_module).or_insert(index);
+            implicit_instances.entry(import.module).or_insert(index);
         }
 
         if self.module.needs_memory {
@@ -334,6 +440,8 @@ impl<'a> ModuleAdapter<'a> {
         );
 
         module.section(&section);
+
+        Ok(())
     }
 
     fn write_module_section(&self, module: &mut wasm_encoder::Module) -> Result<()> {
@@ -460,6 +568,7 @@ impl<'a> ModuleAdapter<'a> {
         &self,
         module: &mut wasm_encoder::Module,
         types: &HashMap<&'a FuncType, u32>,
+        transcode: bool,
         num_defined_funcs: &mut u32,
     ) {
         let mut section = wasm_encoder::FunctionSection::new();
@@ -474,6 +583,13 @@ impl<'a> ModuleAdapter<'a> {
             section.function(types[&info.import_type]);
         }
 
+        // Populate the string transcoding functions (parent to adapted, then adapted to parent)
+        if transcode {
+            *num_defined_funcs += 2;
+            section.function(types[&TRANSCODE_FUNC_TYPE as &FuncType]);
+            section.function(types[&TRANSCODE_FUNC_TYPE as &FuncType]);
+        }
+
         module.section(&section);
     }
 
@@ -481,6 +597,7 @@ impl<'a> ModuleAdapter<'a> {
         &self,
         module: &mut wasm_encoder::Module,
         num_imported_funcs: u32,
+        num_imported_memories: u32,
         num_aliased_funcs: u32,
         num_adapted_func_aliases: u32,
     ) {
@@ -489,7 +606,7 @@ impl<'a> ModuleAdapter<'a> {
         if self.module.needs_memory {
             section.export(
                 MEMORY_EXPORT_NAME,
-                wasm_encoder::Export::Memory(call::ADAPTED_MEMORY_INDEX),
+                wasm_encoder::Export::Memory(call::adapted_memory_index(num_imported_memories)),
             );
         }
 
@@ -543,6 +660,7 @@ impl<'a> ModuleAdapter<'a> {
         &self,
         module: &mut wasm_encoder::Module,
         num_imported_funcs: u32,
+        num_imported_tables: u32,
         num_adapted_func_aliases: u32,
     ) {
         if !self.module.has_resources {
@@ -554,7 +672,9 @@ impl<'a> ModuleAdapter<'a> {
         let alias_start_index =
             num_imported_funcs + if self.module.needs_memory_funcs { 2 } else { 0 };
 
+        // The resources function table is aliased after any imported tables
         self.resources.write_adapter_element_section(
+            num_imported_tables,
             alias_start_index + num_adapted_func_aliases,
             &mut section,
         );
@@ -562,11 +682,19 @@ impl<'a> ModuleAdapter<'a> {
         module.section(&section);
     }
 
+    // `shares_memory` only lets lists returned to the parent be moved instead of
+    // copied. Zero-copy for the parameter direction is out of scope: parameters
+    // are always copied into the adapted module's memory, shared or not.
+    #[allow(clippy::too_many_arguments)]
     fn write_code_section(
         &self,
         module: &mut wasm_encoder::Module,
         parent_realloc_index: Option<u32>,
         num_imported_funcs: u32,
+        num_imported_memories: u32,
+        num_aliased_funcs: u32,
+        parent_encoding: StringEncoding,
+        shares_memory: bool,
         resource_functions: &HashMap<&'a str, (u32, u32)>,
     ) {
         let mut section = wasm_encoder::CodeSection::new();
@@ -582,6 +710,23 @@ impl<'a> ModuleAdapter<'a> {
             (None, None, num_imported_funcs)
         };
 
+        let string_encodings = (parent_encoding, self.module.string_encoding);
+
+        // The transcoding functions are defined after the adapted functions
+        let transcode_indexes = if self.needs_transcoding(parent_encoding) {
+            let num_adapted_funcs = self
+                .module
+                .interfaces
+                .iter()
+                .flat_map(|i| i.iter())
+                .filter(|(_, info)| info.must_adapt)
+                .count() as u32;
+            let index = alias_start_index + num_aliased_funcs + num_adapted_funcs;
+            Some((index, index + 1))
+        } else {
+            None
+        };
+
         for interface in &self.module.interfaces {
             if !interface.must_adapt {
                 continue;
@@ -601,14 +746,122 @@ impl<'a> ModuleAdapter<'a> {
                     free_index,
                     parent_realloc_index,
                     resource_functions,
+                    num_imported_memories,
+                    string_encodings,
+                    transcode_indexes,
+                    shares_memory,
                 );
 
                 section.function(&adapter.adapt());
             }
         }
 
+        if transcode_indexes.is_some() {
+            let parent_memory = call::parent_memory_index(num_imported_memories);
+            let adapted_memory = call::adapted_memory_index(num_imported_memories);
+
+            section.function(&transcode::transcode_function(
+                parent_encoding,
+                self.module.string_encoding,
+                parent_memory,
+                adapted_memory,
+            ));
+            section.function(&transcode::transcode_function(
+                self.module.string_encoding,
+                parent_encoding,
+                adapted_memory,
+                parent_memory,
+            ));
+        }
+
         module.section(&section);
     }
+
+    fn write_names_section(
+        &self,
+        module: &mut wasm_encoder::Module,
+        parent_encoding: StringEncoding,
+        importers: &[&str],
+    ) {
+        let name = self.module.name;
+        let importer = if importers.is_empty() {
+            PARENT_MODULE_NAME.to_string()
+        } else {
+            importers.join("|")
+        };
+
+        let mut names = Names::new(format!("{}-adapter", name));
+        let mut index = 0;
+
+        // Name the forwarded function imports, then the parent's realloc and the runtime imports
+        for import in self
+            .module
+            .imports
+            .iter()
+            .filter(|i| i.module != CANONICAL_ABI_MODULE_NAME)
+        {
+            if let ImportSectionEntryType::Function(_) = import.ty {
+                names.function(
+                    index,
+                    format!("{}::{}", import.module, import.field.unwrap_or("")),
+                );
+                index += 1;
+            }
+        }
+
+        if self.module.needs_memory_funcs {
+            names.function(index, format!("{}::{}", importer, REALLOC_EXPORT_NAME));
+            index += 1;
+        }
+
+        self.resources
+            .write_adapter_import_names(&mut index, &mut names);
+
+        // Name the aliases in the same order as the alias section
+        if self.module.needs_memory_funcs {
+            names.function(index, format!("{}::{}", name, REALLOC_EXPORT_NAME));
+            names.function(index + 1, format!("{}::{}", name, FREE_EXPORT_NAME));
+            index += 2;
+        }
+
+        for (f, _) in self.module.interfaces.iter().flat_map(|i| i.iter()) {
+            names.function(index, format!("{}::{}", name, f.name));
+            index += 1;
+        }
+
+        self.resources
+            .write_adapter_alias_names(&mut index, &mut names);
+
+        // Name the adapter functions after the importer, the module, and the interface function
+        for (f, info) in self.module.interfaces.iter().flat_map(|i| i.iter()) {
+            if !info.must_adapt {
+                continue;
+            }
+
+            names.function(index, format!("{}->{}::{}", importer, name, f.name));
+            index += 1;
+        }
+
+        if self.needs_transcoding(parent_encoding) {
+            let encoding = self.module.string_encoding;
+            names.function(
+                index,
+                format!(
+                    "{}->{}::transcode_{}_to_{}",
+                    importer, name, parent_encoding, encoding
+                ),
+            );
+            names.function(
+                index + 1,
+                format!(
+                    "{}->{}::transcode_{}_to_{}",
+                    name, importer, encoding, parent_encoding
+                ),
+            );
+        }
+
+        names.write(module);
+    }
 }
 
 impl PartialEq for ModuleAdapter<'_> {
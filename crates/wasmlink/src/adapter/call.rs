@@ -1,4 +1,5 @@
-use crate::module::Interface;
+use super::transcode::worst_case_expansion;
+use crate::module::{Interface, StringEncoding};
 use std::collections::HashMap;
 use wasm_encoder::{BlockType, Instruction, MemArg, ValType};
 use wit_parser::{
@@ -6,10 +7,19 @@ use wit_parser::{
     TypeDefKind,
 };
 
-// The parent's memory is imported, so it is always index 0 for the adapter logic
-const PARENT_MEMORY_INDEX: u32 = 0;
-// The adapted module's memory is aliased, so it is always index 1 for the adapter logic
-pub const ADAPTED_MEMORY_INDEX: u32 = 1;
+/// Gets the index of the parent's memory in the adapter.
+///
+/// The parent's memory is imported after any memories the adapted module imports.
+pub fn parent_memory_index(num_imported_memories: u32) -> u32 {
+    num_imported_memories
+}
+
+/// Gets the index of the adapted module's memory in the adapter.
+///
+/// The adapted module's memory is aliased, so it follows every imported memory.
+pub fn adapted_memory_index(num_imported_memories: u32) -> u32 {
+    num_imported_memories + 1
+}
 
 struct Locals {
     start: u32,
@@ -164,6 +174,8 @@ enum Operand<'a> {
         element_size: u32,
         element_alignment: u32,
         operands: Vec<Operand<'a>>,
+        /// Whether or not the list is a string that must be transcoded.
+        transcode: bool,
     },
     Handle {
         addr: ValueRef,
@@ -172,13 +184,19 @@ enum Operand<'a> {
 }
 
 impl Operand<'_> {
-    fn local(&self) -> Option<u32> {
+    fn locals(&self) -> Vec<u32> {
         match self {
+            Operand::List {
+                addr: ValueRef::Local(addr),
+                len: ValueRef::Local(len),
+                transcode: true,
+                ..
+            } => vec![*addr, *len],
             Operand::List { addr, .. } | Operand::Handle { addr, .. } => match addr {
-                ValueRef::Local(i) => Some(*i),
-                _ => None,
+                ValueRef::Local(i) => vec![*i],
+                _ => Vec::new(),
             },
-            Operand::Variant { .. } => None,
+            Operand::Variant { .. } => Vec::new(),
         }
     }
 }
@@ -194,6 +212,19 @@ fn is_char(interface: &WitInterface, ty: &Type) -> bool {
     }
 }
 
+/// The string encodings of the parent and adapted modules.
+#[derive(Debug, Clone, Copy)]
+struct Encodings {
+    parent: StringEncoding,
+    adapted: StringEncoding,
+}
+
+impl Encodings {
+    fn transcode(&self) -> bool {
+        self.parent != self.adapted
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum PushMode {
     Params,
@@ -222,6 +253,11 @@ pub(crate) struct CallAdapter<'a> {
     free_index: Option<u32>,
     parent_realloc_index: Option<u32>,
     resource_functions: &'a HashMap<&'a str, (u32, u32)>,
+    parent_memory: u32,
+    adapted_memory: u32,
+    encodings: Encodings,
+    transcode_indexes: Option<(u32, u32)>,
+    shares_memory: bool,
 }
 
 impl<'a> CallAdapter<'a> {
@@ -235,9 +271,17 @@ impl<'a> CallAdapter<'a> {
         free_index: Option<u32>,
         parent_realloc_index: Option<u32>,
         resource_functions: &'a HashMap<&'a str, (u32, u32)>,
+        num_imported_memories: u32,
+        string_encodings: (StringEncoding, StringEncoding),
+        transcode_indexes: Option<(u32, u32)>,
+        shares_memory: bool,
     ) -> Self {
         let inner = interface.inner();
         let sizes = interface.sizes();
+        let encodings = Encodings {
+            parent: string_encodings.0,
+            adapted: string_encodings.1,
+        };
 
         let mut locals_count = 0;
 
@@ -250,6 +294,8 @@ impl<'a> CallAdapter<'a> {
                 ty,
                 &mut iter,
                 PushMode::Params,
+                encodings,
+                shares_memory,
                 &mut locals_count,
                 &mut params,
             );
@@ -268,6 +314,8 @@ impl<'a> CallAdapter<'a> {
                     ty,
                     &mut iter,
                     PushMode::RetPtr,
+                    encodings,
+                    shares_memory,
                     &mut locals_count,
                     &mut results,
                 );
@@ -286,6 +334,8 @@ impl<'a> CallAdapter<'a> {
                 &func.results[0].1,
                 &mut iter,
                 PushMode::Return,
+                encodings,
+                shares_memory,
                 &mut locals_count,
                 &mut results,
             );
@@ -310,6 +360,11 @@ impl<'a> CallAdapter<'a> {
             free_index,
             parent_realloc_index,
             resource_functions,
+            parent_memory: parent_memory_index(num_imported_memories),
+            adapted_memory: adapted_memory_index(num_imported_memories),
+            encodings,
+            transcode_indexes,
+            shares_memory,
         }
     }
 
@@ -369,12 +424,12 @@ impl<'a> CallAdapter<'a> {
                 function.instruction(Instruction::I64Load(MemArg {
                     offset: (i * 8) as u32,
                     align: 3,
-                    memory_index: ADAPTED_MEMORY_INDEX,
+                    memory_index: self.adapted_memory,
                 }));
                 function.instruction(Instruction::I64Store(MemArg {
                     offset: (i * 8) as u32,
                     align: 3,
-                    memory_index: PARENT_MEMORY_INDEX,
+                    memory_index: self.parent_memory,
                 }));
             }
 
@@ -385,13 +440,13 @@ impl<'a> CallAdapter<'a> {
             let src_base = ElementBase {
                 base: src_retptr,
                 index_and_size: None,
-                memory: ADAPTED_MEMORY_INDEX,
+                memory: self.adapted_memory,
             };
 
             let dst_base = ElementBase {
                 base: dst_retptr,
                 index_and_size: None,
-                memory: PARENT_MEMORY_INDEX,
+                memory: self.parent_memory,
             };
 
             for result in &self.results {
@@ -431,12 +486,15 @@ impl<'a> CallAdapter<'a> {
         function.instruction(Instruction::LocalGet(locals.lookup(src).unwrap_or(src)));
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn push_operands<T>(
         interface: &'a WitInterface,
         sizes: &SizeAlign,
         ty: &Type,
         params: &mut T,
         mode: PushMode,
+        encodings: Encodings,
+        shares_memory: bool,
         locals_count: &mut u32,
         operands: &mut Vec<Operand<'a>>,
     ) where
@@ -444,9 +502,17 @@ impl<'a> CallAdapter<'a> {
     {
         match ty {
             Type::Id(id) => match &interface.types[*id].kind {
-                TypeDefKind::Type(t) => {
-                    Self::push_operands(interface, sizes, t, params, mode, locals_count, operands)
-                }
+                TypeDefKind::Type(t) => Self::push_operands(
+                    interface,
+                    sizes,
+                    t,
+                    params,
+                    mode,
+                    encodings,
+                    shares_memory,
+                    locals_count,
+                    operands,
+                ),
                 TypeDefKind::List(element) => {
                     let addr = params.next().unwrap();
                     let len = params.next().unwrap();
@@ -459,26 +525,39 @@ impl<'a> CallAdapter<'a> {
                             element,
                             0,
                             mode,
+                            encodings,
+                            shares_memory,
                             locals_count,
                             &mut element_operands,
                         );
                     }
 
-                    // Every list copied needs a destination local (and a source for retptr)
+                    let transcode = is_char(interface, element) && encodings.transcode();
+
+                    // Every list copied needs a destination local (and a source for retptr),
+                    // but a returned list moved to the parent only needs a local for the list
                     *locals_count += match mode {
                         PushMode::Params => 1,
+                        PushMode::RetPtr if shares_memory && !transcode => 1,
                         PushMode::RetPtr => 2,
                         PushMode::Return => unreachable!(),
                     };
 
+                    // Transcoded strings need a local for the destination length
+                    if transcode {
+                        *locals_count += 1;
+                    }
+
                     // Lists that copy elements with lists need a local for the counter
                     if !element_operands.is_empty() {
                         *locals_count += 1;
                     }
 
-                    let (element_size, element_alignment) = match element {
-                        Type::Char => (1, 1), // UTF-8
-                        _ => (sizes.size(element) as u32, sizes.align(element) as u32),
+                    let (element_size, element_alignment) = if is_char(interface, element) {
+                        let unit = encodings.adapted.code_unit();
+                        (unit, unit)
+                    } else {
+                        (sizes.size(element) as u32, sizes.align(element) as u32)
                     };
 
                     operands.push(Operand::List {
@@ -487,6 +566,7 @@ impl<'a> CallAdapter<'a> {
                         element_size,
                         element_alignment,
                         operands: element_operands,
+                        transcode,
                     });
                 }
                 TypeDefKind::Record(r) => match r.kind {
@@ -508,6 +588,8 @@ impl<'a> CallAdapter<'a> {
                                 &f.ty,
                                 params,
                                 mode,
+                                encodings,
+                                shares_memory,
                                 locals_count,
                                 operands,
                             );
@@ -532,6 +614,8 @@ impl<'a> CallAdapter<'a> {
                                 ty,
                                 &mut iter,
                                 mode,
+                                encodings,
+                                shares_memory,
                                 locals_count,
                                 &mut operands,
                             );
@@ -580,12 +664,15 @@ impl<'a> CallAdapter<'a> {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn push_element_operands(
         interface: &'a WitInterface,
         sizes: &SizeAlign,
         ty: &Type,
         offset: u32,
         mode: PushMode,
+        encodings: Encodings,
+        shares_memory: bool,
         locals_count: &mut u32,
         operands: &mut Vec<Operand<'a>>,
     ) {
@@ -597,6 +684,8 @@ impl<'a> CallAdapter<'a> {
                     t,
                     offset,
                     mode,
+                    encodings,
+                    shares_memory,
                     locals_count,
                     operands,
                 ),
@@ -609,22 +698,37 @@ impl<'a> CallAdapter<'a> {
                             element,
                             0,
                             mode,
+                            encodings,
+                            shares_memory,
                             locals_count,
                             &mut element_operands,
                         );
                     }
 
-                    // Every list copied needs a source and destination local
-                    *locals_count += 2;
+                    let transcode = is_char(interface, element) && encodings.transcode();
+
+                    // Every list copied needs a source and destination local,
+                    // but a returned list moved to the parent only needs a local for the list
+                    *locals_count += match mode {
+                        PushMode::RetPtr | PushMode::Return if shares_memory && !transcode => 1,
+                        _ => 2,
+                    };
+
+                    // Transcoded strings need a local for the destination length
+                    if transcode {
+                        *locals_count += 1;
+                    }
 
                     // Lists with elements containing lists need a local for the loop counter
                     if !element_operands.is_empty() {
                         *locals_count += 1;
                     }
 
-                    let (element_size, element_alignment) = match element {
-                        Type::Char => (1, 1), // UTF-8
-                        _ => (sizes.size(element) as u32, sizes.align(element) as u32),
+                    let (element_size, element_alignment) = if is_char(interface, element) {
+                        let unit = encodings.adapted.code_unit();
+                        (unit, unit)
+                    } else {
+                        (sizes.size(element) as u32, sizes.align(element) as u32)
                     };
 
                     operands.push(Operand::List {
@@ -633,6 +737,7 @@ impl<'a> CallAdapter<'a> {
                         element_size,
                         element_alignment,
                         operands: element_operands,
+                        transcode,
                     });
                 }
                 TypeDefKind::Record(r) => match r.kind {
@@ -647,6 +752,8 @@ impl<'a> CallAdapter<'a> {
                                 &f.ty,
                                 offset + o as u32,
                                 mode,
+                                encodings,
+                                shares_memory,
                                 locals_count,
                                 operands,
                             );
@@ -667,6 +774,8 @@ impl<'a> CallAdapter<'a> {
                                 ty,
                                 offset + payload_offset,
                                 mode,
+                                encodings,
+                                shares_memory,
                                 locals_count,
                                 &mut operands,
                             );
@@ -755,7 +864,7 @@ impl<'a> CallAdapter<'a> {
 
                         // If the operand had a local value, take from the map and assign it here as part of the
                         // variant's block.
-                        if let Some(i) = operand.local() {
+                        for i in operand.locals() {
                             function.instruction(Instruction::LocalGet(locals.take(i)));
                             function.instruction(Instruction::LocalSet(i));
                         }
@@ -770,17 +879,42 @@ impl<'a> CallAdapter<'a> {
                 element_size,
                 element_alignment,
                 operands,
+                transcode,
             } => {
-                let (src_list, dst_list) = self.emit_copy_list(
-                    function,
-                    locals,
-                    direction,
-                    *addr,
-                    *len,
-                    src_base,
-                    *element_size,
-                    *element_alignment,
-                );
+                // Returned lists are already in the parent's memory when it is shared, so ownership is
+                // transferred to the parent without copying
+                let moved =
+                    !*transcode && self.shares_memory && matches!(direction, Direction::Out);
+
+                let (src_list, dst_list) = if moved {
+                    self.emit_move_list(function, locals, *addr, src_base)
+                } else if *transcode {
+                    let (src_list, dst_list, dst_len) = self
+                        .emit_transcode_string(function, locals, direction, *addr, *len, src_base);
+
+                    // Update the string's length in the parent list
+                    if let Some(offset) = len.offset() {
+                        self.emit_store_from_base(
+                            function,
+                            &dst_base.expect("destination base should be present"),
+                            offset,
+                            dst_len,
+                        );
+                    }
+
+                    (src_list, dst_list)
+                } else {
+                    self.emit_copy_list(
+                        function,
+                        locals,
+                        direction,
+                        *addr,
+                        *len,
+                        src_base,
+                        *element_size,
+                        *element_alignment,
+                    )
+                };
 
                 // Now that the list has been copied, update the element in the parent list
                 if let Some(offset) = addr.offset() {
@@ -807,7 +941,7 @@ impl<'a> CallAdapter<'a> {
                 }
 
                 // Free the source list for returned values
-                if let Direction::Out = direction {
+                if matches!(direction, Direction::Out) && !moved {
                     addr.emit_load(function, src_base, LoadType::I32);
                     len.emit_load(function, src_base, LoadType::I32);
                     if *element_size > 1 {
@@ -876,8 +1010,8 @@ impl<'a> CallAdapter<'a> {
         let index = locals.allocate();
 
         let (src_memory, dst_memory) = match direction {
-            Direction::In => (PARENT_MEMORY_INDEX, ADAPTED_MEMORY_INDEX),
-            Direction::Out => (ADAPTED_MEMORY_INDEX, PARENT_MEMORY_INDEX),
+            Direction::In => (self.parent_memory, self.adapted_memory),
+            Direction::Out => (self.adapted_memory, self.parent_memory),
         };
 
         let src_base = ElementBase {
@@ -939,14 +1073,14 @@ impl<'a> CallAdapter<'a> {
     ) -> (u32, u32) {
         let (src_memory, dst_memory, realloc) = match direction {
             Direction::In => (
-                PARENT_MEMORY_INDEX,
-                ADAPTED_MEMORY_INDEX,
+                self.parent_memory,
+                self.adapted_memory,
                 self.realloc_index
                     .expect("must be given an index to copy lists"),
             ),
             Direction::Out => (
-                ADAPTED_MEMORY_INDEX,
-                PARENT_MEMORY_INDEX,
+                self.adapted_memory,
+                self.parent_memory,
                 self.parent_realloc_index
                     .expect("must be given an index to copy lists"),
             ),
@@ -991,4 +1125,122 @@ impl<'a> CallAdapter<'a> {
 
         (src, dst)
     }
+
+    /// Moves a list from the adapted module to the parent when they share memory.
+    ///
+    /// Returns the local of the list, which is both the source and destination list.
+    fn emit_move_list(
+        &self,
+        function: &mut wasm_encoder::Function,
+        locals: &mut Locals,
+        addr: ValueRef,
+        src_base: Option<ElementBase>,
+    ) -> (u32, u32) {
+        let list = match addr {
+            ValueRef::ElementOffset(_) | ValueRef::RetPtr(_) => locals.allocate(),
+            ValueRef::Local(_) | ValueRef::Return(_) => unreachable!(),
+        };
+
+        addr.emit_load(function, src_base, LoadType::I32);
+        function.instruction(Instruction::LocalSet(list));
+
+        (list, list)
+    }
+
+    /// Transcodes a string between the parent's and adapted module's encodings.
+    ///
+    /// The destination is allocated for the worst case expansion and then shrunk to fit.
+    ///
+    /// Returns the locals of the source string, the destination string, and the destination length.
+    fn emit_transcode_string(
+        &self,
+        function: &mut wasm_encoder::Function,
+        locals: &mut Locals,
+        direction: Direction,
+        addr: ValueRef,
+        len: ValueRef,
+        src_base: Option<ElementBase>,
+    ) -> (u32, u32, u32) {
+        let (in_index, out_index) = self
+            .transcode_indexes
+            .expect("must be given transcoding functions to transcode strings");
+
+        let (from, to, realloc, transcode) = match direction {
+            Direction::In => (
+                self.encodings.parent,
+                self.encodings.adapted,
+                self.realloc_index
+                    .expect("must be given an index to copy lists"),
+                in_index,
+            ),
+            Direction::Out => (
+                self.encodings.adapted,
+                self.encodings.parent,
+                self.parent_realloc_index
+                    .expect("must be given an index to copy lists"),
+                out_index,
+            ),
+        };
+
+        let expansion = worst_case_expansion(from, to) as i32;
+        let alignment = to.code_unit() as i32;
+
+        let (src, dst) = match addr {
+            ValueRef::Local(i) => (i, locals.map(i)),
+            ValueRef::ElementOffset(_) | ValueRef::RetPtr(_) => {
+                let src = locals.allocate();
+                addr.emit_load(function, src_base, LoadType::I32);
+                function.instruction(Instruction::LocalSet(src));
+                (src, locals.allocate())
+            }
+            ValueRef::Return(_) => unreachable!(),
+        };
+
+        let dst_len = match len {
+            ValueRef::Local(i) => locals.map(i),
+            ValueRef::ElementOffset(_) | ValueRef::RetPtr(_) => locals.allocate(),
+            ValueRef::Return(_) => unreachable!(),
+        };
+
+        // Allocate the destination for the worst case
+        function.instruction(Instruction::Block(BlockType::Empty));
+        function.instruction(Instruction::I32Const(0)); // Previous ptr
+        function.instruction(Instruction::I32Const(0)); // Previous size
+        function.instruction(Instruction::I32Const(alignment));
+        len.emit_load(function, src_base, LoadType::I32);
+        function.instruction(Instruction::I32Const(expansion));
+        function.instruction(Instruction::I32Mul);
+        function.instruction(Instruction::Call(realloc));
+        function.instruction(Instruction::LocalTee(dst));
+        function.instruction(Instruction::BrIf(0));
+        function.instruction(Instruction::Unreachable);
+        function.instruction(Instruction::End);
+
+        // Transcode the string, which traps if the source is invalid
+        function.instruction(Instruction::LocalGet(src));
+        len.emit_load(function, src_base, LoadType::I32);
+        function.instruction(Instruction::LocalGet(dst));
+        function.instruction(Instruction::Call(transcode));
+        function.instruction(Instruction::LocalSet(dst_len));
+
+        // Shrink the destination to the transcoded size
+        function.instruction(Instruction::Block(BlockType::Empty));
+        function.instruction(Instruction::LocalGet(dst));
+        len.emit_load(function, src_base, LoadType::I32);
+        function.instruction(Instruction::I32Const(expansion));
+        function.instruction(Instruction::I32Mul);
+        function.instruction(Instruction::I32Const(alignment));
+        function.instruction(Instruction::LocalGet(dst_len));
+        if alignment > 1 {
+            function.instruction(Instruction::I32Const(alignment));
+            function.instruction(Instruction::I32Mul);
+        }
+        function.instruction(Instruction::Call(realloc));
+        function.instruction(Instruction::LocalTee(dst));
+        function.instruction(Instruction::BrIf(0));
+        function.instruction(Instruction::Unreachable);
+        function.instruction(Instruction::End);
+
+        (src, dst, dst_len)
+    }
 }
@@ -0,0 +1,617 @@
+use crate::module::StringEncoding;
+use wasm_encoder::{BlockType, Function, Instruction, MemArg, ValType};
+
+// Parameters of a transcoding function
+const SRC: u32 = 0;
+const LEN: u32 = 1;
+const DST: u32 = 2;
+
+// Locals of a transcoding function
+const INDEX: u32 = 3;
+const OUT: u32 = 4;
+const CODE_POINT: u32 = 5;
+const SCRATCH: u32 = 6;
+
+/// Gets the maximum number of bytes needed in the destination for each code unit in the source.
+pub fn worst_case_expansion(from: StringEncoding, to: StringEncoding) -> u32 {
+    match (from, to) {
+        (StringEncoding::Utf8, StringEncoding::Utf16) => 2,
+        (StringEncoding::Utf16, StringEncoding::Utf8) => 3,
+        (from, _) => from.code_unit(),
+    }
+}
+
+/// Creates a function that transcodes a string from one encoding to another.
+///
+/// The function has the signature `(src: i32, len: i32, dst: i32) -> i32` where `len` is
+/// the source length in code units and the result is the destination length in code units.
+///
+/// The destination must have space for the worst case expansion of the source.
+///
+/// The function traps if the source is not validly encoded.
+pub fn transcode_function(
+    from: StringEncoding,
+    to: StringEncoding,
+    src_memory: u32,
+    dst_memory: u32,
+) -> Function {
+    let mut function = Function::new([(4, ValType::I32)]);
+
+    match (from, to) {
+        (StringEncoding::Utf8, StringEncoding::Utf16) => {
+            emit_utf8_to_utf16(&mut function, src_memory, dst_memory)
+        }
+        (StringEncoding::Utf16, StringEncoding::Utf8) => {
+            emit_utf16_to_utf8(&mut function, src_memory, dst_memory)
+        }
+        _ => unreachable!("strings of the same encoding are copied without transcoding"),
+    }
+
+    function.instruction(Instruction::LocalGet(OUT));
+    function.instruction(Instruction::End);
+
+    function
+}
+
+fn memarg(offset: u32, align: u32, memory_index: u32) -> MemArg {
+    MemArg {
+        offset,
+        align,
+        memory_index,
+    }
+}
+
+/// Traps if the condition on the stack is true.
+fn emit_trap_if(function: &mut Function) {
+    function.instruction(Instruction::If(BlockType::Empty));
+    function.instruction(Instruction::Unreachable);
+    function.instruction(Instruction::End);
+}
+
+fn emit_increment(function: &mut Function, local: u32, amount: i32) {
+    function.instruction(Instruction::LocalGet(local));
+    function.instruction(Instruction::I32Const(amount));
+    function.instruction(Instruction::I32Add);
+    function.instruction(Instruction::LocalSet(local));
+}
+
+/// Pushes the address of the current code unit given a base, an index, and the code unit size.
+fn emit_address(function: &mut Function, base: u32, index: u32, size: u32) {
+    function.instruction(Instruction::LocalGet(base));
+    function.instruction(Instruction::LocalGet(index));
+    if size > 1 {
+        function.instruction(Instruction::I32Const(size as i32));
+        function.instruction(Instruction::I32Mul);
+    }
+    function.instruction(Instruction::I32Add);
+}
+
+fn emit_utf8_to_utf16(function: &mut Function, src_memory: u32, dst_memory: u32) {
+    // Traps if a sequence of `n` bytes would read past the end of the source
+    let emit_check_bounds = |function: &mut Function, n: i32| {
+        function.instruction(Instruction::LocalGet(INDEX));
+        function.instruction(Instruction::I32Const(n));
+        function.instruction(Instruction::I32Add);
+        function.instruction(Instruction::LocalGet(LEN));
+        function.instruction(Instruction::I32GtU);
+        emit_trap_if(function);
+    };
+
+    // Masks the leading byte of a sequence
+    let emit_mask_lead = |function: &mut Function, mask: i32| {
+        function.instruction(Instruction::LocalGet(CODE_POINT));
+        function.instruction(Instruction::I32Const(mask));
+        function.instruction(Instruction::I32And);
+        function.instruction(Instruction::LocalSet(CODE_POINT));
+    };
+
+    // Validates and accumulates the continuation byte at offset `n` of a sequence
+    let emit_continuation = |function: &mut Function, n: u32| {
+        emit_address(function, SRC, INDEX, 1);
+        function.instruction(Instruction::I32Load8_U(memarg(n, 0, src_memory)));
+        function.instruction(Instruction::LocalTee(SCRATCH));
+        function.instruction(Instruction::I32Const(0xc0));
+        function.instruction(Instruction::I32And);
+        function.instruction(Instruction::I32Const(0x80));
+        function.instruction(Instruction::I32Neq);
+        emit_trap_if(function);
+
+        function.instruction(Instruction::LocalGet(CODE_POINT));
+        function.instruction(Instruction::I32Const(6));
+        function.instruction(Instruction::I32Shl);
+        function.instruction(Instruction::LocalGet(SCRATCH));
+        function.instruction(Instruction::I32Const(0x3f));
+        function.instruction(Instruction::I32And);
+        function.instruction(Instruction::I32Or);
+        function.instruction(Instruction::LocalSet(CODE_POINT));
+    };
+
+    let emit_code_point_lt = |function: &mut Function, value: i32| {
+        function.instruction(Instruction::LocalGet(CODE_POINT));
+        function.instruction(Instruction::I32Const(value));
+        function.instruction(Instruction::I32LtU);
+    };
+
+    function.instruction(Instruction::Block(BlockType::Empty));
+    function.instruction(Instruction::Loop(BlockType::Empty));
+
+    function.instruction(Instruction::LocalGet(INDEX));
+    function.instruction(Instruction::LocalGet(LEN));
+    function.instruction(Instruction::I32GeU);
+    function.instruction(Instruction::BrIf(1));
+
+    // Decode the next code point
+    emit_address(function, SRC, INDEX, 1);
+    function.instruction(Instruction::I32Load8_U(memarg(0, 0, src_memory)));
+    function.instruction(Instruction::LocalSet(CODE_POINT));
+
+    emit_code_point_lt(function, 0x80);
+    function.instruction(Instruction::If(BlockType::Empty));
+    emit_increment(function, INDEX, 1);
+    function.instruction(Instruction::Else);
+
+    emit_code_point_lt(function, 0xe0);
+    function.instruction(Instruction::If(BlockType::Empty));
+    // Continuation bytes and overlong leading bytes are invalid
+    emit_code_point_lt(function, 0xc2);
+    emit_trap_if(function);
+    emit_check_bounds(function, 2);
+    emit_mask_lead(function, 0x1f);
+    emit_continuation(function, 1);
+    emit_increment(function, INDEX, 2);
+    function.instruction(Instruction::Else);
+
+    emit_code_point_lt(function, 0xf0);
+    function.instruction(Instruction::If(BlockType::Empty));
+    emit_check_bounds(function, 3);
+    emit_mask_lead(function, 0x0f);
+    emit_continuation(function, 1);
+    emit_continuation(function, 2);
+    // Overlong encodings and surrogates are invalid
+    emit_code_point_lt(function, 0x800);
+    emit_trap_if(function);
+    function.instruction(Instruction::LocalGet(CODE_POINT));
+    function.instruction(Instruction::I32Const(0xf800));
+    function.instruction(Instruction::I32And);
+    function.instruction(Instruction::I32Const(0xd800));
+    function.instruction(Instruction::I32Eq);
+    emit_trap_if(function);
+    emit_increment(function, INDEX, 3);
+    function.instruction(Instruction::Else);
+
+    // Leading bytes that would encode beyond U+10FFFF are invalid
+    emit_code_point_lt(function, 0xf5);
+    function.instruction(Instruction::I32Eqz);
+    emit_trap_if(function);
+    emit_check_bounds(function, 4);
+    emit_mask_lead(function, 0x07);
+    emit_continuation(function, 1);
+    emit_continuation(function, 2);
+    emit_continuation(function, 3);
+    // Overlong encodings and code points beyond U+10FFFF are invalid
+    emit_code_point_lt(function, 0x10000);
+    function.instruction(Instruction::LocalGet(CODE_POINT));
+    function.instruction(Instruction::I32Const(0x10ffff));
+    function.instruction(Instruction::I32GtU);
+    function.instruction(Instruction::I32Or);
+    emit_trap_if(function);
+    emit_increment(function, INDEX, 4);
+
+    function.instruction(Instruction::End);
+    function.instruction(Instruction::End);
+    function.instruction(Instruction::End);
+
+    // Encode the code point as either a single code unit or a surrogate pair
+    emit_code_point_lt(function, 0x10000);
+    function.instruction(Instruction::If(BlockType::Empty));
+    emit_address(function, DST, OUT, 2);
+    function.instruction(Instruction::LocalGet(CODE_POINT));
+    function.instruction(Instruction::I32Store16(memarg(0, 1, dst_memory)));
+    emit_increment(function, OUT, 1);
+    function.instruction(Instruction::Else);
+    emit_increment(function, CODE_POINT, -0x10000);
+    emit_address(function, DST, OUT, 2);
+    function.instruction(Instruction::LocalGet(CODE_POINT));
+    function.instruction(Instruction::I32Const(10));
+    function.instruction(Instruction::I32ShrU);
+    function.instruction(Instruction::I32Const(0xd800));
+    function.instruction(Instruction::I32Or);
+    function.instruction(Instruction::I32Store16(memarg(0, 1, dst_memory)));
+    emit_address(function, DST, OUT, 2);
+    function.instruction(Instruction::LocalGet(CODE_POINT));
+    function.instruction(Instruction::I32Const(0x3ff));
+    function.instruction(Instruction::I32And);
+    function.instruction(Instruction::I32Const(0xdc00));
+    function.instruction(Instruction::I32Or);
+    function.instruction(Instruction::I32Store16(memarg(2, 1, dst_memory)));
+    emit_increment(function, OUT, 2);
+    function.instruction(Instruction::End);
+
+    function.instruction(Instruction::Br(0));
+    function.instruction(Instruction::End);
+    function.instruction(Instruction::End);
+}
+
+fn emit_utf16_to_utf8(function: &mut Function, src_memory: u32, dst_memory: u32) {
+    // Stores a byte of the code point's encoding at offset `n` in the destination
+    let emit_store_byte = |function: &mut Function, n: u32, shift: i32, prefix: i32| {
+        emit_address(function, DST, OUT, 1);
+        function.instruction(Instruction::LocalGet(CODE_POINT));
+        if shift > 0 {
+            function.instruction(Instruction::I32Const(shift));
+            function.instruction(Instruction::I32ShrU);
+        }
+        if n > 0 {
+            function.instruction(Instruction::I32Const(0x3f));
+            function.instruction(Instruction::I32And);
+        }
+        if prefix != 0 {
+            function.instruction(Instruction::I32Const(prefix));
+            function.instruction(Instruction::I32Or);
+        }
+        function.instruction(Instruction::I32Store8(memarg(n, 0, dst_memory)));
+    };
+
+    let emit_code_point_lt = |function: &mut Function, value: i32| {
+        function.instruction(Instruction::LocalGet(CODE_POINT));
+        function.instruction(Instruction::I32Const(value));
+        function.instruction(Instruction::I32LtU);
+    };
+
+    function.instruction(Instruction::Block(BlockType::Empty));
+    function.instruction(Instruction::Loop(BlockType::Empty));
+
+    function.instruction(Instruction::LocalGet(INDEX));
+    function.instruction(Instruction::LocalGet(LEN));
+    function.instruction(Instruction::I32GeU);
+    function.instruction(Instruction::BrIf(1));
+
+    // Decode the next code point
+    emit_address(function, SRC, INDEX, 2);
+    function.instruction(Instruction::I32Load16_U(memarg(0, 1, src_memory)));
+    function.instruction(Instruction::LocalSet(CODE_POINT));
+    emit_increment(function, INDEX, 1);
+
+    function.instruction(Instruction::LocalGet(CODE_POINT));
+    function.instruction(Instruction::I32Const(0xf800));
+    function.instruction(Instruction::I32And);
+    function.instruction(Instruction::I32Const(0xd800));
+    function.instruction(Instruction::I32Eq);
+    function.instruction(Instruction::If(BlockType::Empty));
+    // A surrogate must be a high surrogate followed by a low surrogate
+    emit_code_point_lt(function, 0xdc00);
+    function.instruction(Instruction::I32Eqz);
+    emit_trap_if(function);
+    function.instruction(Instruction::LocalGet(INDEX));
+    function.instruction(Instruction::LocalGet(LEN));
+    function.instruction(Instruction::I32GeU);
+    emit_trap_if(function);
+    emit_address(function, SRC, INDEX, 2);
+    function.instruction(Instruction::I32Load16_U(memarg(0, 1, src_memory)));
+    function.instruction(Instruction::LocalTee(SCRATCH));
+    function.instruction(Instruction::I32Const(0xfc00));
+    function.instruction(Instruction::I32And);
+    function.instruction(Instruction::I32Const(0xdc00));
+    function.instruction(Instruction::I32Neq);
+    emit_trap_if(function);
+    emit_increment(function, INDEX, 1);
+    function.instruction(Instruction::LocalGet(CODE_POINT));
+    function.instruction(Instruction::I32Const(0xd800));
+    function.instruction(Instruction::I32Sub);
+    function.instruction(Instruction::I32Const(10));
+    function.instruction(Instruction::I32Shl);
+    function.instruction(Instruction::LocalGet(SCRATCH));
+    function.instruction(Instruction::I32Const(0xdc00));
+    function.instruction(Instruction::I32Sub);
+    function.instruction(Instruction::I32Or);
+    function.instruction(Instruction::I32Const(0x10000));
+    function.instruction(Instruction::I32Add);
+    function.instruction(Instruction::LocalSet(CODE_POINT));
+    function.instruction(Instruction::End);
+
+    // Encode the code point as one to four bytes
+    emit_code_point_lt(function, 0x80);
+    function.instruction(Instruction::If(BlockType::Empty));
+    emit_store_byte(function, 0, 0, 0);
+    emit_increment(function, OUT, 1);
+    function.instruction(Instruction::Else);
+
+    emit_code_point_lt(function, 0x800);
+    function.instruction(Instruction::If(BlockType::Empty));
+    emit_store_byte(function, 0, 6, 0xc0);
+    emit_store_byte(function, 1, 0, 0x80);
+    emit_increment(function, OUT, 2);
+    function.instruction(Instruction::Else);
+
+    emit_code_point_lt(function, 0x10000);
+    function.instruction(Instruction::If(BlockType::Empty));
+    emit_store_byte(function, 0, 12, 0xe0);
+    emit_store_byte(function, 1, 6, 0x80);
+    emit_store_byte(function, 2, 0, 0x80);
+    emit_increment(function, OUT, 3);
+    function.instruction(Instruction::Else);
+
+    emit_store_byte(function, 0, 18, 0xf0);
+    emit_store_byte(function, 1, 12, 0x80);
+    emit_store_byte(function, 2, 6, 0x80);
+    emit_store_byte(function, 3, 0, 0x80);
+    emit_increment(function, OUT, 4);
+
+    function.instruction(Instruction::End);
+    function.instruction(Instruction::End);
+    function.instruction(Instruction::End);
+
+    function.instruction(Instruction::Br(0));
+    function.instruction(Instruction::End);
+    function.instruction(Instruction::End);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasm_encoder::{
+        CodeSection, Export, ExportSection, FunctionSection, Limits, MemorySection, MemoryType,
+        TypeSection,
+    };
+    use wasmtime::{Engine, Instance, Memory, Module, Store, Trap, TypedFunc};
+
+    const SRC_ADDR: u32 = 0;
+    const DST_ADDR: u32 = 0x8000;
+
+    /// Runs a transcoding function in a module with a single memory for both source and destination.
+    struct Transcoder {
+        store: Store<()>,
+        memory: Memory,
+        function: TypedFunc<(u32, u32, u32), u32>,
+        from: StringEncoding,
+        to: StringEncoding,
+    }
+
+    impl Transcoder {
+        fn new(from: StringEncoding, to: StringEncoding) -> Self {
+            let mut module = wasm_encoder::Module::new();
+
+            let mut section = TypeSection::new();
+            section.function([ValType::I32, ValType::I32, ValType::I32], [ValType::I32]);
+            module.section(&section);
+
+            let mut section = FunctionSection::new();
+            section.function(0);
+            module.section(&section);
+
+            let mut section = MemorySection::new();
+            section.memory(MemoryType {
+                limits: Limits { min: 1, max: None },
+            });
+            module.section(&section);
+
+            let mut section = ExportSection::new();
+            section.export("transcode", Export::Function(0));
+            section.export("memory", Export::Memory(0));
+            module.section(&section);
+
+            let mut section = CodeSection::new();
+            section.function(&transcode_function(from, to, 0, 0));
+            module.section(&section);
+
+            let engine = Engine::default();
+            let module = Module::new(&engine, module.finish()).unwrap();
+            let mut store = Store::new(&engine, ());
+            let instance = Instance::new(&mut store, &module, &[]).unwrap();
+            let memory = instance.get_memory(&mut store, "memory").unwrap();
+            let function = instance.get_typed_func(&mut store, "transcode").unwrap();
+
+            Self {
+                store,
+                memory,
+                function,
+                from,
+                to,
+            }
+        }
+
+        /// Transcodes `src`, returning the bytes written to the destination.
+        fn run(&mut self, src: &[u8]) -> Result<Vec<u8>, Trap> {
+            let len = src.len() as u32 / self.from.code_unit();
+            assert!(len * worst_case_expansion(self.from, self.to) <= DST_ADDR - SRC_ADDR);
+
+            self.memory
+                .write(&mut self.store, SRC_ADDR as usize, src)
+                .unwrap();
+            let out = self
+                .function
+                .call(&mut self.store, (SRC_ADDR, len, DST_ADDR))?;
+
+            let mut dst = vec![0; (out * self.to.code_unit()) as usize];
+            self.memory
+                .read(&self.store, DST_ADDR as usize, &mut dst)
+                .unwrap();
+            Ok(dst)
+        }
+
+        fn utf8_to_utf16(&mut self, src: &[u8]) -> Result<Vec<u16>, Trap> {
+            let dst = self.run(src)?;
+            Ok(dst
+                .chunks(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect())
+        }
+
+        fn utf16_to_utf8(&mut self, src: &[u16]) -> Result<Vec<u8>, Trap> {
+            let src = src.iter().flat_map(|u| u.to_le_bytes()).collect::<Vec<_>>();
+            self.run(&src)
+        }
+    }
+
+    const VALID: &[&str] = &[
+        "",
+        "hello, world",
+        "\0",
+        "\u{7f}\u{80}",
+        "\u{7ff}\u{800}",
+        "héllo wörld",
+        "€ ∑ ☃ \u{d7ff}\u{e000}\u{fffd}\u{ffff}",
+        "\u{10000}",
+        "😀 🎉 𐐷",
+        "\u{10ffff}",
+        "mixed: a é € 😀 z",
+    ];
+
+    #[test]
+    fn utf8_to_utf16_valid() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf8, StringEncoding::Utf16);
+        for s in VALID {
+            assert_eq!(
+                transcoder.utf8_to_utf16(s.as_bytes()).unwrap(),
+                s.encode_utf16().collect::<Vec<_>>(),
+                "{:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn utf8_to_utf16_surrogate_pairs() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf8, StringEncoding::Utf16);
+        assert_eq!(
+            transcoder.utf8_to_utf16("\u{10000}".as_bytes()).unwrap(),
+            [0xd800, 0xdc00]
+        );
+        assert_eq!(
+            transcoder.utf8_to_utf16("𐐷".as_bytes()).unwrap(),
+            [0xd801, 0xdc37]
+        );
+        assert_eq!(
+            transcoder.utf8_to_utf16("a\u{10ffff}b".as_bytes()).unwrap(),
+            [0x61, 0xdbff, 0xdfff, 0x62]
+        );
+    }
+
+    #[test]
+    fn utf8_to_utf16_invalid() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf8, StringEncoding::Utf16);
+        for bytes in [
+            // Unexpected continuation bytes
+            &[0x80][..],
+            &[0x61, 0xbf],
+            // Missing continuation bytes
+            &[0xc3, 0x28],
+            &[0xe2, 0x82, 0x28],
+            &[0xf0, 0x9f, 0x98, 0x28],
+            // Overlong encodings
+            &[0xc0, 0x80],
+            &[0xc1, 0xbf],
+            &[0xe0, 0x80, 0x80],
+            &[0xe0, 0x9f, 0xbf],
+            &[0xf0, 0x80, 0x80, 0x80],
+            &[0xf0, 0x8f, 0xbf, 0xbf],
+            // Encoded surrogates
+            &[0xed, 0xa0, 0x80],
+            &[0xed, 0xbf, 0xbf],
+            &[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80],
+            // Beyond U+10FFFF
+            &[0xf4, 0x90, 0x80, 0x80],
+            &[0xf5, 0x80, 0x80, 0x80],
+            &[0xff],
+            // Truncated sequences
+            &[0xc3],
+            &[0x61, 0xe2, 0x82],
+            &[0xf0, 0x9f, 0x98],
+        ] {
+            assert!(std::str::from_utf8(bytes).is_err());
+            assert!(transcoder.utf8_to_utf16(bytes).is_err(), "{:x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn utf8_to_utf16_truncated_by_length() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf8, StringEncoding::Utf16);
+        let bytes = "a😀".as_bytes();
+
+        // The rest of the sequence is in memory, but beyond the length of the string
+        transcoder.run(bytes).unwrap();
+        let len = bytes.len() as u32 - 1;
+        assert!(transcoder
+            .function
+            .call(&mut transcoder.store, (SRC_ADDR, len, DST_ADDR))
+            .is_err());
+    }
+
+    #[test]
+    fn utf8_to_utf16_all_two_byte_sequences() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf8, StringEncoding::Utf16);
+        for i in 0..=u16::MAX {
+            let bytes = i.to_be_bytes();
+            let expected = std::str::from_utf8(&bytes)
+                .ok()
+                .map(|s| s.encode_utf16().collect::<Vec<_>>());
+            assert_eq!(
+                transcoder.utf8_to_utf16(&bytes).ok(),
+                expected,
+                "{:x?}",
+                bytes
+            );
+        }
+    }
+
+    #[test]
+    fn utf16_to_utf8_valid() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf16, StringEncoding::Utf8);
+        for s in VALID {
+            let units = s.encode_utf16().collect::<Vec<_>>();
+            assert_eq!(
+                transcoder.utf16_to_utf8(&units).unwrap(),
+                s.as_bytes(),
+                "{:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn utf16_to_utf8_surrogate_pairs() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf16, StringEncoding::Utf8);
+        assert_eq!(
+            transcoder.utf16_to_utf8(&[0xd800, 0xdc00]).unwrap(),
+            "\u{10000}".as_bytes()
+        );
+        assert_eq!(
+            transcoder.utf16_to_utf8(&[0xd801, 0xdc37]).unwrap(),
+            "𐐷".as_bytes()
+        );
+        assert_eq!(
+            transcoder
+                .utf16_to_utf8(&[0x61, 0xdbff, 0xdfff, 0x62])
+                .unwrap(),
+            "a\u{10ffff}b".as_bytes()
+        );
+    }
+
+    #[test]
+    fn utf16_to_utf8_lone_surrogates() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf16, StringEncoding::Utf8);
+        for units in [
+            &[0xd800][..],
+            &[0xdbff],
+            &[0xdc00],
+            &[0xdfff],
+            &[0x61, 0xd800],
+            &[0xd800, 0x61],
+            &[0xd800, 0xd800],
+            &[0xdc00, 0xd800],
+            &[0xd800, 0xdc00, 0xdc00],
+        ] {
+            assert!(String::from_utf16(units).is_err());
+            assert!(transcoder.utf16_to_utf8(units).is_err(), "{:x?}", units);
+        }
+    }
+
+    #[test]
+    fn utf16_to_utf8_all_code_units() {
+        let mut transcoder = Transcoder::new(StringEncoding::Utf16, StringEncoding::Utf8);
+        for i in 0..=u16::MAX {
+            let expected = String::from_utf16(&[i]).ok().map(String::into_bytes);
+            assert_eq!(transcoder.utf16_to_utf8(&[i]).ok(), expected, "{:x}", i);
+        }
+    }
+}
@@ -0,0 +1,1096 @@
+use crate::{linker::to_val_type, names};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+use wasmparser::{
+    Alias, Data, DataKind, Element, ElementItem, ElementKind, Export, ExternalKind, FuncType,
+    FunctionBody, Global, Import, ImportSectionEntryType, InitExpr, InstanceArg, MemoryType,
+    Operator, Parser, Payload, TableType, TypeDef, TypeOrFuncType,
+};
+
+/// Represents an item in one of the index spaces of the fused module.
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    Function(u32),
+    Table(u32),
+    Memory(u32),
+    Global(u32),
+}
+
+/// Represents a value that can be imported or exported by an instance.
+#[derive(Debug, Clone)]
+enum Value<'a> {
+    Item(Item),
+    Instance(Rc<Exports<'a>>),
+}
+
+type Exports<'a> = HashMap<&'a str, Value<'a>>;
+
+/// Represents a section of a parsed module, in the order it appeared.
+enum Section<'a> {
+    Type(Vec<Option<FuncType>>),
+    Import(Vec<Import<'a>>),
+    Module(Vec<Rc<ModuleDef<'a>>>),
+    Instance(Vec<(u32, Vec<InstanceArg<'a>>)>),
+    Alias(Vec<Alias<'a>>),
+    Function(Vec<u32>),
+    Table(Vec<TableType>),
+    Memory(Vec<MemoryType>),
+    Global(Vec<Global<'a>>),
+    Export(Vec<Export<'a>>),
+    Start(u32),
+    Element(Vec<Element<'a>>),
+    Data(Vec<Data<'a>>),
+    Code(Vec<FunctionBody<'a>>),
+}
+
+/// Represents a parsed (possibly nested) module.
+#[derive(Default)]
+struct ModuleDef<'a> {
+    sections: Vec<Section<'a>>,
+    function_names: Vec<(u32, &'a str)>,
+}
+
+impl<'a> ModuleDef<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self> {
+        let mut stack = vec![ModuleDef::default()];
+
+        for payload in Parser::new(0).parse_all(bytes) {
+            let current = stack.last_mut().unwrap();
+            let section = match payload? {
+                Payload::TypeSection(types) => Section::Type(
+                    types
+                        .into_iter()
+                        .map(|ty| {
+                            Ok(match ty? {
+                                TypeDef::Func(ty) => Some(ty),
+                                _ => None,
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                ),
+                Payload::ImportSection(imports) => {
+                    Section::Import(imports.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::AliasSection(aliases) => {
+                    Section::Alias(aliases.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::InstanceSection(instances) => {
+                    let mut section = Vec::new();
+                    for instance in instances {
+                        let instance = instance?;
+                        section.push((
+                            instance.module(),
+                            instance.args()?.into_iter().collect::<Result<_, _>>()?,
+                        ));
+                    }
+                    Section::Instance(section)
+                }
+                Payload::FunctionSection(functions) => {
+                    Section::Function(functions.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::TableSection(tables) => {
+                    Section::Table(tables.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::MemorySection(memories) => {
+                    Section::Memory(memories.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::GlobalSection(globals) => {
+                    Section::Global(globals.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::ExportSection(exports) => {
+                    Section::Export(exports.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::StartSection { func, .. } => Section::Start(func),
+                Payload::ElementSection(elements) => {
+                    Section::Element(elements.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::DataSection(data) => {
+                    Section::Data(data.into_iter().collect::<Result<_, _>>()?)
+                }
+                Payload::CodeSectionStart { .. } => Section::Code(Vec::new()),
+                Payload::CodeSectionEntry(body) => {
+                    match current.sections.last_mut() {
+                        Some(Section::Code(bodies)) => bodies.push(body),
+                        _ => unreachable!("code section entry outside of a code section"),
+                    }
+                    continue;
+                }
+                Payload::ModuleSectionStart { .. } => Section::Module(Vec::new()),
+                Payload::ModuleSectionEntry { .. } => {
+                    stack.push(ModuleDef::default());
+                    continue;
+                }
+                Payload::End => {
+                    let module = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some(parent) => match parent.sections.last_mut() {
+                            Some(Section::Module(modules)) => modules.push(Rc::new(module)),
+                            _ => unreachable!("module entry outside of a module section"),
+                        },
+                        None => return Ok(module),
+                    }
+                    continue;
+                }
+                Payload::EventSection(_) => bail!("module contains unsupported event section"),
+                Payload::UnknownSection { id, .. } => bail!("unknown section with id `{}`", id),
+                Payload::CustomSection {
+                    name,
+                    data,
+                    data_offset,
+                    ..
+                } => {
+                    current.function_names.extend(names::read_function_names(
+                        name,
+                        data,
+                        data_offset,
+                    )?);
+                    continue;
+                }
+                Payload::Version { .. } | Payload::DataCountSection { .. } => continue,
+            };
+
+            current.sections.push(section);
+        }
+
+        unreachable!("module should have ended")
+    }
+}
+
+/// Represents the index spaces of a module being instantiated.
+#[derive(Default)]
+struct Scope<'a> {
+    types: Vec<Option<FuncType>>,
+    functions: Vec<u32>,
+    tables: Vec<u32>,
+    memories: Vec<u32>,
+    globals: Vec<u32>,
+    instances: Vec<Rc<Exports<'a>>>,
+    modules: Vec<Rc<ModuleDef<'a>>>,
+    elements: Vec<u32>,
+    data: Vec<u32>,
+    defined_functions: Vec<u32>,
+    num_bodies: usize,
+}
+
+impl<'a> Scope<'a> {
+    fn push(&mut self, item: Item) {
+        match item {
+            Item::Function(i) => self.functions.push(i),
+            Item::Table(i) => self.tables.push(i),
+            Item::Memory(i) => self.memories.push(i),
+            Item::Global(i) => self.globals.push(i),
+        }
+    }
+
+    fn lookup(&self, kind: ExternalKind, index: u32) -> Result<Value<'a>> {
+        let index = index as usize;
+        let value = match kind {
+            ExternalKind::Function => self.functions.get(index).copied().map(Item::Function),
+            ExternalKind::Table => self.tables.get(index).copied().map(Item::Table),
+            ExternalKind::Memory => self.memories.get(index).copied().map(Item::Memory),
+            ExternalKind::Global => self.globals.get(index).copied().map(Item::Global),
+            ExternalKind::Instance => {
+                return self
+                    .instances
+                    .get(index)
+                    .cloned()
+                    .map(Value::Instance)
+                    .ok_or_else(|| anyhow!("instance index {} is out of bounds", index))
+            }
+            ExternalKind::Module => bail!("fusing modules that export modules is not supported"),
+            ExternalKind::Type | ExternalKind::Event => {
+                bail!("fusing modules that export types or events is not supported")
+            }
+        };
+
+        value
+            .map(Value::Item)
+            .ok_or_else(|| anyhow!("{} index {} is out of bounds", kind_name(kind), index))
+    }
+
+    fn function(&self, index: u32) -> Result<u32> {
+        self.functions
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("function index {} is out of bounds", index))
+    }
+
+    fn table(&self, index: u32) -> Result<u32> {
+        self.tables
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("table index {} is out of bounds", index))
+    }
+
+    fn memory(&self, index: u32) -> Result<u32> {
+        self.memories
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("memory index {} is out of bounds", index))
+    }
+
+    fn global(&self, index: u32) -> Result<u32> {
+        self.globals
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("global index {} is out of bounds", index))
+    }
+
+    fn element(&self, index: u32) -> Result<u32> {
+        self.elements
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("element segment index {} is out of bounds", index))
+    }
+
+    fn data(&self, index: u32) -> Result<u32> {
+        self.data
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("data segment index {} is out of bounds", index))
+    }
+}
+
+fn kind_name(kind: ExternalKind) -> &'static str {
+    match kind {
+        ExternalKind::Function => "function",
+        ExternalKind::Table => "table",
+        ExternalKind::Memory => "memory",
+        ExternalKind::Global => "global",
+        ExternalKind::Instance => "instance",
+        ExternalKind::Module => "module",
+        ExternalKind::Type => "type",
+        ExternalKind::Event => "event",
+    }
+}
+
+fn to_memory_type(ty: &MemoryType) -> Result<wasm_encoder::MemoryType> {
+    match ty {
+        MemoryType::M32 {
+            limits,
+            shared: false,
+        } => Ok(wasm_encoder::MemoryType {
+            limits: wasm_encoder::Limits {
+                min: limits.initial,
+                max: limits.maximum,
+            },
+        }),
+        _ => bail!("fusing shared or 64-bit memories is not supported"),
+    }
+}
+
+fn to_table_type(ty: &TableType) -> wasm_encoder::TableType {
+    wasm_encoder::TableType {
+        element_type: to_val_type(&ty.element_type),
+        limits: wasm_encoder::Limits {
+            min: ty.limits.initial,
+            max: ty.limits.maximum,
+        },
+    }
+}
+
+fn read_leb(bytes: &[u8]) -> (u64, usize) {
+    let mut result = 0;
+    let mut shift = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        result |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return (result, i + 1);
+        }
+        shift += 7;
+    }
+    unreachable!("operators should have been validated")
+}
+
+fn write_leb(mut value: u64, bytes: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Determines if the operator with the given opcode has a memory immediate.
+///
+/// Returns the length of the opcode if it does.
+fn memarg_opcode_len(bytes: &[u8]) -> Option<usize> {
+    match bytes[0] {
+        // Loads and stores
+        0x28..=0x3e => Some(1),
+        // SIMD loads and stores (`v128.load*`, `v128.store`, lane and zero variants)
+        0xfd => {
+            let (op, len) = read_leb(&bytes[1..]);
+            match op {
+                0x00..=0x0b | 0x54..=0x5d => Some(1 + len),
+                _ => None,
+            }
+        }
+        // Atomics, except for `atomic.fence`
+        0xfe => {
+            let (op, len) = read_leb(&bytes[1..]);
+            if op == 0x03 {
+                None
+            } else {
+                Some(1 + len)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Gets the length of the opcode of the given operator bytes.
+fn opcode_len(bytes: &[u8]) -> usize {
+    match bytes[0] {
+        0xfc..=0xfe => 1 + read_leb(&bytes[1..]).1,
+        _ => 1,
+    }
+}
+
+/// The mode of a segment in the fused module.
+///
+/// Active segments have the index of the table or memory they initialize and
+/// their offset; passive and declared segments have no mode.
+type SegmentMode = Option<(u32, wasm_encoder::Instruction<'static>)>;
+
+/// Represents an element segment in the fused module.
+struct ElementSegment {
+    mode: SegmentMode,
+    declared: bool,
+    ty: wasm_encoder::ValType,
+    items: Vec<wasm_encoder::Element>,
+}
+
+/// Implements fusing a linked module into a single core WebAssembly module.
+///
+/// Every instantiation in the linked module is evaluated and the items of the
+/// instantiated modules are merged into the index spaces of the fused module.
+/// Each module keeps its own memories and tables, so the fused module relies
+/// on the multi-memory proposal when more than one memory is linked.
+#[derive(Default)]
+struct Fuser<'a> {
+    bytes: &'a [u8],
+    types: Vec<FuncType>,
+    type_map: HashMap<FuncType, u32>,
+    imports: Vec<(&'a str, Option<&'a str>, wasm_encoder::EntityType)>,
+    num_imported_funcs: u32,
+    num_imported_tables: u32,
+    num_imported_memories: u32,
+    num_imported_globals: u32,
+    functions: Vec<u32>,
+    bodies: Vec<Option<wasm_encoder::Function>>,
+    tables: Vec<wasm_encoder::TableType>,
+    memories: Vec<wasm_encoder::MemoryType>,
+    globals: Vec<(wasm_encoder::GlobalType, wasm_encoder::Instruction<'static>)>,
+    elements: Vec<ElementSegment>,
+    data: Vec<(SegmentMode, &'a [u8])>,
+    starts: Vec<u32>,
+    exports: Vec<(&'a str, wasm_encoder::Export)>,
+    function_names: HashMap<u32, &'a str>,
+    needs_data_count: bool,
+}
+
+impl<'a> Fuser<'a> {
+    fn intern_type(&mut self, ty: &FuncType) -> u32 {
+        if let Some(index) = self.type_map.get(ty) {
+            return *index;
+        }
+
+        let index = self.types.len() as u32;
+        self.types.push(ty.clone());
+        self.type_map.insert(ty.clone(), index);
+        index
+    }
+
+    fn has_definitions(&self) -> bool {
+        !self.functions.is_empty()
+            || !self.tables.is_empty()
+            || !self.memories.is_empty()
+            || !self.globals.is_empty()
+    }
+
+    fn import(&mut self, scope: &Scope<'a>, import: &Import<'a>) -> Result<Item> {
+        if self.has_definitions() {
+            bail!("imports of the linked module must precede its definitions");
+        }
+
+        let (entity, item) = match import.ty {
+            ImportSectionEntryType::Function(ty) => {
+                let ty = scope
+                    .types
+                    .get(ty as usize)
+                    .cloned()
+                    .flatten()
+                    .ok_or_else(|| anyhow!("type index {} is not a function type", ty))?;
+                let index = self.num_imported_funcs;
+                self.num_imported_funcs += 1;
+                (
+                    wasm_encoder::EntityType::Function(self.intern_type(&ty)),
+                    Item::Function(index),
+                )
+            }
+            ImportSectionEntryType::Table(ty) => {
+                let index = self.num_imported_tables;
+                self.num_imported_tables += 1;
+                (
+                    wasm_encoder::EntityType::Table(to_table_type(&ty)),
+                    Item::Table(index),
+                )
+            }
+            ImportSectionEntryType::Memory(ty) => {
+                let index = self.num_imported_memories;
+                self.num_imported_memories += 1;
+                (
+                    wasm_encoder::EntityType::Memory(to_memory_type(&ty)?),
+                    Item::Memory(index),
+                )
+            }
+            ImportSectionEntryType::Global(ty) => {
+                let index = self.num_imported_globals;
+                self.num_imported_globals += 1;
+                (
+                    wasm_encoder::EntityType::Global(wasm_encoder::GlobalType {
+                        val_type: to_val_type(&ty.content_type),
+                        mutable: ty.mutable,
+                    }),
+                    Item::Global(index),
+                )
+            }
+            _ => bail!("fusing a linked module that imports modules or instances is not supported"),
+        };
+
+        self.imports.push((import.module, import.field, entity));
+
+        Ok(item)
+    }
+
+    fn instantiate(
+        &mut self,
+        module: &ModuleDef<'a>,
+        args: Option<HashMap<&'a str, Value<'a>>>,
+    ) -> Result<Rc<Exports<'a>>> {
+        let mut scope = Scope::default();
+        let mut exports = Exports::new();
+        let mut implicit_instances: HashMap<&'a str, usize> = HashMap::new();
+
+        for section in &module.sections {
+            match section {
+                Section::Type(types) => scope.types.extend(types.iter().cloned()),
+                Section::Import(imports) => {
+                    implicit_instances.clear();
+
+                    for import in imports {
+                        let field = import.field.ok_or_else(|| {
+                            anyhow!("fusing single-level imports is not supported")
+                        })?;
+
+                        // The root module's imports become imports of the fused module
+                        let item = match &args {
+                            None => self.import(&scope, import)?,
+                            Some(args) => match args.get(import.module) {
+                                Some(Value::Instance(instance)) => match instance.get(field) {
+                                    Some(Value::Item(item)) => *item,
+                                    _ => bail!(
+                                        "instance `{}` does not export an item named `{}`",
+                                        import.module,
+                                        field
+                                    ),
+                                },
+                                _ => bail!(
+                                    "instantiation is missing an instance argument named `{}`",
+                                    import.module
+                                ),
+                            },
+                        };
+
+                        scope.push(item);
+
+                        // Two-level imports implicitly import an instance per module name
+                        let index = match implicit_instances.get(import.module) {
+                            Some(index) => *index,
+                            None => {
+                                let index = scope.instances.len();
+                                scope.instances.push(Rc::new(Exports::new()));
+                                implicit_instances.insert(import.module, index);
+                                index
+                            }
+                        };
+
+                        Rc::get_mut(&mut scope.instances[index])
+                            .expect("implicit instance should not be shared")
+                            .insert(field, Value::Item(item));
+                    }
+                }
+                Section::Module(modules) => scope.modules.extend(modules.iter().cloned()),
+                Section::Instance(instances) => {
+                    for (module, instance_args) in instances {
+                        let module = scope
+                            .modules
+                            .get(*module as usize)
+                            .cloned()
+                            .ok_or_else(|| anyhow!("module index {} is out of bounds", module))?;
+
+                        let instance_args = instance_args
+                            .iter()
+                            .map(|arg| Ok((arg.name, scope.lookup(arg.kind, arg.index)?)))
+                            .collect::<Result<_>>()?;
+
+                        let instance = self.instantiate(&module, Some(instance_args))?;
+                        scope.instances.push(instance);
+                    }
+                }
+                Section::Alias(aliases) => {
+                    for alias in aliases {
+                        match alias {
+                            Alias::InstanceExport {
+                                instance,
+                                kind,
+                                export,
+                            } => {
+                                let instance =
+                                    scope.instances.get(*instance as usize).ok_or_else(|| {
+                                        anyhow!("instance index {} is out of bounds", instance)
+                                    })?;
+
+                                match instance.get(export) {
+                                    Some(Value::Item(item)) => {
+                                        let item = *item;
+                                        scope.push(item)
+                                    }
+                                    Some(Value::Instance(instance)) => {
+                                        let instance = instance.clone();
+                                        scope.instances.push(instance)
+                                    }
+                                    None => bail!(
+                                        "instance does not export a {} named `{}`",
+                                        kind_name(*kind),
+                                        export
+                                    ),
+                                }
+                            }
+                            _ => bail!("fusing outer aliases is not supported"),
+                        }
+                    }
+                }
+                Section::Function(functions) => {
+                    for ty in functions {
+                        let ty = scope
+                            .types
+                            .get(*ty as usize)
+                            .cloned()
+                            .flatten()
+                            .ok_or_else(|| anyhow!("type index {} is not a function type", ty))?;
+                        let ty = self.intern_type(&ty);
+                        let index = self.num_imported_funcs + self.functions.len() as u32;
+                        self.functions.push(ty);
+                        self.bodies.push(None);
+                        scope.functions.push(index);
+                        scope.defined_functions.push(index);
+                    }
+                }
+                Section::Table(tables) => {
+                    for ty in tables {
+                        let index = self.num_imported_tables + self.tables.len() as u32;
+                        self.tables.push(to_table_type(ty));
+                        scope.tables.push(index);
+                    }
+                }
+                Section::Memory(memories) => {
+                    for ty in memories {
+                        let index = self.num_imported_memories + self.memories.len() as u32;
+                        self.memories.push(to_memory_type(ty)?);
+                        scope.memories.push(index);
+                    }
+                }
+                Section::Global(globals) => {
+                    for global in globals {
+                        let init = self.init_expr(&scope, &global.init_expr)?;
+                        let index = self.num_imported_globals + self.globals.len() as u32;
+                        self.globals.push((
+                            wasm_encoder::GlobalType {
+                                val_type: to_val_type(&global.ty.content_type),
+                                mutable: global.ty.mutable,
+                            },
+                            init,
+                        ));
+                        scope.globals.push(index);
+                    }
+                }
+                Section::Export(section) => {
+                    for export in section {
+                        exports.insert(export.field, scope.lookup(export.kind, export.index)?);
+                    }
+                }
+                Section::Start(func) => self.starts.push(scope.function(*func)?),
+                Section::Element(elements) => {
+                    for element in elements {
+                        let index = self.elements.len() as u32;
+                        let element = self.element(&scope, element)?;
+                        self.elements.push(element);
+                        scope.elements.push(index);
+                    }
+                }
+                Section::Data(data) => {
+                    for data in data {
+                        let index = self.data.len() as u32;
+                        let mode = match &data.kind {
+                            DataKind::Passive => {
+                                self.needs_data_count = true;
+                                None
+                            }
+                            DataKind::Active {
+                                memory_index,
+                                init_expr,
+                            } => Some((
+                                scope.memory(*memory_index)?,
+                                self.init_expr(&scope, init_expr)?,
+                            )),
+                        };
+                        self.data.push((mode, data.data));
+                        scope.data.push(index);
+                    }
+                }
+                Section::Code(bodies) => {
+                    for body in bodies {
+                        let index =
+                            *scope
+                                .defined_functions
+                                .get(scope.num_bodies)
+                                .ok_or_else(|| {
+                                    anyhow!("code section has more bodies than functions")
+                                })?;
+                        scope.num_bodies += 1;
+
+                        let function = self.function(&scope, body)?;
+                        self.bodies[(index - self.num_imported_funcs) as usize] = Some(function);
+                    }
+                }
+            }
+        }
+
+        // Carry over the names of the functions introduced by this module
+        let introduced = scope.defined_functions.iter().collect::<HashSet<_>>();
+        for (index, name) in &module.function_names {
+            let index = match scope.functions.get(*index as usize) {
+                Some(index) => *index,
+                None => continue,
+            };
+
+            if introduced.contains(&index) || (args.is_none() && index < self.num_imported_funcs) {
+                self.function_names.insert(index, name);
+            }
+        }
+
+        Ok(Rc::new(exports))
+    }
+
+    fn init_expr(
+        &self,
+        scope: &Scope<'a>,
+        expr: &InitExpr,
+    ) -> Result<wasm_encoder::Instruction<'static>> {
+        use wasm_encoder::Instruction;
+
+        let mut reader = expr.get_operators_reader();
+        Ok(match reader.read()? {
+            Operator::I32Const { value } => Instruction::I32Const(value),
+            Operator::I64Const { value } => Instruction::I64Const(value),
+            Operator::F32Const { value } => Instruction::F32Const(f32::from_bits(value.bits())),
+            Operator::F64Const { value } => Instruction::F64Const(f64::from_bits(value.bits())),
+            Operator::V128Const { value } => {
+                Instruction::V128Const(i128::from_le_bytes(*value.bytes()))
+            }
+            Operator::GlobalGet { global_index } => {
+                Instruction::GlobalGet(scope.global(global_index)?)
+            }
+            Operator::RefNull { ty } => Instruction::RefNull(to_val_type(&ty)),
+            Operator::RefFunc { function_index } => {
+                Instruction::RefFunc(scope.function(function_index)?)
+            }
+            op => bail!("unsupported operator `{:?}` in initializer expression", op),
+        })
+    }
+
+    fn element(&self, scope: &Scope<'a>, element: &Element<'a>) -> Result<ElementSegment> {
+        let (mode, declared) = match &element.kind {
+            ElementKind::Passive => (None, false),
+            ElementKind::Declared => (None, true),
+            ElementKind::Active {
+                table_index,
+                init_expr,
+            } => (
+                Some((
+                    scope.table(*table_index)?,
+                    self.init_expr(scope, init_expr)?,
+                )),
+                false,
+            ),
+        };
+
+        let mut items = Vec::new();
+        for item in element.items.get_items_reader()? {
+            items.push(match item? {
+                ElementItem::Func(f) => wasm_encoder::Element::Func(scope.function(f)?),
+                ElementItem::Null(_) => wasm_encoder::Element::Null,
+            });
+        }
+
+        Ok(ElementSegment {
+            mode,
+            declared,
+            ty: to_val_type(&element.ty),
+            items,
+        })
+    }
+
+    fn function(
+        &mut self,
+        scope: &Scope<'a>,
+        body: &FunctionBody<'a>,
+    ) -> Result<wasm_encoder::Function> {
+        let mut locals = Vec::new();
+        for local in body.get_locals_reader()? {
+            let (count, ty) = local?;
+            locals.push((count, to_val_type(&ty)));
+        }
+
+        let mut function = wasm_encoder::Function::new(locals);
+
+        let mut reader = body.get_operators_reader()?;
+        let mut bytes = Vec::new();
+        while !reader.eof() {
+            let (op, start) = reader.read_with_offset()?;
+            let raw = &self.bytes[start..reader.original_position()];
+            self.rewrite_operator(scope, &op, raw, &mut bytes)?;
+        }
+
+        function.raw(bytes);
+
+        Ok(function)
+    }
+
+    fn rewrite_operator(
+        &mut self,
+        scope: &Scope<'a>,
+        op: &Operator,
+        raw: &[u8],
+        bytes: &mut Vec<u8>,
+    ) -> Result<()> {
+        let index = |bytes: &mut Vec<u8>, index: u32| write_leb(index.into(), bytes);
+        let prefix = &raw[..opcode_len(raw)];
+
+        match op {
+            Operator::Call { function_index }
+            | Operator::ReturnCall { function_index }
+            | Operator::RefFunc { function_index } => {
+                bytes.extend(prefix);
+                index(bytes, scope.function(*function_index)?);
+            }
+            Operator::CallIndirect {
+                index: ty,
+                table_index,
+            }
+            | Operator::ReturnCallIndirect {
+                index: ty,
+                table_index,
+            } => {
+                bytes.extend(prefix);
+                index(bytes, self.scope_type(scope, *ty)?);
+                index(bytes, scope.table(*table_index)?);
+            }
+            Operator::Block {
+                ty: TypeOrFuncType::FuncType(ty),
+            }
+            | Operator::Loop {
+                ty: TypeOrFuncType::FuncType(ty),
+            }
+            | Operator::If {
+                ty: TypeOrFuncType::FuncType(ty),
+            } => {
+                bytes.extend(prefix);
+                bytes.extend(wasm_encoder::encoders::s33(
+                    self.scope_type(scope, *ty)?.into(),
+                ));
+            }
+            Operator::GlobalGet { global_index } | Operator::GlobalSet { global_index } => {
+                bytes.extend(prefix);
+                index(bytes, scope.global(*global_index)?);
+            }
+            Operator::MemorySize { mem, .. }
+            | Operator::MemoryGrow { mem, .. }
+            | Operator::MemoryFill { mem } => {
+                bytes.extend(prefix);
+                index(bytes, scope.memory(*mem)?);
+            }
+            Operator::MemoryInit { segment, mem } => {
+                self.needs_data_count = true;
+                bytes.extend(prefix);
+                index(bytes, scope.data(*segment)?);
+                index(bytes, scope.memory(*mem)?);
+            }
+            Operator::DataDrop { segment } => {
+                self.needs_data_count = true;
+                bytes.extend(prefix);
+                index(bytes, scope.data(*segment)?);
+            }
+            Operator::MemoryCopy { src, dst } => {
+                bytes.extend(prefix);
+                index(bytes, scope.memory(*dst)?);
+                index(bytes, scope.memory(*src)?);
+            }
+            Operator::TableInit { segment, table } => {
+                bytes.extend(prefix);
+                index(bytes, scope.element(*segment)?);
+                index(bytes, scope.table(*table)?);
+            }
+            Operator::ElemDrop { segment } => {
+                bytes.extend(prefix);
+                index(bytes, scope.element(*segment)?);
+            }
+            Operator::TableCopy {
+                dst_table,
+                src_table,
+            } => {
+                bytes.extend(prefix);
+                index(bytes, scope.table(*dst_table)?);
+                index(bytes, scope.table(*src_table)?);
+            }
+            Operator::TableFill { table }
+            | Operator::TableGet { table }
+            | Operator::TableSet { table }
+            | Operator::TableGrow { table }
+            | Operator::TableSize { table } => {
+                bytes.extend(prefix);
+                index(bytes, scope.table(*table)?);
+            }
+            _ => match memarg_opcode_len(raw) {
+                Some(len) => {
+                    // Rewrite the memory index of the memory immediate, which follows the offset
+                    let mut pos = len;
+                    let (flags, n) = read_leb(&raw[pos..]);
+                    pos += n;
+                    let (offset, n) = read_leb(&raw[pos..]);
+                    pos += n;
+                    let memory = if flags & 0x40 != 0 {
+                        let (memory, n) = read_leb(&raw[pos..]);
+                        pos += n;
+                        memory as u32
+                    } else {
+                        0
+                    };
+                    let memory = scope.memory(memory)?;
+
+                    bytes.extend(&raw[..len]);
+                    if memory == 0 {
+                        write_leb(flags & !0x40, bytes);
+                        write_leb(offset, bytes);
+                    } else {
+                        write_leb(flags | 0x40, bytes);
+                        write_leb(offset, bytes);
+                        index(bytes, memory);
+                    }
+
+                    // Copy any trailing immediates (e.g. a lane index)
+                    bytes.extend(&raw[pos..]);
+                }
+                None => bytes.extend(raw),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn scope_type(&mut self, scope: &Scope<'a>, index: u32) -> Result<u32> {
+        let ty = scope
+            .types
+            .get(index as usize)
+            .cloned()
+            .flatten()
+            .ok_or_else(|| anyhow!("type index {} is not a function type", index))?;
+        Ok(self.intern_type(&ty))
+    }
+}
+
+impl Fuser<'_> {
+    fn encode(self) -> Result<wasm_encoder::Module> {
+        let mut module = wasm_encoder::Module::new();
+        let mut types = self.types;
+        let mut functions = self.functions;
+        let mut bodies = self
+            .bodies
+            .into_iter()
+            .map(|b| b.ok_or_else(|| anyhow!("function section has more functions than bodies")))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Synthesize a start function that calls every instantiated module's start function in order
+        let start = match self.starts.as_slice() {
+            [] => None,
+            [start] => Some(*start),
+            starts => {
+                let ty = FuncType {
+                    params: Box::new([]),
+                    returns: Box::new([]),
+                };
+                let type_index = match types.iter().position(|t| *t == ty) {
+                    Some(index) => index as u32,
+                    None => {
+                        types.push(ty);
+                        types.len() as u32 - 1
+                    }
+                };
+
+                let mut function = wasm_encoder::Function::new(std::iter::empty());
+                for start in starts {
+                    function.instruction(wasm_encoder::Instruction::Call(*start));
+                }
+                function.instruction(wasm_encoder::Instruction::End);
+
+                functions.push(type_index);
+                bodies.push(function);
+                Some(self.num_imported_funcs + functions.len() as u32 - 1)
+            }
+        };
+
+        let mut section = wasm_encoder::TypeSection::new();
+        for ty in &types {
+            section.function(
+                ty.params.iter().map(to_val_type),
+                ty.returns.iter().map(to_val_type),
+            );
+        }
+        module.section(&section);
+
+        let mut section = wasm_encoder::ImportSection::new();
+        for (module, field, ty) in &self.imports {
+            section.import(module, *field, *ty);
+        }
+        module.section(&section);
+
+        let mut section = wasm_encoder::FunctionSection::new();
+        for ty in &functions {
+            section.function(*ty);
+        }
+        module.section(&section);
+
+        let mut section = wasm_encoder::TableSection::new();
+        for ty in &self.tables {
+            section.table(*ty);
+        }
+        module.section(&section);
+
+        let mut section = wasm_encoder::MemorySection::new();
+        for ty in &self.memories {
+            section.memory(*ty);
+        }
+        module.section(&section);
+
+        let mut section = wasm_encoder::GlobalSection::new();
+        for (ty, init) in &self.globals {
+            section.global(*ty, *init);
+        }
+        module.section(&section);
+
+        let mut section = wasm_encoder::ExportSection::new();
+        for (name, export) in &self.exports {
+            section.export(name, *export);
+        }
+        module.section(&section);
+
+        if let Some(function_index) = start {
+            module.section(&wasm_encoder::StartSection { function_index });
+        }
+
+        let mut section = wasm_encoder::ElementSection::new();
+        for segment in &self.elements {
+            let items = wasm_encoder::Elements::Expressions(&segment.items);
+            match segment.mode {
+                Some((table, offset)) => {
+                    section.active(
+                        if table == 0 { None } else { Some(table) },
+                        offset,
+                        segment.ty,
+                        items,
+                    );
+                }
+                None if segment.declared => {
+                    section.declared(segment.ty, items);
+                }
+                None => {
+                    section.passive(segment.ty, items);
+                }
+            }
+        }
+        module.section(&section);
+
+        if self.needs_data_count {
+            module.section(&wasm_encoder::DataCountSection {
+                count: self.data.len() as u32,
+            });
+        }
+
+        let mut section = wasm_encoder::CodeSection::new();
+        for body in &bodies {
+            section.function(body);
+        }
+        module.section(&section);
+
+        let mut section = wasm_encoder::DataSection::new();
+        for (mode, data) in &self.data {
+            match mode {
+                Some((memory, offset)) => {
+                    section.active(*memory, *offset, data.iter().copied());
+                }
+                None => {
+                    section.passive(data.iter().copied());
+                }
+            }
+        }
+        module.section(&section);
+
+        let mut names = names::Names::default();
+        for (index, name) in self.function_names {
+            names.function(index, name);
+        }
+        names.write(&mut module);
+
+        Ok(module)
+    }
+}
+
+/// Fuses a linked module into a single core WebAssembly module.
+///
+/// The linked module's instantiations are evaluated ahead of time, so the
+/// fused module does not require the module linking proposal. Each linked
+/// module keeps its own memory; the fused module requires the multi-memory
+/// proposal if more than one module defines a memory.
+pub(crate) fn fuse(bytes: &[u8]) -> Result<Vec<u8>> {
+    let module = ModuleDef::parse(bytes)?;
+
+    let mut fuser = Fuser {
+        bytes,
+        ..Default::default()
+    };
+
+    let exports = fuser.instantiate(&module, None)?;
+
+    let mut names = exports.keys().copied().collect::<Vec<_>>();
+    names.sort_unstable();
+    for name in names {
+        let export = match &exports[name] {
+            Value::Item(Item::Function(i)) => wasm_encoder::Export::Function(*i),
+            Value::Item(Item::Table(i)) => wasm_encoder::Export::Table(*i),
+            Value::Item(Item::Memory(i)) => wasm_encoder::Export::Memory(*i),
+            Value::Item(Item::Global(i)) => wasm_encoder::Export::Global(*i),
+            Value::Instance(_) => bail!("cannot export instance `{}` from a fused module", name),
+        };
+        fuser.exports.push((name, export));
+    }
+
+    Ok(fuser.encode()?.finish())
+}
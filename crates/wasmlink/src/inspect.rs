@@ -0,0 +1,236 @@
+use crate::{
+    adapter::ModuleAdapter, linker::CANONICAL_ABI_MODULE_NAME, module::StringEncoding, Profile,
+};
+use petgraph::Graph;
+use std::fmt;
+use wasmparser::{FuncType, Type};
+use wit_parser::abi::{WasmSignature, WasmType};
+
+fn type_name(ty: &Type) -> &'static str {
+    match ty {
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F32 => "f32",
+        Type::F64 => "f64",
+        Type::V128 => "v128",
+        Type::FuncRef => "funcref",
+        Type::ExternRef => "externref",
+        Type::ExnRef => "exnref",
+        // These only describe block types, but print something rather than
+        // panicking should one ever show up in a signature.
+        Type::Func => "func",
+        Type::EmptyBlockType => "empty",
+    }
+}
+
+fn wasm_type_name(ty: &WasmType) -> &'static str {
+    match ty {
+        WasmType::I32 => "i32",
+        WasmType::I64 => "i64",
+        WasmType::F32 => "f32",
+        WasmType::F64 => "f64",
+    }
+}
+
+/// Represents the core WebAssembly signature of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The parameter types of the function.
+    pub params: Vec<&'static str>,
+    /// The result types of the function.
+    pub results: Vec<&'static str>,
+    /// The types stored in the return area, if the function returns through a return pointer.
+    ///
+    /// The return pointer itself is the last parameter of the function.
+    pub retptr: Option<Vec<&'static str>>,
+}
+
+impl From<&FuncType> for Signature {
+    fn from(ty: &FuncType) -> Self {
+        Self {
+            params: ty.params.iter().map(type_name).collect(),
+            results: ty.returns.iter().map(type_name).collect(),
+            retptr: None,
+        }
+    }
+}
+
+impl From<&WasmSignature> for Signature {
+    fn from(signature: &WasmSignature) -> Self {
+        Self {
+            params: signature.params.iter().map(wasm_type_name).collect(),
+            results: signature.results.iter().map(wasm_type_name).collect(),
+            retptr: signature
+                .retptr
+                .as_ref()
+                .map(|types| types.iter().map(wasm_type_name).collect()),
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(func")?;
+
+        if !self.params.is_empty() {
+            write!(f, " (param {})", self.params.join(" "))?;
+        }
+
+        if !self.results.is_empty() {
+            write!(f, " (result {})", self.results.join(" "))?;
+        }
+
+        write!(f, ")")?;
+
+        if let Some(retptr) = &self.retptr {
+            write!(f, " with return area ({})", retptr.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes a function of an interface exported by a linked module.
+#[derive(Debug, Clone)]
+pub struct FunctionPlan {
+    /// The name of the interface exporting the function.
+    pub interface: String,
+    /// The name of the function.
+    pub name: String,
+    /// Whether or not calls to the function go through an adapter function.
+    pub must_adapt: bool,
+    /// The signature of the function as imported by importing modules.
+    pub import_signature: Signature,
+    /// The type of the function as exported by the linked module.
+    pub export_type: Signature,
+}
+
+/// Describes a resource defined by a linked module.
+#[derive(Debug, Clone)]
+pub struct ResourcePlan {
+    /// The name of the resource.
+    pub name: String,
+    /// The identifier of the resource in the linked runtime.
+    pub id: u32,
+    /// The function the module exports to drop the resource.
+    pub drop_callback: String,
+}
+
+/// Describes a function import satisfied by the linker's profile.
+#[derive(Debug, Clone)]
+pub struct ProfileImport {
+    /// The module name of the import.
+    pub module: String,
+    /// The field name of the import.
+    pub field: String,
+    /// The type of the imported function.
+    pub ty: Signature,
+}
+
+/// Describes a module in the link graph.
+#[derive(Debug, Clone)]
+pub struct ModulePlan {
+    /// The name of the module.
+    pub name: String,
+    /// The names of the linked modules the module imports.
+    pub imports: Vec<String>,
+    /// Whether or not the module is adapted.
+    pub must_adapt: bool,
+    /// The encoding the module uses for strings.
+    pub string_encoding: StringEncoding,
+    /// The functions of the interfaces the module exports.
+    pub functions: Vec<FunctionPlan>,
+    /// The resources the module defines.
+    pub resources: Vec<ResourcePlan>,
+    /// The imports of the module satisfied by the profile.
+    pub profile_imports: Vec<ProfileImport>,
+}
+
+/// Describes how a module and its imports are linked.
+///
+/// See [`Linker::inspect`](crate::Linker::inspect).
+#[derive(Debug, Clone)]
+pub struct LinkPlan {
+    /// The modules in the link graph, starting with the module being linked.
+    pub modules: Vec<ModulePlan>,
+    /// Whether or not the linked module includes the resource runtime.
+    pub needs_runtime: bool,
+}
+
+impl LinkPlan {
+    pub(crate) fn new(
+        graph: &Graph<ModuleAdapter, ()>,
+        needs_runtime: bool,
+        profile: &Profile,
+    ) -> Self {
+        let modules = graph
+            .node_indices()
+            .map(|index| {
+                let adapter = &graph[index];
+                let module = adapter.module;
+
+                let mut imports: Vec<_> = graph
+                    .neighbors(index)
+                    .map(|i| graph[i].module.name.to_string())
+                    .collect();
+                imports.sort();
+
+                let functions = module
+                    .interfaces
+                    .iter()
+                    .flat_map(|i| i.iter().map(move |(f, info)| (i, f, info)))
+                    .map(|(interface, f, info)| FunctionPlan {
+                        interface: interface.inner().name.clone(),
+                        name: f.name.clone(),
+                        must_adapt: info.must_adapt,
+                        import_signature: (&info.import_signature).into(),
+                        export_type: (&info.export_type).into(),
+                    })
+                    .collect();
+
+                let resources = adapter
+                    .resources()
+                    .iter()
+                    .map(|(name, id, drop_callback)| ResourcePlan {
+                        name: name.to_string(),
+                        id,
+                        drop_callback: drop_callback.to_string(),
+                    })
+                    .collect();
+
+                let profile_imports = module
+                    .imports
+                    .iter()
+                    .filter(|i| i.module != CANONICAL_ABI_MODULE_NAME)
+                    .filter_map(|i| {
+                        let ty = module.import_func_type(i)?;
+                        if !profile.provides(i.module, i.field, ty) {
+                            return None;
+                        }
+
+                        Some(ProfileImport {
+                            module: i.module.to_string(),
+                            field: i.field.unwrap_or("").to_string(),
+                            ty: ty.into(),
+                        })
+                    })
+                    .collect();
+
+                ModulePlan {
+                    name: module.name.to_string(),
+                    imports,
+                    must_adapt: module.must_adapt,
+                    string_encoding: module.string_encoding,
+                    functions,
+                    resources,
+                    profile_imports,
+                }
+            })
+            .collect();
+
+        Self {
+            modules,
+            needs_runtime,
+        }
+    }
+}
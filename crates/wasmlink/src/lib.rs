@@ -3,12 +3,18 @@
 #![deny(missing_docs)]
 
 mod adapter;
+mod fuse;
+mod inspect;
 mod linker;
 mod module;
+mod names;
 mod profile;
 mod resources;
 
 pub use self::adapter::ModuleAdapter;
-pub use self::linker::Linker;
-pub use self::module::Module;
+pub use self::inspect::{
+    FunctionPlan, LinkPlan, ModulePlan, ProfileImport, ResourcePlan, Signature,
+};
+pub use self::linker::{InstancePolicy, Linker};
+pub use self::module::{Module, StringEncoding};
 pub use self::profile::Profile;
@@ -3,15 +3,21 @@ use crate::{
         ModuleAdapter, FUNCTION_TABLE_NAME, PARENT_MODULE_NAME, REALLOC_EXPORT_NAME,
         REALLOC_FUNC_TYPE, RUNTIME_MODULE_NAME,
     },
-    Module, Profile,
+    module::import_kind,
+    LinkPlan, Module, Profile, StringEncoding,
 };
 use anyhow::{anyhow, bail, Result};
-use petgraph::{algo::toposort, graph::NodeIndex, Graph};
+use petgraph::{graph::NodeIndex, Direction, Graph};
 use std::collections::{hash_map::Entry, HashMap};
 use wasmparser::{ExternalKind, FuncType, ImportSectionEntryType, Type, TypeDef};
 
 pub const CANONICAL_ABI_MODULE_NAME: &str = "canonical_abi";
 
+#[cfg(feature = "runtime-leak-check")]
+const RESOURCE_LEAKS_FUNC_NAME: &str = "resource_leaks";
+#[cfg(feature = "runtime-leak-check")]
+const RESOURCE_LEAKS_EXPORT_NAME: &str = "canonical_abi_resource_leaks";
+
 pub fn to_val_type(ty: &Type) -> wasm_encoder::ValType {
     match ty {
         Type::I32 => wasm_encoder::ValType::I32,
@@ -27,6 +33,74 @@ pub fn to_val_type(ty: &Type) -> wasm_encoder::ValType {
     }
 }
 
+/// Finds a cycle in the import graph, if there is one.
+///
+/// The returned path starts and ends with the same module. The search is
+/// performed iteratively rather than recursively.
+fn find_cycle(graph: &Graph<ModuleAdapter, ()>) -> Option<Vec<NodeIndex>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        InProgress,
+        Done,
+    }
+
+    let mut states = vec![State::Unvisited; graph.node_count()];
+
+    for start in graph.node_indices() {
+        if states[start.index()] != State::Unvisited {
+            continue;
+        }
+
+        // The stack doubles as the current import path
+        let mut stack = vec![(start, graph.neighbors(start).detach())];
+        states[start.index()] = State::InProgress;
+
+        while let Some((node, neighbors)) = stack.last_mut() {
+            let node = *node;
+            match neighbors.next_node(graph) {
+                Some(next) => match states[next.index()] {
+                    State::Unvisited => {
+                        states[next.index()] = State::InProgress;
+                        stack.push((next, graph.neighbors(next).detach()));
+                    }
+                    State::InProgress => {
+                        let pos = stack.iter().position(|(n, _)| *n == next).unwrap();
+                        let mut cycle: Vec<_> = stack[pos..].iter().map(|(n, _)| *n).collect();
+                        cycle.push(next);
+                        return Some(cycle);
+                    }
+                    State::Done => {}
+                },
+                None => {
+                    states[node.index()] = State::Done;
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// The policy for instantiating a module imported by more than one module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstancePolicy {
+    /// Each importing module gets its own instance of the imported module.
+    Isolated,
+    /// Importing modules share a single instance of the imported module.
+    ///
+    /// Adapted modules are always instantiated per importing module as
+    /// their adapter operates on the importing module's memory.
+    Shared,
+}
+
+impl Default for InstancePolicy {
+    fn default() -> Self {
+        Self::Isolated
+    }
+}
+
 /// Represents a linked module built from a dependency graph.
 #[derive(Default)]
 struct LinkedModule<'a> {
@@ -35,12 +109,16 @@ struct LinkedModule<'a> {
     implicit_instances: HashMap<&'a str, u32>,
     modules: Vec<wasm_encoder::Module>,
     module_map: HashMap<&'a ModuleAdapter<'a>, (u32, Option<u32>)>,
+    /// Adapters for importers that use a different string encoding or share the module's memory.
+    parent_adapters: HashMap<(NodeIndex, StringEncoding, bool), u32>,
     instances: Vec<(u32, Vec<(&'a str, u32)>)>,
     func_aliases: Vec<(u32, &'a str)>,
     memory_aliases: Vec<(u32, &'a str)>,
     table_aliases: Vec<(u32, &'a str)>,
     segments: Vec<(u32, Vec<wasm_encoder::Element>)>,
     exports: Vec<(&'a str, wasm_encoder::Export)>,
+    policy: InstancePolicy,
+    shared_instances: HashMap<NodeIndex, u32>,
 }
 
 impl<'a> LinkedModule<'a> {
@@ -49,8 +127,12 @@ impl<'a> LinkedModule<'a> {
         graph: &'a Graph<ModuleAdapter<'a>, ()>,
         needs_runtime: bool,
         profile: &Profile,
+        policy: InstancePolicy,
     ) -> Result<Self> {
-        let mut linked = Self::default();
+        let mut linked = Self {
+            policy,
+            ..Default::default()
+        };
 
         let mut types = HashMap::new();
         let mut profile_imports = HashMap::new();
@@ -59,10 +141,11 @@ impl<'a> LinkedModule<'a> {
 
             // Add all profile imports to the base set of types and imports
             for import in &adapter.module.imports {
-                let ty = adapter
-                    .module
-                    .import_func_type(import)
-                    .expect("expected import to be a function");
+                // Non-function imports are always provided by other linked modules
+                let ty = match adapter.module.import_func_type(import) {
+                    Some(ty) => ty,
+                    None => continue,
+                };
 
                 if !profile.provides(import.module, import.field, ty) {
                     continue;
@@ -100,8 +183,26 @@ impl<'a> LinkedModule<'a> {
                 }
             }
 
+            // Name the adapters after the importers that use them
+            let mut importers = graph
+                .neighbors_directed(f, Direction::Incoming)
+                .map(|i| graph[i].module)
+                .collect::<Vec<_>>();
+            importers.sort_by_key(|m| m.name);
+            importers.dedup_by_key(|m| m.name);
+
             let module_index = linked.modules.len() as u32;
-            linked.modules.push(adapter.adapt()?);
+            linked.modules.push(
+                adapter.adapt_for(
+                    adapter.module.string_encoding,
+                    false,
+                    &importers
+                        .iter()
+                        .filter(|m| linked.parent_adapter_key(adapter, m).is_none())
+                        .map(|m| m.name)
+                        .collect::<Vec<_>>(),
+                )?,
+            );
 
             let shim_index = adapter.encode_shim().map(|m| {
                 let index = linked.modules.len() as u32;
@@ -112,6 +213,32 @@ impl<'a> LinkedModule<'a> {
             linked
                 .module_map
                 .insert(adapter, (module_index, shim_index));
+
+            // Adapt the module again for each importer with a different string encoding or that shares its memory
+            for importer in &importers {
+                let key = match linked.parent_adapter_key(adapter, importer) {
+                    Some(key) => key,
+                    None => continue,
+                };
+
+                if linked.parent_adapters.contains_key(&(f, key.0, key.1)) {
+                    continue;
+                }
+
+                let index = linked.modules.len() as u32;
+                linked.modules.push(
+                    adapter.adapt_for(
+                        key.0,
+                        key.1,
+                        &importers
+                            .iter()
+                            .filter(|m| linked.parent_adapter_key(adapter, m) == Some(key))
+                            .map(|m| m.name)
+                            .collect::<Vec<_>>(),
+                    )?,
+                );
+                linked.parent_adapters.insert((f, key.0, key.1), index);
+            }
         }
 
         if needs_runtime {
@@ -154,19 +281,67 @@ impl<'a> LinkedModule<'a> {
             }
         }
 
+        // Export the runtime's count of live handles so a host can report leaks at teardown
+        #[cfg(feature = "runtime-leak-check")]
+        if needs_runtime {
+            let func_index = linked.func_aliases.len() as u32;
+            linked.func_aliases.push((
+                linked.implicit_instances.len() as u32,
+                RESOURCE_LEAKS_FUNC_NAME,
+            ));
+            linked.exports.push((
+                RESOURCE_LEAKS_EXPORT_NAME,
+                wasm_encoder::Export::Function(linked.imports.len() as u32 + func_index),
+            ));
+        }
+
         Ok(linked)
     }
 
+    /// Determines if the given module shares the memory and allocator of an adapted module.
+    ///
+    /// This is the case when both modules get them from the same module and that module is instantiated once.
+    fn shares_memory(&self, importer: &Module, adapted: &Module) -> bool {
+        self.policy == InstancePolicy::Shared
+            && adapted.memory_provider().is_some()
+            && importer.memory_provider() == adapted.memory_provider()
+    }
+
+    /// Gets the string encoding and memory sharing of the adapter needed by the given importer.
+    ///
+    /// Returns `None` if the importer can use the module's default adapter.
+    fn parent_adapter_key(
+        &self,
+        adapter: &ModuleAdapter,
+        importer: &Module,
+    ) -> Option<(StringEncoding, bool)> {
+        let transcode = adapter.needs_transcoding(importer.string_encoding);
+        let shares_memory = self.shares_memory(importer, adapter.module);
+
+        if !transcode && !shares_memory {
+            return None;
+        }
+
+        Some((
+            if transcode {
+                importer.string_encoding
+            } else {
+                adapter.module.string_encoding
+            },
+            shares_memory,
+        ))
+    }
+
     fn instantiate(
         &mut self,
         graph: &'a Graph<ModuleAdapter<'a>, ()>,
         current: NodeIndex,
-        parent: Option<u32>,
+        parent: Option<(u32, NodeIndex)>,
     ) -> Result<(u32, bool)> {
         // TODO: make this iterative instead of recursive?
 
         // If a parent module was specified and this is a shim module, just instantiate it
-        let (module_index, shim_index) = self.module_map[&graph[current]];
+        let (mut module_index, shim_index) = self.module_map[&graph[current]];
         if parent.is_none() {
             // Instantiate shims for adapted modules
             if let Some(shim_index) = shim_index {
@@ -176,15 +351,28 @@ impl<'a> LinkedModule<'a> {
             }
         }
 
+        // Reuse the instance of a module that was already instantiated for another importer
+        if parent.is_none() && self.policy == InstancePolicy::Shared {
+            if let Some(index) = self.shared_instances.get(&current) {
+                return Ok((*index, false));
+            }
+        }
+
         // Add the implicit instances to the instantiation args
         let mut args = Vec::new();
         for (name, index) in &self.implicit_instances {
             args.push((*name, *index));
         }
 
-        // Add the parent instance
-        if let Some(parent) = parent {
+        // Add the parent instance, using the adapter that transcodes strings or shares memory with the parent if needed
+        if let Some((parent, importer)) = parent {
             args.push((PARENT_MODULE_NAME, parent));
+
+            if let Some((encoding, shares_memory)) =
+                self.parent_adapter_key(&graph[current], graph[importer].module)
+            {
+                module_index = self.parent_adapters[&(current, encoding, shares_memory)];
+            }
         }
 
         // If the module has resources, import the runtime module
@@ -248,9 +436,13 @@ impl<'a> LinkedModule<'a> {
             }
         }
 
+        if parent.is_none() {
+            self.shared_instances.insert(current, parent_index);
+        }
+
         // For each shim that was instantiated, instantiate the real module passing in the parent
         for (shim, shim_index) in shims {
-            let (child_index, _) = self.instantiate(graph, shim, Some(parent_index))?;
+            let (child_index, _) = self.instantiate(graph, shim, Some((parent_index, current)))?;
 
             // Emit the shim function table
             let adapter = &graph[shim];
@@ -380,12 +572,25 @@ impl<'a> LinkedModule<'a> {
 #[derive(Debug)]
 pub struct Linker {
     profile: Profile,
+    policy: InstancePolicy,
 }
 
 impl Linker {
     /// Constructs a new WebAssembly module linker with the given profile.
+    ///
+    /// Modules imported by more than one module are instantiated once per
+    /// importing module; see [`Linker::with_instance_policy`].
     pub fn new(profile: Profile) -> Self {
-        Self { profile }
+        Self {
+            profile,
+            policy: InstancePolicy::default(),
+        }
+    }
+
+    /// Sets the policy for instantiating modules imported by more than one module.
+    pub fn with_instance_policy(mut self, policy: InstancePolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     /// Links the given module with the given set of imported modules.
@@ -394,11 +599,34 @@ impl Linker {
     pub fn link(&self, module: &Module, imports: &HashMap<&str, Module>) -> Result<Vec<u8>> {
         let (graph, needs_runtime) = self.build_graph(module, imports)?;
 
-        let module = LinkedModule::new(&graph, needs_runtime, &self.profile)?;
+        let module = LinkedModule::new(&graph, needs_runtime, &self.profile, self.policy)?;
 
         Ok(module.encode().finish())
     }
 
+    /// Links the given module with the given set of imported modules into a
+    /// single core WebAssembly module.
+    ///
+    /// Unlike [`Linker::link`], the resulting module does not use the module
+    /// linking proposal: the linked modules and their adapters are fused into
+    /// one module. Each linked module keeps its own memory, so the result
+    /// uses the multi-memory proposal when more than one memory is linked.
+    ///
+    /// On success, returns a vector of bytes representing the fused module.
+    pub fn link_fused(&self, module: &Module, imports: &HashMap<&str, Module>) -> Result<Vec<u8>> {
+        crate::fuse::fuse(&self.link(module, imports)?)
+    }
+
+    /// Inspects how the given module would be linked with the given set of imported modules.
+    ///
+    /// On success, returns a plan describing the module graph, the adapted functions, the
+    /// resources, and the imports satisfied by the linker's profile.
+    pub fn inspect(&self, module: &Module, imports: &HashMap<&str, Module>) -> Result<LinkPlan> {
+        let (graph, needs_runtime) = self.build_graph(module, imports)?;
+
+        Ok(LinkPlan::new(&graph, needs_runtime, &self.profile))
+    }
+
     fn build_graph<'a>(
         &self,
         module: &'a Module,
@@ -432,14 +660,42 @@ impl Linker {
                                 .expect("function index must be in range")
                             {
                                 TypeDef::Func(ft) => {
-                                    if import.module == CANONICAL_ABI_MODULE_NAME
-                                        || self.profile.provides(import.module, import.field, ft)
-                                    {
+                                    if import.module == CANONICAL_ABI_MODULE_NAME {
                                         continue;
                                     }
+
+                                    if self.profile.provides_module(import.module) {
+                                        if self.profile.provides(import.module, import.field, ft) {
+                                            continue;
+                                        }
+
+                                        bail!(
+                                            "module `{}` imports function `{}` from profile module `{}` but {}",
+                                            module.name,
+                                            import.field.unwrap_or(""),
+                                            import.module,
+                                            if import
+                                                .field
+                                                .and_then(|f| self.profile.function(import.module, f))
+                                                .is_some()
+                                            {
+                                                "the types are incompatible"
+                                            } else {
+                                                "the profile does not provide it"
+                                            }
+                                        );
+                                    }
                                 }
                                 _ => unreachable!("import must be a function"),
                             }
+                        } else if self.profile.provides_module(import.module) {
+                            bail!(
+                                "module `{}` imports {} `{}` from profile module `{}` but profiles only provide functions",
+                                module.name,
+                                import_kind(import.ty),
+                                import.field.unwrap_or(""),
+                                import.module
+                            );
                         }
 
                         let imported_module = imported_module.ok_or_else(|| {
@@ -466,15 +722,18 @@ impl Linker {
             };
         }
 
-        // Ensure the graph is acyclic by performing a topographical sort.
-        // This algorithm requires more space than `is_cyclic_directed`, but
-        // performs the check iteratively rather than recursively.
-        toposort(&graph, None).map_err(|e| {
-            anyhow!(
-                "module `{}` and its imports form a cycle in the import graph",
-                graph[e.node_id()].module.name
-            )
-        })?;
+        // Ensure the graph is acyclic, reporting the import path that forms a cycle if not
+        if let Some(cycle) = find_cycle(&graph) {
+            bail!(
+                "module `{}` and its imports form a cycle in the import graph: {}",
+                graph[cycle[0]].module.name,
+                cycle
+                    .iter()
+                    .map(|i| format!("`{}`", graph[*i].module.name))
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            );
+        }
 
         Ok((graph, needs_runtime))
     }
@@ -484,6 +743,12 @@ impl Linker {
 mod test {
     use super::*;
 
+    fn wasi_profile() -> Result<Profile> {
+        Profile::from_module(&wat::parse_str(
+            r#"(module (import "wasi_snapshot_preview1" "a" (func)))"#,
+        )?)
+    }
+
     #[test]
     fn it_errors_on_missing_import() -> Result<()> {
         let bytes = wat::parse_str(
@@ -580,7 +845,7 @@ mod test {
 
         assert_eq!(
             linker.link(&main, &imports).unwrap_err().to_string(),
-            "module `c` and its imports form a cycle in the import graph"
+            "module `a` and its imports form a cycle in the import graph: `a` -> `b` -> `c` -> `a`"
         );
 
         Ok(())
@@ -592,10 +857,10 @@ mod test {
             r#"(module (import "a" "a" (func)) (import "b" "b" (func)) (func (export "_start")))"#,
         )?;
         let a = wat::parse_str(
-            r#"(module (import "wasi_snapshot_preview1" "c" (func)) (func (export "a")))"#,
+            r#"(module (import "wasi_snapshot_preview1" "a" (func)) (func (export "a")))"#,
         )?;
         let b = wat::parse_str(
-            r#"(module (import "wasi_snapshot_preview1" "c" (func (param i32))) (func (export "b")))"#,
+            r#"(module (import "wasi_snapshot_preview1" "a" (func (param i32))) (func (export "b")))"#,
         )?;
 
         let main = Module::new("main", &bytes, [])?;
@@ -604,11 +869,55 @@ mod test {
         imports.insert("a", Module::new("a", &a, [])?);
         imports.insert("b", Module::new("b", &b, [])?);
 
+        let linker = Linker::new(wasi_profile()?);
+
+        assert_eq!(
+            linker.link(&main, &imports).unwrap_err().to_string(),
+            "module `b` imports function `a` from profile module `wasi_snapshot_preview1` but the types are incompatible"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_on_unprovided_profile_imports() -> Result<()> {
+        let bytes = wat::parse_str(r#"(module (import "a" "a" (func)) (func (export "_start")))"#)?;
+        let a = wat::parse_str(
+            r#"(module (import "wasi_snapshot_preview1" "b" (func)) (func (export "a")))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", Module::new("a", &a, [])?);
+
+        let linker = Linker::new(wasi_profile()?);
+
+        assert_eq!(
+            linker.link(&main, &imports).unwrap_err().to_string(),
+            "module `a` imports function `b` from profile module `wasi_snapshot_preview1` but the profile does not provide it"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_on_imports_outside_of_profile() -> Result<()> {
+        let bytes = wat::parse_str(r#"(module (import "a" "a" (func)) (func (export "_start")))"#)?;
+        let a = wat::parse_str(
+            r#"(module (import "wasi_snapshot_preview1" "a" (func)) (func (export "a")))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", Module::new("a", &a, [])?);
+
         let linker = Linker::new(Profile::new());
 
         assert_eq!(
             linker.link(&main, &imports).unwrap_err().to_string(),
-            "profile import `c` from module `wasi_snapshot_preview1` has a conflicting type between different importing modules"
+            "module `a` imports from unknown module `wasi_snapshot_preview1`"
         );
 
         Ok(())
@@ -626,7 +935,7 @@ mod test {
         let mut imports = HashMap::new();
         imports.insert("a", Module::new("a", &a, [])?);
 
-        let linker = Linker::new(Profile::new());
+        let linker = Linker::new(wasi_profile()?);
 
         let bytes = linker.link(&main, &imports)?;
 
@@ -679,7 +988,31 @@ mod test {
         let mut imports = HashMap::new();
         imports.insert("a", a);
 
-        let linker = Linker::new(Profile::new());
+        let linker = Linker::new(wasi_profile()?);
+
+        assert_eq!(
+            linker.link(&main, &imports).unwrap_err().to_string(),
+            "module `main` does not export the required function `canonical_abi_realloc`"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_with_missing_parent_realloc_for_embedded_interface() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "a" (func (param i32 i32))) (func (export "_start")))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (@custom "wit-interface:a" "a: function(p: string)") (import "wasi_snapshot_preview1" "a" (func)) (func (export "a") (param i32 i32)) (memory (export "memory") 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", Module::new("a", &a, [])?);
+
+        let linker = Linker::new(wasi_profile()?);
 
         assert_eq!(
             linker.link(&main, &imports).unwrap_err().to_string(),
@@ -708,7 +1041,7 @@ mod test {
         let mut imports = HashMap::new();
         imports.insert("a", a);
 
-        let linker = Linker::new(Profile::new());
+        let linker = Linker::new(wasi_profile()?);
 
         assert_eq!(
             linker.link(&main, &imports).unwrap_err().to_string(),
@@ -737,7 +1070,7 @@ mod test {
         let mut imports = HashMap::new();
         imports.insert("a", a);
 
-        let linker = Linker::new(Profile::new());
+        let linker = Linker::new(wasi_profile()?);
 
         let bytes = linker.link(&main, &imports)?;
 
@@ -759,13 +1092,13 @@ mod test {
     (export \"_start\" (func 1))
     (export \"canonical_abi_realloc\" (func 2))
     (export \"memory\" (memory 0)))
-  (module (;1;)
+  (module $a-adapter (;1;)
     (type (;0;) (func))
     (type (;1;) (func (param i32 i32)))
     (type (;2;) (func (param i32 i32 i32 i32) (result i32)))
-    (import \"wasi_snapshot_preview1\" \"a\" (func (;0;) (type 0)))
+    (import \"wasi_snapshot_preview1\" \"a\" (func $wasi_snapshot_preview1::a (type 0)))
     (import \"$parent\" \"memory\" (memory (;0;) 0))
-    (import \"$parent\" \"canonical_abi_realloc\" (func (;1;) (type 2)))
+    (import \"$parent\" \"canonical_abi_realloc\" (func $main::canonical_abi_realloc (type 2)))
     (module (;0;)
       (type (;0;) (func))
       (type (;1;) (func (param i32 i32)))
@@ -785,17 +1118,17 @@ mod test {
       (instantiate 0
         (import \"wasi_snapshot_preview1\" (instance 0))))
     (alias 2 \"memory\" (memory (;1;)))
-    (alias 2 \"canonical_abi_realloc\" (func (;2;)))
-    (alias 2 \"canonical_abi_free\" (func (;3;)))
-    (alias 2 \"a\" (func (;4;)))
-    (func (;5;) (type 1) (param i32 i32)
+    (alias 2 \"canonical_abi_realloc\" (func $a::canonical_abi_realloc))
+    (alias 2 \"canonical_abi_free\" (func $a::canonical_abi_free))
+    (alias 2 \"a\" (func $a::a))
+    (func $main->a::a (type 1) (param i32 i32)
       (local i32)
       block  ;; label = @1
         i32.const 0
         i32.const 0
         i32.const 1
         local.get 1
-        call 2
+        call $a::canonical_abi_realloc
         local.tee 2
         br_if 0 (;@1;)
         unreachable
@@ -806,20 +1139,20 @@ mod test {
       memory.copy 1 0
       local.get 2
       local.get 1
-      call 4)
+      call $a::a)
     (export \"memory\" (memory 1))
-    (export \"canonical_abi_realloc\" (func 2))
-    (export \"canonical_abi_free\" (func 3))
-    (export \"a\" (func 5)))
-  (module (;2;)
+    (export \"canonical_abi_realloc\" (func $a::canonical_abi_realloc))
+    (export \"canonical_abi_free\" (func $a::canonical_abi_free))
+    (export \"a\" (func $main->a::a)))
+  (module $a-shim (;2;)
     (type (;0;) (func (param i32 i32)))
-    (func (;0;) (type 0) (param i32 i32)
+    (func $a-shim::a (type 0) (param i32 i32)
       local.get 0
       local.get 1
       i32.const 0
       call_indirect (type 0))
     (table (;0;) 1 1 funcref)
-    (export \"a\" (func 0))
+    (export \"a\" (func $a-shim::a))
     (export \"$funcs\" (table 0)))
   (instance (;1;)
     (instantiate 2))
@@ -844,4 +1177,780 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn it_errors_on_incompatible_memory_import() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "memory" (memory 2)) (func (export "_start")))"#,
+        )?;
+        let a = wat::parse_str(r#"(module (memory (export "memory") 1))"#)?;
+
+        let main = Module::new("main", &bytes, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", Module::new("a", &a, [])?);
+
+        let linker = Linker::new(Profile::new());
+
+        assert_eq!(
+            linker.link(&main, &imports).unwrap_err().to_string(),
+            "module `main` imports memory `memory` from module `a` but the types are incompatible"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_on_non_function_imports_from_adapted_module() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "memory" (memory 0)) (func (export "_start")))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (func (export "a") (param i32 i32)) (memory (export "memory") 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse("a", "a: function(p: string)")?],
+        )?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+
+        let linker = Linker::new(Profile::new());
+
+        assert_eq!(
+            linker.link(&main, &imports).unwrap_err().to_string(),
+            "module `main` imports memory `memory` from module `a` but only functions can be imported from an adapted module"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_links_imported_memories_tables_and_globals() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "memory" (memory 1)) (import "a" "table" (table 1 funcref)) (import "a" "global" (global (mut i32))) (func (export "_start")))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (memory (export "memory") 1 2) (table (export "table") 2 funcref) (global (export "global") (mut i32) (i32.const 0)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", Module::new("a", &a, [])?);
+
+        let linker = Linker::new(Profile::new());
+
+        let bytes = linker.link(&main, &imports)?;
+
+        assert_eq!(
+            wasmprinter::print_bytes(&bytes)?,
+            "\
+(module
+  (module (;0;)
+    (type (;0;) (func))
+    (import \"a\" \"memory\" (memory (;0;) 1))
+    (import \"a\" \"table\" (table (;0;) 1 funcref))
+    (import \"a\" \"global\" (global (;0;) (mut i32)))
+    (func (;0;) (type 0))
+    (export \"_start\" (func 0)))
+  (module (;1;)
+    (table (;0;) 2 funcref)
+    (memory (;0;) 1 2)
+    (global (;0;) (mut i32) (i32.const 0))
+    (export \"memory\" (memory 0))
+    (export \"table\" (table 0))
+    (export \"global\" (global 0)))
+  (instance (;0;)
+    (instantiate 1))
+  (instance (;1;)
+    (instantiate 0
+      (import \"a\" (instance 0))))
+  (alias 1 \"_start\" (func (;0;)))
+  (export \"_start\" (func 0)))"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_links_with_interface_and_imported_memory() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "a" (func (param i32 i32))) (func (export "_start")) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (memory (export "memory") 0))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (import "b" "memory" (memory 0)) (func (export "a") (param i32 i32)) (export "memory" (memory 0)) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+        let b = wat::parse_str(r#"(module (memory (export "memory") 1))"#)?;
+
+        let main = Module::new("main", &bytes, [])?;
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse("a", "a: function(p: string)")?],
+        )?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+        imports.insert("b", Module::new("b", &b, [])?);
+
+        let linker = Linker::new(Profile::new());
+
+        let bytes = linker.link(&main, &imports)?;
+
+        assert_eq!(
+            wasmprinter::print_bytes(&bytes)?,
+            "\
+(module
+  (module (;0;)
+    (type (;0;) (func (param i32 i32)))
+    (type (;1;) (func))
+    (type (;2;) (func (param i32 i32 i32 i32) (result i32)))
+    (import \"a\" \"a\" (func (;0;) (type 0)))
+    (func (;1;) (type 1))
+    (func (;2;) (type 2) (param i32 i32 i32 i32) (result i32)
+      unreachable)
+    (memory (;0;) 0)
+    (export \"_start\" (func 1))
+    (export \"canonical_abi_realloc\" (func 2))
+    (export \"memory\" (memory 0)))
+  (module $a-adapter (;1;)
+    (type (;0;) (func (param i32 i32)))
+    (type (;1;) (func (param i32 i32 i32 i32) (result i32)))
+    (import \"b\" \"memory\" (memory (;0;) 0))
+    (import \"$parent\" \"memory\" (memory (;1;) 0))
+    (import \"$parent\" \"canonical_abi_realloc\" (func $main::canonical_abi_realloc (type 1)))
+    (module (;0;)
+      (type (;0;) (func (param i32 i32)))
+      (type (;1;) (func (param i32 i32 i32 i32) (result i32)))
+      (type (;2;) (func (param i32 i32 i32)))
+      (import \"b\" \"memory\" (memory (;0;) 0))
+      (func (;0;) (type 0) (param i32 i32))
+      (func (;1;) (type 1) (param i32 i32 i32 i32) (result i32)
+        unreachable)
+      (func (;2;) (type 2) (param i32 i32 i32))
+      (export \"a\" (func 0))
+      (export \"memory\" (memory 0))
+      (export \"canonical_abi_realloc\" (func 1))
+      (export \"canonical_abi_free\" (func 2)))
+    (instance (;2;)
+      (instantiate 0
+        (import \"b\" (instance 0))))
+    (alias 2 \"memory\" (memory (;2;)))
+    (alias 2 \"canonical_abi_realloc\" (func $a::canonical_abi_realloc))
+    (alias 2 \"canonical_abi_free\" (func $a::canonical_abi_free))
+    (alias 2 \"a\" (func $a::a))
+    (func $main->a::a (type 0) (param i32 i32)
+      (local i32)
+      block  ;; label = @1
+        i32.const 0
+        i32.const 0
+        i32.const 1
+        local.get 1
+        call $a::canonical_abi_realloc
+        local.tee 2
+        br_if 0 (;@1;)
+        unreachable
+      end
+      local.get 2
+      local.get 0
+      local.get 1
+      memory.copy 2 1
+      local.get 2
+      local.get 1
+      call $a::a)
+    (export \"memory\" (memory 2))
+    (export \"canonical_abi_realloc\" (func $a::canonical_abi_realloc))
+    (export \"canonical_abi_free\" (func $a::canonical_abi_free))
+    (export \"a\" (func $main->a::a)))
+  (module $a-shim (;2;)
+    (type (;0;) (func (param i32 i32)))
+    (func $a-shim::a (type 0) (param i32 i32)
+      local.get 0
+      local.get 1
+      i32.const 0
+      call_indirect (type 0))
+    (table (;0;) 1 1 funcref)
+    (export \"a\" (func $a-shim::a))
+    (export \"$funcs\" (table 0)))
+  (module (;3;)
+    (memory (;0;) 1)
+    (export \"memory\" (memory 0)))
+  (instance (;0;)
+    (instantiate 2))
+  (instance (;1;)
+    (instantiate 0
+      (import \"a\" (instance 0))))
+  (instance (;2;)
+    (instantiate 3))
+  (instance (;3;)
+    (instantiate 1
+      (import \"$parent\" (instance 1))
+      (import \"b\" (instance 2))))
+  (alias 3 \"a\" (func (;0;)))
+  (alias 1 \"_start\" (func (;1;)))
+  (alias 1 \"canonical_abi_realloc\" (func (;2;)))
+  (alias 0 \"$funcs\" (table (;0;)))
+  (alias 1 \"memory\" (memory (;0;)))
+  (export \"_start\" (func 1))
+  (export \"canonical_abi_realloc\" (func 2))
+  (export \"memory\" (memory 0))
+  (elem (;0;) (i32.const 0) funcref (ref.func 0)))"
+        );
+
+        Ok(())
+    }
+
+    fn diamond_modules() -> Result<[Vec<u8>; 4]> {
+        Ok([
+            wat::parse_str(
+                r#"(module (import "a" "a" (func)) (import "b" "b" (func)) (func (export "_start")))"#,
+            )?,
+            wat::parse_str(r#"(module (import "c" "c" (func)) (func (export "a")))"#)?,
+            wat::parse_str(r#"(module (import "c" "c" (func)) (func (export "b")))"#)?,
+            wat::parse_str(r#"(module (func (export "c")))"#)?,
+        ])
+    }
+
+    #[test]
+    fn it_links_isolated_instances() -> Result<()> {
+        let [bytes, a, b, c] = diamond_modules()?;
+
+        let main = Module::new("main", &bytes, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", Module::new("a", &a, [])?);
+        imports.insert("b", Module::new("b", &b, [])?);
+        imports.insert("c", Module::new("c", &c, [])?);
+
+        let linker = Linker::new(Profile::new()).with_instance_policy(InstancePolicy::Isolated);
+
+        let bytes = linker.link(&main, &imports)?;
+
+        assert_eq!(
+            wasmprinter::print_bytes(&bytes)?,
+            "\
+(module
+  (module (;0;)
+    (type (;0;) (func))
+    (import \"a\" \"a\" (func (;0;) (type 0)))
+    (import \"b\" \"b\" (func (;1;) (type 0)))
+    (func (;2;) (type 0))
+    (export \"_start\" (func 2)))
+  (module (;1;)
+    (type (;0;) (func))
+    (import \"c\" \"c\" (func (;0;) (type 0)))
+    (func (;1;) (type 0))
+    (export \"b\" (func 1)))
+  (module (;2;)
+    (type (;0;) (func))
+    (func (;0;) (type 0))
+    (export \"c\" (func 0)))
+  (module (;3;)
+    (type (;0;) (func))
+    (import \"c\" \"c\" (func (;0;) (type 0)))
+    (func (;1;) (type 0))
+    (export \"a\" (func 1)))
+  (instance (;0;)
+    (instantiate 2))
+  (instance (;1;)
+    (instantiate 3
+      (import \"c\" (instance 0))))
+  (instance (;2;)
+    (instantiate 2))
+  (instance (;3;)
+    (instantiate 1
+      (import \"c\" (instance 2))))
+  (instance (;4;)
+    (instantiate 0
+      (import \"a\" (instance 1))
+      (import \"b\" (instance 3))))
+  (alias 4 \"_start\" (func (;0;)))
+  (export \"_start\" (func 0)))"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_links_shared_instances() -> Result<()> {
+        let [bytes, a, b, c] = diamond_modules()?;
+
+        let main = Module::new("main", &bytes, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", Module::new("a", &a, [])?);
+        imports.insert("b", Module::new("b", &b, [])?);
+        imports.insert("c", Module::new("c", &c, [])?);
+
+        let linker = Linker::new(Profile::new()).with_instance_policy(InstancePolicy::Shared);
+
+        let bytes = linker.link(&main, &imports)?;
+
+        assert_eq!(
+            wasmprinter::print_bytes(&bytes)?,
+            "\
+(module
+  (module (;0;)
+    (type (;0;) (func))
+    (import \"a\" \"a\" (func (;0;) (type 0)))
+    (import \"b\" \"b\" (func (;1;) (type 0)))
+    (func (;2;) (type 0))
+    (export \"_start\" (func 2)))
+  (module (;1;)
+    (type (;0;) (func))
+    (import \"c\" \"c\" (func (;0;) (type 0)))
+    (func (;1;) (type 0))
+    (export \"b\" (func 1)))
+  (module (;2;)
+    (type (;0;) (func))
+    (func (;0;) (type 0))
+    (export \"c\" (func 0)))
+  (module (;3;)
+    (type (;0;) (func))
+    (import \"c\" \"c\" (func (;0;) (type 0)))
+    (func (;1;) (type 0))
+    (export \"a\" (func 1)))
+  (instance (;0;)
+    (instantiate 2))
+  (instance (;1;)
+    (instantiate 3
+      (import \"c\" (instance 0))))
+  (instance (;2;)
+    (instantiate 1
+      (import \"c\" (instance 0))))
+  (instance (;3;)
+    (instantiate 0
+      (import \"a\" (instance 1))
+      (import \"b\" (instance 2))))
+  (alias 3 \"_start\" (func (;0;)))
+  (export \"_start\" (func 0)))"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_links_fused_with_interface() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "a" (func (param i32 i32))) (func (export "_start") i32.const 0 i32.const 0 call 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (memory (export "memory") 0))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (import "wasi_snapshot_preview1" "a" (func)) (func (export "a") (param i32 i32) call 0) (memory (export "memory") 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse("a", "a: function(p: string)")?],
+        )?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+
+        let linker = Linker::new(wasi_profile()?);
+
+        let bytes = linker.link_fused(&main, &imports)?;
+
+        let mut validator = wasmparser::Validator::new();
+        validator.wasm_features(wasmparser::WasmFeatures {
+            multi_memory: true,
+            bulk_memory: true,
+            ..Default::default()
+        });
+        validator.validate_all(&bytes)?;
+
+        assert_eq!(
+            wasmprinter::print_bytes(&bytes)?,
+            "\
+(module
+  (type (;0;) (func))
+  (type (;1;) (func (param i32 i32)))
+  (type (;2;) (func (param i32 i32 i32 i32) (result i32)))
+  (type (;3;) (func (param i32 i32 i32)))
+  (import \"wasi_snapshot_preview1\" \"a\" (func (;0;) (type 0)))
+  (func $a-shim::a (type 1) (param i32 i32)
+    local.get 0
+    local.get 1
+    i32.const 0
+    call_indirect (type 1))
+  (func (;2;) (type 0)
+    i32.const 0
+    i32.const 0
+    call $a-shim::a)
+  (func (;3;) (type 2) (param i32 i32 i32 i32) (result i32)
+    unreachable)
+  (func (;4;) (type 1) (param i32 i32)
+    call 0)
+  (func (;5;) (type 2) (param i32 i32 i32 i32) (result i32)
+    unreachable)
+  (func (;6;) (type 3) (param i32 i32 i32))
+  (func $main->a::a (type 1) (param i32 i32)
+    (local i32)
+    block  ;; label = @1
+      i32.const 0
+      i32.const 0
+      i32.const 1
+      local.get 1
+      call 5
+      local.tee 2
+      br_if 0 (;@1;)
+      unreachable
+    end
+    local.get 2
+    local.get 0
+    local.get 1
+    memory.copy 1 0
+    local.get 2
+    local.get 1
+    call 4)
+  (table (;0;) 1 1 funcref)
+  (memory (;0;) 0)
+  (memory (;1;) 0)
+  (export \"_start\" (func 2))
+  (export \"canonical_abi_realloc\" (func 3))
+  (export \"memory\" (memory 0))
+  (elem (;0;) (i32.const 0) funcref (ref.func $main->a::a)))"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_links_fused_with_interface_results() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "a" (func (param i32))) (func (export "_start") i32.const 0 call 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (memory (export "memory") 0))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (func (export "a") (result i32) i32.const 0) (memory (export "memory") 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse(
+                "a",
+                "a: function() -> string",
+            )?],
+        )?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+
+        let linker = Linker::new(wasi_profile()?);
+
+        let bytes = linker.link_fused(&main, &imports)?;
+
+        // The adapter loads from the adapted module's memory, which must be remapped
+        let mut validator = wasmparser::Validator::new();
+        validator.wasm_features(wasmparser::WasmFeatures {
+            multi_memory: true,
+            bulk_memory: true,
+            ..Default::default()
+        });
+        validator.validate_all(&bytes)?;
+
+        assert!(wasmprinter::print_bytes(&bytes)?.contains("i64.load (memory 1) offset=8"));
+
+        Ok(())
+    }
+
+    fn string_modules(
+        main_encoding: StringEncoding,
+        a_encoding: StringEncoding,
+    ) -> Result<Vec<u8>> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "a" (func (param i32 i32 i32))) (func (export "_start") i32.const 0 i32.const 0 i32.const 0 call 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)) (memory (export "memory") 0))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (func (export "a") (param i32 i32) (result i32) i32.const 0) (memory (export "memory") 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?.with_string_encoding(main_encoding);
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse(
+                "a",
+                "a: function(p: string) -> string",
+            )?],
+        )?
+        .with_string_encoding(a_encoding);
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+
+        let linker = Linker::new(wasi_profile()?);
+
+        let bytes = linker.link_fused(&main, &imports)?;
+
+        let mut validator = wasmparser::Validator::new();
+        validator.wasm_features(wasmparser::WasmFeatures {
+            multi_memory: true,
+            bulk_memory: true,
+            ..Default::default()
+        });
+        validator.validate_all(&bytes)?;
+
+        Ok(bytes)
+    }
+
+    #[test]
+    fn it_links_with_string_transcoding() -> Result<()> {
+        let printed = wasmprinter::print_bytes(&string_modules(
+            StringEncoding::Utf16,
+            StringEncoding::Utf8,
+        )?)?;
+
+        // A transcoding function is generated for both parameters and results
+        assert_eq!(
+            printed
+                .matches("(type 4) (param i32 i32 i32) (result i32)")
+                .count(),
+            2
+        );
+
+        // The UTF-8 parameter is allocated for the worst case expansion, transcoded, and then shrunk
+        assert!(printed.contains(
+            "\
+    local.get 0
+    local.get 1
+    local.get 3
+    call $main->a::transcode_utf16_to_utf8
+    local.set 4
+    block  ;; label = @1
+      local.get 3
+      local.get 1
+      i32.const 3
+      i32.mul
+      i32.const 1
+      local.get 4
+      call 5"
+        ));
+
+        // The UTF-16 result's length is updated in the caller's return area
+        assert!(printed.contains(
+            "\
+    local.get 2
+    local.get 8
+    i32.store offset=8
+    local.get 2
+    local.get 7
+    i32.store"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_links_same_string_encodings_without_transcoding() -> Result<()> {
+        let printed = wasmprinter::print_bytes(&string_modules(
+            StringEncoding::Utf16,
+            StringEncoding::Utf16,
+        )?)?;
+
+        assert!(!printed.contains("(param i32 i32 i32) (result i32)"));
+
+        // UTF-16 strings are copied as two byte code units
+        assert!(printed.contains(
+            "\
+      i32.const 2
+      local.get 1
+      i32.const 2
+      i32.mul
+      call 5"
+        ));
+
+        Ok(())
+    }
+
+    fn shared_memory_modules(policy: InstancePolicy) -> Result<Vec<u8>> {
+        let bytes = wat::parse_str(
+            r#"(module (import "alloc" "memory" (memory 1)) (import "alloc" "canonical_abi_realloc" (func $realloc (param i32 i32 i32 i32) (result i32))) (import "alloc" "canonical_abi_free" (func $free (param i32 i32 i32))) (import "a" "get" (func (param i32))) (import "a" "put" (func (param i32 i32))) (export "memory" (memory 0)) (export "canonical_abi_realloc" (func $realloc)) (export "canonical_abi_free" (func $free)) (func (export "_start")))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (import "alloc" "memory" (memory 1)) (import "alloc" "canonical_abi_realloc" (func $realloc (param i32 i32 i32 i32) (result i32))) (import "alloc" "canonical_abi_free" (func $free (param i32 i32 i32))) (export "memory" (memory 0)) (export "canonical_abi_realloc" (func $realloc)) (export "canonical_abi_free" (func $free)) (func (export "get") (result i32) unreachable) (func (export "put") (param i32 i32)))"#,
+        )?;
+        let alloc = wat::parse_str(
+            r#"(module (memory (export "memory") 1) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse(
+                "a",
+                "get: function() -> list<u8>\nput: function(x: list<u8>)",
+            )?],
+        )?;
+        let alloc = Module::new("alloc", &alloc, [])?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+        imports.insert("alloc", alloc);
+
+        Linker::new(Profile::new())
+            .with_instance_policy(policy)
+            .link(&main, &imports)
+    }
+
+    #[test]
+    fn it_moves_returned_lists_with_shared_memory() -> Result<()> {
+        let printed = wasmprinter::print_bytes(&shared_memory_modules(InstancePolicy::Shared)?)?;
+
+        // The returned list is stored in the caller's return area without being copied or freed
+        assert!(printed.contains(
+            "\
+      local.get 1
+      i32.load (memory 2)
+      local.set 2
+      local.get 0
+      local.get 2
+      i32.store (memory 1))
+    (func $main->a::put"
+        ));
+
+        // The parameter is still copied as the module takes ownership of it
+        assert!(printed.contains(
+            "\
+      memory.copy 2 1
+      local.get 2
+      local.get 1
+      call $a::put)"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_copies_returned_lists_without_shared_memory() -> Result<()> {
+        let printed = wasmprinter::print_bytes(&shared_memory_modules(InstancePolicy::Isolated)?)?;
+
+        // The allocator module is instantiated for each importer, so the returned list is copied and freed
+        assert!(printed.contains("memory.copy 1 2"));
+        assert!(printed.contains("call $a::canonical_abi_free)\n    (func $main->a::put"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_inspects() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "wasi_snapshot_preview1" "a" (func)) (import "a" "acquire" (func (param i32 i32) (result i32))) (func (export "_start")) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (memory (export "memory") 0))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (func (export "acquire") (param i32 i32) (result i32) unreachable) (func (export "count") (result i32) unreachable) (func (export "canonical_abi_drop_x") (param i32)) (memory (export "memory") 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse(
+                "a",
+                "resource x\nacquire: function(s: string) -> x\ncount: function() -> u32",
+            )?],
+        )?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+
+        let linker = Linker::new(wasi_profile()?);
+
+        let plan = linker.inspect(&main, &imports)?;
+        assert!(plan.needs_runtime);
+        assert_eq!(plan.modules.len(), 2);
+
+        let main = &plan.modules[0];
+        assert_eq!(main.name, "main");
+        assert_eq!(main.imports, ["a"]);
+        assert!(!main.must_adapt);
+        assert!(main.functions.is_empty());
+        assert!(main.resources.is_empty());
+        assert_eq!(main.profile_imports.len(), 1);
+        assert_eq!(main.profile_imports[0].module, "wasi_snapshot_preview1");
+        assert_eq!(main.profile_imports[0].field, "a");
+        assert_eq!(main.profile_imports[0].ty.to_string(), "(func)");
+
+        let a = &plan.modules[1];
+        assert_eq!(a.name, "a");
+        assert!(a.imports.is_empty());
+        assert!(a.must_adapt);
+        assert_eq!(a.string_encoding, StringEncoding::Utf8);
+        assert_eq!(
+            a.functions
+                .iter()
+                .map(|f| (
+                    f.interface.as_str(),
+                    f.name.as_str(),
+                    f.must_adapt,
+                    f.import_signature.to_string(),
+                    f.export_type.to_string()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "a",
+                    "acquire",
+                    true,
+                    "(func (param i32 i32) (result i32))".to_string(),
+                    "(func (param i32 i32) (result i32))".to_string()
+                ),
+                (
+                    "a",
+                    "count",
+                    false,
+                    "(func (result i32))".to_string(),
+                    "(func (result i32))".to_string()
+                )
+            ]
+        );
+        assert_eq!(a.resources.len(), 1);
+        assert_eq!(a.resources[0].name, "x");
+        assert_eq!(a.resources[0].id, 0);
+        assert_eq!(a.resources[0].drop_callback, "canonical_abi_drop_x");
+        assert!(a.profile_imports.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "runtime-leak-check")]
+    #[test]
+    fn it_exports_resource_leaks() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "a" "acquire" (func (param i32 i32) (result i32))) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (memory (export "memory") 0))"#,
+        )?;
+        let a = wat::parse_str(
+            r#"(module (func (export "acquire") (param i32 i32) (result i32) unreachable) (func (export "canonical_abi_drop_x") (param i32)) (memory (export "memory") 0) (func (export "canonical_abi_realloc") (param i32 i32 i32 i32) (result i32) unreachable) (func (export "canonical_abi_free") (param i32 i32 i32)))"#,
+        )?;
+
+        let main = Module::new("main", &bytes, [])?;
+        let a = Module::new(
+            "a",
+            &a,
+            [wit_parser::Interface::parse(
+                "a",
+                "resource x\nacquire: function(s: string) -> x",
+            )?],
+        )?;
+
+        let mut imports = HashMap::new();
+        imports.insert("a", a);
+
+        let output = Linker::new(Profile::new()).link(&main, &imports)?;
+        let text = wasmprinter::print_bytes(&output)?;
+        assert!(text.contains(&format!(
+            "(export \"{}\" (func ",
+            RESOURCE_LEAKS_EXPORT_NAME
+        )));
+
+        Ok(())
+    }
 }
@@ -1,19 +1,20 @@
 use crate::adapter::{
     FREE_EXPORT_NAME, FREE_FUNC_TYPE, MEMORY_EXPORT_NAME, REALLOC_EXPORT_NAME, REALLOC_FUNC_TYPE,
 };
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use core::fmt;
 use std::collections::HashMap;
 use wasmparser::{
-    Chunk, Export, ExternalKind, FuncType, Import, ImportSectionEntryType, Parser, Payload, Range,
-    SectionReader, Type, TypeDef, Validator,
+    Chunk, Export, ExternalKind, FuncType, GlobalType, Import, ImportSectionEntryType, MemoryType,
+    Parser, Payload, Range, ResizableLimits, ResizableLimits64, SectionReader, TableType, Type,
+    TypeDef, Validator,
 };
 use wit_parser::{
     abi::{AbiVariant, WasmSignature, WasmType},
     Function, Interface as WitInterface, SizeAlign, Type as WitType,
 };
 
-fn import_kind(ty: ImportSectionEntryType) -> &'static str {
+pub(crate) fn import_kind(ty: ImportSectionEntryType) -> &'static str {
     match ty {
         ImportSectionEntryType::Function(_) => "function",
         ImportSectionEntryType::Table(_) => "table",
@@ -40,6 +41,54 @@ pub(crate) fn export_kind(kind: ExternalKind) -> &'static str {
     }
 }
 
+fn limits_compatible(import: &ResizableLimits, export: &ResizableLimits) -> bool {
+    export.initial >= import.initial
+        && match (import.maximum, export.maximum) {
+            (Some(i), Some(e)) => e <= i,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+}
+
+fn limits64_compatible(import: &ResizableLimits64, export: &ResizableLimits64) -> bool {
+    export.initial >= import.initial
+        && match (import.maximum, export.maximum) {
+            (Some(i), Some(e)) => e <= i,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+}
+
+fn table_compatible(import: &TableType, export: &TableType) -> bool {
+    import.element_type == export.element_type && limits_compatible(&import.limits, &export.limits)
+}
+
+fn memory_compatible(import: &MemoryType, export: &MemoryType) -> bool {
+    match (import, export) {
+        (
+            MemoryType::M32 {
+                limits: i,
+                shared: is,
+            },
+            MemoryType::M32 {
+                limits: e,
+                shared: es,
+            },
+        ) => is == es && limits_compatible(i, e),
+        (
+            MemoryType::M64 {
+                limits: i,
+                shared: is,
+            },
+            MemoryType::M64 {
+                limits: e,
+                shared: es,
+            },
+        ) => is == es && limits64_compatible(i, e),
+        _ => false,
+    }
+}
+
 fn has_list(interface: &WitInterface, ty: &WitType) -> bool {
     use wit_parser::{Type, TypeDefKind};
 
@@ -59,6 +108,25 @@ fn has_list(interface: &WitInterface, ty: &WitType) -> bool {
     }
 }
 
+fn has_string(interface: &WitInterface, ty: &WitType) -> bool {
+    use wit_parser::{Type, TypeDefKind};
+
+    match ty {
+        Type::Id(id) => match &interface.types[*id].kind {
+            TypeDefKind::List(Type::Char) => true,
+            TypeDefKind::List(t) | TypeDefKind::Type(t) => has_string(interface, t),
+            TypeDefKind::Record(r) => r.fields.iter().any(|f| has_string(interface, &f.ty)),
+            TypeDefKind::Variant(v) => v.cases.iter().any(|c| {
+                c.ty.as_ref()
+                    .map(|t| has_string(interface, t))
+                    .unwrap_or(false)
+            }),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
 pub(crate) struct FunctionInfo {
     pub import_signature: WasmSignature,
     pub import_type: FuncType,
@@ -73,6 +141,7 @@ pub(crate) struct Interface {
     pub(crate) must_adapt: bool,
     needs_memory: bool,
     needs_memory_funcs: bool,
+    has_strings: bool,
     has_resources: bool,
 }
 
@@ -81,6 +150,7 @@ impl Interface {
         let mut must_adapt_module = false;
         let mut needs_memory = false;
         let mut needs_memory_funcs = false;
+        let mut has_strings = false;
 
         let func_infos = inner
             .functions
@@ -105,6 +175,8 @@ impl Interface {
                             || f.results.iter().any(|(_, ty)| has_list(&inner, ty));
                     }
 
+                    has_strings |= f.params.iter().any(|(_, ty)| has_string(&inner, ty))
+                        || f.results.iter().any(|(_, ty)| has_string(&inner, ty));
                     needs_memory |= has_retptr | needs_memory_funcs;
                     must_adapt_module = true;
                 }
@@ -133,6 +205,7 @@ impl Interface {
             must_adapt: must_adapt_module,
             needs_memory,
             needs_memory_funcs,
+            has_strings,
             has_resources,
         }
     }
@@ -153,7 +226,7 @@ impl Interface {
         Some(&self.func_infos[self.inner.functions.iter().position(|f| f.name == name)?])
     }
 
-    fn sig_to_type(signature: &WasmSignature) -> FuncType {
+    pub(crate) fn sig_to_type(signature: &WasmSignature) -> FuncType {
         fn from_wasm_type(ty: &WasmType) -> Type {
             match ty {
                 WasmType::I32 => Type::I32,
@@ -181,6 +254,52 @@ impl Interface {
     }
 }
 
+/// The encoding a module uses for strings passed through its interfaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StringEncoding {
+    /// Strings are UTF-8 encoded and their lengths are in bytes.
+    Utf8,
+    /// Strings are UTF-16 (little-endian) encoded and their lengths are in code units.
+    Utf16,
+}
+
+impl Default for StringEncoding {
+    fn default() -> Self {
+        Self::Utf8
+    }
+}
+
+impl StringEncoding {
+    /// Gets the size and alignment, in bytes, of a code unit in the encoding.
+    pub(crate) fn code_unit(self) -> u32 {
+        match self {
+            Self::Utf8 => 1,
+            Self::Utf16 => 2,
+        }
+    }
+}
+
+impl fmt::Display for StringEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Utf8 => write!(f, "utf8"),
+            Self::Utf16 => write!(f, "utf16"),
+        }
+    }
+}
+
+impl std::str::FromStr for StringEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "utf8" | "utf-8" => Ok(Self::Utf8),
+            "utf16" | "utf-16" => Ok(Self::Utf16),
+            _ => bail!("unknown string encoding `{}`", s),
+        }
+    }
+}
+
 /// Represents a parsed WebAssembly module.
 pub struct Module<'a> {
     /// The name of the parsed module.
@@ -192,22 +311,35 @@ pub struct Module<'a> {
     pub(crate) exports: Vec<Export<'a>>,
     pub(crate) interfaces: Vec<Interface>,
     pub(crate) functions: Vec<u32>,
+    pub(crate) tables: Vec<TableType>,
+    pub(crate) memories: Vec<MemoryType>,
+    pub(crate) globals: Vec<GlobalType>,
     pub(crate) sections: Vec<wasm_encoder::RawSection<'a>>,
     pub(crate) must_adapt: bool,
     pub(crate) needs_memory: bool,
     pub(crate) needs_memory_funcs: bool,
+    pub(crate) has_strings: bool,
     pub(crate) has_resources: bool,
+    pub(crate) string_encoding: StringEncoding,
 }
 
 impl<'a> Module<'a> {
     /// Constructs a new WebAssembly module from a name and the module's bytes.
     ///
     /// The specified interfaces are for every interface the module exports.
+    ///
+    /// If no interfaces are specified, the interfaces embedded in the module's
+    /// custom sections (see [`wit_parser::INTERFACE_SECTION_PREFIX`]) are used.
     pub fn new(
         name: &'a str,
         bytes: &'a [u8],
         interfaces: impl IntoIterator<Item = WitInterface>,
     ) -> Result<Self> {
+        let mut interfaces = interfaces.into_iter().collect::<Vec<_>>();
+        if interfaces.is_empty() {
+            interfaces = Self::embedded_interfaces(bytes)?;
+        }
+
         let mut module = Self {
             name,
             bytes,
@@ -215,18 +347,24 @@ impl<'a> Module<'a> {
             imports: Vec::new(),
             exports: Vec::new(),
             functions: Vec::new(),
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
             sections: Vec::new(),
             interfaces: interfaces.into_iter().map(Interface::new).collect(),
             must_adapt: false,
             needs_memory: false,
             needs_memory_funcs: false,
+            has_strings: false,
             has_resources: false,
+            string_encoding: StringEncoding::default(),
         };
 
         for interface in &module.interfaces {
             module.must_adapt |= interface.must_adapt;
             module.needs_memory |= interface.needs_memory;
             module.needs_memory_funcs |= interface.needs_memory_funcs;
+            module.has_strings |= interface.has_strings;
             module.has_resources |= interface.has_resources;
         }
 
@@ -237,6 +375,46 @@ impl<'a> Module<'a> {
         Ok(module)
     }
 
+    /// Sets the encoding the module uses for strings passed through its interfaces.
+    ///
+    /// Strings are transcoded by the adapters when linked modules use different encodings.
+    pub fn with_string_encoding(mut self, encoding: StringEncoding) -> Self {
+        self.string_encoding = encoding;
+        self
+    }
+
+    /// Gets the encoding the module uses for strings passed through its interfaces.
+    pub fn string_encoding(&self) -> StringEncoding {
+        self.string_encoding
+    }
+
+    fn embedded_interfaces(bytes: &[u8]) -> Result<Vec<WitInterface>> {
+        let mut interfaces = Vec::new();
+
+        for payload in Parser::new(0).parse_all(bytes) {
+            if let Payload::CustomSection { name, data, .. } = payload? {
+                let interface_name = match name.strip_prefix(wit_parser::INTERFACE_SECTION_PREFIX) {
+                    Some(name) => name,
+                    None => continue,
+                };
+
+                let interface = std::str::from_utf8(data)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|source| WitInterface::parse(interface_name, source))
+                    .with_context(|| {
+                        format!(
+                            "failed to parse interface embedded in custom section `{}`",
+                            name
+                        )
+                    })?;
+
+                interfaces.push(interface);
+            }
+        }
+
+        Ok(interfaces)
+    }
+
     fn add_section(&mut self, id: wasm_encoder::SectionId, range: Range) {
         self.sections.push(wasm_encoder::RawSection {
             id: id as u8,
@@ -276,7 +454,14 @@ impl<'a> Module<'a> {
                     self.imports.reserve(imports.get_count() as usize);
 
                     for import in imports {
-                        self.imports.push(import?);
+                        let import = import?;
+                        match import.ty {
+                            ImportSectionEntryType::Table(ty) => self.tables.push(ty),
+                            ImportSectionEntryType::Memory(ty) => self.memories.push(ty),
+                            ImportSectionEntryType::Global(ty) => self.globals.push(ty),
+                            _ => {}
+                        }
+                        self.imports.push(import);
                     }
                 }
                 Payload::AliasSection(_) => {
@@ -296,16 +481,28 @@ impl<'a> Module<'a> {
                 }
                 Payload::TableSection(tables) => {
                     validator.table_section(&tables)?;
-                    self.add_section(wasm_encoder::SectionId::Table, tables.range())
+                    self.add_section(wasm_encoder::SectionId::Table, tables.range());
+
+                    for table in tables {
+                        self.tables.push(table?);
+                    }
                 }
                 Payload::MemorySection(memories) => {
                     validator.memory_section(&memories)?;
-                    self.add_section(wasm_encoder::SectionId::Memory, memories.range())
+                    self.add_section(wasm_encoder::SectionId::Memory, memories.range());
+
+                    for memory in memories {
+                        self.memories.push(memory?);
+                    }
                 }
                 Payload::EventSection(_) => bail!("module contains unsupported event section"),
                 Payload::GlobalSection(globals) => {
                     validator.global_section(&globals)?;
-                    self.add_section(wasm_encoder::SectionId::Global, globals.range())
+                    self.add_section(wasm_encoder::SectionId::Global, globals.range());
+
+                    for global in globals {
+                        self.globals.push(global?.ty);
+                    }
                 }
                 Payload::ExportSection(exports) => {
                     validator.export_section(&exports)?;
@@ -493,13 +690,51 @@ impl<'a> Module<'a> {
         Ok(())
     }
 
+    /// Gets the name of the module providing this module's memory and allocator, if any.
+    ///
+    /// A module's memory is provided by another module when the module re-exports the memory,
+    /// realloc, and free it imports from that module.
+    pub(crate) fn memory_provider(&self) -> Option<&'a str> {
+        let memory = self.reexported_import(MEMORY_EXPORT_NAME, ExternalKind::Memory)?;
+        let realloc = self.reexported_import(REALLOC_EXPORT_NAME, ExternalKind::Function)?;
+        let free = self.reexported_import(FREE_EXPORT_NAME, ExternalKind::Function)?;
+
+        if memory.module == realloc.module && memory.module == free.module {
+            Some(memory.module)
+        } else {
+            None
+        }
+    }
+
+    fn reexported_import(&self, name: &str, kind: ExternalKind) -> Option<&Import<'a>> {
+        let export = self.exports.iter().find(|e| {
+            e.field == name && std::mem::discriminant(&e.kind) == std::mem::discriminant(&kind)
+        })?;
+
+        // Imports come first in each index space, so an exported import's index is its position among the imports
+        self.imports
+            .iter()
+            .filter(|i| {
+                matches!(
+                    (i.ty, kind),
+                    (ImportSectionEntryType::Function(_), ExternalKind::Function)
+                        | (ImportSectionEntryType::Memory(_), ExternalKind::Memory)
+                )
+            })
+            .nth(export.index as usize)
+    }
+
     pub(crate) fn func_type(&self, index: u32) -> Option<&FuncType> {
-        let ty = match self.imports.get(index as usize) {
-            Some(import) => match &import.ty {
-                ImportSectionEntryType::Function(ty) => *ty,
-                _ => return None,
-            },
-            None => *self.functions.get(index as usize - self.imports.len())?,
+        let mut imported_funcs = self.imports.iter().filter_map(|i| match i.ty {
+            ImportSectionEntryType::Function(ty) => Some(ty),
+            _ => None,
+        });
+
+        let num_imported_funcs = imported_funcs.clone().count();
+        let ty = if (index as usize) < num_imported_funcs {
+            imported_funcs.nth(index as usize)?
+        } else {
+            *self.functions.get(index as usize - num_imported_funcs)?
         };
 
         self.types.get(ty as usize).and_then(|t| match t {
@@ -532,47 +767,61 @@ impl<'a> Module<'a> {
                 )
             })?;
 
-        // For adapted functions, resolve by the function's import type and not the actual wasm type
-        let func_type = if self.interfaces.is_empty() {
-            self.func_type(export.index)
-        } else {
-            let info = self
-                .interfaces
-                .iter()
-                .find_map(|i| i.lookup_info(import.field.unwrap_or("")))
-                .ok_or_else(|| {
-                    anyhow!(
-                        "module `{}` does not export a function named `{}` in its interface",
-                        self.name,
-                        import.field.unwrap_or("")
-                    )
-                })?;
-
-            Some(&info.import_type)
-        };
-
-        match (import.ty, export.kind) {
+        let compatible = match (import.ty, export.kind) {
             (ImportSectionEntryType::Function(_), ExternalKind::Function) => {
-                let compatible = match (module.import_func_type(import), func_type) {
-                    (Some(i), Some(e)) => e == i,
-                    _ => false,
+                // For adapted functions, resolve by the function's import type and not the actual wasm type
+                let func_type = if self.interfaces.is_empty() {
+                    self.func_type(export.index)
+                } else {
+                    let info = self
+                        .interfaces
+                        .iter()
+                        .find_map(|i| i.lookup_info(import.field.unwrap_or("")))
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "module `{}` does not export a function named `{}` in its interface",
+                                self.name,
+                                import.field.unwrap_or("")
+                            )
+                        })?;
+
+                    Some(&info.import_type)
                 };
 
-                if !compatible {
-                    bail!(
-                        "module `{}` imports function `{}` from module `{}` but the types are incompatible", module.name, import.field.unwrap_or(""), self.name
-                    );
+                match (module.import_func_type(import), func_type) {
+                    (Some(i), Some(e)) => e == i,
+                    _ => false,
                 }
             }
-            (ImportSectionEntryType::Table(_), ExternalKind::Table) => {
-                bail!("importing tables is not currently supported")
-            }
-            (ImportSectionEntryType::Memory(_), ExternalKind::Memory) => {
-                bail!("importing memories is not currently supported")
-            }
-            (ImportSectionEntryType::Global(_), ExternalKind::Global) => {
-                bail!("importing globals is not currently supported")
+            (ImportSectionEntryType::Table(_), ExternalKind::Table)
+            | (ImportSectionEntryType::Memory(_), ExternalKind::Memory)
+            | (ImportSectionEntryType::Global(_), ExternalKind::Global)
+                if self.must_adapt =>
+            {
+                // Adapted modules are only reachable through their adapted functions
+                bail!(
+                    "module `{}` imports {} `{}` from module `{}` but only functions can be imported from an adapted module",
+                    module.name,
+                    import_kind(import.ty),
+                    import.field.unwrap_or(""),
+                    self.name
+                )
             }
+            (ImportSectionEntryType::Table(ty), ExternalKind::Table) => self
+                .tables
+                .get(export.index as usize)
+                .map(|e| table_compatible(&ty, e))
+                .unwrap_or(false),
+            (ImportSectionEntryType::Memory(ty), ExternalKind::Memory) => self
+                .memories
+                .get(export.index as usize)
+                .map(|e| memory_compatible(&ty, e))
+                .unwrap_or(false),
+            (ImportSectionEntryType::Global(ty), ExternalKind::Global) => self
+                .globals
+                .get(export.index as usize)
+                .map(|e| *e == ty)
+                .unwrap_or(false),
             (ImportSectionEntryType::Module(_), ExternalKind::Module) => {
                 bail!("importing modules is not currently supported")
             }
@@ -587,6 +836,16 @@ impl<'a> Module<'a> {
                 self.name,
                 export_kind(export.kind)
             ),
+        };
+
+        if !compatible {
+            bail!(
+                "module `{}` imports {} `{}` from module `{}` but the types are incompatible",
+                module.name,
+                import_kind(import.ty),
+                import.field.unwrap_or(""),
+                self.name
+            );
         }
 
         Ok(())
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use wasm_encoder::encoders;
+use wasmparser::{Name, NameSectionReader};
+
+const NAME_SECTION_NAME: &str = "name";
+const MODULE_SUBSECTION_ID: u8 = 0;
+const FUNCTION_SUBSECTION_ID: u8 = 1;
+
+/// Builds the `name` custom section of a generated module.
+///
+/// The names are only used for debugging, such as in the backtraces of traps.
+#[derive(Debug, Default)]
+pub struct Names {
+    module: Option<String>,
+    functions: Vec<(u32, String)>,
+}
+
+impl Names {
+    /// Creates a new set of names for the module with the given name.
+    pub fn new(module: impl Into<String>) -> Self {
+        Self {
+            module: Some(module.into()),
+            functions: Vec::new(),
+        }
+    }
+
+    /// Names the function with the given index.
+    pub fn function(&mut self, index: u32, name: impl Into<String>) {
+        self.functions.push((index, name.into()));
+    }
+
+    /// Writes the names as a custom section of the given module.
+    ///
+    /// Nothing is written if there are no names.
+    pub fn write(mut self, module: &mut wasm_encoder::Module) {
+        if self.module.is_none() && self.functions.is_empty() {
+            return;
+        }
+
+        let mut data = Vec::new();
+
+        if let Some(name) = &self.module {
+            let bytes = encoders::str(name).collect::<Vec<_>>();
+            data.push(MODULE_SUBSECTION_ID);
+            data.extend(encoders::u32(bytes.len() as u32));
+            data.extend(bytes);
+        }
+
+        if !self.functions.is_empty() {
+            // The function names must be sorted by index
+            self.functions.sort_by_key(|(index, _)| *index);
+
+            let mut bytes = encoders::u32(self.functions.len() as u32).collect::<Vec<_>>();
+            for (index, name) in &self.functions {
+                bytes.extend(encoders::u32(*index));
+                bytes.extend(encoders::str(name));
+            }
+
+            data.push(FUNCTION_SUBSECTION_ID);
+            data.extend(encoders::u32(bytes.len() as u32));
+            data.extend(bytes);
+        }
+
+        module.section(&wasm_encoder::CustomSection {
+            name: NAME_SECTION_NAME,
+            data: &data,
+        });
+    }
+}
+
+/// Reads the function names from the given `name` custom section.
+///
+/// Returns an empty list if the section is not a `name` section.
+pub fn read_function_names<'a>(
+    name: &str,
+    data: &'a [u8],
+    offset: usize,
+) -> Result<Vec<(u32, &'a str)>> {
+    let mut functions = Vec::new();
+
+    if name != NAME_SECTION_NAME {
+        return Ok(functions);
+    }
+
+    for section in NameSectionReader::new(data, offset)? {
+        if let Name::Function(names) = section? {
+            let mut map = names.get_map()?;
+            for _ in 0..map.get_count() {
+                let naming = map.read()?;
+                functions.push((naming.index, naming.name));
+            }
+        }
+    }
+
+    Ok(functions)
+}
@@ -1,20 +1,220 @@
-use wasmparser::FuncType;
+use crate::module::{import_kind, Interface, Module};
+use anyhow::{bail, Result};
+use std::collections::{hash_map::Entry, HashMap};
+use wasmparser::{FuncType, ImportSectionEntryType};
+use wit_parser::{abi::AbiVariant, Interface as WitInterface};
 
 /// Represents a link profile.
 ///
-/// Link profiles represent information about the target environment.
-#[derive(Default, Debug)]
-pub struct Profile {}
+/// Link profiles represent information about the target environment, namely
+/// the host functions (and their signatures) that it provides to linked
+/// modules.
+///
+/// A module that imports from a module named by the profile must import a
+/// function the profile provides, with the exact signature the profile
+/// provides it with.
+#[derive(Default, Debug, Clone)]
+pub struct Profile {
+    modules: HashMap<String, HashMap<String, FuncType>>,
+}
 
 impl Profile {
-    /// Constructs a new link profile.
+    /// Constructs a new, empty link profile.
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Constructs a link profile from a WebAssembly module.
+    ///
+    /// The module declares the profile: each of its function imports is a
+    /// host function provided by the target environment. The module may not
+    /// import anything other than functions.
+    pub fn from_module(bytes: &[u8]) -> Result<Self> {
+        let mut profile = Self::new();
+        profile.add_module(bytes)?;
+        Ok(profile)
+    }
+
+    /// Adds the function imports of the given WebAssembly module to the
+    /// profile.
+    ///
+    /// See [`Profile::from_module`] for the expected shape of the module.
+    pub fn add_module(&mut self, bytes: &[u8]) -> Result<()> {
+        let module = Module::new("profile", bytes, [])?;
+
+        for import in &module.imports {
+            let field = match (import.ty, import.field) {
+                (ImportSectionEntryType::Function(_), Some(field)) => field,
+                _ => bail!(
+                    "profile module imports {} `{}` from module `{}` but only function imports are supported",
+                    import_kind(import.ty),
+                    import.field.unwrap_or(""),
+                    import.module
+                ),
+            };
+
+            self.add_function(
+                import.module,
+                field,
+                module.import_func_type(import).unwrap().clone(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the functions of the given interface to the profile, as provided
+    /// by a module named `module`.
+    ///
+    /// The function signatures are those a guest uses to import the
+    /// functions, so this works for both `*.wit` interfaces and `*.witx`
+    /// interfaces using the preview1 ABI.
+    pub fn add_interface(&mut self, module: &str, interface: &WitInterface) -> Result<()> {
+        for f in &interface.functions {
+            let signature = interface.wasm_signature(AbiVariant::GuestImport, f);
+            self.add_function(module, &f.name, Interface::sig_to_type(&signature))?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a single host function to the profile.
+    ///
+    /// It is an error to add a function that was already added with a
+    /// different signature.
+    pub fn add_function(&mut self, module: &str, field: &str, ty: FuncType) -> Result<()> {
+        match self
+            .modules
+            .entry(module.to_string())
+            .or_default()
+            .entry(field.to_string())
+        {
+            Entry::Occupied(e) => {
+                if *e.get() != ty {
+                    bail!(
+                        "profile provides function `{}` from module `{}` with conflicting types",
+                        field,
+                        module
+                    );
+                }
+            }
+            Entry::Vacant(e) => {
+                e.insert(ty);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Determines if the profile provides anything from the given module.
+    ///
+    /// Imports from such a module must be satisfied by the profile.
+    pub fn provides_module(&self, module: &str) -> bool {
+        self.modules.contains_key(module)
+    }
+
+    /// Gets the type of the given function provided by the profile.
+    pub fn function(&self, module: &str, field: &str) -> Option<&FuncType> {
+        self.modules.get(module)?.get(field)
     }
 
     /// Determines if the profile provides the given import.
-    pub fn provides(&self, module: &str, _field: Option<&str>, _ty: &FuncType) -> bool {
-        // TODO: provide some actual implementation for this
-        module == "wasi_snapshot_preview1"
+    pub fn provides(&self, module: &str, field: Option<&str>, ty: &FuncType) -> bool {
+        field
+            .and_then(|field| self.function(module, field))
+            .map(|expected| expected == ty)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmparser::Type;
+
+    #[test]
+    fn it_reads_a_profile_module() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"(module (import "env" "a" (func (param i32) (result i64))) (import "env" "b" (func)))"#,
+        )?;
+        let profile = Profile::from_module(&bytes)?;
+
+        assert!(profile.provides_module("env"));
+        assert!(!profile.provides_module("other"));
+        assert!(profile.provides(
+            "env",
+            Some("a"),
+            &FuncType {
+                params: Box::new([Type::I32]),
+                returns: Box::new([Type::I64]),
+            }
+        ));
+        assert!(!profile.provides(
+            "env",
+            Some("b"),
+            &FuncType {
+                params: Box::new([Type::I32]),
+                returns: Box::new([]),
+            }
+        ));
+        assert!(profile.function("env", "c").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_on_non_function_profile_imports() -> Result<()> {
+        let bytes = wat::parse_str(r#"(module (import "env" "memory" (memory 1)))"#)?;
+
+        assert_eq!(
+            Profile::from_module(&bytes).unwrap_err().to_string(),
+            "profile module imports memory `memory` from module `env` but only function imports are supported"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reads_a_profile_interface() -> Result<()> {
+        let interface =
+            WitInterface::parse("host", "log: function(msg: string)\nnow: function() -> u64")?;
+
+        let mut profile = Profile::new();
+        profile.add_interface("host", &interface)?;
+
+        assert_eq!(
+            profile.function("host", "log"),
+            Some(&FuncType {
+                params: Box::new([Type::I32, Type::I32]),
+                returns: Box::new([]),
+            })
+        );
+        assert_eq!(
+            profile.function("host", "now"),
+            Some(&FuncType {
+                params: Box::new([]),
+                returns: Box::new([Type::I64]),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_on_conflicting_profile_functions() -> Result<()> {
+        let mut profile =
+            Profile::from_module(&wat::parse_str(r#"(module (import "env" "a" (func)))"#)?)?;
+
+        assert_eq!(
+            profile
+                .add_module(&wat::parse_str(
+                    r#"(module (import "env" "a" (func (param i32))))"#
+                )?)
+                .unwrap_err()
+                .to_string(),
+            "profile provides function `a` from module `env` with conflicting types"
+        );
+
+        Ok(())
     }
 }
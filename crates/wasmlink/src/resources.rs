@@ -2,6 +2,7 @@ use crate::{
     adapter::{FUNCTION_TABLE_NAME, RUNTIME_MODULE_NAME},
     linker::to_val_type,
     module::export_kind,
+    names::Names,
     Module,
 };
 use anyhow::{anyhow, bail, Result};
@@ -198,6 +199,13 @@ impl<'a> Resources<'a> {
         }
     }
 
+    /// Iterates the name, identifier, and drop callback of each resource.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32, &str)> {
+        self.resources
+            .iter()
+            .map(|r| (r.inner.name.as_str(), r.id, r.drop_callback.as_str()))
+    }
+
     pub fn exported_count(&self) -> u32 {
         self.resources.iter().fold(0, |v, r| {
             v + r.funcs().filter(|f| f.exported).count() as u32
@@ -256,6 +264,22 @@ impl<'a> Resources<'a> {
             .or_insert(index);
     }
 
+    pub fn write_adapter_import_names(&self, index: &mut u32, names: &mut Names) {
+        if self.resources.is_empty() {
+            return;
+        }
+
+        for name in [
+            RESOURCE_INSERT_FUNC_NAME,
+            RESOURCE_GET_FUNC_NAME,
+            RESOURCE_CLONE_FUNC_NAME,
+            RESOURCE_REMOVE_FUNC_NAME,
+        ] {
+            names.function(*index, format!("{}::{}", RUNTIME_MODULE_NAME, name));
+            *index += 1;
+        }
+    }
+
     pub fn write_adapter_instance_section(
         &self,
         module_index: u32,
@@ -330,6 +354,18 @@ impl<'a> Resources<'a> {
         }
     }
 
+    pub fn write_adapter_alias_names(&self, index: &mut u32, names: &mut Names) {
+        for r in &self.resources {
+            for f in r.funcs() {
+                names.function(*index, format!("{}::{}", self.module.name, f.name));
+                *index += 1;
+            }
+
+            names.function(*index, format!("{}::{}", self.module.name, r.drop_callback));
+            *index += 1;
+        }
+    }
+
     pub fn write_adapter_export_section(
         &self,
         mut start_index: u32,
@@ -350,6 +386,7 @@ impl<'a> Resources<'a> {
 
     pub fn write_adapter_element_section(
         &self,
+        table_index: u32,
         mut start_index: u32,
         section: &mut wasm_encoder::ElementSection,
     ) {
@@ -367,13 +404,14 @@ impl<'a> Resources<'a> {
         }
 
         section.active(
-            Some(0),
+            Some(table_index),
             wasm_encoder::Instruction::I32Const(0),
             wasm_encoder::ValType::FuncRef,
             wasm_encoder::Elements::Expressions(&elements),
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn write_shim_sections(
         &self,
         type_map: &mut HashMap<&FuncType, u32>,
@@ -382,6 +420,7 @@ impl<'a> Resources<'a> {
         functions: &mut wasm_encoder::FunctionSection,
         exports: &mut wasm_encoder::ExportSection,
         code: &mut wasm_encoder::CodeSection,
+        names: &mut Names,
     ) {
         if self.resources.is_empty() {
             return;
@@ -421,6 +460,10 @@ impl<'a> Resources<'a> {
                 func.instruction(wasm_encoder::Instruction::End);
 
                 code.function(&func);
+                names.function(
+                    start_index,
+                    format!("{}-shim::{}", self.module.name, f.name),
+                );
 
                 start_index += 1;
             }
@@ -448,8 +491,7 @@ impl<'a> Resources<'a> {
         self.write_table_section(&mut module);
         self.write_export_section(&mut module);
         self.write_code_section(&mut module);
-
-        // TODO: write a names section for the module?
+        self.write_names_section(&mut module);
 
         Ok(Some(module))
     }
@@ -568,24 +610,33 @@ impl<'a> Resources<'a> {
         let mut section = wasm_encoder::CodeSection::new();
 
         for (index, r) in self.resources.iter().enumerate() {
+            // Order here matters: the functions must be in the order of `Resource::funcs`
+            section.function(&self.emit_resource_new(r.id));
+            section.function(&self.emit_resource_get(r.id));
+            section.function(&self.emit_resource_clone(r.id));
+            section.function(&self.emit_resource_drop(r.id, index as u32));
+        }
+
+        module.section(&section);
+    }
+
+    fn write_names_section(&self, module: &mut wasm_encoder::Module) {
+        let mut names = Names::new(format!("{}-resources", self.module.name));
+        let mut index = 0;
+
+        for (module, name, _) in &self.imports {
+            names.function(index, format!("{}::{}", module, name.unwrap_or("")));
+            index += 1;
+        }
+
+        for r in &self.resources {
             for f in r.funcs() {
-                let func = if std::ptr::eq(f, &r.new) {
-                    self.emit_resource_new(r.id)
-                } else if std::ptr::eq(f, &r.get) {
-                    self.emit_resource_get(r.id)
-                } else if std::ptr::eq(f, &r.clone) {
-                    self.emit_resource_clone(r.id)
-                } else if std::ptr::eq(f, &r.drop) {
-                    self.emit_resource_drop(r.id, index as u32)
-                } else {
-                    unimplemented!()
-                };
-
-                section.function(&func);
+                names.function(index, format!("{}::{}", self.module.name, f.name));
+                index += 1;
             }
         }
 
-        module.section(&section);
+        names.write(module);
     }
 
     fn emit_resource_new(&self, id: u32) -> wasm_encoder::Function {
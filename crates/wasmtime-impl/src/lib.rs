@@ -2,7 +2,7 @@ use proc_macro::TokenStream;
 use syn::parse::{Error, Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
 use syn::{token, Token};
-use wit_bindgen_gen_core::{wit_parser::Interface, Direction, Files, Generator};
+use wit_bindgen_gen_core::{wit_parser::Interface, AbiExport, Direction, Files, Generator};
 use wit_bindgen_gen_wasmtime::Async;
 
 /// Generate code to support consuming the given interfaces, importaing them
@@ -61,6 +61,14 @@ mod kw {
     syn::custom_keyword!(src);
     syn::custom_keyword!(paths);
     syn::custom_keyword!(custom_error);
+    syn::custom_keyword!(trait_only);
+    syn::custom_keyword!(trait_module);
+    syn::custom_keyword!(limits);
+    syn::custom_keyword!(has_data);
+    syn::custom_keyword!(tracing_redact);
+    syn::custom_keyword!(abi_exports);
+    syn::custom_keyword!(memory);
+    syn::custom_keyword!(memory64);
 }
 
 impl Parse for Opts {
@@ -74,14 +82,34 @@ impl Parse for Opts {
             let content;
             syn::braced!(content in input);
             let mut interfaces = Vec::new();
+            let mut memory = 0;
+            let mut memory64 = false;
             let fields = Punctuated::<ConfigField, Token![,]>::parse_terminated(&content)?;
             for field in fields.into_pairs() {
                 match field.into_value() {
                     ConfigField::Interfaces(v) => interfaces = v,
                     ConfigField::Async(v) => opts.async_ = v,
                     ConfigField::CustomError(v) => opts.custom_error = v,
+                    ConfigField::TraitOnly(v) => opts.trait_only = v,
+                    ConfigField::TraitModule(v) => opts.trait_module = Some(v),
+                    ConfigField::Limits(v) => opts.limits = v,
+                    ConfigField::HasData(v) => opts.has_data = v,
+                    ConfigField::TracingRedact(v) => opts.tracing_redact = v,
+                    ConfigField::AbiExports(v) => opts.abi_exports = v,
+                    ConfigField::Memory(v) => memory = v,
+                    ConfigField::Memory64(v) => memory64 = v,
                 }
             }
+            for iface in interfaces.iter_mut() {
+                iface.memory = memory;
+                iface.memory64 = memory64;
+            }
+            if opts.trait_only && opts.trait_module.is_some() {
+                return Err(Error::new(
+                    call_site,
+                    "cannot specify both `trait_only` and `trait_module`",
+                ));
+            }
             if interfaces.is_empty() {
                 return Err(Error::new(
                     call_site,
@@ -113,6 +141,14 @@ enum ConfigField {
     Interfaces(Vec<Interface>),
     Async(wit_bindgen_gen_wasmtime::Async),
     CustomError(bool),
+    TraitOnly(bool),
+    TraitModule(String),
+    Limits(bool),
+    HasData(bool),
+    TracingRedact(Vec<String>),
+    AbiExports(Vec<AbiExport>),
+    Memory(u32),
+    Memory64(bool),
 }
 
 impl Parse for ConfigField {
@@ -166,6 +202,54 @@ impl Parse for ConfigField {
             Ok(ConfigField::CustomError(
                 input.parse::<syn::LitBool>()?.value,
             ))
+        } else if l.peek(kw::trait_only) {
+            input.parse::<kw::trait_only>()?;
+            input.parse::<Token![:]>()?;
+            Ok(ConfigField::TraitOnly(input.parse::<syn::LitBool>()?.value))
+        } else if l.peek(kw::trait_module) {
+            input.parse::<kw::trait_module>()?;
+            input.parse::<Token![:]>()?;
+            Ok(ConfigField::TraitModule(
+                input.parse::<syn::LitStr>()?.value(),
+            ))
+        } else if l.peek(kw::limits) {
+            input.parse::<kw::limits>()?;
+            input.parse::<Token![:]>()?;
+            Ok(ConfigField::Limits(input.parse::<syn::LitBool>()?.value))
+        } else if l.peek(kw::has_data) {
+            input.parse::<kw::has_data>()?;
+            input.parse::<Token![:]>()?;
+            Ok(ConfigField::HasData(input.parse::<syn::LitBool>()?.value))
+        } else if l.peek(kw::tracing_redact) {
+            input.parse::<kw::tracing_redact>()?;
+            input.parse::<Token![:]>()?;
+            let names;
+            syn::bracketed!(names in input);
+            let names = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&names)?;
+            Ok(ConfigField::TracingRedact(
+                names.iter().map(|s| s.value()).collect(),
+            ))
+        } else if l.peek(kw::abi_exports) {
+            input.parse::<kw::abi_exports>()?;
+            input.parse::<Token![:]>()?;
+            let exports;
+            syn::bracketed!(exports in input);
+            let exports = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&exports)?;
+            let exports = exports
+                .iter()
+                .map(|s| s.value().parse().map_err(|e| Error::new(s.span(), e)))
+                .collect::<Result<_>>()?;
+            Ok(ConfigField::AbiExports(exports))
+        } else if l.peek(kw::memory64) {
+            input.parse::<kw::memory64>()?;
+            input.parse::<Token![:]>()?;
+            Ok(ConfigField::Memory64(input.parse::<syn::LitBool>()?.value))
+        } else if l.peek(kw::memory) {
+            input.parse::<kw::memory>()?;
+            input.parse::<Token![:]>()?;
+            Ok(ConfigField::Memory(
+                input.parse::<syn::LitInt>()?.base10_parse()?,
+            ))
         } else {
             Err(l.error())
         }
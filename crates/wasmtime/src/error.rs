@@ -1,12 +1,25 @@
 use crate::Region;
+#[cfg(feature = "wasmtime")]
+use std::{error::Error, fmt};
 use thiserror::Error;
+#[cfg(feature = "wasmtime")]
+use wasmtime::Trap;
 
+/// An error caused by invalid data or pointers passed from wasm.
+///
+/// Generated bindings trap with this error, usually wrapped in
+/// [`GuestError::InFunc`] to describe where the invalid value was found. Use
+/// [`GuestError::from_trap`] to recover it from the resulting trap.
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum GuestError {
-    #[error("Invalid flag value {0}")]
-    InvalidFlagValue(&'static str),
-    #[error("Invalid enum value {0}")]
-    InvalidEnumValue(&'static str),
+    #[error("Invalid flag value {bits:#x} for `{name}`")]
+    InvalidFlagValue { name: &'static str, bits: i64 },
+    #[error("Invalid enum value {value} for `{name}`")]
+    InvalidEnumValue { name: &'static str, value: i64 },
+    #[error("Invalid char value {0:#x}")]
+    InvalidChar(u32),
+    #[error("Int value {value} out of range for `{ty}`")]
+    InvalidInt { ty: &'static str, value: i64 },
     #[error("Pointer overflow")]
     PtrOverflow,
     #[error("Pointer out of bounds: {0:?}")]
@@ -19,14 +32,14 @@ pub enum GuestError {
     BorrowCheckerOutOfHandles,
     #[error("Slice length mismatch")]
     SliceLengthsDiffer,
-    #[error("In func {funcname}:{location}:")]
+    #[error("In func {funcname}:{location}: {err}")]
     InFunc {
         funcname: &'static str,
         location: &'static str,
         #[source]
         err: Box<GuestError>,
     },
-    #[error("In data {typename}.{field}:")]
+    #[error("In data {typename}.{field}: {err}")]
     InDataField {
         typename: String,
         field: String,
@@ -38,3 +51,84 @@ pub enum GuestError {
     #[error("Int conversion error: {0:?}")]
     TryFromIntError(#[from] ::std::num::TryFromIntError),
 }
+
+impl GuestError {
+    /// Wraps this error with the function and the parameter or field path
+    /// (for example `points[].x`) where it occurred.
+    pub fn in_func(self, funcname: &'static str, location: &'static str) -> GuestError {
+        GuestError::InFunc {
+            funcname,
+            location,
+            err: Box::new(self),
+        }
+    }
+
+    /// Returns the `GuestError` that caused `trap`, if any.
+    #[cfg(feature = "wasmtime")]
+    pub fn from_trap(trap: &Trap) -> Option<&GuestError> {
+        from_trap(trap)
+    }
+}
+
+/// An error caused by wasm exceeding the [`Limits`](crate::Limits) of a call.
+///
+/// Use [`LimitError::from_trap`] to recover it from the trap returned by
+/// generated exports.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LimitError {
+    #[error("Call ran out of its {0} units of fuel")]
+    OutOfFuel(u64),
+    #[error("Allocation of {size} bytes exceeds the limit of {limit} bytes")]
+    AllocTooLarge { size: usize, limit: usize },
+}
+
+impl LimitError {
+    /// Returns the `LimitError` that caused `trap`, if any.
+    #[cfg(feature = "wasmtime")]
+    pub fn from_trap(trap: &Trap) -> Option<&LimitError> {
+        from_trap(trap)
+    }
+}
+
+#[cfg(feature = "wasmtime")]
+fn from_trap<E: Error + 'static>(trap: &Trap) -> Option<&E> {
+    let err = trap.source()?;
+    err.downcast_ref::<TrapSource<E>>()
+        .map(|t| &t.0)
+        .or_else(|| err.downcast_ref::<E>())
+}
+
+// Traps only expose the source of the error they're created from, so errors
+// are wrapped to make sure `from_trap` finds the whole error rather than just
+// its innermost cause.
+#[cfg(feature = "wasmtime")]
+#[derive(Debug)]
+struct TrapSource<E>(E);
+
+#[cfg(feature = "wasmtime")]
+impl<E: fmt::Display> fmt::Display for TrapSource<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "wasmtime")]
+impl<E: Error + 'static> Error for TrapSource<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+#[cfg(feature = "wasmtime")]
+impl From<GuestError> for Trap {
+    fn from(err: GuestError) -> Trap {
+        Trap::from(Box::new(TrapSource(err)) as Box<dyn Error + Send + Sync>)
+    }
+}
+
+#[cfg(feature = "wasmtime")]
+impl From<LimitError> for Trap {
+    fn from(err: LimitError) -> Trap {
+        Trap::from(Box::new(TrapSource(err)) as Box<dyn Error + Send + Sync>)
+    }
+}
@@ -1,4 +1,6 @@
 use crate::slab::Slab;
+use crate::{Endian, Le};
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::mem;
@@ -22,7 +24,9 @@ struct Buffer<T> {
 }
 
 enum Input {
-    Bytes(*const u8, usize),
+    // The pointer to the values, their size, and the conversion of their
+    // bytes to little-endian.
+    Bytes(*const u8, usize, fn(&mut [u8])),
     General {
         shim: unsafe fn([usize; 2], *const u8, &Memory, i32, u32, &mut u32) -> Result<(), Trap>,
         iterator: [usize; 2],
@@ -31,7 +35,9 @@ enum Input {
 }
 
 enum Output {
-    Bytes(*mut u8, usize),
+    // The pointer to the values, their size, and the conversion of their
+    // bytes from little-endian.
+    Bytes(*mut u8, usize, fn(&mut [u8])),
     General {
         shim: unsafe fn(*mut u8, *const u8, &Memory, i32, u32) -> Result<(), Trap>,
         dst: *mut u8,
@@ -77,14 +83,14 @@ impl BufferGlue {
         }
         unsafe {
             match &mut b.kind {
-                Input::Bytes(ptr, elem_size) => {
+                Input::Bytes(ptr, elem_size, into_le) => {
                     let write_size = (len as usize) * *elem_size;
+                    let mut bytes = Cow::Borrowed(std::slice::from_raw_parts(*ptr, write_size));
+                    if cfg!(target_endian = "big") {
+                        into_le(bytes.to_mut());
+                    }
                     memory
-                        .write(
-                            store,
-                            base as usize,
-                            std::slice::from_raw_parts(*ptr, write_size),
-                        )
+                        .write(store, base as usize, &bytes)
                         .map_err(|_| Trap::new("out-of-bounds write while reading in-buffer"))?;
                     *ptr = (*ptr).add(write_size);
                     b.len -= len;
@@ -147,15 +153,13 @@ impl BufferGlue {
         }
         unsafe {
             match &mut b.kind {
-                Output::Bytes(ptr, elem_size) => {
+                Output::Bytes(ptr, elem_size, from_le) => {
                     let read_size = (len as usize) * *elem_size;
+                    let bytes = std::slice::from_raw_parts_mut(*ptr, read_size);
                     memory
-                        .read(
-                            &store,
-                            base as usize,
-                            std::slice::from_raw_parts_mut(*ptr, read_size),
-                        )
+                        .read(&store, base as usize, bytes)
                         .map_err(|_| Trap::new("out-of-bounds read while writing to out-buffer"))?;
+                    from_le(bytes);
                     *ptr = (*ptr).add(read_size);
                     b.len -= len;
                     Ok(())
@@ -182,12 +186,17 @@ pub struct BufferTransaction<'a> {
 impl<'call> BufferTransaction<'call> {
     pub unsafe fn push_in_raw<'a, T>(&mut self, buffer: &'a [T]) -> i32
     where
+        T: Endian,
         'a: 'call,
     {
         let mut inner = self.glue.inner.borrow_mut();
         let handle = inner.in_buffers.insert(Buffer {
             len: u32::try_from(buffer.len()).unwrap(),
-            kind: Input::Bytes(buffer.as_ptr() as *const u8, mem::size_of::<T>()),
+            kind: Input::Bytes(
+                buffer.as_ptr() as *const u8,
+                mem::size_of::<T>(),
+                Le::<T>::bytes_into_le,
+            ),
         });
         self.handles.push((false, handle));
         return handle as i32;
@@ -238,12 +247,17 @@ impl<'call> BufferTransaction<'call> {
 
     pub unsafe fn push_out_raw<'a, T>(&mut self, buffer: &'a mut [T]) -> i32
     where
+        T: Endian,
         'a: 'call,
     {
         let mut inner = self.glue.inner.borrow_mut();
         let handle = inner.out_buffers.insert(Buffer {
             len: u32::try_from(buffer.len()).unwrap(),
-            kind: Output::Bytes(buffer.as_mut_ptr() as *mut u8, mem::size_of::<T>()),
+            kind: Output::Bytes(
+                buffer.as_mut_ptr() as *mut u8,
+                mem::size_of::<T>(),
+                Le::<T>::bytes_from_le,
+            ),
         });
         self.handles.push((true, handle));
         return handle as i32;
@@ -64,6 +64,26 @@ where
         }
     }
 
+    /// Converts `bytes`, holding values of `T` in the host's byte order, to
+    /// little-endian in place.
+    pub(crate) fn bytes_into_le(bytes: &mut [u8]) {
+        if cfg!(target_endian = "big") {
+            for slot in Le::<T>::from_slice_mut(bytes) {
+                slot.0 = { slot.0 }.into_le();
+            }
+        }
+    }
+
+    /// Converts `bytes`, holding little-endian values of `T`, to the host's
+    /// byte order in place.
+    pub(crate) fn bytes_from_le(bytes: &mut [u8]) {
+        if cfg!(target_endian = "big") {
+            for slot in Le::<T>::from_slice_mut(bytes) {
+                slot.0 = { slot.0 }.from_le();
+            }
+        }
+    }
+
     pub(crate) fn from_slice_mut(bytes: &mut [u8]) -> &mut [Le<T>] {
         // SAFETY: see `from_slice` above
         //
@@ -192,3 +212,34 @@ tuples! {
     (T1 T2 T3 T4 T5 T6 T7 T8 T9)
     (T1 T2 T3 T4 T5 T6 T7 T8 T9 T10)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn little_endian_layout() {
+        let mut bytes = [0; 8];
+        Le::from_slice_mut(&mut bytes)[0].set(0x01020304u32);
+        assert_eq!(bytes[..4], [4, 3, 2, 1]);
+        assert_eq!(Le::<u32>::from_slice(&bytes)[0].get(), 0x01020304);
+
+        Le::from_slice_mut(&mut bytes)[0].set(-2.5f64);
+        assert_eq!(bytes, (-2.5f64).to_le_bytes());
+        assert_eq!(Le::<f64>::from_slice(&bytes)[0].get(), -2.5);
+
+        Le::from_slice_mut(&mut bytes[1..3])[0].set(0x0102u16);
+        assert_eq!(bytes[1..3], [2, 1]);
+        assert_eq!(Le::<u16>::from_slice(&bytes[1..3])[0].get(), 0x0102);
+    }
+
+    #[test]
+    fn convert_bytes() {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&0x01020304u32.to_ne_bytes());
+        Le::<u32>::bytes_into_le(&mut bytes);
+        assert_eq!(bytes, [4, 3, 2, 1]);
+        Le::<u32>::bytes_from_le(&mut bytes);
+        assert_eq!(bytes, 0x01020304u32.to_ne_bytes());
+    }
+}
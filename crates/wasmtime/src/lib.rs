@@ -4,20 +4,30 @@ pub use wit_bindgen_wasmtime_impl::{export, import};
 pub use async_trait::async_trait;
 #[cfg(feature = "tracing-lib")]
 pub use tracing_lib as tracing;
+#[cfg(feature = "wasmtime")]
 #[doc(hidden)]
-pub use {anyhow, bitflags, wasmtime};
+pub use wasmtime;
+#[doc(hidden)]
+pub use {anyhow, bitflags};
 
 mod error;
+#[cfg(feature = "wasmtime")]
 pub mod exports;
+#[cfg(feature = "wasmtime")]
 pub mod imports;
 mod le;
+#[cfg(feature = "wasmtime")]
+mod limits;
 mod region;
+#[cfg(feature = "wasmtime")]
 mod slab;
 mod table;
 
-pub use error::GuestError;
+pub use error::{GuestError, LimitError};
 pub use le::{Endian, Le};
-pub use region::{AllBytesValid, BorrowChecker, Region};
+#[cfg(feature = "wasmtime")]
+pub use limits::Limits;
+pub use region::{AllBytesValid, BorrowChecker, Pointer, Region};
 pub use table::*;
 
 pub struct RawMemory {
@@ -32,73 +42,106 @@ unsafe impl Sync for RawMemory {}
 
 #[doc(hidden)]
 pub mod rt {
+    #[cfg(feature = "wasmtime")]
     use crate::slab::Slab;
-    use crate::{Endian, Le};
+    use crate::{Endian, GuestError, Le, Pointer, Region};
+    use std::convert::TryFrom;
     use std::mem;
+    #[cfg(feature = "wasmtime")]
     use wasmtime::*;
 
     pub trait RawMem {
-        fn store<T: Endian>(&mut self, offset: i32, val: T) -> Result<(), Trap>;
-        fn store_many<T: Endian>(&mut self, offset: i32, vals: &[T]) -> Result<(), Trap>;
-        fn load<T: Endian>(&self, offset: i32) -> Result<T, Trap>;
+        fn store<T: Endian>(&mut self, offset: impl Pointer, val: T) -> Result<(), GuestError>;
+        fn store_many<T: Endian>(
+            &mut self,
+            offset: impl Pointer,
+            vals: &[T],
+        ) -> Result<(), GuestError>;
+        fn load<T: Endian>(&self, offset: impl Pointer) -> Result<T, GuestError>;
+    }
+
+    fn out_of_bounds(offset: u64, len: usize) -> GuestError {
+        GuestError::PtrOutOfBounds(Region {
+            start: offset,
+            len: len as u64,
+        })
     }
 
     impl RawMem for [u8] {
-        fn store<T: Endian>(&mut self, offset: i32, val: T) -> Result<(), Trap> {
-            let mem = self
-                .get_mut(offset as usize..)
+        fn store<T: Endian>(&mut self, offset: impl Pointer, val: T) -> Result<(), GuestError> {
+            let offset = offset.to_u64();
+            let mem = usize::try_from(offset)
+                .ok()
+                .and_then(|o| self.get_mut(o..))
                 .and_then(|m| m.get_mut(..mem::size_of::<T>()))
-                .ok_or_else(|| Trap::new("out of bounds write"))?;
+                .ok_or_else(|| out_of_bounds(offset, mem::size_of::<T>()))?;
             Le::from_slice_mut(mem)[0].set(val);
             Ok(())
         }
 
-        fn store_many<T: Endian>(&mut self, offset: i32, val: &[T]) -> Result<(), Trap> {
-            let mem = self
-                .get_mut(offset as usize..)
-                .and_then(|m| {
-                    let len = mem::size_of::<T>().checked_mul(val.len())?;
-                    m.get_mut(..len)
-                })
-                .ok_or_else(|| Trap::new("out of bounds write"))?;
+        fn store_many<T: Endian>(
+            &mut self,
+            offset: impl Pointer,
+            val: &[T],
+        ) -> Result<(), GuestError> {
+            let offset = offset.to_u64();
+            let len = mem::size_of::<T>()
+                .checked_mul(val.len())
+                .ok_or(GuestError::PtrOverflow)?;
+            let mem = usize::try_from(offset)
+                .ok()
+                .and_then(|o| self.get_mut(o..))
+                .and_then(|m| m.get_mut(..len))
+                .ok_or_else(|| out_of_bounds(offset, len))?;
             for (slot, val) in Le::from_slice_mut(mem).iter_mut().zip(val) {
                 slot.set(*val);
             }
             Ok(())
         }
 
-        fn load<T: Endian>(&self, offset: i32) -> Result<T, Trap> {
-            let mem = self
-                .get(offset as usize..)
+        fn load<T: Endian>(&self, offset: impl Pointer) -> Result<T, GuestError> {
+            let offset = offset.to_u64();
+            let mem = usize::try_from(offset)
+                .ok()
+                .and_then(|o| self.get(o..))
                 .and_then(|m| m.get(..mem::size_of::<Le<T>>()))
-                .ok_or_else(|| Trap::new("out of bounds read"))?;
+                .ok_or_else(|| out_of_bounds(offset, mem::size_of::<Le<T>>()))?;
             Ok(Le::from_slice(mem)[0].get())
         }
     }
 
-    pub fn char_from_i32(val: i32) -> Result<char, Trap> {
-        core::char::from_u32(val as u32).ok_or_else(|| Trap::new("char value out of valid range"))
+    pub fn char_from_i32(val: i32) -> Result<char, GuestError> {
+        core::char::from_u32(val as u32).ok_or(GuestError::InvalidChar(val as u32))
+    }
+
+    pub fn int_from_i32<T: TryFrom<i32>>(val: i32) -> Result<T, GuestError> {
+        T::try_from(val).map_err(|_| GuestError::InvalidInt {
+            ty: std::any::type_name::<T>(),
+            value: val.into(),
+        })
     }
 
-    pub fn invalid_variant(name: &str) -> Trap {
-        let msg = format!("invalid discriminant for `{}`", name);
-        Trap::new(msg)
+    pub fn invalid_variant(name: &'static str, discriminant: i64) -> GuestError {
+        GuestError::InvalidEnumValue {
+            name,
+            value: discriminant,
+        }
     }
 
     pub fn validate_flags<U>(
         bits: i64,
         all: i64,
-        name: &str,
+        name: &'static str,
         mk: impl FnOnce(i64) -> U,
-    ) -> Result<U, Trap> {
+    ) -> Result<U, GuestError> {
         if bits & !all != 0 {
-            let msg = format!("invalid flags specified for `{}`", name);
-            Err(Trap::new(msg))
+            Err(GuestError::InvalidFlagValue { name, bits })
         } else {
             Ok(mk(bits))
         }
     }
 
+    #[cfg(feature = "wasmtime")]
     pub fn get_func<T>(caller: &mut Caller<'_, T>, func: &str) -> Result<Func, wasmtime::Trap> {
         let func = caller
             .get_export(func)
@@ -114,6 +157,7 @@ pub mod rt {
         Ok(func)
     }
 
+    #[cfg(feature = "wasmtime")]
     pub fn get_memory<T>(caller: &mut Caller<'_, T>, mem: &str) -> Result<Memory, wasmtime::Trap> {
         let mem = caller
             .get_export(mem)
@@ -129,30 +173,22 @@ pub mod rt {
         Ok(mem)
     }
 
-    pub fn bad_int(_: std::num::TryFromIntError) -> Trap {
-        let msg = "out-of-bounds integer conversion";
-        Trap::new(msg)
-    }
-
     pub fn copy_slice<T: Endian>(
-        store: impl AsContextMut,
-        memory: &Memory,
-        free: &TypedFunc<(i32, i32, i32), ()>,
-        base: i32,
-        len: i32,
-        align: i32,
-    ) -> Result<Vec<T>, Trap> {
-        let size = (len as u32)
-            .checked_mul(mem::size_of::<T>() as u32)
-            .ok_or_else(|| Trap::new("array too large to fit in wasm memory"))?;
-        let slice = memory
-            .data(&store)
-            .get(base as usize..)
-            .and_then(|s| s.get(..size as usize))
-            .ok_or_else(|| Trap::new("out of bounds read"))?;
-        let result = Le::from_slice(slice).iter().map(|s| s.get()).collect();
-        free.call(store, (base, size as i32, align))?;
-        Ok(result)
+        memory: &[u8],
+        base: impl Pointer,
+        len: impl Pointer,
+    ) -> Result<Vec<T>, GuestError> {
+        let base = base.to_u64();
+        let size = len
+            .to_u64()
+            .checked_mul(mem::size_of::<T>() as u64)
+            .ok_or(GuestError::PtrOverflow)?;
+        let slice = usize::try_from(base)
+            .ok()
+            .zip(usize::try_from(size).ok())
+            .and_then(|(base, size)| memory.get(base..)?.get(..size))
+            .ok_or_else(|| out_of_bounds(base, size as usize))?;
+        Ok(Le::from_slice(slice).iter().map(|s| s.get()).collect())
     }
 
     macro_rules! as_traits {
@@ -189,11 +225,13 @@ pub mod rt {
         (as_f64 AsF64 f64 (f64))
     }
 
+    #[cfg(feature = "wasmtime")]
     #[derive(Default, Debug)]
     pub struct IndexSlab {
         slab: Slab<ResourceIndex>,
     }
 
+    #[cfg(feature = "wasmtime")]
     impl IndexSlab {
         pub fn insert(&mut self, resource: ResourceIndex) -> u32 {
             self.slab.insert(resource)
@@ -214,11 +252,13 @@ pub mod rt {
         }
     }
 
+    #[cfg(feature = "wasmtime")]
     #[derive(Default, Debug)]
     pub struct ResourceSlab {
         slab: Slab<Resource>,
     }
 
+    #[cfg(feature = "wasmtime")]
     #[derive(Debug)]
     struct Resource {
         wasm: i32,
@@ -226,8 +266,10 @@ pub mod rt {
     }
 
     #[derive(Debug, Copy, Clone)]
+    #[cfg_attr(not(feature = "wasmtime"), allow(dead_code))]
     pub struct ResourceIndex(u32);
 
+    #[cfg(feature = "wasmtime")]
     impl ResourceSlab {
         pub fn insert(&mut self, wasm: i32) -> ResourceIndex {
             ResourceIndex(self.slab.insert(Resource { wasm, refcnt: 1 }))
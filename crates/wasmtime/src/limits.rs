@@ -0,0 +1,165 @@
+use crate::LimitError;
+use wasmtime::{AsContextMut, Trap};
+
+/// Limits enforced on each call into wasm by exports generated with the
+/// `limits` option.
+///
+/// Limits are disabled by default, and exceeding them results in a trap
+/// carrying a [`LimitError`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Amount of fuel each call may consume.
+    ///
+    /// The store's fuel is set to this amount at the start of every call, so
+    /// fuel consumption must be enabled with `Config::consume_fuel`.
+    pub fuel: Option<u64>,
+
+    /// Maximum size in bytes of a single list or string allocated in wasm
+    /// through `canonical_abi_realloc` when passing it to a call.
+    pub max_alloc: Option<usize>,
+}
+
+impl Limits {
+    /// Prepares `store` for a call, setting its fuel to the fuel limit.
+    pub fn begin_call(&self, mut store: impl AsContextMut) -> Result<(), Trap> {
+        let fuel = match self.fuel {
+            Some(fuel) => fuel,
+            None => return Ok(()),
+        };
+        let mut store = store.as_context_mut();
+        // A call which ran out of fuel may have used more than it had, so top
+        // the store up until its remaining fuel can be read again.
+        let remaining = loop {
+            match store.consume_fuel(0) {
+                Ok(remaining) => break remaining,
+                Err(_) => store.add_fuel(fuel.max(1))?,
+            }
+        };
+        if remaining < fuel {
+            store.add_fuel(fuel - remaining)?;
+        } else {
+            store.consume_fuel(remaining - fuel)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the error for `trap`, raised by a call into wasm, replacing it
+    /// with a [`LimitError`] if the call ran out of fuel.
+    pub fn call_error(&self, mut store: impl AsContextMut, trap: Trap) -> Trap {
+        let fuel = match self.fuel {
+            Some(fuel) => fuel,
+            None => return trap,
+        };
+        // Wasm may overshoot its fuel before trapping, in which case there's
+        // none left to consume at all.
+        match store.as_context_mut().consume_fuel(0) {
+            Ok(remaining) if remaining > 0 => trap,
+            _ => LimitError::OutOfFuel(fuel).into(),
+        }
+    }
+
+    /// Checks that allocating `size` bytes in wasm is within the limits.
+    pub fn check_alloc(&self, size: usize) -> Result<(), LimitError> {
+        match self.max_alloc {
+            Some(limit) if size > limit => Err(LimitError::AllocTooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime::{Config, Engine, Instance, Module, Store};
+
+    fn store() -> Store<()> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Store::new(&Engine::new(&config).unwrap(), ())
+    }
+
+    #[test]
+    fn begin_call_sets_fuel() {
+        let limits = Limits {
+            fuel: Some(100),
+            ..Limits::default()
+        };
+        let mut store = store();
+        limits.begin_call(&mut store).unwrap();
+        assert_eq!(store.consume_fuel(0).unwrap(), 100);
+
+        store.add_fuel(50).unwrap();
+        limits.begin_call(&mut store).unwrap();
+        assert_eq!(store.consume_fuel(0).unwrap(), 100);
+
+        store.consume_fuel(100).unwrap();
+        limits.begin_call(&mut store).unwrap();
+        assert_eq!(store.consume_fuel(0).unwrap(), 100);
+    }
+
+    #[test]
+    fn call_error() {
+        let limits = Limits {
+            fuel: Some(10),
+            ..Limits::default()
+        };
+        let mut store = store();
+        limits.begin_call(&mut store).unwrap();
+        let trap = limits.call_error(&mut store, Trap::new("unreachable"));
+        assert_eq!(LimitError::from_trap(&trap), None);
+
+        store.consume_fuel(10).unwrap();
+        let trap = limits.call_error(&mut store, Trap::new("all fuel consumed"));
+        assert_eq!(
+            LimitError::from_trap(&trap),
+            Some(&LimitError::OutOfFuel(10))
+        );
+
+        let trap = Limits::default().call_error(&mut store, Trap::new("unreachable"));
+        assert_eq!(LimitError::from_trap(&trap), None);
+    }
+
+    #[test]
+    fn out_of_fuel() {
+        let limits = Limits {
+            fuel: Some(1000),
+            ..Limits::default()
+        };
+        let mut store = store();
+        let module = Module::new(
+            store.engine(),
+            r#"(module (func (export "run") (loop (br 0))))"#,
+        )
+        .unwrap();
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let run = instance
+            .get_typed_func::<(), (), _>(&mut store, "run")
+            .unwrap();
+
+        limits.begin_call(&mut store).unwrap();
+        let trap = run.call(&mut store, ()).unwrap_err();
+        let trap = limits.call_error(&mut store, trap);
+        assert_eq!(
+            LimitError::from_trap(&trap),
+            Some(&LimitError::OutOfFuel(1000))
+        );
+
+        // The next call starts with its full fuel again.
+        limits.begin_call(&mut store).unwrap();
+        assert_eq!(store.consume_fuel(0).unwrap(), 1000);
+    }
+
+    #[test]
+    fn check_alloc() {
+        let limits = Limits {
+            max_alloc: Some(4),
+            ..Limits::default()
+        };
+        assert_eq!(limits.check_alloc(4), Ok(()));
+        assert_eq!(
+            limits.check_alloc(5),
+            Err(LimitError::AllocTooLarge { size: 5, limit: 4 })
+        );
+        assert_eq!(Limits::default().check_alloc(usize::MAX), Ok(()));
+    }
+}
@@ -4,7 +4,6 @@ use std::collections::HashSet;
 use std::convert::TryInto;
 use std::marker;
 use std::mem;
-use wasmtime::Trap;
 
 // This is a pretty naive way to account for borrows. This datastructure
 // could be made a lot more efficient with some effort.
@@ -25,10 +24,6 @@ pub struct BorrowChecker<'a> {
 unsafe impl Send for BorrowChecker<'_> {}
 unsafe impl Sync for BorrowChecker<'_> {}
 
-fn to_trap(err: impl std::error::Error + Send + Sync + 'static) -> Trap {
-    Trap::from(Box::new(err) as Box<dyn std::error::Error + Send + Sync>)
-}
-
 impl<'a> BorrowChecker<'a> {
     pub fn new(data: &'a mut [u8]) -> BorrowChecker<'a> {
         BorrowChecker {
@@ -40,8 +35,12 @@ impl<'a> BorrowChecker<'a> {
         }
     }
 
-    pub fn slice<T: AllBytesValid>(&mut self, ptr: i32, len: i32) -> Result<&'a [T], Trap> {
-        let (ret, r) = self.get_slice(ptr, len)?;
+    pub fn slice<T: AllBytesValid>(
+        &mut self,
+        ptr: impl Pointer,
+        len: impl Pointer,
+    ) -> Result<&'a [T], GuestError> {
+        let (ret, r) = self.get_slice(ptr.to_u64(), len.to_u64())?;
         // SAFETY: We're promoting the valid lifetime of `ret` from a temporary
         // borrow on `self` to `'a` on this `BorrowChecker`. At the same time
         // we're recording that this is a persistent shared borrow (until this
@@ -52,8 +51,12 @@ impl<'a> BorrowChecker<'a> {
         Ok(ret)
     }
 
-    pub fn slice_mut<T: AllBytesValid>(&mut self, ptr: i32, len: i32) -> Result<&'a mut [T], Trap> {
-        let (ret, r) = self.get_slice_mut(ptr, len)?;
+    pub fn slice_mut<T: AllBytesValid>(
+        &mut self,
+        ptr: impl Pointer,
+        len: impl Pointer,
+    ) -> Result<&'a mut [T], GuestError> {
+        let (ret, r) = self.get_slice_mut(ptr.to_u64(), len.to_u64())?;
         // SAFETY: see `slice` for how we're extending the lifetime by
         // recording the borrow here. Note that the `mut_borrows` list is
         // checked on both shared and mutable borrows in the future since a
@@ -63,10 +66,14 @@ impl<'a> BorrowChecker<'a> {
         Ok(ret)
     }
 
-    fn get_slice<T: AllBytesValid>(&self, ptr: i32, len: i32) -> Result<(&[T], Region), Trap> {
+    fn get_slice<T: AllBytesValid>(
+        &self,
+        ptr: u64,
+        len: u64,
+    ) -> Result<(&[T], Region), GuestError> {
         let r = self.region::<T>(ptr, len)?;
         if self.is_mut_borrowed(r) {
-            Err(to_trap(GuestError::PtrBorrowed(r)))
+            Err(GuestError::PtrBorrowed(r))
         } else {
             Ok((
                 // SAFETY: invariants to uphold:
@@ -92,10 +99,10 @@ impl<'a> BorrowChecker<'a> {
         }
     }
 
-    fn get_slice_mut<T>(&mut self, ptr: i32, len: i32) -> Result<(&mut [T], Region), Trap> {
+    fn get_slice_mut<T>(&mut self, ptr: u64, len: u64) -> Result<(&mut [T], Region), GuestError> {
         let r = self.region::<T>(ptr, len)?;
         if self.is_mut_borrowed(r) || self.is_shared_borrowed(r) {
-            Err(to_trap(GuestError::PtrBorrowed(r)))
+            Err(GuestError::PtrBorrowed(r))
         } else {
             Ok((
                 // SAFETY: same as `get_slice`, except for that we're threading
@@ -111,32 +118,36 @@ impl<'a> BorrowChecker<'a> {
         }
     }
 
-    fn region<T>(&self, ptr: i32, len: i32) -> Result<Region, Trap> {
+    fn region<T>(&self, ptr: u64, len: u64) -> Result<Region, GuestError> {
         assert_eq!(std::mem::align_of::<T>(), 1);
         let r = Region {
-            start: ptr as u32,
-            len: (len as u32)
-                .checked_mul(mem::size_of::<T>() as u32)
-                .ok_or_else(|| to_trap(GuestError::PtrOverflow))?,
+            start: ptr,
+            len: len
+                .checked_mul(mem::size_of::<T>() as u64)
+                .ok_or(GuestError::PtrOverflow)?,
         };
         self.validate_contains(&r)?;
         Ok(r)
     }
 
-    pub fn slice_str(&mut self, ptr: i32, len: i32) -> Result<&'a str, Trap> {
+    pub fn slice_str(
+        &mut self,
+        ptr: impl Pointer,
+        len: impl Pointer,
+    ) -> Result<&'a str, GuestError> {
         let bytes = self.slice(ptr, len)?;
-        std::str::from_utf8(bytes).map_err(to_trap)
+        Ok(std::str::from_utf8(bytes)?)
     }
 
-    fn validate_contains(&self, region: &Region) -> Result<(), Trap> {
+    fn validate_contains(&self, region: &Region) -> Result<(), GuestError> {
         let end = region
             .start
             .checked_add(region.len)
-            .ok_or_else(|| to_trap(GuestError::PtrOverflow))? as usize;
-        if end <= self.len {
+            .ok_or(GuestError::PtrOverflow)?;
+        if end <= self.len as u64 {
             Ok(())
         } else {
-            Err(to_trap(GuestError::PtrOutOfBounds(*region)))
+            Err(GuestError::PtrOutOfBounds(*region))
         }
     }
 
@@ -154,18 +165,16 @@ impl<'a> BorrowChecker<'a> {
 }
 
 impl RawMem for BorrowChecker<'_> {
-    fn store<T: Endian>(&mut self, offset: i32, val: T) -> Result<(), Trap> {
-        let (slice, _) = self.get_slice_mut::<Le<T>>(offset, 1)?;
+    fn store<T: Endian>(&mut self, offset: impl Pointer, val: T) -> Result<(), GuestError> {
+        let (slice, _) = self.get_slice_mut::<Le<T>>(offset.to_u64(), 1)?;
         slice[0].set(val);
         Ok(())
     }
 
-    fn store_many<T: Endian>(&mut self, offset: i32, val: &[T]) -> Result<(), Trap> {
+    fn store_many<T: Endian>(&mut self, offset: impl Pointer, val: &[T]) -> Result<(), GuestError> {
         let (slice, _) = self.get_slice_mut::<Le<T>>(
-            offset,
-            val.len()
-                .try_into()
-                .map_err(|_| to_trap(GuestError::PtrOverflow))?,
+            offset.to_u64(),
+            val.len().try_into().map_err(|_| GuestError::PtrOverflow)?,
         )?;
         for (slot, val) in slice.iter_mut().zip(val) {
             slot.set(*val);
@@ -173,12 +182,31 @@ impl RawMem for BorrowChecker<'_> {
         Ok(())
     }
 
-    fn load<T: Endian>(&self, offset: i32) -> Result<T, Trap> {
-        let (slice, _) = self.get_slice::<Le<T>>(offset, 1)?;
+    fn load<T: Endian>(&self, offset: impl Pointer) -> Result<T, GuestError> {
+        let (slice, _) = self.get_slice::<Le<T>>(offset.to_u64(), 1)?;
         Ok(slice[0].get())
     }
 }
 
+/// An address or length in wasm linear memory, which is an `i32` for 32-bit
+/// memories and an `i64` for 64-bit memories.
+pub trait Pointer: Copy {
+    /// Returns this value as an unsigned 64-bit integer.
+    fn to_u64(self) -> u64;
+}
+
+impl Pointer for i32 {
+    fn to_u64(self) -> u64 {
+        self as u32 as u64
+    }
+}
+
+impl Pointer for i64 {
+    fn to_u64(self) -> u64 {
+        self as u64
+    }
+}
+
 /// Unsafe trait representing types where every byte pattern is valid for their
 /// representation.
 ///
@@ -220,8 +248,8 @@ tuples! {
 /// Represents a contiguous region in memory.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Region {
-    pub start: u32,
-    pub len: u32,
+    pub start: u64,
+    pub len: u64,
 }
 
 impl Region {
@@ -232,11 +260,11 @@ impl Region {
             return false;
         }
 
-        let self_start = self.start as u64;
-        let self_end = self_start + (self.len - 1) as u64;
+        let self_start = self.start;
+        let self_end = self_start + (self.len - 1);
 
-        let rhs_start = rhs.start as u64;
-        let rhs_end = rhs_start + (rhs.len - 1) as u64;
+        let rhs_start = rhs.start;
+        let rhs_end = rhs_start + (rhs.len - 1);
 
         if self_start <= rhs_start {
             self_end >= rhs_start
@@ -309,4 +337,18 @@ mod test {
         bc.slice_mut::<u8>(0, 0).unwrap();
         bc.slice::<u8>(0, 1).unwrap();
     }
+
+    #[test]
+    fn pointer64() {
+        let mut bytes = [0; 100];
+        let mut bc = BorrowChecker::new(&mut bytes);
+        bc.slice::<u8>(0i64, 10i64).unwrap();
+        bc.slice_mut::<u8>(5i64, 10i64).unwrap_err();
+        bc.slice::<u8>(5i32, 10i64).unwrap();
+
+        // Addresses past 4GiB don't wrap around to the start of memory.
+        bc.slice::<u8>(1i64 << 32, 1i64).unwrap_err();
+        bc.slice::<u8>(-1i64, 2i64).unwrap_err();
+        bc.slice::<u8>(0i64, -1i64).unwrap_err();
+    }
 }
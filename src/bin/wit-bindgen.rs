@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::path::PathBuf;
 use structopt::StructOpt;
 use wit_bindgen_gen_core::{wit_parser, Files, Generator};
@@ -55,6 +55,13 @@ enum Command {
         #[structopt(flatten)]
         common: Common,
     },
+    #[structopt(name = "cpython")]
+    CPython {
+        #[structopt(flatten)]
+        opts: wit_bindgen_gen_cpython::Opts,
+        #[structopt(flatten)]
+        common: Common,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -72,10 +79,54 @@ struct Common {
     /// specified multiple times.
     #[structopt(long = "export", short)]
     exports: Vec<PathBuf>,
+
+    /// Use the linear memory with the given index for an interface, as in
+    /// `--memory my-interface=1`. Can be specified multiple times. Only host
+    /// generators support this, and the memory's export must also be named
+    /// with `--abi-export my-interface:memory=<name>`: compiled guest code,
+    /// including the SpiderMonkey and CPython glue, can only use memory 0.
+    #[structopt(long = "memory", parse(try_from_str = parse_memory))]
+    memories: Vec<(String, u32)>,
+
+    /// Use 64-bit pointers and lengths for the given interface, whose memory
+    /// is a memory64 memory. Can be specified multiple times.
+    #[structopt(long = "memory64")]
+    memory64: Vec<String>,
+}
+
+fn find_interface<'a>(
+    imports: &'a mut [Interface],
+    exports: &'a mut [Interface],
+    name: &str,
+) -> Result<&'a mut Interface> {
+    imports
+        .iter_mut()
+        .chain(exports.iter_mut())
+        .find(|iface| iface.name == name)
+        .with_context(|| format!("no interface named `{}`", name))
+}
+
+fn parse_memory(s: &str) -> Result<(String, u32)> {
+    let (name, index) = match s.find('=') {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => bail!("expected `interface=index`, found `{}`", s),
+    };
+    let index = index
+        .parse()
+        .with_context(|| format!("invalid memory index `{}`", index))?;
+    Ok((name.to_string(), index))
 }
 
 fn main() -> Result<()> {
     let opt = Opt::from_args();
+    // Guest generators can only address the default linear memory.
+    let guest = matches!(
+        opt.command,
+        Command::RustWasm { .. }
+            | Command::C { .. }
+            | Command::SpiderMonkey { .. }
+            | Command::CPython { .. }
+    );
     let (mut generator, common): (Box<dyn Generator>, _) = match opt.command {
         Command::RustWasm { opts, common } => (Box::new(opts.build()), common),
         Command::Wasmtime { opts, common } => (Box::new(opts.build()), common),
@@ -86,21 +137,62 @@ fn main() -> Result<()> {
         Command::SpiderMonkey { opts, common } => {
             let js_source = std::fs::read_to_string(&opts.js)
                 .with_context(|| format!("failed to read {}", opts.js.display()))?;
-            (Box::new(opts.build(js_source)), common)
+            let smw = match &opts.spidermonkey_wasm {
+                Some(path) => Some(
+                    std::fs::read(path)
+                        .with_context(|| format!("failed to read {}", path.display()))?,
+                ),
+                None => None,
+            };
+            let mut gen = opts.build(js_source);
+            if let Some(smw) = smw {
+                gen.spidermonkey_wasm(smw);
+            }
+            (Box::new(gen), common)
+        }
+        Command::CPython { opts, common } => {
+            let py_source = std::fs::read_to_string(&opts.py)
+                .with_context(|| format!("failed to read {}", opts.py.display()))?;
+            let cpw = match &opts.cpython_wasm {
+                Some(path) => Some(
+                    std::fs::read(path)
+                        .with_context(|| format!("failed to read {}", path.display()))?,
+                ),
+                None => None,
+            };
+            let mut gen = opts.build(py_source);
+            if let Some(cpw) = cpw {
+                gen.cpython_wasm(cpw);
+            }
+            (Box::new(gen), common)
         }
     };
 
-    let imports = common
+    let mut imports = common
         .imports
         .iter()
         .map(|wit| Interface::parse_file(wit))
         .collect::<Result<Vec<_>>>()?;
-    let exports = common
+    let mut exports = common
         .exports
         .iter()
         .map(|wit| Interface::parse_file(wit))
         .collect::<Result<Vec<_>>>()?;
 
+    for (name, index) in common.memories.iter() {
+        if guest && *index != 0 {
+            bail!(
+                "interface `{}` cannot use memory {}: guest bindings only support memory 0",
+                name,
+                index
+            );
+        }
+        find_interface(&mut imports, &mut exports, name)?.memory = *index;
+    }
+    for name in common.memory64.iter() {
+        find_interface(&mut imports, &mut exports, name)?.memory64 = true;
+    }
+
     let mut files = Files::default();
     generator.generate_all(&imports, &exports, &mut files);
 
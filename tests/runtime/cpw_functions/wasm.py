@@ -0,0 +1,66 @@
+import imports
+
+
+def test_imports():
+    #
+    # Testing arguments.
+    #
+
+    imports.f1()
+
+    imports.f2(42)
+
+    # Min and max `u32`.
+    imports.f3(0, 4294967295)
+
+    #
+    # Testing returns.
+    #
+
+    a = imports.f4()
+    assert a == 1337
+
+    a, b = imports.f5()
+    assert a == 1
+    assert b == 2
+
+    a, b, c = imports.f6(100, 200, 300)
+    assert a == 101
+    assert b == 201
+    assert c == 301
+
+
+#
+# Testing arguments.
+#
+
+def f1():
+    pass
+
+
+def f2(x):
+    assert x == 42
+
+
+def f3(a, b):
+    assert a == 0
+    assert b == 4294967295
+
+
+#
+# Testing returns.
+#
+
+def f4():
+    return 1337
+
+
+def f5():
+    return 1, 2
+
+
+def f6(a, b, c):
+    assert a == 100
+    assert b == 200
+    assert c == 300
+    return a + 1, b + 1, c + 1
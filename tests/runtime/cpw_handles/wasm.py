@@ -0,0 +1,120 @@
+from types import SimpleNamespace
+
+import imports
+
+
+def test_imports():
+    s = imports.host_state_create()
+    assert imports.host_state_get(s) == 100
+    imports.drop_host_state(s)
+
+    assert imports.host_state2_saw_close() is False
+    s2 = imports.host_state2_create()
+    assert imports.host_state2_saw_close() is False
+    imports.drop_host_state2(s2)
+    assert imports.host_state2_saw_close() is True
+
+    a = imports.host_state_create()
+    b = imports.host_state2_create()
+    c, d = imports.two_host_states(a, b)
+    assert imports.host_state_get(c) == 2
+    imports.drop_host_state(a)
+    imports.drop_host_state(c)
+    imports.drop_host_state2(b)
+
+    imports.host_state2_param_record(SimpleNamespace(a=d))
+    imports.host_state2_param_tuple((d,))
+    imports.host_state2_param_option(d)
+    imports.host_state2_param_option(None)
+    imports.host_state2_param_result(("ok", d))
+    imports.host_state2_param_result(("err", 2))
+    imports.host_state2_param_variant(("0", d))
+    imports.host_state2_param_variant(("1", 2))
+    imports.host_state2_param_list([])
+    imports.host_state2_param_list([d])
+    imports.host_state2_param_list([d, d])
+    imports.drop_host_state2(d)
+
+    imports.drop_host_state2(imports.host_state2_result_record().a)
+    imports.drop_host_state2(imports.host_state2_result_tuple()[0])
+    imports.drop_host_state2(imports.host_state2_result_option())
+    imports.drop_host_state2(imports.host_state2_result_result()[1])
+    imports.drop_host_state2(imports.host_state2_result_variant()[1])
+    for h in imports.host_state2_result_list():
+        imports.drop_host_state2(h)
+
+
+wasm_state2_closed = False
+
+
+def wasm_state_create():
+    return SimpleNamespace(val=100)
+
+
+def wasm_state_get_val(state):
+    return state.val
+
+
+def wasm_state2_create():
+    return SimpleNamespace(val=33)
+
+
+def wasm_state2_saw_close():
+    return wasm_state2_closed
+
+
+def drop_wasm_state2(state):
+    global wasm_state2_closed
+    wasm_state2_closed = True
+
+
+def two_wasm_states(a, b):
+    return SimpleNamespace(val=101), SimpleNamespace(val=102)
+
+
+def wasm_state2_param_record(a):
+    pass
+
+
+def wasm_state2_param_tuple(a):
+    pass
+
+
+def wasm_state2_param_option(a):
+    pass
+
+
+def wasm_state2_param_result(a):
+    pass
+
+
+def wasm_state2_param_variant(a):
+    pass
+
+
+def wasm_state2_param_list(a):
+    pass
+
+
+def wasm_state2_result_record():
+    return SimpleNamespace(a=SimpleNamespace(val=222))
+
+
+def wasm_state2_result_tuple():
+    return (SimpleNamespace(val=333),)
+
+
+def wasm_state2_result_option():
+    return SimpleNamespace(val=444)
+
+
+def wasm_state2_result_result():
+    return ("ok", SimpleNamespace(val=555))
+
+
+def wasm_state2_result_variant():
+    return ("0", SimpleNamespace(val=666))
+
+
+def wasm_state2_result_list():
+    return [SimpleNamespace(val=777), SimpleNamespace(val=888)]
@@ -0,0 +1,35 @@
+import imports
+
+
+def test_imports():
+    imports.f1([1, 2, 3])
+
+    l = imports.f2()
+    assert l == [1, 2, 3]
+
+    # Any iterable works where a list is expected.
+    a, b = imports.f3((), range(1, 4))
+    assert a == []
+    assert b == [1, 2, 3]
+
+    l2 = imports.f4([[], [1], [2, 3]])
+    assert l2 == [[], [4], [5, 6]]
+
+
+def f1(l):
+    assert l == [1, 2, 3]
+
+
+def f2():
+    return [1, 2, 3]
+
+
+def f3(a, b):
+    assert a == []
+    assert b == [1, 2, 3]
+    return [], [1, 2, 3]
+
+
+def f4(l):
+    assert l == [[], [1], [2, 3]]
+    return [[], [4], [5, 6]]
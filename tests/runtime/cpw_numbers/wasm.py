@@ -0,0 +1,112 @@
+import math
+
+import imports
+
+
+def test_imports():
+    assert imports.roundtrip_u8(1) == 1
+    assert imports.roundtrip_u8(0) == 0
+    assert imports.roundtrip_u8(2**8 - 1) == 2**8 - 1
+
+    assert imports.roundtrip_s8(1) == 1
+    assert imports.roundtrip_s8(-2**7) == -2**7
+    assert imports.roundtrip_s8(2**7 - 1) == 2**7 - 1
+
+    assert imports.roundtrip_u16(1) == 1
+    assert imports.roundtrip_u16(0) == 0
+    assert imports.roundtrip_u16(2**16 - 1) == 2**16 - 1
+
+    assert imports.roundtrip_s16(1) == 1
+    assert imports.roundtrip_s16(-2**15) == -2**15
+    assert imports.roundtrip_s16(2**15 - 1) == 2**15 - 1
+
+    assert imports.roundtrip_u32(1) == 1
+    assert imports.roundtrip_u32(0) == 0
+    assert imports.roundtrip_u32(2**32 - 1) == 2**32 - 1
+
+    assert imports.roundtrip_s32(1) == 1
+    assert imports.roundtrip_s32(-2**31) == -2**31
+    assert imports.roundtrip_s32(2**31 - 1) == 2**31 - 1
+
+    assert imports.roundtrip_u64(1) == 1
+    assert imports.roundtrip_u64(0) == 0
+    assert imports.roundtrip_u64(2**64 - 1) == 2**64 - 1
+
+    assert imports.roundtrip_s64(1) == 1
+    assert imports.roundtrip_s64(-2**63) == -2**63
+    assert imports.roundtrip_s64(2**63 - 1) == 2**63 - 1
+
+    assert imports.roundtrip_f32(1.0) == 1.0
+    assert imports.roundtrip_f32(math.inf) == math.inf
+    assert imports.roundtrip_f32(-math.inf) == -math.inf
+    assert math.isnan(imports.roundtrip_f32(math.nan))
+
+    assert imports.roundtrip_f64(1.0) == 1.0
+    assert imports.roundtrip_f64(math.inf) == math.inf
+    assert imports.roundtrip_f64(-math.inf) == -math.inf
+    assert math.isnan(imports.roundtrip_f64(math.nan))
+
+    assert imports.roundtrip_char("a") == "a"
+    assert imports.roundtrip_char(" ") == " "
+    assert imports.roundtrip_char("🚩") == "🚩"
+
+    imports.set_scalar(2)
+    assert imports.get_scalar() == 2
+    imports.set_scalar(4)
+    assert imports.get_scalar() == 4
+
+
+def roundtrip_u8(a):
+    return a
+
+
+def roundtrip_s8(a):
+    return a
+
+
+def roundtrip_u16(a):
+    return a
+
+
+def roundtrip_s16(a):
+    return a
+
+
+def roundtrip_u32(a):
+    return a
+
+
+def roundtrip_s32(a):
+    return a
+
+
+def roundtrip_u64(a):
+    return a
+
+
+def roundtrip_s64(a):
+    return a
+
+
+def roundtrip_f32(a):
+    return a
+
+
+def roundtrip_f64(a):
+    return a
+
+
+def roundtrip_char(a):
+    return a
+
+
+scalar = 0
+
+
+def set_scalar(a):
+    global scalar
+    scalar = a
+
+
+def get_scalar():
+    return scalar
@@ -0,0 +1,73 @@
+from types import SimpleNamespace
+
+import imports
+
+
+def test_imports():
+    a, b = imports.multiple_results()
+    assert a == 4
+    assert b == 5
+
+    assert imports.swap_tuple((1, 2)) == (2, 1)
+
+    # Flags are plain integers with one bit per flag, in declaration order.
+    assert imports.roundtrip_flags1(1) == 1
+    assert imports.roundtrip_flags1(0) == 0
+    assert imports.roundtrip_flags1(2) == 2
+    assert imports.roundtrip_flags1(3) == 3
+
+    assert imports.roundtrip_flags2(1) == 1
+    assert imports.roundtrip_flags2(0) == 0
+    assert imports.roundtrip_flags2(2) == 2
+    assert imports.roundtrip_flags2(1 | 4) == 1 | 4
+
+    a, b, c, d = imports.roundtrip_flags3(1, 2, 4, 1 << 63)
+    assert a == 1
+    assert b == 2
+    assert c == 4
+    assert d == 1 << 63
+
+    r = imports.roundtrip_record1(SimpleNamespace(a=8, b=0))
+    assert r.a == 8
+    assert r.b == 0
+
+    r = imports.roundtrip_record1(SimpleNamespace(a=0, b=3))
+    assert r.a == 0
+    assert r.b == 3
+
+    assert imports.tuple0(()) == ()
+    assert imports.tuple1([1]) == (1,)
+
+
+def multiple_results():
+    return 100, 200
+
+
+def swap_tuple(a):
+    x, y = a
+    return y, x
+
+
+def roundtrip_flags1(a):
+    return a
+
+
+def roundtrip_flags2(a):
+    return a
+
+
+def roundtrip_flags3(a, b, c, d):
+    return a, b, c, d
+
+
+def roundtrip_record1(a):
+    return a
+
+
+def tuple0(a):
+    assert a == ()
+    return a
+
+
+def tuple1(a):
+    return a
@@ -0,0 +1,28 @@
+import imports
+
+
+def test_imports():
+    imports.f1("Hello, WIT!")
+
+    s = imports.f2()
+    assert s == "36 chambers"
+
+    a, b, c = imports.f3("", "🚀", "hello")
+    assert a == ""
+    assert b == "🚀"
+    assert c == "hello"
+
+
+def f1(s):
+    assert s == "Hello, WIT!"
+
+
+def f2():
+    return "36 chambers"
+
+
+def f3(a, b, c):
+    assert a == ""
+    assert b == "🚀"
+    assert c == "hello"
+    return a, b, c
@@ -0,0 +1,94 @@
+import imports
+
+
+def test_imports():
+    assert imports.roundtrip_option(1.0) == 1
+    assert imports.roundtrip_option(None) is None
+    assert imports.roundtrip_option(2.0) == 2
+
+    # Variants other than `bool`, `option` and `enum`s are `(case, payload)`
+    # pairs, where the payload is `None` for cases without one.
+    assert imports.roundtrip_result(("ok", 2)) == ("ok", 2.0)
+    assert imports.roundtrip_result(("ok", 4)) == ("ok", 4.0)
+    assert imports.roundtrip_result(("err", 5.5)) == ("err", 5)
+
+    assert imports.roundtrip_enum("a") == "a"
+    assert imports.roundtrip_enum("b") == "b"
+
+    assert imports.invert_bool(True) is False
+    assert imports.invert_bool(False) is True
+
+    casts = imports.variant_casts((
+        ("a", 1),
+        ("a", 2),
+        ("a", 3),
+        ("a", 4),
+        ("a", 5),
+        ("a", 6.0),
+    ))
+    assert casts == (
+        ("a", 1),
+        ("a", 2),
+        ("a", 3),
+        ("a", 4),
+        ("a", 5),
+        ("a", 6.0),
+    )
+
+    casts = imports.variant_casts((
+        ("b", 1),
+        ("b", 2.0),
+        ("b", 3.0),
+        ("b", 4.0),
+        ("b", 5.0),
+        ("b", 6.0),
+    ))
+    assert casts == (
+        ("b", 1),
+        ("b", 2.0),
+        ("b", 3.0),
+        ("b", 4.0),
+        ("b", 5.0),
+        ("b", 6.0),
+    )
+
+    zeros = imports.variant_zeros((("a", 1), ("a", 2), ("a", 3.0), ("a", 4.0)))
+    assert zeros == (("a", 1), ("a", 2), ("a", 3.0), ("a", 4.0))
+
+    zeros = imports.variant_zeros((("b", None), ("b", None), ("b", None), ("b", None)))
+    assert zeros == (("b", None), ("b", None), ("b", None), ("b", None))
+
+    imports.variant_typedefs(None, False, ("err", None))
+
+    a, b, c = imports.variant_enums(True, ("ok", None), "success")
+    assert a is False
+    assert b == ("err", None)
+    assert c == "a"
+
+
+def roundtrip_option(a):
+    return a
+
+
+def roundtrip_result(a):
+    return a
+
+
+def roundtrip_enum(a):
+    return a
+
+
+def invert_bool(a):
+    return not a
+
+
+def variant_casts(a):
+    return a
+
+
+def variant_zeros(a):
+    return a
+
+
+def variant_typedefs(a, b, c):
+    pass
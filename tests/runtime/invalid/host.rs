@@ -3,6 +3,7 @@ wit_bindgen_wasmtime::export!("./tests/runtime/invalid/imports.wit");
 use anyhow::Result;
 use imports::*;
 use wasmtime::Trap;
+use wit_bindgen_wasmtime::GuestError;
 
 #[derive(Default)]
 pub struct MyImports;
@@ -54,33 +55,58 @@ fn run(wasm: &str) -> Result<()> {
         |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
     )?;
 
-    assert_err(
+    assert_guest_err(
         exports.invalid_bool(&mut store),
-        "invalid discriminant for `bool`",
+        "roundtrip-bool",
+        GuestError::InvalidEnumValue {
+            name: "bool",
+            value: 2,
+        },
     )?;
-    assert_err(
+    assert_guest_err(
         exports.invalid_u8(&mut store),
-        "out-of-bounds integer conversion",
+        "roundtrip-u8",
+        GuestError::InvalidInt {
+            ty: "u8",
+            value: i32::MAX.into(),
+        },
     )?;
-    assert_err(
+    assert_guest_err(
         exports.invalid_s8(&mut store),
-        "out-of-bounds integer conversion",
+        "roundtrip-s8",
+        GuestError::InvalidInt {
+            ty: "i8",
+            value: i32::MAX.into(),
+        },
     )?;
-    assert_err(
+    assert_guest_err(
         exports.invalid_u16(&mut store),
-        "out-of-bounds integer conversion",
+        "roundtrip-u16",
+        GuestError::InvalidInt {
+            ty: "u16",
+            value: i32::MAX.into(),
+        },
     )?;
-    assert_err(
+    assert_guest_err(
         exports.invalid_s16(&mut store),
-        "out-of-bounds integer conversion",
+        "roundtrip-s16",
+        GuestError::InvalidInt {
+            ty: "i16",
+            value: i32::MAX.into(),
+        },
     )?;
-    assert_err(
+    assert_guest_err(
         exports.invalid_char(&mut store),
-        "char value out of valid range",
+        "roundtrip-char",
+        GuestError::InvalidChar(0xd800),
     )?;
-    assert_err(
+    assert_guest_err(
         exports.invalid_enum(&mut store),
-        "invalid discriminant for `E`",
+        "roundtrip-enum",
+        GuestError::InvalidEnumValue {
+            name: "E",
+            value: 400,
+        },
     )?;
     assert_err(exports.invalid_handle(&mut store), "invalid handle index")?;
     assert_err(
@@ -96,4 +122,19 @@ fn run(wasm: &str) -> Result<()> {
             Err(e) => Err(e.into()),
         }
     }
+
+    fn assert_guest_err(result: Result<(), Trap>, funcname: &str, err: GuestError) -> Result<()> {
+        let trap = match result {
+            Ok(()) => anyhow::bail!("export didn't trap"),
+            Err(trap) => trap,
+        };
+        match GuestError::from_trap(&trap) {
+            Some(GuestError::InFunc {
+                funcname: f,
+                location: "a",
+                err: e,
+            }) if *f == funcname && **e == err => Ok(()),
+            _ => Err(trap.into()),
+        }
+    }
 }
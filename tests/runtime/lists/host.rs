@@ -124,6 +124,13 @@ impl Imports for MyImports {
 
 wit_bindgen_wasmtime::import!("./tests/runtime/lists/exports.wit");
 
+mod limited {
+    wit_bindgen_wasmtime::import!({
+        paths: ["./tests/runtime/lists/exports.wit"],
+        limits: true,
+    });
+}
+
 fn run(wasm: &str) -> Result<()> {
     use exports::*;
 
@@ -151,5 +158,54 @@ fn run(wasm: &str) -> Result<()> {
     // Ensure that we properly called `free` everywhere in all the glue that we
     // needed to.
     assert_eq!(bytes, exports.allocated_bytes(&mut store)?);
+
+    run_limited(wasm)
+}
+
+fn run_limited(wasm: &str) -> Result<()> {
+    use limited::exports::*;
+    use wit_bindgen_wasmtime::{LimitError, Limits};
+
+    let (mut exports, mut store) = crate::instantiate(
+        wasm,
+        |linker| imports::add_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
+        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+    )?;
+    exports.set_limits(Limits {
+        max_alloc: Some(3),
+        ..Limits::default()
+    });
+
+    exports.list_param2(&mut store, "foo")?;
+    let err = exports.list_param(&mut store, &[1, 2, 3, 4]).unwrap_err();
+    assert_eq!(
+        LimitError::from_trap(&err),
+        Some(&LimitError::AllocTooLarge { size: 4, limit: 3 })
+    );
+
+    // Each call gets its own fuel, so a call that runs out doesn't affect the
+    // calls after it once the limit is raised again.
+    let mut config = crate::default_config()?;
+    config.consume_fuel(true);
+    let (mut exports, mut store) = crate::instantiate_with_config(
+        &config,
+        wasm,
+        |linker| imports::add_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
+        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+    )?;
+    exports.set_limits(Limits {
+        fuel: Some(1),
+        ..Limits::default()
+    });
+    let err = exports
+        .list_param3(&mut store, &["foo", "bar", "baz"])
+        .unwrap_err();
+    assert_eq!(LimitError::from_trap(&err), Some(&LimitError::OutOfFuel(1)));
+
+    exports.set_limits(Limits {
+        fuel: Some(10_000_000),
+        ..Limits::default()
+    });
+    exports.list_param3(&mut store, &["foo", "bar", "baz"])?;
     Ok(())
 }
@@ -1,10 +1,14 @@
-import { addImportsToImports, Imports, FLAG32_B8, FLAG64_B9 } from "./imports.js";
+import { addImportsToImports, Imports } from "./imports.js";
 import { Exports } from "./exports.js";
 import * as exports from "./exports.js";
 import { getWasm, addWasiToImports } from "./helpers.js";
 // @ts-ignore
 import * as assert from 'assert';
 
+function setFlags(flags: object): string[] {
+  return Object.entries(flags).filter(([_, set]) => set).map(([name, _]) => name);
+}
+
 async function run() {
   const importObj = {};
   const imports: Imports = {
@@ -31,8 +35,10 @@ async function run() {
       assert.deepStrictEqual(Array.from(u16), [1]);
       assert.deepStrictEqual(Array.from(u32), [2]);
       assert.deepStrictEqual(Array.from(u64), [3n]);
-      assert.deepStrictEqual(flag32, [FLAG32_B8]);
-      assert.deepStrictEqual(flag64, [FLAG64_B9]);
+      assert.deepStrictEqual(flag32.length, 1);
+      assert.deepStrictEqual(setFlags(flag32[0]), ['b8']);
+      assert.deepStrictEqual(flag64.length, 1);
+      assert.deepStrictEqual(setFlags(flag64[0]), ['b9']);
     },
     unalignedRoundtrip2(record, f32, f64, string, list) {
       assert.deepStrictEqual(Array.from(record), [{ a: 10, b: 11n }]);
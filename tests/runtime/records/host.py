@@ -51,6 +51,8 @@ def run(wasm_file: str) -> None:
     assert(wasm.roundtrip_flags1(store, e.F1.A) == e.F1.A)
     assert(wasm.roundtrip_flags1(store, e.F1(0)) == e.F1(0))
     assert(wasm.roundtrip_flags1(store, e.F1.A | e.F1.B) == (e.F1.A | e.F1.B))
+    assert(wasm.roundtrip_flags1(store, e.F1(3)) == (e.F1.A | e.F1.B))
+    assert(isinstance(wasm.roundtrip_flags1(store, e.F1.B), int))
 
     assert(wasm.roundtrip_flags2(store, e.F2.C) == e.F2.C)
     assert(wasm.roundtrip_flags2(store, e.F2(0)) == e.F2(0))
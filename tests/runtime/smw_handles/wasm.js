@@ -0,0 +1,132 @@
+import * as imports from "imports";
+
+function assert(condition, message) {
+  if (!condition) {
+    throw new Error(message);
+  }
+}
+
+function assertEq(a, b) {
+  assert(a == b, `assertEq failed: ${a} != ${b}`);
+}
+
+export function test_imports() {
+  const {
+    host_state_create,
+    host_state_get,
+    host_state2_create,
+    host_state2_saw_close,
+    two_host_states,
+    host_state2_param_record,
+    host_state2_param_tuple,
+    host_state2_param_option,
+    host_state2_param_result,
+    host_state2_param_variant,
+    host_state2_param_list,
+    host_state2_result_record,
+    host_state2_result_tuple,
+    host_state2_result_option,
+    host_state2_result_result,
+    host_state2_result_variant,
+    host_state2_result_list,
+    drop_host_state,
+    drop_host_state2,
+  } = imports;
+
+  const s = host_state_create();
+  assertEq(host_state_get(s), 100);
+  drop_host_state(s);
+
+  assertEq(host_state2_saw_close(), false);
+  const s2 = host_state2_create();
+  assertEq(host_state2_saw_close(), false);
+  drop_host_state2(s2);
+  assertEq(host_state2_saw_close(), true);
+
+  const a = host_state_create();
+  const b = host_state2_create();
+  const [c, d] = two_host_states(a, b);
+  assertEq(host_state_get(c), 2);
+  drop_host_state(a);
+  drop_host_state(c);
+  drop_host_state2(b);
+
+  host_state2_param_record({ a: d });
+  host_state2_param_tuple([d]);
+  host_state2_param_option(d);
+  host_state2_param_option(null);
+  host_state2_param_result({ tag: "ok", val: d });
+  host_state2_param_result({ tag: "err", val: 2 });
+  host_state2_param_variant({ tag: "0", val: d });
+  host_state2_param_variant({ tag: "1", val: 2 });
+  host_state2_param_list([]);
+  host_state2_param_list([d]);
+  host_state2_param_list([d, d]);
+  drop_host_state2(d);
+
+  drop_host_state2(host_state2_result_record().a);
+  drop_host_state2(host_state2_result_tuple()[0]);
+  drop_host_state2(host_state2_result_option());
+  drop_host_state2(host_state2_result_result().val);
+  drop_host_state2(host_state2_result_variant().val);
+  for (const h of host_state2_result_list()) {
+    drop_host_state2(h);
+  }
+}
+
+let wasmState2Closed = false;
+
+export function wasm_state_create() {
+  return { val: 100 };
+}
+
+export function wasm_state_get_val(state) {
+  return state.val;
+}
+
+export function wasm_state2_create() {
+  return { val: 33 };
+}
+
+export function wasm_state2_saw_close() {
+  return wasmState2Closed;
+}
+
+export function drop_wasm_state2(state) {
+  wasmState2Closed = true;
+}
+
+export function two_wasm_states(a, b) {
+  return [{ val: 101 }, { val: 102 }];
+}
+
+export function wasm_state2_param_record(a) {}
+export function wasm_state2_param_tuple(a) {}
+export function wasm_state2_param_option(a) {}
+export function wasm_state2_param_result(a) {}
+export function wasm_state2_param_variant(a) {}
+export function wasm_state2_param_list(a) {}
+
+export function wasm_state2_result_record() {
+  return { a: { val: 222 } };
+}
+
+export function wasm_state2_result_tuple() {
+  return [{ val: 333 }];
+}
+
+export function wasm_state2_result_option() {
+  return { val: 444 };
+}
+
+export function wasm_state2_result_result() {
+  return { tag: "ok", val: { val: 555 } };
+}
+
+export function wasm_state2_result_variant() {
+  return { tag: "0", val: { val: 666 } };
+}
+
+export function wasm_state2_result_list() {
+  return [{ val: 777 }, { val: 888 }];
+}
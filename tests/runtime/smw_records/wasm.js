@@ -0,0 +1,113 @@
+import * as imports from "imports";
+
+function assert(condition, message) {
+  if (!condition) {
+    throw new Error(message);
+  }
+}
+
+function assertEq(a, b) {
+  assert(a == b, `assertEq failed: ${a} != ${b}`);
+}
+
+function assertFlags(flags, expected) {
+  for (const name of Object.keys(flags)) {
+    assertEq(flags[name], expected.includes(name));
+  }
+}
+
+export function test_imports() {
+  const {
+    multiple_results,
+    swap_tuple,
+    roundtrip_flags1,
+    roundtrip_flags2,
+    roundtrip_flags3,
+    roundtrip_record1,
+    tuple0,
+    tuple1,
+  } = imports;
+
+  {
+    const [a, b] = multiple_results();
+    assertEq(a, 4);
+    assertEq(b, 5);
+  }
+
+  {
+    const [a, b] = swap_tuple([1, 2]);
+    assertEq(a, 2);
+    assertEq(b, 1);
+  }
+
+  assertFlags(roundtrip_flags1({ a: true }), ["a"]);
+  assertFlags(roundtrip_flags1({}), []);
+  assertFlags(roundtrip_flags1({ b: true }), ["b"]);
+  assertFlags(roundtrip_flags1({ a: true, b: true }), ["a", "b"]);
+
+  assertFlags(roundtrip_flags2({ c: true }), ["c"]);
+  assertFlags(roundtrip_flags2({}), []);
+  assertFlags(roundtrip_flags2({ d: true }), ["d"]);
+  assertFlags(roundtrip_flags2({ c: true, e: true }), ["c", "e"]);
+
+  {
+    const [a, b, c, d] = roundtrip_flags3({ b0: true }, { b1: true }, { b2: true }, { b63: true });
+    assertFlags(a, ["b0"]);
+    assertFlags(b, ["b1"]);
+    assertFlags(c, ["b2"]);
+    assertFlags(d, ["b63"]);
+  }
+
+  {
+    const r = roundtrip_record1({ a: 8, b: {} });
+    assertEq(r.a, 8);
+    assertFlags(r.b, []);
+  }
+
+  {
+    const r = roundtrip_record1({ a: 0, b: { a: true, b: true } });
+    assertEq(r.a, 0);
+    assertFlags(r.b, ["a", "b"]);
+  }
+
+  assertEq(tuple0([]).length, 0);
+
+  {
+    const t = tuple1([1]);
+    assertEq(t.length, 1);
+    assertEq(t[0], 1);
+  }
+}
+
+export function multiple_results() {
+  return [100, 200];
+}
+
+export function swap_tuple([a, b]) {
+  return [b, a];
+}
+
+export function roundtrip_flags1(a) {
+  return a;
+}
+
+export function roundtrip_flags2(a) {
+  return a;
+}
+
+export function roundtrip_flags3(a, b, c, d) {
+  return [a, b, c, d];
+}
+
+export function roundtrip_record1(a) {
+  return a;
+}
+
+export function tuple0(a) {
+  assertEq(a.length, 0);
+  return a;
+}
+
+export function tuple1(a) {
+  return a;
+}
@@ -0,0 +1,137 @@
+import * as imports from "imports";
+
+function assert(condition, message) {
+  if (!condition) {
+    throw new Error(message);
+  }
+}
+
+function assertEq(a, b) {
+  assert(a == b, `assertEq failed: ${a} != ${b}`);
+}
+
+function assertVariant(v, tag, val) {
+  assertEq(v.tag, tag);
+  assertEq(v.val, val);
+}
+
+export function test_imports() {
+  const {
+    roundtrip_option,
+    roundtrip_result,
+    roundtrip_enum,
+    invert_bool,
+    variant_casts,
+    variant_zeros,
+    variant_typedefs,
+    variant_enums,
+  } = imports;
+
+  assertEq(roundtrip_option(1.0), 1);
+  assertEq(roundtrip_option(null), null);
+  assertEq(roundtrip_option(2.0), 2);
+  assertVariant(roundtrip_result({ tag: "ok", val: 2 }), "ok", 2.0);
+  assertVariant(roundtrip_result({ tag: "ok", val: 4 }), "ok", 4.0);
+  assertVariant(roundtrip_result({ tag: "err", val: 5.5 }), "err", 5);
+
+  assertEq(roundtrip_enum("a"), "a");
+  assertEq(roundtrip_enum("b"), "b");
+
+  assertEq(invert_bool(true), false);
+  assertEq(invert_bool(false), true);
+
+  {
+    const [a1, a2, a3, a4, a5, a6] = variant_casts([
+      { tag: "a", val: 1 },
+      { tag: "a", val: 2 },
+      { tag: "a", val: 3 },
+      { tag: "a", val: 4n },
+      { tag: "a", val: 5n },
+      { tag: "a", val: 6.0 },
+    ]);
+    assertVariant(a1, "a", 1);
+    assertVariant(a2, "a", 2);
+    assertVariant(a3, "a", 3);
+    assertVariant(a4, "a", 4n);
+    assertVariant(a5, "a", 5n);
+    assertVariant(a6, "a", 6.0);
+  }
+
+  {
+    const [a1, a2, a3, a4, a5, a6] = variant_casts([
+      { tag: "b", val: 1n },
+      { tag: "b", val: 2.0 },
+      { tag: "b", val: 3.0 },
+      { tag: "b", val: 4.0 },
+      { tag: "b", val: 5.0 },
+      { tag: "b", val: 6.0 },
+    ]);
+    assertVariant(a1, "b", 1n);
+    assertVariant(a2, "b", 2.0);
+    assertVariant(a3, "b", 3.0);
+    assertVariant(a4, "b", 4.0);
+    assertVariant(a5, "b", 5.0);
+    assertVariant(a6, "b", 6.0);
+  }
+
+  {
+    const [a1, a2, a3, a4] = variant_zeros([
+      { tag: "a", val: 1 },
+      { tag: "a", val: 2n },
+      { tag: "a", val: 3.0 },
+      { tag: "a", val: 4.0 },
+    ]);
+    assertVariant(a1, "a", 1);
+    assertVariant(a2, "a", 2n);
+    assertVariant(a3, "a", 3.0);
+    assertVariant(a4, "a", 4.0);
+  }
+
+  {
+    const [a1, a2, a3, a4] = variant_zeros([
+      { tag: "b" },
+      { tag: "b" },
+      { tag: "b" },
+      { tag: "b" },
+    ]);
+    assertVariant(a1, "b", undefined);
+    assertVariant(a2, "b", undefined);
+    assertVariant(a3, "b", undefined);
+    assertVariant(a4, "b", undefined);
+  }
+
+  variant_typedefs(null, false, { tag: "err" });
+
+  {
+    const [a, b, c] = variant_enums(true, { tag: "ok" }, "success");
+    assertEq(a, false);
+    assertVariant(b, "err", undefined);
+    assertEq(c, "a");
+  }
+}
+
+export function roundtrip_option(a) {
+  return a;
+}
+
+export function roundtrip_result(a) {
+  return a;
+}
+
+export function roundtrip_enum(a) {
+  return a;
+}
+
+export function invert_bool(a) {
+  return !a;
+}
+
+export function variant_casts(a) {
+  return a;
+}
+
+export function variant_zeros(a) {
+  return a;
+}
+
+export function variant_typedefs(a, b, c) {}
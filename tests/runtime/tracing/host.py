@@ -0,0 +1,72 @@
+from exports.bindings import Exports
+from imports.bindings import add_imports_to_linker, Imports
+from typing import List
+import exports.bindings as e
+import imports.bindings as i
+import sys
+import wasmtime
+
+class MyImports:
+    def double(self, a: int) -> int:
+        return a * 2
+
+    def check(self, s: i.Secret) -> bool:
+        return s.key == 'hunter2'
+
+def run(wasm_file: str) -> None:
+    store = wasmtime.Store()
+    module = wasmtime.Module.from_file(store.engine, wasm_file)
+    linker = wasmtime.Linker(store.engine)
+    linker.define_wasi()
+    wasi = wasmtime.WasiConfig()
+    wasi.inherit_stdout()
+    wasi.inherit_stderr()
+    store.set_wasi(wasi)
+
+    imports = MyImports()
+    add_imports_to_linker(linker, store, imports)
+    wasm = Exports(store, linker, module)
+
+    # The imports and exports bindings are generated separately, so each
+    # has its own hook.
+    import_records: List[i.TraceRecord] = []
+    export_records: List[e.TraceRecord] = []
+    i.set_trace_hook(import_records.append)
+    e.set_trace_hook(export_records.append)
+
+    wasm.test_imports(store)
+    assert(wasm.add(store, 1, 2) == 3)
+    assert(wasm.unlock(store, e.Secret('hunter2')))
+    try:
+        wasm.trap(store)
+        raise RuntimeError('expected exception')
+    except wasmtime.Trap:
+        pass
+
+    i.set_trace_hook(None)
+    e.set_trace_hook(None)
+    wasm.add(store, 3, 4)
+
+    for record in import_records + export_records:
+        assert(isinstance(record.duration_us, int))
+        assert(record.duration_us >= 0)
+
+    calls = [(r.module, r.function, r.params, r.results) for r in import_records]
+    assert(calls == [
+        ('imports', 'double', {'a': 2}, {'result': 4}),
+        ('imports', 'check', {'s': '<redacted>'}, {'result': True}),
+    ])
+    assert(all(r.error is None for r in import_records))
+
+    calls = [(r.module, r.function, r.params, r.results) for r in export_records]
+    assert(calls == [
+        ('exports', 'test-imports', {}, {}),
+        ('exports', 'add', {'a': 1, 'b': 2}, {'result': 3}),
+        ('exports', 'unlock', {'s': '<redacted>'}, {'result': True}),
+        ('exports', 'trap', {}, None),
+    ])
+    assert(all(r.error is None for r in export_records[:3]))
+    assert(isinstance(export_records[3].error, wasmtime.Trap))
+
+if __name__ == '__main__':
+    run(sys.argv[1])
@@ -0,0 +1,171 @@
+use anyhow::Result;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wit_bindgen_wasmtime::tracing::field::{Field, Visit};
+use wit_bindgen_wasmtime::tracing::{self, span, Event, Metadata, Subscriber};
+
+wit_bindgen_wasmtime::export!({
+    paths: ["./tests/runtime/tracing/imports.wit"],
+    tracing_redact: ["secret"],
+});
+
+use imports::*;
+
+#[derive(Default)]
+pub struct MyImports;
+
+impl Imports for MyImports {
+    fn double(&mut self, a: u32) -> u32 {
+        a * 2
+    }
+
+    fn check(&mut self, s: Secret<'_>) -> bool {
+        s.key == "hunter2"
+    }
+}
+
+wit_bindgen_wasmtime::import!({
+    paths: ["./tests/runtime/tracing/exports.wit"],
+    tracing_redact: ["secret"],
+});
+
+/// The fields of an event or span, formatted as strings.
+#[derive(Default)]
+struct Fields(Vec<(String, String)>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_string(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+#[derive(Default)]
+struct State {
+    // The `module` and `function` of each span, indexed by its id minus one.
+    spans: Vec<(String, String)>,
+    entered: Vec<u64>,
+    // Each event's fields along with the function of the span it's in.
+    events: Vec<(String, Vec<(String, String)>)>,
+}
+
+/// Records the events emitted by the generated bindings.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<State>>);
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let field = |name: &str| {
+            fields
+                .0
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        let mut state = self.0.lock().unwrap();
+        state.spans.push((field("module"), field("function")));
+        span::Id::from_u64(state.spans.len() as u64)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let mut state = self.0.lock().unwrap();
+        let span = *state.entered.last().expect("event outside of a span");
+        let (module, function) = &state.spans[span as usize - 1];
+        let function = format!("{}::{}", module, function);
+        state.events.push((function, fields.0));
+    }
+
+    fn enter(&self, span: &span::Id) {
+        self.0.lock().unwrap().entered.push(span.into_u64());
+    }
+
+    fn exit(&self, span: &span::Id) {
+        let mut state = self.0.lock().unwrap();
+        assert_eq!(state.entered.pop(), Some(span.into_u64()));
+    }
+}
+
+fn run(wasm: &str) -> Result<()> {
+    use exports::*;
+
+    let (exports, mut store) = crate::instantiate(
+        wasm,
+        |linker| imports::add_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
+        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+    )?;
+
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || -> Result<()> {
+        exports.test_imports(&mut store)?;
+        assert_eq!(exports.add(&mut store, 1, 2)?, 3);
+        assert!(exports.unlock(&mut store, Secret { key: "hunter2" })?);
+        assert!(exports.trap(&mut store).is_err());
+        Ok(())
+    })?;
+
+    // Durations and errors vary between runs, so only check that they're
+    // there.
+    let state = recorder.0.lock().unwrap();
+    let events = state
+        .events
+        .iter()
+        .map(|(function, fields)| {
+            let fields = fields
+                .iter()
+                .map(|(name, value)| match name.as_str() {
+                    "duration_us" => {
+                        value.parse::<u64>().unwrap();
+                        (name.as_str(), "_")
+                    }
+                    "error" => {
+                        assert!(!value.is_empty());
+                        (name.as_str(), "_")
+                    }
+                    _ => (name.as_str(), value.as_str()),
+                })
+                .collect::<Vec<_>>();
+            (function.as_str(), fields)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            ("imports::double", vec![("params.a", "2")]),
+            ("imports::double", vec![("results.result", "4")]),
+            ("imports::double", vec![("duration_us", "_")]),
+            ("imports::check", vec![("params.s", "<redacted>")]),
+            ("imports::check", vec![("results.result", "true")]),
+            ("imports::check", vec![("duration_us", "_")]),
+            ("exports::test-imports", vec![("duration_us", "_")]),
+            ("exports::add", vec![("params.a", "1"), ("params.b", "2")]),
+            (
+                "exports::add",
+                vec![("duration_us", "_"), ("results.result", "3")]
+            ),
+            ("exports::unlock", vec![("params.s", "<redacted>")]),
+            (
+                "exports::unlock",
+                vec![("duration_us", "_"), ("results.result", "true")]
+            ),
+            ("exports::trap", vec![("duration_us", "_"), ("error", "_")]),
+        ]
+    );
+    Ok(())
+}
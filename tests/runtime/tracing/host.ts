@@ -0,0 +1,52 @@
+import { addImportsToImports, Imports } from "./imports.js";
+import { Exports, setTraceHook, TraceRecord } from "./exports.js";
+import { getWasm, addWasiToImports } from "./helpers.js";
+// @ts-ignore
+import * as assert from 'assert';
+
+async function run() {
+  const importObj = {};
+  const imports: Imports = {
+    double(a) { return a * 2; },
+    check(s) { return s.key === 'hunter2'; },
+  };
+  let instance: WebAssembly.Instance;
+  addImportsToImports(importObj, imports, name => instance.exports[name]);
+  const wasi = addWasiToImports(importObj);
+
+  const wasm = new Exports();
+  await wasm.instantiate(getWasm(), importObj);
+  wasi.start(wasm.instance);
+  instance = wasm.instance;
+
+  const records: TraceRecord[] = [];
+  setTraceHook(record => records.push(record));
+
+  wasm.testImports();
+  assert.strictEqual(wasm.add(1, 2), 3);
+  assert.strictEqual(wasm.unlock({ key: 'hunter2' }), true);
+  assert.throws(() => wasm.trap());
+
+  setTraceHook(null);
+  wasm.add(3, 4);
+
+  for (const record of records) {
+    assert.ok(Number.isInteger(record.duration_us));
+    assert.ok(record.duration_us >= 0);
+  }
+  const calls = records.map(({ module, function: func, params, results }) => ({ module, func, params, results }));
+  assert.deepStrictEqual(calls, [
+    // Imports called by `test-imports` finish before it does.
+    { module: 'imports', func: 'double', params: { a: 2 }, results: { result: 4 } },
+    { module: 'imports', func: 'check', params: { s: '<redacted>' }, results: { result: true } },
+    { module: 'exports', func: 'test-imports', params: {}, results: {} },
+    { module: 'exports', func: 'add', params: { a: 1, b: 2 }, results: { result: 3 } },
+    { module: 'exports', func: 'unlock', params: { s: '<redacted>' }, results: { result: true } },
+    { module: 'exports', func: 'trap', params: {}, results: undefined },
+  ]);
+  assert.strictEqual(records[5].error instanceof WebAssembly.RuntimeError, true);
+  for (const record of records.slice(0, 5))
+    assert.strictEqual(record.error, undefined);
+}
+
+await run()
@@ -0,0 +1,23 @@
+wit_bindgen_rust::import!("./tests/runtime/tracing/imports.wit");
+wit_bindgen_rust::export!("./tests/runtime/tracing/exports.wit");
+
+struct Exports;
+
+impl exports::Exports for Exports {
+    fn test_imports() {
+        assert_eq!(imports::double(2), 4);
+        assert!(imports::check(imports::Secret { key: "hunter2" }));
+    }
+
+    fn add(a: u32, b: u32) -> u32 {
+        a + b
+    }
+
+    fn unlock(s: exports::Secret) -> bool {
+        s.key == "hunter2"
+    }
+
+    fn trap() {
+        panic!("trap");
+    }
+}
@@ -1,11 +1,32 @@
 from exports.bindings import Exports
 from imports.bindings import add_imports_to_linker, Imports
-from typing import Optional, Tuple
+from typing import NoReturn, Optional, Tuple
 import exports.bindings as e
 import imports.bindings as i
 import sys
 import wasmtime
 
+def assert_never(x: NoReturn) -> NoReturn:
+    raise AssertionError(f'unhandled variant case: {x!r}')
+
+def z1_value(z: i.Z1) -> Optional[int]:
+    # Payload variants are a `Union` of dataclasses so this chain of
+    # `isinstance` checks is verified by mypy to be exhaustive.
+    if isinstance(z, i.Z1A):
+        return z.value
+    elif isinstance(z, i.Z1B):
+        return None
+    else:
+        assert_never(z)
+
+def e1_name(e: i.E1) -> str:
+    if e is i.E1.A:
+        return 'a'
+    elif e is i.E1.B:
+        return 'b'
+    else:
+        assert_never(e)
+
 class MyImports:
     def roundtrip_option(self, a: Optional[float]) -> Optional[int]:
         if a:
@@ -18,6 +39,8 @@ class MyImports:
         return i.Err(int(a.value))
 
     def roundtrip_enum(self, a: i.E1) -> i.E1:
+        assert(isinstance(a, i.E1))
+        assert(e1_name(a) in ['a', 'b'])
         return a
 
     def invert_bool(self, a: bool) -> bool:
@@ -27,10 +50,13 @@ class MyImports:
         return a
 
     def variant_zeros(self, a: i.Zeros) -> i.Zeros:
+        assert(z1_value(a[0]) in [1, None])
         return a
 
     def variant_typedefs(self, a: i.OptionTypedef, b: i.BoolTypedef, c: i.ResultTypedef) -> None:
-        pass
+        assert(a is None)
+        assert(b is False)
+        assert(c == i.Err(None))
 
     def variant_enums(self, a: bool, b: i.Expected[None, None], c: i.MyErrno) -> Tuple[bool, i.Expected[None, None], i.MyErrno]:
         assert(a)
@@ -63,6 +89,7 @@ def run(wasm_file: str) -> None:
 
     assert(wasm.roundtrip_enum(store, e.E1.A) == e.E1.A)
     assert(wasm.roundtrip_enum(store, e.E1.B) == e.E1.B)
+    assert(wasm.roundtrip_enum(store, e.E1.B) is e.E1.B)
 
     assert(wasm.invert_bool(store, True) == False)
     assert(wasm.invert_bool(store, False) == True)
@@ -108,6 +135,17 @@ def run(wasm_file: str) -> None:
     assert(z3 == e.Z3A(3))
     assert(z4 == e.Z4A(4))
 
+    z1, z2, z3, z4 = wasm.variant_zeros(store, (
+        e.Z1B(),
+        e.Z2B(),
+        e.Z3B(),
+        e.Z4B(),
+    ))
+    assert(z1 == e.Z1B())
+    assert(z2 == e.Z2B())
+    assert(z3 == e.Z3B())
+    assert(z4 == e.Z4B())
+
     wasm.variant_typedefs(store, None, False, e.Err(None))
 
 if __name__ == '__main__':